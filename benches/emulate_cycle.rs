@@ -0,0 +1,86 @@
+use chip8_emu::Chip8;
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+const CYCLES: usize = 100_000;
+
+// Register arithmetic in a tight loop, no memory or display access
+const ALU_LOOP: &[u8] = &[
+    0x60, 0x01, // 200: LD V0, 0x01
+    0x61, 0x02, // 202: LD V1, 0x02
+    0x80, 0x14, // 204: ADD V0, V1
+    0x81, 0x05, // 206: SUB V1, V0
+    0x82, 0x03, // 208: XOR V2, V0
+    0x73, 0x01, // 20A: ADD V3, 0x01
+    0x82, 0x36, // 20C: SHR V2, V3
+    0x33, 0x00, // 20E: SE V3, 0x00
+    0x12, 0x04, // 210: JP 0x204
+    0x12, 0x04, // 212: JP 0x204
+];
+
+// Full-height sprites drawn across the screen, wrapping at the edges
+const DRAW_LOOP: &[u8] = &[
+    0xA2, 0x10, // 200: LD I, 0x210
+    0x60, 0x00, // 202: LD V0, 0x00
+    0x61, 0x00, // 204: LD V1, 0x00
+    0xD0, 0x1F, // 206: DRW V0, V1, 15
+    0x70, 0x07, // 208: ADD V0, 0x07
+    0x71, 0x03, // 20A: ADD V1, 0x03
+    0x12, 0x06, // 20C: JP 0x206
+    0x00, 0x00, // 20E: padding
+    0xFF, 0x81, 0xBD, 0xA5, 0xA5, 0xBD, 0x81, 0xFF, // 210: sprite
+    0x18, 0x3C, 0x7E, 0xFF, 0x7E, 0x3C, 0x18,
+];
+
+// A game-like mix: font digits, random positions, BCD, timers
+// and a subroutine call every iteration
+const GAME_LOOP: &[u8] = &[
+    0x60, 0x00, // 200: LD V0, 0x00
+    0x00, 0xE0, // 202: CLS
+    0xC1, 0x3F, // 204: RND V1, 0x3F
+    0xC2, 0x1F, // 206: RND V2, 0x1F
+    0xF0, 0x29, // 208: LD F, V0
+    0xD1, 0x25, // 20A: DRW V1, V2, 5
+    0x22, 0x18, // 20C: CALL 0x218
+    0x70, 0x01, // 20E: ADD V0, 0x01
+    0x40, 0x10, // 210: SNE V0, 0x10
+    0x60, 0x00, // 212: LD V0, 0x00
+    0x12, 0x02, // 214: JP 0x202
+    0x00, 0x00, // 216: padding
+    0xA3, 0x00, // 218: LD I, 0x300
+    0xF0, 0x33, // 21A: LD B, V0
+    0xF2, 0x65, // 21C: LD V2, [I]
+    0xF3, 0x15, // 21E: LD DT, V3
+    0x00, 0xEE, // 220: RET
+];
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_seed(1);
+    chip8.load_default_fontset();
+    chip8.load_rom_bytes(rom).unwrap();
+    chip8
+}
+
+fn bench_rom(c: &mut Criterion, name: &str, rom: &[u8]) {
+    c.bench_function(name, |b| {
+        b.iter_batched_ref(
+            || machine(rom),
+            |chip8| {
+                for _ in 0..CYCLES {
+                    black_box(chip8.emulate_cycle().unwrap());
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+fn emulate_cycle(c: &mut Criterion) {
+    bench_rom(c, "alu_loop_100k", ALU_LOOP);
+    bench_rom(c, "draw_loop_100k", DRAW_LOOP);
+    bench_rom(c, "game_loop_100k", GAME_LOOP);
+}
+
+criterion_group!(benches, emulate_cycle);
+criterion_main!(benches);
@@ -1,4 +1,4 @@
-import init, { WasmChip8 } from "./pkg/chip8_emu.js";
+import init, { WasmChip8, WasmInspector, WasmTickClock, fontset, disassemble } from "./pkg/chip8_emu.js";
 
 const KB_MAP = {
   1: 0x1,
@@ -19,18 +19,48 @@ const KB_MAP = {
   v: 0xf,
 };
 
-const W = 64,
-  H = 32;
-const TIMER_MS = 16; // ~60Hz
+// Keypad key for a keyboard event. Digits go by their physical
+// key, so they still work with Shift (slow motion) held.
+function keypadKey(e) {
+  if (e.code.startsWith("Digit")) return KB_MAP[e.code.slice(5)];
+  return KB_MAP[e.key.toLowerCase()];
+}
+
+const TITLE = document.title;
+let W = 64; // 128 in SUPER-CHIP's high resolution, see fitDisplay()
+let H = 32; // 48 or 64 for hi-res ROMs
+const FRAME_HZ = 60; // CPF batches run at 60Hz
+const MAX_CATCHUP_MS = 100; // Time we're willing to catch up on after a stall
+const SLOW_SPEED = 0.25; // Shift held
 
+const screenWrap = document.getElementById("screen-wrap");
 const canvas = document.getElementById("screen");
 const ctx = canvas.getContext("2d");
+const crtCanvas = document.getElementById("crt");
+const overlay = document.getElementById("overlay");
+const octx = overlay.getContext("2d");
+const memoryWrap = document.getElementById("memory-wrap");
+const memoryCanvas = document.getElementById("memory");
+const mctx = memoryCanvas.getContext("2d");
+const inspectorWrap = document.getElementById("inspector-wrap");
+const inspectorCanvas = document.getElementById("inspector");
+const ictx = inspectorCanvas.getContext("2d");
+const memoryPageEl = document.getElementById("memory-page");
 const statusEl = document.getElementById("status");
 const statusDot = document.getElementById("status-dot");
 const romNameEl = document.getElementById("rom-name");
 const cpfSlider = document.getElementById("cpf-slider");
 const cpfVal = document.getElementById("cpf-val");
+const timerSlider = document.getElementById("timer-slider");
+const timerVal = document.getElementById("timer-val");
+const turboSlider = document.getElementById("turbo-slider");
+const turboVal = document.getElementById("turbo-val");
 const logEl = document.getElementById("log");
+const freqSlider = document.getElementById("freq-slider");
+const freqVal = document.getElementById("freq-val");
+const volumeSlider = document.getElementById("volume-slider");
+const volumeVal = document.getElementById("volume-val");
+const waveSelect = document.getElementById("wave-select");
 
 // State
 let chip8 = null;
@@ -38,11 +68,62 @@ let romBytes = null;
 let running = false;
 let rafId = null;
 let cpf = 15;
-let lastTimer = 0;
+let timerHz = 60;
+let turbo = false; // Tab held
+let turboFactor = 8;
+let slow = false; // Shift held
+let stopReason = null; // Error that stopped the ROM, shown in the title
+let rewinding = false; // Backspace held
+// Emulated time not yet run, as WasmTickClocks made once wasm is
+// loaded. Real time is multiplied by the speed before it's added,
+// and fractions carry over, so at 0.25x a frame runs every fourth
+// host frame. Timers have their own, so they speed up and slow
+// down along with the game.
+let frameClock = null;
+let timerClock = null;
+let lastTs = 0;
+let tracing = false;
+let romKey = null;
+let showOverlay = false;
+let showMemory = false;
+let memoryPage = 2; // Programs usually start at 0x200
+let showInspector = false;
+let inspector = null; // WasmInspector for the current machine
+let font = null;
+
+// Buzzer. The oscillator runs all the time and the gain is
+// faded in and out, which avoids clicks at the edges of a beep.
+// The tone starts and stops on the timer ticks that turn the
+// buzzer on and off, scheduled BEEP_LEAD_S ahead at the time each
+// tick was due, so a frame's ticks keep their spacing and a beep
+// lasts exactly as many ticks as it was set for.
+const MIN_BEEP_MS = 50; // One-tick beeps are too short to hear
+const BEEP_LEAD_S = 0.02;
+const WAVES = ["square", "sine", "triangle"];
+let audioCtx = null;
+let osc = null;
+let gain = null;
+let beepFreq = 440;
+let beepVolume = 0.3; // 0 mutes
+let beepWave = "square";
+let beeping = false;
+let beepStart = 0; // audioCtx time the current beep started
+const beepLight = document.getElementById("beep-light");
+
+// XO-CHIP ROMs can load their own 1-bit waveform (F002), 128
+// samples looped at a rate the ROM sets (FX3A). It plays in place
+// of the oscillator, through the same gain, so it follows the
+// sound timer like the beep does.
+const PATTERN_RATE = 8000; // Sample rate of the pattern buffer
+let patternSrc = null;
+let patternBytes = ""; // What patternSrc plays, to spot changes
 
 // Default pixel colors
 let COLOR_ON = [0x39, 0xff, 0x14];
 let COLOR_OFF = [0x00, 0x00, 0x00];
+// XO-CHIP's second plane, and pixels set in both planes
+let COLOR_PLANE2 = [0xff, 0x66, 0x00];
+let COLOR_BOTH = [0xff, 0xcc, 0x00];
 
 function log(msg, type = "") {
   const ts = performance.now().toFixed(0).padStart(7, "0");
@@ -53,96 +134,631 @@ function log(msg, type = "") {
   logEl.scrollTop = logEl.scrollHeight;
 }
 
-const imgData = ctx.createImageData(W, H);
+let imgData = ctx.createImageData(W, H);
+
+// Phosphor mode: instead of going black at once, a cleared pixel
+// fades over a few frames, which hides most XOR flicker. The
+// display is then drawn once per frame rather than on every DXYN.
+const PHOSPHOR_DECAY = 0.5; // Intensity kept per frame
+let phosphor = false;
+let glow = new Uint8Array(W * H); // 0-255 per pixel
+let glowColor = new Uint8Array(W * H); // Pixel value it fades from
+
+// Hi-res ROMs get a taller display when they're loaded (see
+// load_rom()), and 00FE/00FF switch between 64x32 and 128x64 as
+// they run. The canvas and the per-pixel buffers follow the
+// machine's size, and the display stays inside the 2:1 frame
+// with black either side.
+function fitDisplay() {
+  const cols = chip8.display_width(),
+    rows = chip8.display_height();
+  if (cols === W && rows === H) return;
+  [W, H] = [cols, rows];
+  canvas.width = W;
+  canvas.height = H;
+  imgData = ctx.createImageData(W, H);
+  glow = new Uint8Array(W * H);
+  glowColor = new Uint8Array(W * H);
+  screenWrap.style.setProperty("--display-aspect", W / H);
+  drawCrt();
+}
+
+function hex(n, width) {
+  return "0x" + n.toString(16).toUpperCase().padStart(width, "0");
+}
+
+// Logs the last `count` traced instructions, oldest first
+function dumpTrace(count) {
+  const trace = chip8.trace();
+  const start = Math.max(0, trace.length - count * 2);
+  for (let i = start; i < trace.length; i += 2) {
+    const op = trace[i + 1];
+    log(`PC=${hex(trace[i], 4)} OP=${hex(op, 4)}  ${disassemble(op)}`);
+  }
+}
+
+// Full traces are far too chatty for the log panel, so they go
+// to the browser console instead
+function flushTrace() {
+  const lines = chip8.take_trace_log();
+  if (lines) console.log(lines.trimEnd());
+}
 
 function render(display) {
+  fitDisplay();
   const d = imgData.data;
+  const colors = [COLOR_OFF, COLOR_ON, COLOR_PLANE2, COLOR_BOTH];
   for (let i = 0; i < W * H; i++) {
-    const on = display[i];
-    d[i * 4] = on ? COLOR_ON[0] : COLOR_OFF[0];
-    d[i * 4 + 1] = on ? COLOR_ON[1] : COLOR_OFF[1];
-    d[i * 4 + 2] = on ? COLOR_ON[2] : COLOR_OFF[2];
+    const p = display[i] & 3;
+    let c = colors[p];
+    if (phosphor) {
+      // Lit pixels are at full intensity; cleared ones fade out
+      // from the color they last had
+      if (p) {
+        glow[i] = 255;
+        glowColor[i] = p;
+      } else if (glow[i]) {
+        const k = glow[i] / 255,
+          lit = colors[glowColor[i]];
+        c = COLOR_OFF.map((off, j) => off + (lit[j] - off) * k);
+        glow[i] = Math.floor(glow[i] * PHOSPHOR_DECAY);
+      }
+    }
+    d[i * 4] = c[0];
+    d[i * 4 + 1] = c[1];
+    d[i * 4 + 2] = c[2];
     d[i * 4 + 3] = 255;
   }
   ctx.putImageData(imgData, 0, 0);
 }
 
 let fpsFrames = 0,
-  fpsLast = 0;
+  fpsLast = 0,
+  // cycle_count() when the current second started
+  ipsFrom = 0,
+  fps = 0,
+  ips = 0;
 const fpsEl = document.getElementById("fps-display");
 
+// Tab (fast) wins over Shift (slow)
+function speed() {
+  if (turbo) return turboFactor;
+  if (slow) return SLOW_SPEED;
+  return 1;
+}
+
+function updateTitle() {
+  const s = speed();
+  if (stopReason) document.title = `${TITLE} - stopped: ${stopReason}`;
+  else document.title = s === 1 ? TITLE : `${TITLE} (${s}x)`;
+}
+
+// Runs one instruction and draws if needed. The core throws when
+// the ROM does something it can't continue from (like overflowing
+// the stack), which stops emulation.
+function runCycle() {
+  try {
+    chip8.emulate_cycle();
+    if (chip8.take_draw_flag() && !phosphor) render(framebuffer());
+    return true;
+  } catch (err) {
+    pauseEmulation();
+    log(`Stopped: ${err.message}`, "err");
+    // e.g. "Invalid opcode 0xF301 at 0x200"
+    stopReason = err.message;
+    updateTitle();
+    return false;
+  }
+}
+
+// The display as a view into wasm memory rather than a copy.
+// Views go stale when memory grows, so this makes a fresh one.
+function framebuffer() {
+  return new Uint8Array(wasm.memory.buffer, chip8.framebuffer_ptr(), chip8.framebuffer_len());
+}
+
+// Steps back one recorded frame, if there is one left
+function rewindFrame() {
+  if (chip8.rewind()) {
+    chip8.take_draw_flag();
+    render(framebuffer());
+  }
+}
+
 function frame(ts) {
   if (!running) return;
 
-  for (let i = 0; i < cpf; i++) {
-    const display = chip8.emulate_cycle();
-    if (chip8.draw_flag()) {
-      render(display);
-      chip8.clear_draw_flag();
-    }
-  }
+  // One batch of CPF cycles per elapsed 60Hz frame, and one timer
+  // tick per elapsed timer interval (60Hz unless configured), both
+  // in emulated time. A long stall (tab switch, GC pause, window
+  // drag) is only caught up on so far rather than spiralling.
+  const elapsed = Math.min(ts - lastTs, MAX_CATCHUP_MS) * speed();
+  lastTs = ts;
+  const frames = frameClock.take(elapsed);
+  const timerTicks = timerClock.take(elapsed);
 
-  if (ts - lastTimer >= TIMER_MS) {
-    chip8.update_timers();
-    lastTimer = ts;
+  if (rewinding) {
+    // Play recorded frames backwards at normal speed
+    for (let f = 0; f < frames; f++) rewindFrame();
+  } else {
+    for (let f = 0; f < frames; f++) {
+      chip8.push_rewind();
+      chip8.vblank();
+      for (let i = 0; i < cpf; i++) {
+        if (!runCycle()) return;
+        if (chip8.waiting_for_vblank()) break;
+      }
+      if (phosphor) render(framebuffer());
+    }
+    const tickMs = 1000 / timerHz;
+    for (let t = 0; t < timerTicks; t++) {
+      chip8.update_timers();
+      // Real time since this tick was due
+      beepEdge(((timerTicks - 1 - t) * tickMs + timerClock.carried_ms()) / speed());
+    }
   }
+  if (gain) updatePattern();
+  beepLight.classList.toggle("on", chip8.beeping());
+  if (tracing) flushTrace();
+  updateHealth();
 
   fpsFrames++;
   if (ts - fpsLast >= 1000) {
-    fpsEl.textContent = `${fpsFrames} FPS`;
+    fps = fpsFrames;
+    // What actually ran, so display wait stalls and stops count
+    ips = chip8.cycle_count() - ipsFrom;
+    fpsEl.textContent = `${fps} FPS`;
     fpsFrames = 0;
+    ipsFrom = chip8.cycle_count();
     fpsLast = ts;
   }
 
+  drawOverlay();
+
+  drawMemory();
+  drawInspector();
+
   rafId = requestAnimationFrame(frame);
 }
 
+// Draws a string with the emulator's 4x5 hex font, each font
+// pixel being `size` canvas pixels. Characters the font can't
+// represent are left as blank cells.
+function drawText(c, text, x, y, size) {
+  for (const ch of text) {
+    const digit = parseInt(ch, 16);
+    if (!Number.isNaN(digit)) {
+      for (let row = 0; row < 5; row++) {
+        const bits = font[digit * 5 + row];
+        for (let col = 0; col < 4; col++) {
+          if (bits & (0x80 >> col)) {
+            c.fillRect(x + col * size, y + row * size, size, size);
+          }
+        }
+      }
+    }
+    x += 5 * size;
+  }
+}
+
+function drawOverlay() {
+  octx.clearRect(0, 0, overlay.width, overlay.height);
+  if (!showOverlay || !chip8) return;
+
+  const rows = [
+    ["FPS", fps],
+    ["IPS", ips],
+    ["CPF", cpf],
+    ["DT", chip8.delay_timer()],
+    ["ST", chip8.sound_timer()],
+  ];
+  const size = 3,
+    lineH = 7 * size,
+    boxW = 150,
+    x = overlay.width - boxW - 8,
+    y = 8;
+
+  octx.fillStyle = "rgba(0, 0, 0, 0.7)";
+  octx.fillRect(x, y, boxW, rows.length * lineH + 2 * size);
+  octx.font = `${5 * size}px "Share Tech Mono", monospace`;
+  octx.textBaseline = "top";
+  rows.forEach(([label, value], i) => {
+    const ly = y + 2 * size + i * lineH;
+    octx.fillStyle = "#4a7a44";
+    octx.fillText(label, x + 2 * size, ly);
+    octx.fillStyle = "#00ccff";
+    drawText(octx, String(value), x + 16 * size, ly, size);
+  });
+}
+
+function toggleOverlay() {
+  showOverlay = !showOverlay;
+  drawOverlay();
+}
+
+// Memory viewer: one 256-byte page as a 16x16 grid of cells.
+// Zero bytes are dark, others brighter the bigger they are. PC
+// (both opcode bytes) is outlined in cyan and I in orange.
+function drawMemory() {
+  if (!showMemory || !chip8) return;
+
+  const cw = memoryCanvas.width / 16,
+    ch = memoryCanvas.height / 16,
+    base = memoryPage * 0x100;
+  mctx.fillStyle = "#000";
+  mctx.fillRect(0, 0, memoryCanvas.width, memoryCanvas.height);
+  chip8.memory_page(memoryPage).forEach((b, n) => {
+    const k = b === 0 ? 0.08 : 0.25 + (0.75 * b) / 255;
+    mctx.fillStyle = `rgb(${COLOR_ON.map((c) => Math.round(c * k)).join(",")})`;
+    mctx.fillRect(
+      (n % 16) * cw + 1,
+      Math.floor(n / 16) * ch + 1,
+      cw - 2,
+      ch - 2,
+    );
+  });
+
+  const mark = (addr, color) => {
+    const n = addr - base;
+    if (n < 0 || n >= 0x100) return;
+    mctx.strokeStyle = color;
+    mctx.lineWidth = 3;
+    mctx.strokeRect(
+      (n % 16) * cw + 1.5,
+      Math.floor(n / 16) * ch + 1.5,
+      cw - 3,
+      ch - 3,
+    );
+  };
+  const pc = chip8.pc();
+  mark(pc, "#00ccff");
+  mark(pc + 1, "#00ccff");
+  mark(chip8.i(), "#ffaa00");
+}
+
+// Register inspector: V0-VF, I, PC, the timers and the return
+// addresses on the stack. While paused, V0-VF, I and PC can be
+// clicked (or tabbed to) and typed over in hex.
+const INSPECTOR_SIZE = 3; // Canvas pixels per font pixel
+const INSPECTOR_PAD = 12;
+
+function drawInspector() {
+  if (!showInspector || !chip8) return;
+
+  const size = INSPECTOR_SIZE,
+    cw = 5 * size,
+    lineH = 7 * size;
+  ictx.fillStyle = "#000";
+  ictx.fillRect(0, 0, inspectorCanvas.width, inspectorCanvas.height);
+  ictx.font = `${5 * size}px "Share Tech Mono", monospace`;
+  ictx.textBaseline = "top";
+  const cells = inspector.cells(chip8);
+  inspector.labels(chip8).forEach((label, n) => {
+    const [col, row, value, digits, state] = cells.slice(n * 5, n * 5 + 5);
+    const x = INSPECTOR_PAD + col * cw,
+      y = INSPECTOR_PAD + row * lineH,
+      vx = x + 3 * cw;
+    ictx.fillStyle = "#4a7a44";
+    ictx.fillText(label, x, y);
+    if (state === 2) {
+      ictx.strokeStyle = "#ffaa00";
+      ictx.lineWidth = 2;
+      ictx.strokeRect(vx - size, y - size, 4 * cw + size, lineH);
+    }
+    // Values that can't be edited are dimmer
+    ictx.fillStyle = state === 0 ? "#4a7a44" : "#00ccff";
+    drawText(ictx, value.toString(16).toUpperCase().padStart(digits, "0"), vx, y, size);
+  });
+  // How close the stack is to overflowing, on the row above it
+  ictx.fillStyle = "#4a7a44";
+  ictx.fillText(
+    `CALLS ${chip8.stack_depth()}/${chip8.stack_limit()}`,
+    INSPECTOR_PAD,
+    INSPECTOR_PAD + 5 * lineH,
+  );
+}
+
+// Made again whenever the machine is, since how many digits an
+// address has depends on the memory size
+function newInspector() {
+  inspector = new WasmInspector(chip8.memory_size());
+  inspector.set_paused(!running);
+}
+
+function toggleInspector() {
+  showInspector = !showInspector;
+  inspectorWrap.hidden = !showInspector;
+  drawInspector();
+}
+
+// Keys for the inspector while it's open and the machine is
+// paused: Tab/Shift+Tab move between values, hex digits type over
+// the selected one and Esc backs out. Returns whether `e` was used.
+function inspectorKey(e) {
+  if (running || !chip8) return false;
+  if (e.key === "Tab") {
+    inspector.select_next(!e.shiftKey);
+    drawInspector();
+    return true;
+  }
+  if (!inspector.has_selection()) return false;
+  if (e.key === "Escape") {
+    inspector.escape();
+    drawInspector();
+    return true;
+  }
+  const digit = parseInt(e.key, 16);
+  if (e.key.length !== 1 || Number.isNaN(digit)) return false;
+  try {
+    if (inspector.type_digit(chip8, digit)) {
+      drawOverlay();
+      drawMemory();
+    }
+  } catch (err) {
+    log(`Can't set that: ${err.message}`, "err");
+  }
+  drawInspector();
+  return true;
+}
+
+inspectorCanvas.addEventListener("click", (e) => {
+  if (!chip8) return;
+  if (running) {
+    log("Pause to edit registers");
+    return;
+  }
+  const r = inspectorCanvas.getBoundingClientRect();
+  const x = ((e.clientX - r.left) * inspectorCanvas.width) / r.width - INSPECTOR_PAD,
+    y = ((e.clientY - r.top) * inspectorCanvas.height) / r.height - INSPECTOR_PAD;
+  if (x < 0 || y < 0) return;
+  const col = Math.floor(x / (5 * INSPECTOR_SIZE)),
+    row = Math.floor(y / (7 * INSPECTOR_SIZE));
+  inspector.click(chip8, col, row);
+  drawInspector();
+});
+
+function setMemoryPage(page) {
+  const pages = chip8 ? Math.ceil(chip8.memory_size() / 0x100) : 16;
+  memoryPage = Math.max(0, Math.min(pages - 1, page));
+  memoryPageEl.textContent =
+    "0x" + (memoryPage * 0x100).toString(16).toUpperCase().padStart(3, "0");
+  drawMemory();
+}
+
+function toggleMemory() {
+  showMemory = !showMemory;
+  memoryWrap.hidden = !showMemory;
+  setMemoryPage(memoryPage);
+}
+
+function toggleFullscreen() {
+  if (document.fullscreenElement) {
+    document.exitFullscreen();
+  } else {
+    screenWrap.requestFullscreen().catch((err) => {
+      log(`Fullscreen failed: ${err.message}`, "err");
+    });
+  }
+}
+
 function setStatus(s) {
   statusEl.textContent = s;
   statusDot.classList.toggle("running", s === "RUNNING");
 }
 
+// A ROM that has stopped on a jump to itself, or spins without
+// drawing or reading the keys, otherwise looks like a frozen page
+function updateHealth() {
+  let s = "RUNNING";
+  if (chip8.is_halted()) s = "HALTED";
+  else if (chip8.is_looping()) s = "LOOPING";
+  if (statusEl.textContent !== s) setStatus(s);
+}
+
 function startEmulation() {
   if (!chip8 || running) return;
+  initAudio();
   running = true;
-  lastTimer = performance.now();
+  updateBeep();
+  inspector.set_paused(false);
+  stopReason = null;
+  updateTitle();
+  lastTs = performance.now();
+  ipsFrom = chip8.cycle_count();
+  frameClock.reset();
+  timerClock.reset();
   setStatus("RUNNING");
   log("Emulation started", "ok");
   rafId = requestAnimationFrame(frame);
   document.getElementById("btn-run").disabled = true;
   document.getElementById("btn-pause").disabled = false;
   document.getElementById("btn-step").disabled = true;
+  document.getElementById("btn-step-back").disabled = true;
 }
 
 function pauseEmulation() {
   if (!running) return;
   running = false;
+  inspector.set_paused(true);
   cancelAnimationFrame(rafId);
+  updateBeep();
   setStatus("PAUSED");
   log("Paused");
   document.getElementById("btn-run").disabled = false;
   document.getElementById("btn-pause").disabled = true;
   document.getElementById("btn-step").disabled = false;
+  document.getElementById("btn-step-back").disabled = false;
+  if (tracing) dumpTrace(16);
 }
 
 function resetEmulation() {
   pauseEmulation();
   if (chip8) {
     chip8.reset();
+    newInspector();
+    chip8.load_rom(romBytes);
+    fitDisplay();
+    if (tracing) chip8.enable_trace();
     ctx.clearRect(0, 0, W, H);
     setStatus("READY");
+    stopReason = null;
+    updateTitle();
+    drawOverlay();
+    drawMemory();
+    drawInspector();
     log("Reset", "ok");
   }
 }
 
 function stepEmulation() {
   if (running || !chip8) return;
-  const display = chip8.emulate_cycle();
-  chip8.update_timers();
-  if (chip8.draw_flag()) {
-    render(display);
-    chip8.clear_draw_flag();
+  chip8.vblank();
+  if (runCycle()) chip8.update_timers();
+  if (phosphor) render(framebuffer());
+  if (tracing) {
+    dumpTrace(1);
+    flushTrace();
+  }
+  drawOverlay();
+  drawMemory();
+  drawInspector();
+}
+
+// Takes back one instruction (see WasmChip8.undo)
+function stepBack() {
+  if (running || !chip8) return;
+  if (!chip8.undo()) {
+    log("Nothing to step back to");
+    return;
+  }
+  chip8.take_draw_flag();
+  render(framebuffer());
+  drawOverlay();
+  drawMemory();
+  drawInspector();
+}
+
+function toggleTrace() {
+  tracing = !tracing;
+  document.getElementById("btn-trace").classList.toggle("on", tracing);
+  if (chip8) {
+    if (tracing) chip8.enable_trace();
+    else chip8.disable_trace();
   }
+  log(`Trace ${tracing ? "enabled" : "disabled"}`);
+}
+
+// Quirk toggles: name -> [button id, WasmChip8 setter, log label]
+const QUIRKS = {
+  wrapX: ["btn-wrap-x", "set_wrap_x", "Horizontal wrapping"],
+  wrapY: ["btn-wrap-y", "set_wrap_y", "Vertical wrapping"],
+  displayWait: ["btn-vblank", "set_display_wait", "Display wait"],
+  shiftVy: ["btn-shift", "set_shift_uses_vy", "VY shifts"],
+  incrementI: ["btn-inc-i", "set_index_increment_on_store", "I increment"],
+  vfReset: ["btn-vf-reset", "set_vf_reset_on_logic", "VF reset"],
+  ignore0nnn: ["btn-0nnn", "set_ignore_machine_code", "Ignoring 0NNN"],
+  jumpVx: ["btn-jump-vx", "set_jump_with_vx", "BXNN jumps"],
+  halfScroll: ["btn-half-scroll", "set_half_scroll", "Half-distance scrolling"],
+  keyGuard: ["btn-key-guard", "set_key_repeat_guard", "Key repeat guard"],
+};
+const quirks = {
+  wrapX: true,
+  wrapY: true,
+  displayWait: false,
+  shiftVy: false,
+  incrementI: false,
+  vfReset: false,
+  ignore0nnn: false,
+  jumpVx: false,
+  halfScroll: false,
+  keyGuard: false,
+};
+
+function setQuirk(name, on) {
+  const [btn, setter] = QUIRKS[name];
+  quirks[name] = on;
+  document.getElementById(btn).classList.toggle("on", on);
+  if (chip8) chip8[setter](on);
+}
+
+function toggleQuirk(name) {
+  setQuirk(name, !quirks[name]);
+  saveSettings();
+  log(`${QUIRKS[name][2]} ${quirks[name] ? "enabled" : "disabled"}`);
+}
+
+// Per-ROM settings (speed, colors, quirks) are remembered in
+// localStorage, keyed by a hash of the ROM so renamed files
+// still match
+function hashRom(bytes) {
+  let h = 0x811c9dc5;
+  for (const b of bytes) {
+    h ^= b;
+    h = Math.imul(h, 0x01000193);
+  }
+  return "chip8-emu:rom:" + (h >>> 0).toString(16).padStart(8, "0");
+}
+
+function saveSettings() {
+  if (!romKey) return;
+  const settings = {
+    cpf,
+    timerHz,
+    turboFactor,
+    beepFreq,
+    beepVolume: Math.round(beepVolume * 100),
+    beepWave,
+    colorOn: document.getElementById("color-on").value,
+    colorOff: document.getElementById("color-off").value,
+    colorPlane2: document.getElementById("color-plane2").value,
+    colorBoth: document.getElementById("color-both").value,
+    phosphor,
+    crt,
+    xoChip,
+    ...quirks,
+  };
+  try {
+    localStorage.setItem(romKey, JSON.stringify(settings));
+  } catch (err) {
+    log(`Couldn't save settings: ${err.message}`, "err");
+  }
+}
+
+// Anything missing or malformed keeps its current value
+function loadSettings() {
+  let settings;
+  try {
+    settings = JSON.parse(localStorage.getItem(romKey));
+  } catch (err) {
+    log(`Ignoring saved settings: ${err.message}`, "err");
+    return;
+  }
+  if (!settings || typeof settings !== "object") return;
+
+  const isInt = (v, min, max) => Number.isInteger(v) && v >= min && v <= max;
+  const isColor = (v) => typeof v === "string" && /^#[0-9a-f]{6}$/i.test(v);
+  if (isInt(settings.cpf, 1, 50)) setCpf(settings.cpf);
+  if (isInt(settings.timerHz, 1, 240)) setTimerHz(settings.timerHz);
+  if (isInt(settings.turboFactor, 2, 16)) setTurboFactor(settings.turboFactor);
+  if (isInt(settings.beepFreq, 110, 1760)) setBeepFreq(settings.beepFreq);
+  if (isInt(settings.beepVolume, 0, 100)) setBeepVolume(settings.beepVolume);
+  if (WAVES.includes(settings.beepWave)) setBeepWave(settings.beepWave);
+  if (isColor(settings.colorOn)) setColorOn(settings.colorOn);
+  if (isColor(settings.colorOff)) setColorOff(settings.colorOff);
+  if (isColor(settings.colorPlane2)) setColorPlane2(settings.colorPlane2);
+  if (isColor(settings.colorBoth)) setColorBoth(settings.colorBoth);
+  if (typeof settings.phosphor === "boolean") setPhosphor(settings.phosphor);
+  if (typeof settings.crt === "boolean") setCrt(settings.crt);
+  if (typeof settings.xoChip === "boolean") setXoChip(settings.xoChip);
+  // Saved before wrapping could be set for each axis
+  if (typeof settings.wrap === "boolean") {
+    setQuirk("wrapX", settings.wrap);
+    setQuirk("wrapY", settings.wrap);
+  }
+  for (const name in QUIRKS) {
+    if (typeof settings[name] === "boolean") setQuirk(name, settings[name]);
+  }
+  log("Restored saved settings", "ok");
 }
 
 function loadRom(file) {
@@ -150,14 +766,24 @@ function loadRom(file) {
   reader.onload = (e) => {
     try {
       romBytes = new Uint8Array(e.target.result);
-      chip8 = new WasmChip8();
+      const loadAddr = parseInt(document.getElementById("load-addr").value, 16);
+      if (Number.isNaN(loadAddr)) throw new Error("Invalid load address");
+      chip8 = WasmChip8.with_start(loadAddr);
+      romKey = hashRom(romBytes);
+      loadSettings();
+      if (xoChip) chip8.set_xo_chip(true);
+      newInspector();
+      for (const name in QUIRKS) setQuirk(name, quirks[name]);
       chip8.load_rom(romBytes);
+      fitDisplay();
+      if (tracing) chip8.enable_trace();
       romNameEl.textContent = `► ${file.name} (${romBytes.length}b)`;
-      ["btn-run", "btn-step", "btn-reset"].forEach(
+      ["btn-run", "btn-step", "btn-step-back", "btn-reset"].forEach(
         (id) => (document.getElementById(id).disabled = false),
       );
       setStatus("READY");
       ctx.clearRect(0, 0, W, H);
+      setMemoryPage(memoryPage);
       log(`Loaded: ${file.name}`, "ok");
     } catch (err) {
       log(`Error: ${err.message}`, "err");
@@ -197,8 +823,76 @@ KEYPAD_LAYOUT.forEach(([hex, pc]) => {
   keyEls[hex] = el;
 });
 
+// Don't steal keystrokes meant for the settings fields
+function isTyping(e) {
+  return e.target instanceof HTMLInputElement && e.target.type === "text";
+}
+
 document.addEventListener("keydown", (e) => {
-  const k = KB_MAP[e.key.toLowerCase()];
+  if (isTyping(e)) return;
+  if (e.key === "F1") {
+    e.preventDefault();
+    toggleOverlay();
+    return;
+  }
+  if (e.key === "F2") {
+    e.preventDefault();
+    toggleMemory();
+    return;
+  }
+  if (showMemory && (e.key === "PageUp" || e.key === "PageDown")) {
+    e.preventDefault();
+    setMemoryPage(memoryPage + (e.key === "PageDown" ? 1 : -1));
+    return;
+  }
+  if (e.key === "F3") {
+    e.preventDefault();
+    fileInput.click();
+    return;
+  }
+  if (e.key === "F4") {
+    e.preventDefault();
+    toggleInspector();
+    return;
+  }
+  if (showInspector && inspectorKey(e)) {
+    e.preventDefault();
+    return;
+  }
+  if (e.key === "F6") {
+    e.preventDefault();
+    toggleCrt();
+    return;
+  }
+  if (e.key === "F11" || (e.key === "Enter" && e.altKey)) {
+    e.preventDefault();
+    toggleFullscreen();
+    return;
+  }
+  if (e.key === "Backspace") {
+    e.preventDefault();
+    rewinding = true;
+    // While paused, each press steps back one frame
+    if (!running && chip8) {
+      rewindFrame();
+      drawOverlay();
+      drawMemory();
+      drawInspector();
+    }
+    return;
+  }
+  if (e.key === "Tab") {
+    e.preventDefault();
+    turbo = true;
+    updateTitle();
+    return;
+  }
+  if (e.key === "Shift") {
+    slow = true;
+    updateTitle();
+    return;
+  }
+  const k = keypadKey(e);
   if (k !== undefined) {
     e.preventDefault();
     if (chip8) chip8.key_down(k);
@@ -206,7 +900,12 @@ document.addEventListener("keydown", (e) => {
   }
 });
 document.addEventListener("keyup", (e) => {
-  const k = KB_MAP[e.key.toLowerCase()];
+  if (e.key === "Tab") turbo = false;
+  if (e.key === "Shift") slow = false;
+  if (e.key === "Backspace") rewinding = false;
+  updateTitle();
+  if (isTyping(e)) return;
+  const k = keypadKey(e);
   if (k !== undefined) {
     if (chip8) chip8.key_up(k);
     if (keyEls[k]) keyEls[k].classList.remove("active");
@@ -216,22 +915,297 @@ document.addEventListener("keyup", (e) => {
 document.getElementById("btn-run").addEventListener("click", startEmulation);
 document.getElementById("btn-pause").addEventListener("click", pauseEmulation);
 document.getElementById("btn-step").addEventListener("click", stepEmulation);
+document.getElementById("btn-step-back").addEventListener("click", stepBack);
 document.getElementById("btn-reset").addEventListener("click", resetEmulation);
+document.getElementById("btn-trace").addEventListener("click", toggleTrace);
+for (const [name, [btn]] of Object.entries(QUIRKS)) {
+  document.getElementById(btn).addEventListener("click", () => toggleQuirk(name));
+}
+
+// XO-CHIP changes the machine's memory size, so switching it
+// starts the ROM over
+let xoChip = false;
+
+function setXoChip(on) {
+  xoChip = on;
+  document.getElementById("btn-xo-chip").classList.toggle("on", on);
+}
+
+function toggleXoChip() {
+  setXoChip(!xoChip);
+  saveSettings();
+  log(`XO-CHIP ${xoChip ? "enabled" : "disabled"}`);
+  if (chip8) {
+    chip8.set_xo_chip(xoChip);
+    resetEmulation();
+  }
+}
+
+document.getElementById("btn-xo-chip").addEventListener("click", toggleXoChip);
+
+function setCpf(v) {
+  cpf = v;
+  cpfSlider.value = v;
+  cpfVal.textContent = v;
+}
+
+// Browsers only allow audio to start from a user gesture, so
+// this is called when emulation is started
+function initAudio() {
+  if (audioCtx) return;
+  audioCtx = new AudioContext();
+  gain = audioCtx.createGain();
+  gain.gain.value = 0;
+  gain.connect(audioCtx.destination);
+  osc = audioCtx.createOscillator();
+  osc.type = beepWave;
+  osc.frequency.value = beepFreq;
+  osc.connect(gain);
+  osc.start();
+}
+
+// Turns the tone on or off at audioCtx time `at`, dropping
+// anything scheduled after
+function setTone(on, at) {
+  gain.gain.cancelScheduledValues(at);
+  gain.gain.setTargetAtTime(on ? beepVolume : 0, at, 0.005);
+}
+
+// Called after each timer tick, `agoMs` after it was due. Each
+// beep is held for at least MIN_BEEP_MS so a single-tick beep is
+// still audible.
+function beepEdge(agoMs) {
+  if (!gain) return;
+  const now = audioCtx.currentTime;
+  const at = Math.max(now, now + BEEP_LEAD_S - agoMs / 1000);
+  if (chip8.beep_started()) {
+    beeping = true;
+    beepStart = at;
+    setTone(true, at);
+  } else if (chip8.beep_ended()) {
+    beeping = false;
+    setTone(false, Math.max(at, beepStart + MIN_BEEP_MS / 1000));
+  }
+}
+
+// Matches the tone to the buzzer right away, on pausing and
+// resuming
+function updateBeep() {
+  if (!gain) return;
+  updatePattern();
+  beeping = running && chip8.beeping();
+  setTone(beeping, audioCtx.currentTime);
+  beepLight.classList.toggle("on", beeping);
+}
+
+function updatePattern() {
+  const pattern = chip8 && chip8.audio_pattern();
+  const bytes = pattern ? pattern.join(",") : "";
+  if (bytes !== patternBytes) {
+    patternBytes = bytes;
+    if (patternSrc) patternSrc.stop();
+    else osc.disconnect();
+    patternSrc = null;
+    if (pattern) {
+      const buffer = audioCtx.createBuffer(1, 128, PATTERN_RATE);
+      const samples = buffer.getChannelData(0);
+      for (let i = 0; i < 128; i++) {
+        samples[i] = (pattern[i >> 3] >> (7 - (i & 7))) & 1 ? 1 : -1;
+      }
+      patternSrc = audioCtx.createBufferSource();
+      patternSrc.buffer = buffer;
+      patternSrc.loop = true;
+      patternSrc.connect(gain);
+      patternSrc.start();
+    } else {
+      osc.connect(gain);
+    }
+  }
+  if (patternSrc) patternSrc.playbackRate.value = chip8.audio_rate() / PATTERN_RATE;
+}
+
+function setBeepFreq(v) {
+  beepFreq = v;
+  freqSlider.value = v;
+  freqVal.textContent = v;
+  if (osc) osc.frequency.setTargetAtTime(v, audioCtx.currentTime, 0.01);
+}
+
+function setBeepVolume(percent) {
+  beepVolume = percent / 100;
+  volumeSlider.value = percent;
+  volumeVal.textContent = percent;
+  if (gain && beeping) setTone(true, audioCtx.currentTime);
+}
+
+function setBeepWave(wave) {
+  beepWave = wave;
+  waveSelect.value = wave;
+  if (osc) osc.type = wave;
+}
+
+function setTurboFactor(v) {
+  turboFactor = v;
+  turboSlider.value = v;
+  turboVal.textContent = `×${v}`;
+}
+
+function setTimerHz(v) {
+  timerHz = v;
+  timerClock?.set_hz(v);
+  timerSlider.value = v;
+  timerVal.textContent = v;
+}
 
 cpfSlider.addEventListener("input", () => {
-  cpf = parseInt(cpfSlider.value);
-  cpfVal.textContent = cpf;
+  setCpf(parseInt(cpfSlider.value));
+});
+cpfSlider.addEventListener("change", saveSettings);
+
+timerSlider.addEventListener("input", () => {
+  setTimerHz(parseInt(timerSlider.value));
+});
+timerSlider.addEventListener("change", saveSettings);
+
+turboSlider.addEventListener("input", () => {
+  setTurboFactor(parseInt(turboSlider.value));
+});
+turboSlider.addEventListener("change", saveSettings);
+
+freqSlider.addEventListener("input", () => {
+  setBeepFreq(parseInt(freqSlider.value));
+});
+freqSlider.addEventListener("change", saveSettings);
+
+volumeSlider.addEventListener("input", () => {
+  setBeepVolume(parseInt(volumeSlider.value));
+});
+volumeSlider.addEventListener("change", saveSettings);
+
+waveSelect.addEventListener("change", () => {
+  setBeepWave(waveSelect.value);
+  saveSettings();
+});
+
+// The keyup is lost if the page loses focus while a key is held
+window.addEventListener("blur", () => {
+  turbo = false;
+  slow = false;
+  rewinding = false;
+  updateTitle();
 });
 
 function hexToRgb(hex) {
   const n = parseInt(hex.slice(1), 16);
   return [(n >> 16) & 0xff, (n >> 8) & 0xff, n & 0xff];
 }
+
+function setColorOn(hex) {
+  COLOR_ON = hexToRgb(hex);
+  document.getElementById("color-on").value = hex;
+}
+
+function setColorOff(hex) {
+  COLOR_OFF = hexToRgb(hex);
+  document.getElementById("color-off").value = hex;
+  screenWrap.style.background = hex;
+}
+
+function setColorPlane2(hex) {
+  COLOR_PLANE2 = hexToRgb(hex);
+  document.getElementById("color-plane2").value = hex;
+}
+
+function setColorBoth(hex) {
+  COLOR_BOTH = hexToRgb(hex);
+  document.getElementById("color-both").value = hex;
+}
+
 document.getElementById("color-on").addEventListener("input", (e) => {
-  COLOR_ON = hexToRgb(e.target.value);
+  setColorOn(e.target.value);
 });
+document.getElementById("color-on").addEventListener("change", saveSettings);
 document.getElementById("color-off").addEventListener("input", (e) => {
-  COLOR_OFF = hexToRgb(e.target.value);
+  setColorOff(e.target.value);
+});
+document.getElementById("color-off").addEventListener("change", saveSettings);
+document.getElementById("color-plane2").addEventListener("input", (e) => {
+  setColorPlane2(e.target.value);
+});
+document.getElementById("color-plane2").addEventListener("change", saveSettings);
+document.getElementById("color-both").addEventListener("input", (e) => {
+  setColorBoth(e.target.value);
+});
+document.getElementById("color-both").addEventListener("change", saveSettings);
+
+// The CRT look is a fixed layer over the display: darker
+// alternate rows of real screen pixels (scanlines), a thin gap
+// between CHIP-8 pixels, and corners fading out. It only depends
+// on the display's size, so it's drawn again when that changes
+// and costs nothing per frame. Being a separate layer it works
+// the same with any colors and with phosphor.
+const CRT_SCANLINE = 0.3; // How much every other row is darkened
+const CRT_GAP = 0.35; // Darkening of the lines between pixels
+const CRT_VIGNETTE = 0.45; // Darkening in the corners
+let crt = false;
+
+function drawCrt() {
+  if (!crt) return;
+  const scale = window.devicePixelRatio || 1;
+  const w = Math.round(crtCanvas.clientWidth * scale),
+    h = Math.round(crtCanvas.clientHeight * scale);
+  if (!w || !h) return;
+  crtCanvas.width = w;
+  crtCanvas.height = h;
+
+  const cctx = crtCanvas.getContext("2d");
+  cctx.fillStyle = `rgba(0, 0, 0, ${CRT_SCANLINE})`;
+  for (let y = 1; y < h; y += 2) cctx.fillRect(0, y, w, 1);
+
+  // Gaps only once pixels are big enough to spare a line
+  const px = w / W;
+  if (px >= 4) {
+    cctx.fillStyle = `rgba(0, 0, 0, ${CRT_GAP})`;
+    for (let x = 1; x < W; x++) cctx.fillRect(Math.round(x * px), 0, 1, h);
+    for (let y = 1; y < H; y++) cctx.fillRect(0, Math.round(y * px), w, 1);
+  }
+
+  const [cx, cy] = [w / 2, h / 2];
+  const vignette = cctx.createRadialGradient(cx, cy, h / 3, cx, cy, Math.hypot(cx, cy));
+  vignette.addColorStop(0, "rgba(0, 0, 0, 0)");
+  vignette.addColorStop(1, `rgba(0, 0, 0, ${CRT_VIGNETTE})`);
+  cctx.fillStyle = vignette;
+  cctx.fillRect(0, 0, w, h);
+}
+
+new ResizeObserver(drawCrt).observe(crtCanvas);
+
+function setCrt(on) {
+  crt = on;
+  crtCanvas.hidden = !on;
+  document.getElementById("btn-crt").classList.toggle("on", on);
+  drawCrt();
+}
+
+function toggleCrt() {
+  setCrt(!crt);
+  log(`CRT ${crt ? "enabled" : "disabled"}`);
+  saveSettings();
+}
+
+document.getElementById("btn-crt").addEventListener("click", toggleCrt);
+
+function setPhosphor(on) {
+  phosphor = on;
+  glow.fill(0);
+  document.getElementById("btn-phosphor").classList.toggle("on", on);
+  if (chip8) render(framebuffer());
+}
+
+document.getElementById("btn-phosphor").addEventListener("click", () => {
+  setPhosphor(!phosphor);
+  log(`Phosphor ${phosphor ? "enabled" : "disabled"}`);
+  saveSettings();
 });
 
 const dropZone = document.getElementById("drop-zone");
@@ -258,6 +1232,9 @@ dropZone.addEventListener("drop", (e) => {
   if (file) loadRom(file);
 });
 
-await init();
+const wasm = await init();
+font = fontset();
+frameClock = new WasmTickClock(FRAME_HZ);
+timerClock = new WasmTickClock(timerHz);
 setStatus("AWAITING ROM");
 log("WASM initialised", "ok");
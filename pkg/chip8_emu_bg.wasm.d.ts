@@ -1,19 +0,0 @@
-/* tslint:disable */
-/* eslint-disable */
-export const memory: WebAssembly.Memory;
-export const __wbg_wasmchip8_free: (a: number, b: number) => void;
-export const wasmchip8_clear_draw_flag: (a: number) => void;
-export const wasmchip8_draw_flag: (a: number) => number;
-export const wasmchip8_emulate_cycle: (a: number) => [number, number];
-export const wasmchip8_key_down: (a: number, b: number) => void;
-export const wasmchip8_key_up: (a: number, b: number) => void;
-export const wasmchip8_load_rom: (a: number, b: number, c: number) => void;
-export const wasmchip8_new: () => number;
-export const wasmchip8_reset: (a: number) => void;
-export const wasmchip8_update_timers: (a: number) => void;
-export const __wbindgen_exn_store: (a: number) => void;
-export const __externref_table_alloc: () => number;
-export const __wbindgen_externrefs: WebAssembly.Table;
-export const __wbindgen_free: (a: number, b: number, c: number) => void;
-export const __wbindgen_malloc: (a: number, b: number) => number;
-export const __wbindgen_start: () => void;
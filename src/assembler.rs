@@ -0,0 +1,561 @@
+use std::{collections::HashMap, error::Error, fmt, io};
+
+// Where assembled programs are loaded
+const ORIGIN: u16 = 0x200;
+
+// Assembles Octo (.8o) source into a ROM. The supported subset:
+//
+//     : label          define a label here
+//     :const NAME N    name a number
+//     :call label      2NNN, as does naming a label on its own
+//     jump label       1NNN; jump0 is BNNN
+//     return ;         00EE, and clear is 00E0
+//     loop ... again   jump back to the loop
+//     if ... then      run the next statement only if the
+//                      condition holds (vx == vy, vx != N,
+//                      vx key, vx -key)
+//     vx := vy N random N delay key
+//     vx += -= =- |= &= ^= >>= <<= (vy, or N for += and -=)
+//     i := N label hex vx bighex vx
+//     i += vx, delay := vx, buzzer := vx, pitch := vx
+//     sprite vx vy N, bcd vx, save vx, load vx, audio
+//     :byte N, or a number on its own, for data
+//
+// Everything after # on a line is a comment. Like Octo, if the
+// program has a `main` label that isn't at the start, a jump to
+// it is put at 0x200.
+//
+// Labels can be used before they're defined: the first pass
+// lays the program out and the second fills in the addresses.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let tokens = tokenize(source);
+    let mut asm = Assembler {
+        tokens: &tokens,
+        pos: 0,
+        items: Vec::new(),
+        addr: ORIGIN,
+        labels: HashMap::new(),
+        consts: HashMap::new(),
+        loops: Vec::new(),
+    };
+
+    let starts_with_main = tokens.len() >= 2 && tokens[0].text == ":" && tokens[1].text == "main";
+    let has_main = tokens
+        .windows(2)
+        .any(|w| w[0].text == ":" && w[1].text == "main");
+    if has_main && !starts_with_main {
+        let arg = Arg {
+            value: Value::Name("main".to_string()),
+            field: Field::Nnn,
+            token: tokens[0].clone(),
+        };
+        asm.emit(Item::Op(0x1000, Some(arg)));
+    }
+
+    while asm.pos < tokens.len() {
+        asm.statement()?;
+    }
+    if let Some(token) = asm.loops.last().map(|(_, token)| token) {
+        return Err(AsmError::new(token, "loop without again"));
+    }
+
+    asm.link()
+}
+
+// An assembly error, with the line it's on and the token that
+// caused it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub token: String,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(token: &Token, message: impl Into<String>) -> Self {
+        AsmError {
+            line: token.line,
+            token: token.text.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(f, "Line {}: {}", self.line, self.message)
+        } else {
+            write!(f, "Line {}: {} ('{}')", self.line, self.message, self.token)
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+impl From<AsmError> for io::Error {
+    fn from(err: AsmError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(n, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            code.split_whitespace().map(move |text| Token {
+                text: text.to_string(),
+                line: n + 1,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+enum Value {
+    Number(i64),
+    // A label or constant, looked up in the second pass
+    Name(String),
+}
+
+// Where an operand goes in the instruction
+#[derive(Clone, Copy)]
+enum Field {
+    Nnn,
+    Nn,
+    N,
+}
+
+#[derive(Clone)]
+struct Arg {
+    value: Value,
+    field: Field,
+    token: Token,
+}
+
+enum Item {
+    // An instruction, with the operand to OR in, if any
+    Op(u16, Option<Arg>),
+    Byte(Arg),
+}
+
+struct Assembler<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    items: Vec<Item>,
+    // Address of the next item
+    addr: u16,
+    labels: HashMap<String, u16>,
+    consts: HashMap<String, i64>,
+    // Start of each open loop, and the token that opened it
+    loops: Vec<(u16, Token)>,
+}
+
+impl Assembler<'_> {
+    fn emit(&mut self, item: Item) {
+        self.addr = self.addr.wrapping_add(match item {
+            Item::Op(..) => 2,
+            Item::Byte(_) => 1,
+        });
+        self.items.push(item);
+    }
+
+    fn op(&mut self, opcode: u16) {
+        self.emit(Item::Op(opcode, None));
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => {
+                let mut end = self.tokens.last().cloned().unwrap_or(Token {
+                    text: String::new(),
+                    line: 1,
+                });
+                end.text.clear();
+                Err(AsmError::new(&end, "unexpected end of file"))
+            }
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(AsmError::new(&token, format!("expected '{}'", text)))
+        }
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        register(&token.text).ok_or_else(|| AsmError::new(&token, "expected a register (v0-vf)"))
+    }
+
+    fn value(&mut self, field: Field) -> Result<Arg, AsmError> {
+        let token = self.next()?;
+        let value = if let Some(n) = number(&token.text) {
+            Value::Number(n)
+        } else if is_name(&token.text) {
+            Value::Name(token.text.clone())
+        } else {
+            return Err(AsmError::new(&token, "expected a number or label"));
+        };
+        Ok(Arg {
+            value,
+            field,
+            token,
+        })
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                if !is_name(&name.text) {
+                    return Err(AsmError::new(&name, "not a valid label name"));
+                }
+                if self.labels.insert(name.text.clone(), self.addr).is_some() {
+                    return Err(AsmError::new(&name, "label defined twice"));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let n = number(&value.text)
+                    .or_else(|| self.consts.get(&value.text).copied())
+                    .ok_or_else(|| AsmError::new(&value, "expected a number"))?;
+                if !is_name(&name.text) {
+                    return Err(AsmError::new(&name, "not a valid constant name"));
+                }
+                self.consts.insert(name.text, n);
+            }
+            ":call" => {
+                let arg = self.value(Field::Nnn)?;
+                self.emit(Item::Op(0x2000, Some(arg)));
+            }
+            ":byte" => {
+                let arg = self.value(Field::Nn)?;
+                self.emit(Item::Byte(arg));
+            }
+            "return" | ";" => self.op(0x00EE),
+            "clear" => self.op(0x00E0),
+            "jump" => {
+                let arg = self.value(Field::Nnn)?;
+                self.emit(Item::Op(0x1000, Some(arg)));
+            }
+            "jump0" => {
+                let arg = self.value(Field::Nnn)?;
+                self.emit(Item::Op(0xB000, Some(arg)));
+            }
+            "loop" => self.loops.push((self.addr, token)),
+            "again" => match self.loops.pop() {
+                Some((start, _)) => {
+                    let arg = Arg {
+                        value: Value::Number(start as i64),
+                        field: Field::Nnn,
+                        token,
+                    };
+                    self.emit(Item::Op(0x1000, Some(arg)));
+                }
+                None => return Err(AsmError::new(&token, "again without loop")),
+            },
+            "if" => self.condition()?,
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.value(Field::N)?;
+                self.emit(Item::Op(0xD000 | x << 8 | y << 4, Some(n)));
+            }
+            "bcd" => self.register_op(0xF033)?,
+            "save" => self.register_op(0xF055)?,
+            "load" => self.register_op(0xF065)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let low = match token.text.as_str() {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.op(0xF000 | x << 8 | low);
+            }
+            "audio" => self.op(0xF002),
+            "scroll-down" | "scroll-up" => {
+                let n = self.value(Field::N)?;
+                let op = if token.text == "scroll-down" {
+                    0x00C0
+                } else {
+                    0x00D0
+                };
+                self.emit(Item::Op(op, Some(n)));
+            }
+            "scroll-right" => self.op(0x00FB),
+            "scroll-left" => self.op(0x00FC),
+            "lores" => self.op(0x00FE),
+            "hires" => self.op(0x00FF),
+            "i" => self.index()?,
+            text => {
+                if let Some(x) = register(text) {
+                    self.assign(x)?;
+                } else if let Some(n) = number(text) {
+                    self.emit(Item::Byte(Arg {
+                        value: Value::Number(n),
+                        field: Field::Nn,
+                        token,
+                    }));
+                } else if is_name(text) {
+                    // A label on its own calls it
+                    let arg = Arg {
+                        value: Value::Name(token.text.clone()),
+                        field: Field::Nnn,
+                        token,
+                    };
+                    self.emit(Item::Op(0x2000, Some(arg)));
+                } else {
+                    return Err(AsmError::new(&token, "unknown statement"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // FX?? instructions that take one register
+    fn register_op(&mut self, opcode: u16) -> Result<(), AsmError> {
+        let x = self.register()?;
+        self.op(opcode | x << 8);
+        Ok(())
+    }
+
+    // `if` is compiled to the skip that jumps over the next
+    // statement when the condition doesn't hold
+    fn condition(&mut self) -> Result<(), AsmError> {
+        let x = self.register()?;
+        let test = self.next()?;
+        match test.text.as_str() {
+            "key" => self.op(0xE0A1 | x << 8),
+            "-key" => self.op(0xE09E | x << 8),
+            "==" | "!=" => {
+                let equal = test.text == "==";
+                if let Some(y) = self.tokens.get(self.pos).and_then(|t| register(&t.text)) {
+                    self.pos += 1;
+                    let opcode = if equal { 0x9000 } else { 0x5000 };
+                    self.op(opcode | x << 8 | y << 4);
+                } else {
+                    let n = self.value(Field::Nn)?;
+                    let opcode = if equal { 0x4000 } else { 0x3000 };
+                    self.emit(Item::Op(opcode | x << 8, Some(n)));
+                }
+            }
+            _ => return Err(AsmError::new(&test, "expected ==, !=, key or -key")),
+        }
+        self.expect("then")
+    }
+
+    // vx <op> ...
+    fn assign(&mut self, x: u16) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let source = self.tokens.get(self.pos).map(|t| t.text.as_str());
+        let y = source.and_then(register);
+        let vx = x << 8;
+
+        match (op.text.as_str(), y) {
+            (_, Some(y)) => {
+                self.pos += 1;
+                let low = match op.text.as_str() {
+                    ":=" => 0x0,
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    "+=" => 0x4,
+                    "-=" => 0x5,
+                    ">>=" => 0x6,
+                    "=-" => 0x7,
+                    "<<=" => 0xE,
+                    _ => return Err(AsmError::new(&op, "unknown register operation")),
+                };
+                self.op(0x8000 | vx | y << 4 | low);
+            }
+            (":=", None) => match source {
+                Some("random") => {
+                    self.pos += 1;
+                    let n = self.value(Field::Nn)?;
+                    self.emit(Item::Op(0xC000 | vx, Some(n)));
+                }
+                Some("delay") => {
+                    self.pos += 1;
+                    self.op(0xF007 | vx);
+                }
+                Some("key") => {
+                    self.pos += 1;
+                    self.op(0xF00A | vx);
+                }
+                _ => {
+                    let n = self.value(Field::Nn)?;
+                    self.emit(Item::Op(0x6000 | vx, Some(n)));
+                }
+            },
+            ("+=", None) => {
+                let n = self.value(Field::Nn)?;
+                self.emit(Item::Op(0x7000 | vx, Some(n)));
+            }
+            ("-=", None) => {
+                // There's no subtract-immediate; add the negation
+                let mut n = self.value(Field::Nn)?;
+                n.value = match n.value {
+                    Value::Number(v) => Value::Number((-v).rem_euclid(256)),
+                    Value::Name(_) => {
+                        return Err(AsmError::new(&n.token, "-= needs a number or register"));
+                    }
+                };
+                self.emit(Item::Op(0x7000 | vx, Some(n)));
+            }
+            _ => return Err(AsmError::new(&op, "unknown register operation")),
+        }
+        Ok(())
+    }
+
+    // i := ..., i += vx
+    fn index(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.text.as_str() {
+            ":=" => match self.tokens.get(self.pos).map(|t| t.text.as_str()) {
+                Some("hex") => {
+                    self.pos += 1;
+                    self.register_op(0xF029)
+                }
+                Some("bighex") => {
+                    self.pos += 1;
+                    self.register_op(0xF030)
+                }
+                _ => {
+                    let n = self.value(Field::Nnn)?;
+                    self.emit(Item::Op(0xA000, Some(n)));
+                    Ok(())
+                }
+            },
+            "+=" => self.register_op(0xF01E),
+            _ => Err(AsmError::new(&op, "expected := or +=")),
+        }
+    }
+
+    // Second pass: fills in the operands now every label is known
+    fn link(&self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = Vec::new();
+        for item in &self.items {
+            match item {
+                Item::Op(opcode, arg) => {
+                    let operand = match arg {
+                        Some(arg) => self.resolve(arg)?,
+                        None => 0,
+                    };
+                    rom.extend_from_slice(&(opcode | operand).to_be_bytes());
+                }
+                Item::Byte(arg) => rom.push(self.resolve(arg)? as u8),
+            }
+        }
+        Ok(rom)
+    }
+
+    fn resolve(&self, arg: &Arg) -> Result<u16, AsmError> {
+        let value = match &arg.value {
+            Value::Number(n) => *n,
+            Value::Name(name) => match self.consts.get(name) {
+                Some(&n) => n,
+                None => match self.labels.get(name) {
+                    Some(&addr) => addr as i64,
+                    None => return Err(AsmError::new(&arg.token, "undefined label")),
+                },
+            },
+        };
+        // Bytes can be written as negative numbers
+        let (min, max) = match arg.field {
+            Field::Nnn => (0, 0xFFF),
+            Field::Nn => (-128, 0xFF),
+            Field::N => (0, 0xF),
+        };
+        if !(min..=max).contains(&value) {
+            return Err(AsmError::new(&arg.token, "value out of range"));
+        }
+        Ok((value as u16) & max as u16)
+    }
+}
+
+// v0-vf (or V0-VF)
+fn register(text: &str) -> Option<u16> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u16::from_str_radix(digit, 16).ok()
+}
+
+// Decimal, 0x hex or 0b binary, optionally negative
+fn number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let n = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -n } else { n })
+}
+
+// Words that can't be label names
+const KEYWORDS: &[&str] = &[
+    "return",
+    "clear",
+    "jump",
+    "jump0",
+    "loop",
+    "again",
+    "if",
+    "then",
+    "key",
+    "sprite",
+    "bcd",
+    "save",
+    "load",
+    "delay",
+    "buzzer",
+    "pitch",
+    "audio",
+    "i",
+    "random",
+    "hex",
+    "bighex",
+    "scroll-down",
+    "scroll-up",
+    "scroll-right",
+    "scroll-left",
+    "lores",
+    "hires",
+];
+
+fn is_name(text: &str) -> bool {
+    !KEYWORDS.contains(&text)
+        && text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && register(text).is_none()
+}
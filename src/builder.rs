@@ -0,0 +1,323 @@
+use std::{error::Error, fmt, io};
+
+use crate::{
+    BIG_FONTSET, CHIP8_HEIGHT, Chip8, DISPLAY_HEIGHTS, FONTSET, FONTSET_START_ADDR, MEMORY_SIZE,
+    PROGRAM_START_ADDR, Quirks, STACK_DEPTH, Timing, Variant, Watch, XO_CHIP_MEMORY_SIZE,
+};
+
+// Reasons Chip8Builder::build() can refuse a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    // Memory has to at least hold the fontset
+    MemoryTooSmall { size: usize, need: usize },
+    // The program would start past the end of memory
+    StartAddressOutOfRange { addr: u16, memory_size: usize },
+    // The program would start inside the font sprites
+    StartAddressInFontset { addr: u16, font: u16 },
+    // The small font has to fit between the font address and the
+    // program (or 0x200)
+    FontAddress { addr: u16, end: usize },
+    // The entry point is past the end of memory
+    EntryOutOfRange { addr: u16, memory_size: usize },
+    // A font has to be the 16 small hex digits (80 bytes), or
+    // those and the 16 large ones (240), and end before the
+    // program
+    InvalidFontset { len: usize, max: usize },
+    // The display has to be one of DISPLAY_HEIGHTS rows tall
+    DisplayHeight(usize),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::MemoryTooSmall { size, need } => write!(
+                f,
+                "Memory size {:#X} is too small to hold the fontset (need {:#X})",
+                size, need
+            ),
+            BuildError::StartAddressOutOfRange { addr, memory_size } => write!(
+                f,
+                "Start address {:#X} is outside memory (0x000..{:#X})",
+                addr, memory_size
+            ),
+            BuildError::StartAddressInFontset { addr, font } => write!(
+                f,
+                "Start address {:#X} overlaps the fontset ({:#X}..{:#X})",
+                addr,
+                font,
+                font as usize + FONTSET.len()
+            ),
+            BuildError::FontAddress { addr, end } => write!(
+                f,
+                "Font address {:#X} leaves no room for the {}-byte font before {:#X}",
+                addr,
+                FONTSET.len(),
+                end
+            ),
+            BuildError::EntryOutOfRange { addr, memory_size } => write!(
+                f,
+                "Entry point {:#X} is outside memory (0x000..{:#X})",
+                addr, memory_size
+            ),
+            BuildError::InvalidFontset { len, max } => write!(
+                f,
+                "Fontset must be {} bytes, or {} with the large digits (at most {} fit before the program), got {}",
+                FONTSET.len(),
+                FONTSET.len() + BIG_FONTSET.len(),
+                max,
+                len
+            ),
+            BuildError::DisplayHeight(rows) => write!(
+                f,
+                "Display height must be one of {:?} rows, got {}",
+                DISPLAY_HEIGHTS, rows
+            ),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+// So frontends can keep using io::Result and `?`
+impl From<BuildError> for io::Error {
+    fn from(err: BuildError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+// Configures a machine before it's created. Options are only
+// checked against each other in build(), so they can be set in
+// any order.
+//
+//     let chip8 = Chip8::builder().quirks(quirks).seed(1).build()?;
+//
+// A builder can be cloned to create several machines with the
+// same settings.
+#[derive(Clone)]
+pub struct Chip8Builder {
+    quirks: Quirks,
+    start_address: u16,
+    entry: Option<u16>,
+    font_address: u16,
+    memory_size: Option<usize>,
+    variant: Variant,
+    timing: Timing,
+    seed: Option<u64>,
+    fontset: Vec<u8>,
+    flicker_filter: usize,
+    stack_depth: usize,
+    watches: Vec<Watch>,
+    key_repeat_guard: bool,
+    halt_past_rom: bool,
+    truncate_oversize: bool,
+    trace: bool,
+    display_height: usize,
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8Builder {
+    pub fn new() -> Self {
+        Chip8Builder {
+            quirks: Quirks::default(),
+            start_address: PROGRAM_START_ADDR as u16,
+            entry: None,
+            font_address: FONTSET_START_ADDR as u16,
+            memory_size: None,
+            variant: Variant::Chip8,
+            timing: Timing::Simple,
+            seed: None,
+            fontset: FONTSET.to_vec(),
+            flicker_filter: 1,
+            stack_depth: STACK_DEPTH,
+            watches: Vec::new(),
+            key_repeat_guard: false,
+            halt_past_rom: false,
+            truncate_oversize: false,
+            trace: false,
+            display_height: CHIP8_HEIGHT,
+        }
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    // Where programs are loaded and started (default 0x200)
+    pub fn start_address(mut self, addr: u16) -> Self {
+        self.start_address = addr;
+        self
+    }
+
+    // Where PC starts, if not at the start address. The program
+    // is still loaded at the start address.
+    pub fn entry(mut self, addr: u16) -> Self {
+        self.entry = Some(addr);
+        self
+    }
+
+    // Where the small font goes, with the large one right after
+    // it (default 0x50). FX29 and FX30 point into it wherever it
+    // is, for ROMs that expect the digits somewhere else.
+    pub fn font_address(mut self, addr: u16) -> Self {
+        self.font_address = addr;
+        self
+    }
+
+    // Bytes of RAM (default 4096, or 64KB for XO-CHIP)
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.memory_size = Some(size);
+        self
+    }
+
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    // How Chip8::step_frame() counts its budget
+    pub fn timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    // Fixed CXNN seed; randomly seeded if not set
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Maximum nesting of 2NNN calls (default 16, the VIP
+    // allowed 12)
+    pub fn stack_depth(mut self, depth: usize) -> Self {
+        self.stack_depth = depth;
+        self
+    }
+
+    // Adds a watchpoint; see Chip8::add_watchpoint()
+    pub fn watch(mut self, watch: Watch) -> Self {
+        self.watches.push(watch);
+        self
+    }
+
+    // Frames the flicker filter blends; see
+    // Chip8::set_flicker_filter()
+    pub fn flicker_filter(mut self, frames: usize) -> Self {
+        self.flicker_filter = frames;
+        self
+    }
+
+    // Counts each key press once; see
+    // Chip8::set_key_repeat_guard()
+    pub fn key_repeat_guard(mut self, on: bool) -> Self {
+        self.key_repeat_guard = on;
+        self
+    }
+
+    // Stops at the end of the program; see
+    // Chip8::set_halt_past_rom()
+    pub fn halt_past_rom(mut self, on: bool) -> Self {
+        self.halt_past_rom = on;
+        self
+    }
+
+    // Loads what fits of a ROM too large for memory; see
+    // Chip8::set_truncate_oversize()
+    pub fn truncate_oversize(mut self, on: bool) -> Self {
+        self.truncate_oversize = on;
+        self
+    }
+
+    // Logs every instruction for take_trace_log(); see
+    // Chip8::enable_trace()
+    pub fn trace(mut self, on: bool) -> Self {
+        self.trace = on;
+        self
+    }
+
+    // Rows on the display: 32, or 64 (or 48) for the VIP's hi-res
+    // ROMs; see Chip8::set_display_height()
+    pub fn display_height(mut self, rows: usize) -> Self {
+        self.display_height = rows;
+        self
+    }
+
+    // Replaces the built-in hex font; see Chip8::load_fontset()
+    pub fn fontset(mut self, fontset: &[u8]) -> Self {
+        self.fontset = fontset.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Result<Chip8, BuildError> {
+        let memory_size = self.memory_size.unwrap_or(match self.variant {
+            Variant::Chip8 | Variant::SuperChip => MEMORY_SIZE,
+            Variant::XoChip => XO_CHIP_MEMORY_SIZE,
+        });
+        let font = self.font_address as usize..self.font_address as usize + FONTSET.len();
+        if memory_size < font.end {
+            return Err(BuildError::MemoryTooSmall {
+                size: memory_size,
+                need: font.end,
+            });
+        }
+
+        let start = self.start_address as usize;
+        if start >= memory_size {
+            return Err(BuildError::StartAddressOutOfRange {
+                addr: self.start_address,
+                memory_size,
+            });
+        }
+        if font.contains(&start) {
+            return Err(BuildError::StartAddressInFontset {
+                addr: self.start_address,
+                font: self.font_address,
+            });
+        }
+        if let Some(addr) = self.entry
+            && addr as usize >= memory_size
+        {
+            return Err(BuildError::EntryOutOfRange { addr, memory_size });
+        }
+        if !DISPLAY_HEIGHTS.contains(&self.display_height) {
+            return Err(BuildError::DisplayHeight(self.display_height));
+        }
+        let mut chip8 = Chip8::with_layout(memory_size, start);
+        chip8.font_start = font.start;
+        if font.end > chip8.font_end() {
+            return Err(BuildError::FontAddress {
+                addr: self.font_address,
+                end: chip8.font_end(),
+            });
+        }
+        if let Some(addr) = self.entry {
+            chip8.PC = addr;
+        }
+        chip8.quirks = self.quirks;
+        chip8.variant = self.variant;
+        chip8.timing = self.timing;
+        chip8.stack_limit = self.stack_depth;
+        chip8.set_flicker_filter(self.flicker_filter);
+        chip8.set_key_repeat_guard(self.key_repeat_guard);
+        chip8.set_halt_past_rom(self.halt_past_rom);
+        chip8.set_truncate_oversize(self.truncate_oversize);
+        if self.trace {
+            chip8.enable_trace();
+        }
+        // Checked above
+        let _ = chip8.set_display_height(self.display_height);
+        if let Some(seed) = self.seed {
+            chip8.set_seed(seed);
+        }
+        for watch in self.watches {
+            chip8.add_watchpoint(watch);
+        }
+        chip8.load_fontset(self.fontset)?;
+        Ok(chip8)
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+// Splits elapsed time into whole ticks of a fixed rate (60Hz
+// frames, or timer ticks), carrying what's left over into the
+// next call, so a frontend whose frames come late, early or
+// uneven still runs them at the right rate on average, and one
+// that stalls catches up. Time is counted in whole nanoseconds,
+// so 50ms at 60Hz is exactly 3 ticks rather than 2.999... of them
+// as it is in floating point.
+//
+// How much of a stall to catch up on is up to the caller, who
+// should cap `elapsed` so a long one doesn't spiral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickClock {
+    interval: Duration,
+    // Time since the last tick
+    carried: Duration,
+}
+
+impl TickClock {
+    // Ticks `hz` times a second (at least once)
+    pub fn new(hz: u32) -> Self {
+        TickClock {
+            interval: Duration::from_secs(1) / hz.max(1),
+            carried: Duration::ZERO,
+        }
+    }
+
+    // Changes the rate, keeping the time carried over
+    pub fn set_hz(&mut self, hz: u32) {
+        self.interval = Duration::from_secs(1) / hz.max(1);
+    }
+
+    // Adds `elapsed` and returns how many ticks have come due
+    pub fn take(&mut self, elapsed: Duration) -> u32 {
+        let time = (self.carried + elapsed).as_nanos();
+        let interval = self.interval.as_nanos();
+        self.carried = Duration::from_nanos((time % interval) as u64);
+        (time / interval).min(u32::MAX as u128) as u32
+    }
+
+    // Time since the last tick was due
+    pub fn carried(&self) -> Duration {
+        self.carried
+    }
+
+    // Forgets the time carried over, e.g. after a pause
+    pub fn reset(&mut self) {
+        self.carried = Duration::ZERO;
+    }
+}
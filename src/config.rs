@@ -0,0 +1,660 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip8_emu::{DISPLAY_HEIGHTS, Quirks, Timing};
+
+use crate::romdb::DEFAULT_CPF;
+
+// Settings that can be given in chip8-emu.toml as well as on the
+// command line. Both are read into one of these, and the command
+// line's is laid over the file's, which is laid over the
+// defaults. Unset values are None (or empty), so a ROM's profile
+// can still fill them in.
+//
+// The file is a small subset of TOML: [sections], `key = value`
+// lines with strings, whole numbers, true/false and one-line
+// lists of strings, and # comments:
+//
+//     [emulation]
+//     cpf = 20
+//     quirks = ["vf-reset", "shift-vy"]
+//
+//     [video]
+//     fg = "FFB000"
+//
+//     [input]
+//     autofire = ["5=10"]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    // [emulation]
+    pub cpf: Option<usize>,
+    pub timing: Option<Timing>,
+    pub seed: Option<u64>,
+    pub schip: Option<bool>,
+    pub xo_chip: Option<bool>,
+    pub romdb: Option<bool>,
+    pub detect: Option<bool>,
+    pub font: Option<PathBuf>,
+    pub halt_past_rom: Option<bool>,
+    pub truncate_oversize: Option<bool>,
+    pub display_height: Option<usize>,
+    // Names from QUIRK_FLAGS, applied in order
+    pub quirks: Vec<&'static str>,
+
+    // [video]
+    pub fg: Option<[u8; 3]>,
+    pub bg: Option<[u8; 3]>,
+    // XO-CHIP pixels in the second plane, and in both
+    pub plane2: Option<[u8; 3]>,
+    pub both: Option<[u8; 3]>,
+    pub scale: Option<u16>,
+    pub flicker_filter: Option<usize>,
+    pub flash_guard: Option<bool>,
+    pub show_speed: Option<bool>,
+
+    // [input]
+    pub split_keys: Option<bool>,
+    pub key_repeat_guard: Option<bool>,
+    // Later entries for the same key win
+    pub autofire: Vec<(usize, u32)>,
+}
+
+pub const FILE_NAME: &str = "chip8-emu.toml";
+
+// Applies one quirk flag on top of a ROM's profile
+pub type QuirkFlag = fn(&mut Quirks);
+
+// The quirk flags by name, as --<name>, --quirk <name> and in the
+// config file's quirks list
+pub const QUIRK_FLAGS: [(&str, QuirkFlag); 10] = [
+    ("clip-sprites", |q| (q.wrap_x, q.wrap_y) = (false, false)),
+    ("clip-x", |q| q.wrap_x = false),
+    ("clip-y", |q| q.wrap_y = false),
+    ("display-wait", |q| q.display_wait = true),
+    ("shift-vy", |q| q.shift_uses_vy = true),
+    ("increment-i", |q| q.index_increment_on_store = true),
+    ("vf-reset", |q| q.vf_reset_on_logic = true),
+    ("ignore-0nnn", |q| q.ignore_machine_code = true),
+    ("jump-vx", |q| q.jump_with_vx = true),
+    ("half-scroll", |q| q.half_scroll = true),
+];
+
+// The name as stored in Config, and what it does
+pub fn quirk_flag(name: &str) -> Option<(&'static str, QuirkFlag)> {
+    QUIRK_FLAGS.iter().find(|(flag, _)| *flag == name).copied()
+}
+
+impl Config {
+    // `over`'s settings, with this one's wherever `over` has none.
+    // Lists are added to rather than replaced, so a quirk or
+    // autofire key from the command line comes on top of the
+    // file's.
+    pub fn merge(self, over: Config) -> Config {
+        Config {
+            cpf: over.cpf.or(self.cpf),
+            timing: over.timing.or(self.timing),
+            seed: over.seed.or(self.seed),
+            schip: over.schip.or(self.schip),
+            xo_chip: over.xo_chip.or(self.xo_chip),
+            romdb: over.romdb.or(self.romdb),
+            detect: over.detect.or(self.detect),
+            font: over.font.or(self.font),
+            halt_past_rom: over.halt_past_rom.or(self.halt_past_rom),
+            truncate_oversize: over.truncate_oversize.or(self.truncate_oversize),
+            display_height: over.display_height.or(self.display_height),
+            quirks: [self.quirks, over.quirks].concat(),
+            fg: over.fg.or(self.fg),
+            bg: over.bg.or(self.bg),
+            plane2: over.plane2.or(self.plane2),
+            both: over.both.or(self.both),
+            scale: over.scale.or(self.scale),
+            flicker_filter: over.flicker_filter.or(self.flicker_filter),
+            flash_guard: over.flash_guard.or(self.flash_guard),
+            show_speed: over.show_speed.or(self.show_speed),
+            split_keys: over.split_keys.or(self.split_keys),
+            key_repeat_guard: over.key_repeat_guard.or(self.key_repeat_guard),
+            autofire: [self.autofire, over.autofire].concat(),
+        }
+    }
+
+    pub fn quirk_flags(&self) -> Vec<QuirkFlag> {
+        self.quirks
+            .iter()
+            .filter_map(|name| quirk_flag(name))
+            .map(|(_, set)| set)
+            .collect()
+    }
+}
+
+// Where to look when no --config is given: the current directory,
+// then the platform's config directory
+pub fn find() -> Option<PathBuf> {
+    let local = PathBuf::from(FILE_NAME);
+    if local.is_file() {
+        return Some(local);
+    }
+    let global = config_dir()?.join("chip8-emu").join(FILE_NAME);
+    global.is_file().then_some(global)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))
+    }
+}
+
+// Reads a config file. Keys and sections it doesn't know are
+// skipped, and come back as warnings; a value of the wrong type
+// or out of range is an error. Both name the line.
+pub fn load(path: &Path) -> io::Result<(Config, Vec<String>)> {
+    let text = fs::read_to_string(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't read config {}: {}", path.display(), err),
+        )
+    })?;
+    parse(&text)
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}", path.display(), err),
+            )
+        })
+        .map(|(config, warnings)| {
+            let warnings = warnings
+                .into_iter()
+                .map(|w| format!("{}:{}", path.display(), w))
+                .collect();
+            (config, warnings)
+        })
+}
+
+enum Value {
+    Str(String),
+    Int(u64),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+// Errors and warnings start with the line number, for load() to
+// put the file name in front of
+pub fn parse(text: &str) -> Result<(Config, Vec<String>), String> {
+    let mut config = Config::default();
+    let mut warnings = Vec::new();
+    // None in a section that isn't known, whose keys are skipped
+    let mut section = Some("");
+
+    for (n, line) in text.lines().enumerate() {
+        let n = n + 1;
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            section = ["emulation", "video", "input"]
+                .into_iter()
+                .find(|&s| s == name);
+            if section.is_none() {
+                warnings.push(format!("{}: unknown section [{}]", n, name));
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("{}: expected `key = value`", n));
+        };
+        let key = key.trim();
+        let value = parse_value(value.trim())
+            .ok_or_else(|| format!("{}: can't read the value of {}", n, key))?;
+        let Some(section) = section else {
+            continue;
+        };
+        match set(&mut config, section, key, value) {
+            Ok(true) => {}
+            Ok(false) if section.is_empty() => {
+                warnings.push(format!("{}: unknown key {} outside a section", n, key))
+            }
+            Ok(false) => warnings.push(format!("{}: unknown key {} in [{}]", n, key, section)),
+            Err(msg) => return Err(format!("{}: {} {}", n, key, msg)),
+        }
+    }
+    Ok((config, warnings))
+}
+
+// A # starts a comment unless it's inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    match s {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        _ => {}
+    }
+    if let Some(list) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        let mut items = Vec::new();
+        let mut rest = list.trim();
+        while !rest.is_empty() {
+            let (item, after) = parse_string(rest)?;
+            items.push(item);
+            rest = after.trim_start();
+            rest = match rest.strip_prefix(',') {
+                Some(after) => after.trim_start(),
+                None if rest.is_empty() => rest,
+                None => return None,
+            };
+        }
+        return Some(Value::List(items));
+    }
+    if s.starts_with('"') {
+        return match parse_string(s)? {
+            (string, "") => Some(Value::Str(string)),
+            _ => None,
+        };
+    }
+    s.replace('_', "").parse().ok().map(Value::Int)
+}
+
+// A "quoted" string at the start of `s`, and what's after it
+fn parse_string(s: &str) -> Option<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &s[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                c @ ('"' | '\\') => out.push(c),
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+// Sets `key` in `section` from `value`. Returns false for keys it
+// doesn't know, and the rest of an error message ("must be ...")
+// for values that don't fit.
+fn set(config: &mut Config, section: &str, key: &str, value: Value) -> Result<bool, String> {
+    match (section, key) {
+        ("emulation", "cpf") => config.cpf = Some(int(value, 1, 1000)? as usize),
+        ("emulation", "timing") => {
+            config.timing = Some(match string(value)?.as_str() {
+                "simple" => Timing::Simple,
+                "vip" => Timing::Vip,
+                "schip" => Timing::Schip,
+                _ => return Err("must be \"simple\", \"vip\" or \"schip\"".to_string()),
+            })
+        }
+        ("emulation", "seed") => config.seed = Some(int(value, 0, u64::MAX)?),
+        ("emulation", "schip") => config.schip = Some(boolean(value)?),
+        ("emulation", "xo_chip") => config.xo_chip = Some(boolean(value)?),
+        ("emulation", "romdb") => config.romdb = Some(boolean(value)?),
+        ("emulation", "detect") => config.detect = Some(boolean(value)?),
+        ("emulation", "font") => config.font = Some(PathBuf::from(string(value)?)),
+        ("emulation", "halt_past_rom") => config.halt_past_rom = Some(boolean(value)?),
+        ("emulation", "truncate_oversize") => config.truncate_oversize = Some(boolean(value)?),
+        ("emulation", "display_height") => match int(value, 32, 64)? as usize {
+            rows if DISPLAY_HEIGHTS.contains(&rows) => config.display_height = Some(rows),
+            _ => return Err("must be 32, 48 or 64".to_string()),
+        },
+        ("emulation", "quirks") => {
+            for name in list(value)? {
+                let Some((name, _)) = quirk_flag(&name) else {
+                    return Err(format!("has an unknown quirk {:?}", name));
+                };
+                config.quirks.push(name);
+            }
+        }
+        ("video", "fg") => config.fg = Some(color(value)?),
+        ("video", "bg") => config.bg = Some(color(value)?),
+        ("video", "plane2") => config.plane2 = Some(color(value)?),
+        ("video", "both") => config.both = Some(color(value)?),
+        ("video", "scale") => config.scale = Some(int(value, 1, 64)? as u16),
+        ("video", "flicker_filter") => config.flicker_filter = Some(int(value, 1, 3)? as usize),
+        ("video", "flash_guard") => config.flash_guard = Some(boolean(value)?),
+        ("video", "show_speed") => config.show_speed = Some(boolean(value)?),
+        ("input", "split_keys") => config.split_keys = Some(boolean(value)?),
+        ("input", "key_repeat_guard") => config.key_repeat_guard = Some(boolean(value)?),
+        ("input", "autofire") => {
+            for entry in list(value)? {
+                let Some(key) = parse_autofire(&entry) else {
+                    return Err(format!(
+                        "entry {:?} must be KEY=HZ, e.g. 5=10 (1-30 Hz)",
+                        entry
+                    ));
+                };
+                config.autofire.push(key);
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn int(value: Value, min: u64, max: u64) -> Result<u64, String> {
+    match value {
+        Value::Int(n) if (min..=max).contains(&n) => Ok(n),
+        Value::Int(_) => Err(format!("must be between {} and {}", min, max)),
+        _ => Err("must be a number".to_string()),
+    }
+}
+
+fn boolean(value: Value) -> Result<bool, String> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        _ => Err("must be true or false".to_string()),
+    }
+}
+
+fn string(value: Value) -> Result<String, String> {
+    match value {
+        Value::Str(s) => Ok(s),
+        _ => Err("must be a string in quotes".to_string()),
+    }
+}
+
+fn list(value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::List(items) => Ok(items),
+        _ => Err("must be a list of strings, e.g. [\"a\", \"b\"]".to_string()),
+    }
+}
+
+fn color(value: Value) -> Result<[u8; 3], String> {
+    parse_color(&string(value)?).ok_or_else(|| "must be a color like \"00CD00\"".to_string())
+}
+
+// RRGGBB in hex, with or without a leading #
+pub fn parse_color(s: &str) -> Option<[u8; 3]> {
+    let s = s.strip_prefix('#').unwrap_or(s);
+    if s.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(s, 16).ok()?;
+    let [_, r, g, b] = rgb.to_be_bytes();
+    Some([r, g, b])
+}
+
+// A keypad key in hex and a rate, e.g. 5=10
+pub fn parse_autofire(s: &str) -> Option<(usize, u32)> {
+    let (key, hz) = s.split_once('=')?;
+    if key.len() != 1 {
+        return None;
+    }
+    let key = usize::from_str_radix(key, 16).ok()?;
+    match hz.parse() {
+        Ok(hz @ 1..=30) => Some((key, hz)),
+        _ => None,
+    }
+}
+
+// The settings in effect, as a config file (chip8-emu config
+// --print). Values left to the defaults are filled in; those that
+// have none, or depend on the ROM, are commented out.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = |[r, g, b]: [u8; 3]| format!("\"{:02X}{:02X}{:02X}\"", r, g, b);
+        let strings = |items: Vec<String>| {
+            let quoted: Vec<String> = items.iter().map(|s| format!("{:?}", s)).collect();
+            format!("[{}]", quoted.join(", "))
+        };
+
+        writeln!(f, "[emulation]")?;
+        match self.cpf {
+            Some(cpf) => writeln!(f, "cpf = {}", cpf)?,
+            None => writeln!(
+                f,
+                "# cpf = {} (unless the ROM's profile says otherwise)",
+                DEFAULT_CPF
+            )?,
+        }
+        let timing = match self.timing.unwrap_or(Timing::Simple) {
+            Timing::Simple => "simple",
+            Timing::Vip => "vip",
+            Timing::Schip => "schip",
+        };
+        writeln!(f, "timing = \"{}\"", timing)?;
+        match self.seed {
+            Some(seed) => writeln!(f, "seed = {}", seed)?,
+            None => writeln!(f, "# seed = (random; 0 headless and scripted)")?,
+        }
+        writeln!(f, "schip = {}", self.schip.unwrap_or(false))?;
+        writeln!(f, "xo_chip = {}", self.xo_chip.unwrap_or(false))?;
+        writeln!(f, "romdb = {}", self.romdb.unwrap_or(true))?;
+        writeln!(f, "detect = {}", self.detect.unwrap_or(true))?;
+        match &self.font {
+            Some(font) => writeln!(f, "font = {:?}", font.display().to_string())?,
+            None => writeln!(f, "# font = (built in)")?,
+        }
+        writeln!(f, "halt_past_rom = {}", self.halt_past_rom.unwrap_or(false))?;
+        writeln!(
+            f,
+            "truncate_oversize = {}",
+            self.truncate_oversize.unwrap_or(false)
+        )?;
+        match self.display_height {
+            Some(rows) => writeln!(f, "display_height = {}", rows)?,
+            None => writeln!(f, "# display_height = (32, or 64 for hi-res ROMs)")?,
+        }
+        let quirks = self.quirks.iter().map(|q| q.to_string()).collect();
+        writeln!(f, "quirks = {}", strings(quirks))?;
+
+        writeln!(f, "\n[video]")?;
+        match self.fg {
+            Some(fg) => writeln!(f, "fg = {}", hex(fg))?,
+            None => writeln!(f, "# fg = (the terminal's green)")?,
+        }
+        match self.bg {
+            Some(bg) => writeln!(f, "bg = {}", hex(bg))?,
+            None => writeln!(f, "# bg = (the terminal's black)")?,
+        }
+        match self.plane2 {
+            Some(plane2) => writeln!(f, "plane2 = {}", hex(plane2))?,
+            None => writeln!(f, "# plane2 = (the terminal's red)")?,
+        }
+        match self.both {
+            Some(both) => writeln!(f, "both = {}", hex(both))?,
+            None => writeln!(f, "# both = (the terminal's yellow)")?,
+        }
+        writeln!(f, "scale = {}", self.scale.unwrap_or(4))?;
+        writeln!(f, "flicker_filter = {}", self.flicker_filter.unwrap_or(1))?;
+        writeln!(f, "flash_guard = {}", self.flash_guard.unwrap_or(false))?;
+        writeln!(f, "show_speed = {}", self.show_speed.unwrap_or(false))?;
+
+        writeln!(f, "\n[input]")?;
+        writeln!(f, "split_keys = {}", self.split_keys.unwrap_or(false))?;
+        writeln!(
+            f,
+            "key_repeat_guard = {}",
+            self.key_repeat_guard.unwrap_or(false)
+        )?;
+        let autofire = self
+            .autofire
+            .iter()
+            .map(|(key, hz)| format!("{:X}={}", key, hz))
+            .collect();
+        writeln!(f, "autofire = {}", strings(autofire))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(text: &str) -> Config {
+        let (config, warnings) = parse(text).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        config
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let config = file(
+            r##"
+            # Amber, and a bit faster
+            [emulation]
+            cpf = 20
+            timing = "vip"
+            quirks = ["vf-reset", "shift-vy"]
+
+            [video]
+            fg = "#FFB000"  # a comment
+            scale = 2
+
+            [input]
+            split_keys = true
+            autofire = ["5=10", "A=15"]
+            "##,
+        );
+        assert_eq!(config.cpf, Some(20));
+        assert_eq!(config.timing, Some(Timing::Vip));
+        assert_eq!(config.quirks, ["vf-reset", "shift-vy"]);
+        assert_eq!(config.fg, Some([0xFF, 0xB0, 0x00]));
+        assert_eq!(config.scale, Some(2));
+        assert_eq!(config.split_keys, Some(true));
+        assert_eq!(config.autofire, [(5, 10), (0xA, 15)]);
+    }
+
+    // Each setting from the defaults, the file or the command
+    // line, whichever is the last to set it
+    #[test]
+    fn command_line_beats_file_beats_defaults() {
+        let defaults = Config::default();
+        let file = file("[emulation]\ncpf = 20\nseed = 7\n[video]\nscale = 2");
+        let cli = Config {
+            cpf: Some(30),
+            flash_guard: Some(true),
+            ..Config::default()
+        };
+
+        let none = defaults.clone().merge(Config::default());
+        assert_eq!(none, Config::default());
+
+        let file_only = defaults.clone().merge(file.clone());
+        assert_eq!((file_only.cpf, file_only.seed), (Some(20), Some(7)));
+
+        let cli_only = defaults.clone().merge(cli.clone());
+        assert_eq!((cli_only.cpf, cli_only.seed), (Some(30), None));
+
+        let both = defaults.merge(file).merge(cli);
+        assert_eq!(both.cpf, Some(30));
+        assert_eq!(both.seed, Some(7));
+        assert_eq!(both.scale, Some(2));
+        assert_eq!(both.flash_guard, Some(true));
+        assert_eq!(both.show_speed, None);
+    }
+
+    #[test]
+    fn lists_add_up() {
+        let file = file("[emulation]\nquirks = [\"vf-reset\"]\n[input]\nautofire = [\"5=10\"]");
+        let cli = Config {
+            quirks: vec!["jump-vx"],
+            autofire: vec![(5, 20)],
+            ..Config::default()
+        };
+        let both = file.merge(cli);
+        assert_eq!(both.quirks, ["vf-reset", "jump-vx"]);
+        // The command line's rate is set last, so it's the one used
+        assert_eq!(both.autofire, [(5, 10), (5, 20)]);
+    }
+
+    #[test]
+    fn unknown_keys_warn_with_their_line() {
+        let (config, warnings) =
+            parse("[emulation]\ncpf = 20\nspeed = 3\n[audio]\nmute = true\n[video]\nscale = 2\n")
+                .unwrap();
+        assert_eq!(
+            warnings,
+            [
+                "3: unknown key speed in [emulation]",
+                "4: unknown section [audio]"
+            ]
+        );
+        assert_eq!((config.cpf, config.scale), (Some(20), Some(2)));
+    }
+
+    #[test]
+    fn wrong_types_are_errors_with_their_line() {
+        let cases = [
+            ("[emulation]\n\ncpf = \"fast\"", "3: cpf must be a number"),
+            ("[emulation]\ncpf = 0", "2: cpf must be between 1 and 1000"),
+            (
+                "[video]\nflash_guard = 1",
+                "2: flash_guard must be true or false",
+            ),
+            (
+                "[video]\nfg = \"green\"",
+                "2: fg must be a color like \"00CD00\"",
+            ),
+            (
+                "[emulation]\nquirks = [\"fast\"]",
+                "2: quirks has an unknown quirk \"fast\"",
+            ),
+            ("[emulation]\ncpf = 20 30", "2: can't read the value of cpf"),
+            (
+                "[input]\nautofire = [\"5=10\"",
+                "2: can't read the value of autofire",
+            ),
+            ("[video]\nscale", "2: expected `key = value`"),
+        ];
+        for (text, error) in cases {
+            assert_eq!(parse(text).unwrap_err(), error, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn printed_config_reads_back_the_same() {
+        let config = Config {
+            cpf: Some(12),
+            timing: Some(Timing::Vip),
+            seed: Some(99),
+            schip: Some(true),
+            xo_chip: Some(true),
+            romdb: Some(false),
+            detect: Some(true),
+            font: Some(PathBuf::from("fonts/a \"b\".bin")),
+            halt_past_rom: Some(true),
+            truncate_oversize: Some(true),
+            display_height: Some(48),
+            quirks: vec!["clip-x", "vf-reset"],
+            fg: Some([1, 2, 3]),
+            bg: Some([0xAA, 0xBB, 0xCC]),
+            plane2: Some([0xFF, 0, 0x80]),
+            both: Some([0x10, 0x20, 0x30]),
+            scale: Some(5),
+            flicker_filter: Some(2),
+            flash_guard: Some(false),
+            show_speed: Some(true),
+            split_keys: Some(true),
+            key_repeat_guard: Some(false),
+            autofire: vec![(0xF, 30)],
+        };
+        assert_eq!(file(&config.to_string()), config);
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{crash, read_rom, romdb, savestate, video};
+use chip8_emu::{Chip8, Chip8Error};
+use log::info;
+
+// One line of a control script
+enum Command {
+    // key down 5 / key up 5
+    Key(usize, bool),
+    // Run this many frames before the next command
+    Wait(u32),
+    Screenshot(PathBuf),
+    SaveState(u8),
+    LoadState(u8),
+    // Start the ROM over on a fresh machine with the same seed
+    Reset,
+    Quit,
+}
+
+// Plays a ROM under the control of a script read from `source`
+// (a file or named pipe, or stdin for "-"), one command a frame:
+//
+//     key down 5
+//     wait 30
+//     key up 5
+//     screenshot out.ppm
+//     quit
+//
+// Each command is answered on stdout with `ok` or `err <msg>`
+// once it's done. Nothing runs in real time; the game only moves
+// on as commands come in, so with a fixed seed the same script
+// always gives the same screenshots. The script ending is the
+// same as quit.
+pub fn run(
+    path: &str,
+    source: &Path,
+    machine: &romdb::Options,
+    seed: u64,
+    scale: u16,
+    palette: video::Palette,
+) -> io::Result<()> {
+    let rom_path = Path::new(path);
+    let rom = read_rom(rom_path)?;
+    let setup = machine.setup(rom_path, &rom)?;
+    let budget = setup.timing.frame_budget(setup.cpf);
+    let rom_hash = savestate::rom_hash(&rom);
+    let start = || -> io::Result<Chip8> {
+        let mut chip8 = setup.builder.clone().seed(seed).build()?;
+        chip8.set_timer_hz(setup.timer_hz);
+        chip8.load_rom_bytes(&rom)?;
+        Ok(chip8)
+    };
+    let mut chip8 = start()?;
+
+    let mut stdout = io::stdout();
+    for line in spawn_reader(source)? {
+        let line = line.trim();
+        // Blank lines and comments don't use up a frame
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let command = match parse(line) {
+            Ok(command) => command,
+            Err(msg) => {
+                writeln!(stdout, "err {}", msg)?;
+                continue;
+            }
+        };
+        let mut frames = 1;
+        let done = match command {
+            Command::Key(key, pressed) => {
+                chip8.set_key(key, pressed);
+                Ok(())
+            }
+            Command::Wait(n) => {
+                frames = n;
+                Ok(())
+            }
+            Command::Screenshot(path) => {
+                video::screenshot(&path, &chip8.display, chip8.display_width(), palette, scale)
+                    .map_err(|err| format!("Can't save {}: {}", path.display(), err))
+            }
+            Command::SaveState(slot) => {
+                let path = savestate::slot_path(rom_path, slot);
+                savestate::save(&path, &rom_hash, &chip8.snapshot())
+                    .map_err(|err| format!("Can't save slot {}: {}", slot, err))
+            }
+            Command::LoadState(slot) => {
+                let path = savestate::slot_path(rom_path, slot);
+                match savestate::load(&path, &rom_hash) {
+                    Ok(Some(snapshot)) => {
+                        chip8.restore(&snapshot);
+                        Ok(())
+                    }
+                    Ok(None) => Err(format!("Slot {} is empty", slot)),
+                    Err(err) => Err(format!("Can't load slot {}: {}", slot, err)),
+                }
+            }
+            Command::Reset => {
+                chip8 = start()?;
+                Ok(())
+            }
+            Command::Quit => {
+                writeln!(stdout, "ok")?;
+                return Ok(());
+            }
+        };
+
+        // A failed command still takes its frame, so the rest of
+        // the script stays in step
+        let ran = crash::guard(&mut chip8, |chip8| {
+            (0..frames).try_for_each(|_| step(chip8, budget))
+        })?;
+        match (done, ran) {
+            (_, Err(err)) => {
+                writeln!(stdout, "err Stopped: {}", err)?;
+                return Err(crash::stopped(&chip8, err));
+            }
+            (Err(msg), _) => writeln!(stdout, "err {}", msg)?,
+            (Ok(()), _) => writeln!(stdout, "ok")?,
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+fn step(chip8: &mut Chip8, budget: u32) -> Result<(), Chip8Error> {
+    chip8.step_frame(budget)?;
+    // stdout is for replies, so watchpoints go to the log
+    for hit in chip8.take_watchpoint_hits() {
+        info!("watch: {}", hit);
+    }
+    Ok(())
+}
+
+fn parse(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.as_slice() {
+        ["key", state @ ("down" | "up"), key] => match u8::from_str_radix(key, 16) {
+            Ok(key) if key < 16 => Command::Key(key as usize, *state == "down"),
+            _ => return Err(format!("Not a key (0-F): {}", key)),
+        },
+        ["wait", frames] => match frames.parse() {
+            Ok(frames) => Command::Wait(frames),
+            Err(_) => return Err(format!("Not a number of frames: {}", frames)),
+        },
+        ["screenshot", path] => Command::Screenshot(PathBuf::from(path)),
+        ["savestate", slot] => Command::SaveState(parse_slot(slot)?),
+        ["loadstate", slot] => Command::LoadState(parse_slot(slot)?),
+        ["reset"] => Command::Reset,
+        ["quit"] => Command::Quit,
+        _ => return Err(format!("Unknown command: {}", line)),
+    };
+    Ok(command)
+}
+
+// 3 or slot3, as in the save state file names
+fn parse_slot(s: &str) -> Result<u8, String> {
+    match s.strip_prefix("slot").unwrap_or(s).parse() {
+        Ok(slot) if slot < savestate::SLOTS => Ok(slot),
+        _ => Err(format!("Not a slot (0-{}): {}", savestate::SLOTS - 1, s)),
+    }
+}
+
+// Reads lines on their own thread, so a slow writer on the other
+// end of a pipe is only waited for between frames
+fn spawn_reader(source: &Path) -> io::Result<Receiver<String>> {
+    let input: Box<dyn BufRead + Send> = if source == Path::new("-") {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let file = File::open(source).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Can't open {}: {}", source.display(), err),
+            )
+        })?;
+        Box::new(BufReader::new(file))
+    };
+
+    let (lines, received) = mpsc::channel();
+    thread::spawn(move || {
+        for line in input.lines() {
+            let Ok(line) = line else { break };
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(received)
+}
@@ -0,0 +1,67 @@
+use std::{
+    any::Any,
+    error::Error,
+    fmt, io,
+    panic::{self, AssertUnwindSafe},
+};
+
+use chip8_emu::{Chip8, Chip8Error};
+
+// Runs `f` on the machine, turning a panic inside it (a bug in
+// the emulator, not the ROM) into an error carrying the CPU state
+// and the memory around PC, so a crash on some odd ROM can be
+// tracked down. The panic hook still prints the message and
+// location first. The machine is only read afterwards, so it
+// being left halfway through an instruction doesn't matter.
+pub fn guard<T>(chip8: &mut Chip8, f: impl FnOnce(&mut Chip8) -> T) -> io::Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(|| f(chip8))).map_err(|payload| {
+        let msg = format!(
+            "The emulator crashed ({}). Machine state:\n{}",
+            message(&*payload),
+            chip8.crash_report().trim_end()
+        );
+        with_trace(chip8, msg.into())
+    })
+}
+
+// A ROM stopping on `err`, for main to report along with the
+// instructions that led there
+pub fn stopped(chip8: &Chip8, err: Chip8Error) -> io::Error {
+    with_trace(chip8, Box::new(err))
+}
+
+// An error that stopped the machine, with its last instructions
+// (Chip8::dump_trace()), which main prints after the error
+#[derive(Debug)]
+pub struct WithTrace {
+    pub error: Box<dyn Error + Send + Sync>,
+    pub trace: String,
+}
+
+impl fmt::Display for WithTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl Error for WithTrace {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+fn with_trace(chip8: &Chip8, error: Box<dyn Error + Send + Sync>) -> io::Error {
+    io::Error::other(WithTrace {
+        error,
+        trace: chip8.dump_trace(),
+    })
+}
+
+fn message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("panic", String::as_str),
+    }
+}
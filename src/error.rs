@@ -0,0 +1,48 @@
+use std::{error::Error, fmt, io};
+
+// Errors a ROM can cause while running. The instruction that
+// failed isn't executed and PC stays on it, so the machine can
+// be inspected afterwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    // 2NNN with the stack already at its depth limit
+    StackOverflow { pc: u16, depth: usize },
+    // 00EE with nothing on the stack to return to
+    StackUnderflow { pc: u16 },
+    // 0NNN, a call into native COSMAC VIP (RCA 1802) code
+    MachineCodeCall { pc: u16, addr: u16 },
+    // An opcode that isn't an instruction, or belongs to an
+    // extension that isn't enabled
+    InvalidOpcode { pc: u16, opcode: u16 },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::StackOverflow { pc, depth } => write!(
+                f,
+                "Stack overflow at {:#05X} (more than {} nested calls)",
+                pc, depth
+            ),
+            Chip8Error::StackUnderflow { pc } => {
+                write!(f, "Return at {:#05X} with an empty stack", pc)
+            }
+            Chip8Error::MachineCodeCall { pc, addr } => write!(
+                f,
+                "{:#05X} calls native COSMAC VIP code at {:#05X} (0NNN), which can't be emulated",
+                pc, addr
+            ),
+            Chip8Error::InvalidOpcode { pc, opcode } => {
+                write!(f, "Invalid opcode {:#06X} at {:#05X}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl Error for Chip8Error {}
+
+impl From<Chip8Error> for io::Error {
+    fn from(err: Chip8Error) -> Self {
+        io::Error::other(err)
+    }
+}
@@ -0,0 +1,95 @@
+// With --flash-guard the terminal doesn't show big changes to
+// the display all at once. Games that clear the screen and draw
+// everything again a frame or two later strobe the whole screen;
+// showing at most a third of the pixels changing each frame turns
+// that into a fade over up to three frames. Ordinary movement
+// changes far fewer pixels and shows straight away.
+//
+// Only what's shown is held back; the machine's display is left
+// alone.
+pub struct FlashGuard {
+    shown: Vec<u8>,
+}
+
+impl FlashGuard {
+    pub fn new() -> Self {
+        FlashGuard { shown: Vec::new() }
+    }
+
+    // Moves what's shown toward `display` and returns it
+    pub fn present(&mut self, display: &[u8]) -> &[u8] {
+        if self.shown.len() != display.len() {
+            self.shown = vec![0; display.len()];
+        }
+        let changed: Vec<usize> = (0..display.len())
+            .filter(|&i| self.shown[i] != display[i])
+            .collect();
+        let cap = display.len().div_ceil(3);
+        if changed.len() <= cap {
+            self.shown.copy_from_slice(display);
+        } else {
+            // Spread the pixels that change over the whole screen
+            // rather than wiping from the top
+            for n in 0..cap {
+                let i = changed[n * changed.len() / cap];
+                self.shown[i] = display[i];
+            }
+        }
+        &self.shown
+    }
+
+    // Whether what's shown has caught up with `display`
+    pub fn settled(&self, display: &[u8]) -> bool {
+        self.shown == display
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: usize = 64 * 32;
+
+    fn flips(a: &[u8], b: &[u8]) -> usize {
+        a.iter().zip(b).filter(|(x, y)| x != y).count()
+    }
+
+    #[test]
+    fn small_changes_show_at_once() {
+        let mut guard = FlashGuard::new();
+        let mut display = [0; PIXELS];
+        display[..200].fill(1);
+        assert_eq!(guard.present(&display), display);
+        assert!(guard.settled(&display));
+    }
+
+    #[test]
+    fn full_screen_change_is_capped() {
+        let mut guard = FlashGuard::new();
+        let blank = [0; PIXELS];
+        guard.present(&blank);
+
+        let lit = [1; PIXELS];
+        let mut before = blank.to_vec();
+        for frame in 0..3 {
+            let shown = guard.present(&lit).to_vec();
+            assert!(
+                flips(&before, &shown) <= PIXELS.div_ceil(3),
+                "frame {}",
+                frame
+            );
+            before = shown;
+        }
+        assert!(guard.settled(&lit));
+    }
+
+    #[test]
+    fn capped_changes_are_spread_out() {
+        let mut guard = FlashGuard::new();
+        guard.present(&[0; PIXELS]);
+        let shown = guard.present(&[1; PIXELS]).to_vec();
+        // Some pixels change in both the top and bottom rows
+        assert!(shown[..64].contains(&1));
+        assert!(shown[PIXELS - 64..].contains(&1));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{Chip8, Chip8Error, FramePacer, Pacing};
+
+// A run loop for frontends that only need to show the display,
+// read the keypad and sound the buzzer: run_emulator() owns the
+// frame timing, pausing and stepping, and hands each part to one
+// of three traits. The terminal keeps its own loop, since rewind,
+// save states, netplay and the debugger go beyond these.
+
+// The display as a frontend shows it: one byte per pixel, row by
+// row, bit 0 lit in the first plane and bit 1 in XO-CHIP's
+// second. The size comes with it, since hi-res and SUPER-CHIP
+// displays aren't 64x32.
+pub struct Framebuffer<'a> {
+    pub pixels: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+}
+
+impl<'a> Framebuffer<'a> {
+    // What the machine shows now (see Chip8::display_composited())
+    pub fn of(chip8: &'a Chip8) -> Self {
+        Framebuffer {
+            pixels: chip8.display_composited(),
+            width: chip8.display_width(),
+            height: chip8.display_height(),
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    // A keypad key (0x0-0xF) pressed or let go
+    Down(usize),
+    Up(usize),
+    // Pauses, or resumes if paused
+    Pause,
+    // Runs one frame while paused
+    Step,
+}
+
+// Shows frames. Only called when the display changed.
+pub trait DisplaySink {
+    fn present(&mut self, fb: &Framebuffer);
+}
+
+// Polled once a frame, paused or not
+pub trait InputSource {
+    fn poll(&mut self) -> Vec<KeyEvent>;
+    fn should_quit(&self) -> bool;
+}
+
+// Told when the buzzer turns on and off
+pub trait AudioSink {
+    fn set_beeping(&mut self, on: bool);
+}
+
+// Sinks that do nothing, for running without a frontend (headless
+// mode, tests)
+pub struct NoDisplay;
+pub struct NoInput;
+pub struct NoAudio;
+
+impl DisplaySink for NoDisplay {
+    fn present(&mut self, _: &Framebuffer) {}
+}
+
+impl InputSource for NoInput {
+    fn poll(&mut self) -> Vec<KeyEvent> {
+        Vec::new()
+    }
+
+    fn should_quit(&self) -> bool {
+        false
+    }
+}
+
+impl AudioSink for NoAudio {
+    fn set_beeping(&mut self, _: bool) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunConfig {
+    // Instructions per frame; with VIP timing the frame's budget
+    // is fixed instead (see Timing::frame_budget())
+    pub cpf: usize,
+    // How long a frame takes in real time, or None to run frames
+    // back to back
+    pub frame_time: Option<Duration>,
+    // Stop after this many frames have run
+    pub max_frames: Option<u64>,
+    // Stop after this many instructions have run, partway through
+    // a frame if need be (see Chip8::step_frame_upto())
+    pub max_cycles: Option<u64>,
+    // Delay and sound timer ticks a second of emulated time (see
+    // Chip8::set_timer_hz()); 60 as on real hardware
+    pub timer_hz: u32,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            cpf: 15,
+            frame_time: Some(Duration::from_micros(16_667)),
+            max_frames: None,
+            max_cycles: None,
+            timer_hz: 60,
+        }
+    }
+}
+
+// Runs `chip8` until the input says to quit, `config.max_frames`
+// or `config.max_cycles` have run, or the ROM fails. Time is
+// measured out in whole frames: each time round, the input is
+// polled, the frames that have come due run (unless paused), and
+// the display is presented once if any of them changed it. A
+// frontend that's slow to present gets fewer presents rather
+// than a slower game. The audio is told when the buzzer turns on
+// or off (and off while paused). Returns the frames run.
+pub fn run_emulator(
+    chip8: &mut Chip8,
+    display: &mut impl DisplaySink,
+    input: &mut impl InputSource,
+    audio: &mut impl AudioSink,
+    config: RunConfig,
+) -> Result<u64, Chip8Error> {
+    let budget = chip8.timing().frame_budget(config.cpf);
+    chip8.set_timer_hz(config.timer_hz);
+    // The display starts out shown, so the first frame doesn't
+    // show it again unless it draws
+    display.present(&Framebuffer::of(chip8));
+    chip8.take_draw_flag();
+    let (mut frames, mut paused, mut beeping) = (0, false, false);
+    let mut cycles = 0;
+    let mut pacer = config
+        .frame_time
+        .map(|frame| FramePacer::new(Pacing::Sleep, frame, Instant::now()));
+
+    let done = |frames, cycles| {
+        config.max_frames.is_some_and(|max| frames >= max)
+            || config.max_cycles.is_some_and(|max| cycles >= max)
+    };
+
+    while !done(frames, cycles) {
+        let mut step = false;
+        for event in input.poll() {
+            match event {
+                KeyEvent::Down(key) => chip8.set_key(key, true),
+                KeyEvent::Up(key) => chip8.set_key(key, false),
+                KeyEvent::Pause => {
+                    paused = !paused;
+                    if beeping {
+                        audio.set_beeping(!paused);
+                    }
+                }
+                KeyEvent::Step => step = true,
+            }
+        }
+        if input.should_quit() {
+            break;
+        }
+
+        let due = match &mut pacer {
+            Some(pacer) => pacer.take_due(Instant::now()),
+            None => 1,
+        };
+        let mut changed = false;
+        for _ in 0..due {
+            if done(frames, cycles) || (paused && !step) {
+                break;
+            }
+            step = false;
+            let limit = config.max_cycles.map_or(u64::MAX, |max| max - cycles);
+            let output = chip8.step_frame_upto(budget, limit)?;
+            cycles += output.instructions as u64;
+            changed |= output.display_changed;
+            if output.sound != beeping {
+                beeping = output.sound;
+                if !paused {
+                    audio.set_beeping(beeping);
+                }
+            }
+            frames += 1;
+        }
+        if changed {
+            display.present(&Framebuffer::of(chip8));
+        }
+
+        if let Some(pacer) = &pacer {
+            thread::sleep(pacer.timeout(Instant::now()));
+        }
+    }
+
+    if beeping && !paused {
+        audio.set_beeping(false);
+    }
+    Ok(frames)
+}
@@ -0,0 +1,242 @@
+use std::{fs, io, path::Path};
+
+use crate::{crash, info::json_string, read_rom, romdb, trace::Trace};
+use chip8_emu::{Chip8, Chip8Error, NoAudio, NoDisplay, NoInput, RunConfig, decode, run_emulator};
+
+// Runs a ROM for a fixed number of cycles without any window or
+// terminal UI, then prints the final machine state. With a fixed
+// seed the output is deterministic, so it can be diffed between
+// builds to catch opcode regressions.
+//
+// A ROM that stops on an error still gets its state printed (and
+// its report written) before the error is returned, which main
+// turns into an exit code saying what went wrong. With `profile`
+// the most common instructions are listed after the state, and
+// with `trace` every instruction run ahead of it.
+pub fn run(
+    path: &str,
+    machine: &romdb::Options,
+    cycles: u64,
+    seed: u64,
+    report: Option<&Path>,
+    profile: bool,
+    trace: Option<&Trace>,
+) -> io::Result<()> {
+    let rom = read_rom(Path::new(path))?;
+    let setup = machine.setup(Path::new(path), &rom)?;
+
+    let mut chip8 = setup.builder.seed(seed).build()?;
+    chip8.load_rom_bytes(&rom)?;
+    chip8.count_families(report.is_some());
+    chip8.set_profiling(profile);
+
+    let run = crash::guard(&mut chip8, |chip8| {
+        execute(chip8, setup.cpf, setup.timer_hz, cycles)
+    })?;
+    if let Some(trace) = trace {
+        trace.write(&mut chip8)?;
+    }
+
+    println!("cycles: {}", run.cycles);
+    println!("pc: {:#06X}", chip8.pc());
+    println!("i: {:#06X}", chip8.i());
+    println!("sp: {}", chip8.stack_depth());
+    for (x, v) in chip8.registers().iter().enumerate() {
+        println!("v{:x}: {:#04X}", x, v);
+    }
+    println!("dt: {}", chip8.delay_timer());
+    println!("st: {}", chip8.sound_timer());
+    println!("display: {:#018x}", chip8.display_hash());
+    if profile {
+        print!("{}", profile_text(&chip8));
+    }
+
+    if let Some(path) = report {
+        fs::write(path, report_json(&chip8, &run)).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Can't write report {}: {}", path.display(), err),
+            )
+        })?;
+    }
+    match run.error {
+        Some(err) => Err(crash::stopped(&chip8, err)),
+        None => Ok(()),
+    }
+}
+
+// How far a run got: the instructions executed, and the error
+// that stopped it short of `cycles`, if any
+struct Run {
+    cycles: u64,
+    error: Option<Chip8Error>,
+}
+
+// Runs with run_emulator(), frames back to back and nothing to
+// show them on, so a frame (timers, vblank) passes every CPF
+// cycles as in the terminal; with VIP or SUPER-CHIP timing, once
+// its budget is spent. A display wait stall or a watchpoint hit
+// ends the frame early, as it does there too. Watchpoint hits are
+// printed once the run is over, ahead of the final state.
+fn execute(chip8: &mut Chip8, cpf: usize, timer_hz: u32, cycles: u64) -> Run {
+    let config = RunConfig {
+        cpf,
+        frame_time: None,
+        max_frames: None,
+        max_cycles: Some(cycles),
+        timer_hz,
+    };
+    let start = chip8.cycle_count();
+    let result = run_emulator(chip8, &mut NoDisplay, &mut NoInput, &mut NoAudio, config);
+    for hit in chip8.take_watchpoint_hits() {
+        println!("watch: {}", hit);
+    }
+    match result {
+        Ok(_) => Run {
+            cycles,
+            error: None,
+        },
+        // The failed instruction isn't counted
+        Err(err) => Run {
+            cycles: chip8.cycle_count() - start,
+            error: Some(err),
+        },
+    }
+}
+
+// Instruction classes listed by --profile
+const PROFILE_TOP: usize = 10;
+
+// The most executed instruction classes, with their share of all
+// instructions run
+fn profile_text(chip8: &Chip8) -> String {
+    let stats = chip8.opcode_stats();
+    let total: u64 = stats.iter().map(|&(_, count)| count).sum();
+    let mut out = String::from("profile:\n");
+    for &(class, count) in stats.iter().take(PROFILE_TOP) {
+        out += &format!(
+            "  {}  {:>10}  {:5.1}%\n",
+            decode(class).pattern(),
+            count,
+            count as f64 * 100.0 / total as f64
+        );
+    }
+    if stats.len() > PROFILE_TOP {
+        out += &format!("  ({} more)\n", stats.len() - PROFILE_TOP);
+    }
+    out
+}
+
+// The --report file: what a CI script wants to check without
+// parsing the printed state. `stop` is "cycles" when every cycle
+// ran, and otherwise names the error. The instruction families
+// are null unless the machine was counting them.
+fn report_json(chip8: &Chip8, run: &Run) -> String {
+    let stop = match run.error {
+        None => "cycles",
+        Some(Chip8Error::InvalidOpcode { .. }) => "invalid-opcode",
+        Some(Chip8Error::StackOverflow { .. }) => "stack-overflow",
+        Some(Chip8Error::StackUnderflow { .. }) => "stack-underflow",
+        Some(Chip8Error::MachineCodeCall { .. }) => "machine-code-call",
+    };
+    let error = match &run.error {
+        Some(err) => json_string(&err.to_string()),
+        None => "null".to_string(),
+    };
+    let families = match chip8.family_counts() {
+        Some(counts) => {
+            let counts: Vec<String> = counts
+                .iter()
+                .enumerate()
+                .map(|(family, count)| format!("\"{:X}\": {}", family, count))
+                .collect();
+            format!("{{{}}}", counts.join(", "))
+        }
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"cycles\": {}, \"pc\": {}, \"stop\": \"{}\", \"error\": {}, \"halted\": {}, \
+         \"display\": \"{:016x}\", \"families\": {}, \"peak_stack_depth\": {}}}\n",
+        run.cycles,
+        chip8.pc(),
+        stop,
+        error,
+        chip8.is_halted(),
+        chip8.display_hash(),
+        families,
+        chip8.peak_stack_depth(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_emu::assemble;
+
+    fn machine(source: &str) -> Chip8 {
+        let mut chip8 = Chip8::builder().seed(0).build().unwrap();
+        chip8.load_rom_bytes(&assemble(source).unwrap()).unwrap();
+        chip8.count_families(true);
+        chip8
+    }
+
+    #[test]
+    fn report_for_a_rom_that_finishes() {
+        // Two calls deep, then a jump to itself at 0x202
+        let mut chip8 = machine("f : end jump end : f g return : g v0 := 1 return");
+        let run = execute(&mut chip8, 15, 60, 100);
+        assert_eq!(run.cycles, 100);
+        assert_eq!(
+            report_json(&chip8, &run),
+            "{\"cycles\": 100, \"pc\": 514, \"stop\": \"cycles\", \"error\": null, \
+             \"halted\": true, \"display\": \"28c31cf8df2ec325\", \"families\": {\
+             \"0\": 2, \"1\": 95, \"2\": 2, \"3\": 0, \"4\": 0, \"5\": 0, \"6\": 1, \"7\": 0, \
+             \"8\": 0, \"9\": 0, \"A\": 0, \"B\": 0, \"C\": 0, \"D\": 0, \"E\": 0, \"F\": 0}, \
+             \"peak_stack_depth\": 2}\n"
+        );
+    }
+
+    #[test]
+    fn report_names_the_error() {
+        let mut chip8 = machine("v0 := 1 0xFF 0xFF");
+        let run = execute(&mut chip8, 15, 60, 100);
+        assert_eq!(run.cycles, 1);
+        let report = report_json(&chip8, &run);
+        assert!(
+            report.contains("\"stop\": \"invalid-opcode\""),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("\"error\": \"Invalid opcode 0xFFFF at 0x202\""),
+            "{}",
+            report
+        );
+        assert!(report.contains("\"halted\": false"), "{}", report);
+
+        let mut chip8 = machine("return");
+        let run = execute(&mut chip8, 15, 60, 100);
+        assert_eq!((run.cycles, chip8.pc()), (0, 0x200));
+        assert!(report_json(&chip8, &run).contains("\"stop\": \"stack-underflow\""));
+    }
+
+    #[test]
+    fn profile_lists_the_most_common_instructions() {
+        let mut chip8 = machine("v0 := 0 loop v0 += 1 v1 := v0 v1 += v0 again");
+        chip8.set_profiling(true);
+        execute(&mut chip8, 15, 60, 401);
+        assert_eq!(
+            profile_text(&chip8),
+            "profile:\n  1NNN         100   24.9%\n  7XNN         100   24.9%\n  \
+             8XY0         100   24.9%\n  8XY4         100   24.9%\n  6XNN           1    0.2%\n"
+        );
+    }
+
+    #[test]
+    fn families_are_only_counted_when_asked() {
+        let mut chip8 = machine(": end jump end");
+        chip8.count_families(false);
+        let run = execute(&mut chip8, 15, 60, 10);
+        assert!(report_json(&chip8, &run).contains("\"families\": null"));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{collections::HashSet, fmt, io, mem, path::Path};
+
+use crate::{
+    read_rom, romdb,
+    scan::{self, Extension},
+};
+use chip8_emu::{Opcode, decode};
+
+// Describes a ROM without running it: size, hash, where it
+// lands in memory, a guess at the CHIP-8 variant it was written
+// for, and anything that looks off. Prints one `key: value` per
+// line, or a JSON object with the same keys.
+pub fn run(path: &str, machine: &romdb::Options, json: bool) -> io::Result<()> {
+    let rom = read_rom(Path::new(path))?;
+
+    let hash = romdb::sha256(&rom);
+    let words: Vec<u16> = rom
+        .chunks_exact(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]))
+        .collect();
+
+    let families: HashSet<_> = words
+        .iter()
+        .map(|&w| decode(w))
+        .filter(|op| !matches!(op, Opcode::Invalid(_)))
+        .map(|op| mem::discriminant(&op))
+        .collect();
+
+    let variant = match scan::detect(&rom) {
+        Some(Extension::XoChip) => "xo-chip",
+        Some(Extension::SuperChip) => "schip",
+        None => "chip8",
+    };
+
+    // Loaded into the machine it would be played on, with the
+    // same profile, entry point and memory size, but nothing runs
+    let mut chip8 = machine.setup(Path::new(path), &rom)?.builder.build()?;
+    let start = chip8.rom_range().start;
+    let room = chip8.memory().len() - start;
+    let fits = rom.len() <= room && chip8.load_rom_bytes(&rom).is_ok();
+    let entry = chip8.pc() as usize;
+
+    let mut warnings = Vec::new();
+    if rom.is_empty() {
+        warnings.push("empty file".to_string());
+    }
+    if rom.len() % 2 != 0 {
+        warnings.push("odd file size, the last byte isn't a whole instruction".to_string());
+    }
+    if !fits {
+        warnings.push(format!(
+            "too large, only {} bytes fit after {:#05X} (see --truncate-oversize)",
+            room, start
+        ));
+    }
+    // An entry point outside the ROM has nothing to check
+    let first = entry
+        .checked_sub(start)
+        .and_then(|at| rom.get(at..at + 2))
+        .map(|w| u16::from_be_bytes([w[0], w[1]]));
+    if let Some(word) = first
+        && let Opcode::Invalid(_) | Opcode::Sys(_) = decode(word)
+    {
+        warnings.push(format!(
+            "entry instruction at {:#05X} is {:#06X}, which looks like data",
+            entry, word
+        ));
+    }
+
+    let fields = [
+        ("file", Value::Str(path.to_string())),
+        ("size", Value::Num(rom.len())),
+        ("sha256", Value::Str(hash)),
+        ("load_start", Value::Str(format!("{:#05X}", start))),
+        (
+            "load_end",
+            Value::Str(format!("{:#05X}", start + rom.len().max(1) - 1)),
+        ),
+        ("fits", Value::Bool(fits)),
+        ("entry", Value::Str(format!("{:#05X}", entry))),
+        ("variant", Value::Str(variant.to_string())),
+        ("opcode_families", Value::Num(families.len())),
+    ];
+
+    if json {
+        let mut out = String::from("{");
+        for (key, value) in &fields {
+            out += &format!("\"{}\": {}, ", key, value.to_json());
+        }
+        let warnings: Vec<String> = warnings.iter().map(|w| json_string(w)).collect();
+        out += &format!("\"warnings\": [{}]}}", warnings.join(", "));
+        println!("{}", out);
+    } else {
+        for (key, value) in &fields {
+            println!("{}: {}", key, value);
+        }
+        for warning in &warnings {
+            println!("warning: {}", warning);
+        }
+    }
+
+    Ok(())
+}
+
+enum Value {
+    Str(String),
+    Num(usize),
+    Bool(bool),
+}
+
+impl Value {
+    fn to_json(&self) -> String {
+        match self {
+            Value::Str(s) => json_string(s),
+            Value::Num(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", if *b { "yes" } else { "no" }),
+        }
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if c.is_control() => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
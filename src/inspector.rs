@@ -0,0 +1,335 @@
+use crate::Chip8;
+use std::io::Result;
+
+// The register inspector: where each of V0-VF, I, PC, the timers
+// and the call stack goes in the panel, and typing new values
+// into V0-VF, I and PC while the machine is paused. Positions
+// are in character cells; the frontend does the drawing.
+
+// Characters from one cell to the next: a two-character label,
+// a space, up to four digits and a gap
+const CELL_WIDTH: usize = 8;
+const COLUMNS: usize = 4;
+// Rows before the stack: V0-VF, then I, PC and the timers, then
+// a blank one
+const STACK_ROW: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    V(usize),
+    I,
+    Pc,
+    Dt,
+    St,
+    // The return address `n` calls deep
+    Stack(usize),
+}
+
+impl Field {
+    pub fn label(self) -> String {
+        match self {
+            Field::V(x) => format!("V{:X}", x),
+            Field::I => "I".to_string(),
+            Field::Pc => "PC".to_string(),
+            Field::Dt => "DT".to_string(),
+            Field::St => "ST".to_string(),
+            Field::Stack(n) => format!("S{:X}", n),
+        }
+    }
+
+    // Only what has a setter on Chip8; the timers and the stack
+    // are shown but not edited
+    pub fn editable(self) -> bool {
+        matches!(self, Field::V(_) | Field::I | Field::Pc)
+    }
+
+    // The editable fields, in the order Tab moves through them
+    fn all_editable() -> Vec<Field> {
+        (0..16).map(Field::V).chain([Field::I, Field::Pc]).collect()
+    }
+
+    pub fn read(self, chip8: &Chip8) -> u16 {
+        match self {
+            Field::V(x) => chip8.registers()[x] as u16,
+            Field::I => chip8.i(),
+            Field::Pc => chip8.pc(),
+            Field::Dt => chip8.delay_timer() as u16,
+            Field::St => chip8.sound_timer() as u16,
+            Field::Stack(n) => chip8.stack().get(n).copied().unwrap_or(0),
+        }
+    }
+
+    // Fails for values the machine can't hold (see the setters)
+    fn write(self, chip8: &mut Chip8, value: u16) -> Result<()> {
+        match self {
+            Field::V(x) => chip8.set_register(x, value as u8),
+            Field::I => chip8.set_i(value),
+            Field::Pc => chip8.set_pc(value),
+            _ => unreachable!("{:?} isn't editable", self),
+        }
+    }
+}
+
+// Picking a field and typing its new value in hex. An edit goes
+// in once every digit has been typed; there's nothing to confirm.
+pub struct Inspector {
+    // Digits in an address: 3 for 4KB of memory, 4 for 64KB
+    addr_digits: usize,
+    paused: bool,
+    selected: Option<Field>,
+    // The digits typed so far into the selected field
+    typed: u16,
+    count: usize,
+}
+
+impl Inspector {
+    pub fn new(memory_size: usize) -> Self {
+        let addr_digits = format!("{:X}", memory_size.max(2) - 1).len();
+        Inspector {
+            addr_digits,
+            paused: false,
+            selected: None,
+            typed: 0,
+            count: 0,
+        }
+    }
+
+    // Every field with its column and row, for a stack `depth` calls
+    // deep. Only the return addresses in use are shown.
+    pub fn layout(depth: usize) -> Vec<(Field, usize, usize)> {
+        let fixed = (0..16)
+            .map(Field::V)
+            .chain([Field::I, Field::Pc, Field::Dt, Field::St]);
+        let stack = (0..depth).map(Field::Stack);
+        fixed
+            .enumerate()
+            .map(|(n, field)| (field, n % COLUMNS * CELL_WIDTH, n / COLUMNS))
+            .chain(
+                stack
+                    .enumerate()
+                    .map(|(n, field)| (field, n % COLUMNS * CELL_WIDTH, STACK_ROW + n / COLUMNS)),
+            )
+            .collect()
+    }
+
+    // The field whose cell covers a character position, e.g. where
+    // the panel was clicked
+    pub fn field_at(depth: usize, col: usize, row: usize) -> Option<Field> {
+        Self::layout(depth)
+            .into_iter()
+            .find(|&(_, c, r)| r == row && (c..c + CELL_WIDTH).contains(&col))
+            .map(|(field, _, _)| field)
+    }
+
+    // Edits are only taken while paused, so a value can't change
+    // under the ROM mid-frame. Running again drops the selection.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        if !paused {
+            self.selected = None;
+            self.cancel();
+        }
+    }
+
+    // Digits shown and typed for a field
+    pub fn digits(&self, field: Field) -> usize {
+        match field {
+            Field::V(_) | Field::Dt | Field::St => 2,
+            Field::I | Field::Pc | Field::Stack(_) => self.addr_digits,
+        }
+    }
+
+    pub fn selected(&self) -> Option<Field> {
+        self.selected
+    }
+
+    // The digits typed into the selected field so far, and how
+    // many there are
+    pub fn typed(&self) -> (u16, usize) {
+        (self.typed, self.count)
+    }
+
+    // Selects a field to type into. Returns false (and leaves the
+    // selection alone) while running or for fields that can't be
+    // edited.
+    pub fn select(&mut self, field: Field) -> bool {
+        if !self.paused || !field.editable() {
+            return false;
+        }
+        self.selected = Some(field);
+        self.cancel();
+        true
+    }
+
+    // Moves to the next editable field (Tab), or the previous one
+    // (Shift+Tab), starting from V0
+    pub fn select_next(&mut self, forward: bool) -> bool {
+        let fields = Field::all_editable();
+        let len = fields.len();
+        let next = match self
+            .selected
+            .and_then(|f| fields.iter().position(|&e| e == f))
+        {
+            Some(n) if forward => (n + 1) % len,
+            Some(n) => (n + len - 1) % len,
+            None => 0,
+        };
+        self.select(fields[next])
+    }
+
+    // Drops the digits typed so far; the field stays selected
+    pub fn cancel(&mut self) {
+        self.typed = 0;
+        self.count = 0;
+    }
+
+    // Esc: drops the digits typed so far, or the selection if
+    // there aren't any
+    pub fn escape(&mut self) {
+        if self.count == 0 {
+            self.selected = None;
+        }
+        self.cancel();
+    }
+
+    // Types one hex digit (0-F) into the selected field. The last
+    // digit writes the value into `chip8`, which can still refuse
+    // it (a PC past the end of memory, say); either way the field
+    // is ready to be typed into again. Returns whether a value
+    // was written.
+    pub fn type_digit(&mut self, chip8: &mut Chip8, digit: u8) -> Result<bool> {
+        let Some(field) = self.selected.filter(|_| self.paused && digit < 16) else {
+            return Ok(false);
+        };
+        self.typed = self.typed << 4 | digit as u16;
+        self.count += 1;
+        if self.count < self.digits(field) {
+            return Ok(false);
+        }
+        let value = self.typed;
+        self.cancel();
+        field.write(chip8, value)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paused() -> Inspector {
+        let mut inspector = Inspector::new(4096);
+        inspector.set_paused(true);
+        inspector
+    }
+
+    #[test]
+    fn layout_has_no_overlaps() {
+        let cells = Inspector::layout(16);
+        assert_eq!(cells.len(), 20 + 16);
+        for (n, a) in cells.iter().enumerate() {
+            for b in &cells[n + 1..] {
+                assert!((a.1, a.2) != (b.1, b.2), "{:?} and {:?}", a.0, b.0);
+            }
+        }
+    }
+
+    #[test]
+    fn clicks_find_their_cell() {
+        assert_eq!(Inspector::field_at(0, 0, 0), Some(Field::V(0)));
+        assert_eq!(Inspector::field_at(0, CELL_WIDTH + 5, 1), Some(Field::V(5)));
+        assert_eq!(Inspector::field_at(0, CELL_WIDTH, 4), Some(Field::Pc));
+        assert_eq!(
+            Inspector::field_at(2, CELL_WIDTH, STACK_ROW),
+            Some(Field::Stack(1))
+        );
+        // Past the stack in use, and past the last column
+        assert_eq!(Inspector::field_at(1, CELL_WIDTH, STACK_ROW), None);
+        assert_eq!(Inspector::field_at(0, COLUMNS * CELL_WIDTH, 0), None);
+    }
+
+    #[test]
+    fn two_digits_set_a_register() {
+        let mut chip8 = Chip8::new();
+        let mut inspector = paused();
+        assert!(inspector.select(Field::V(3)));
+        assert!(!inspector.type_digit(&mut chip8, 0x0).unwrap());
+        assert_eq!(inspector.typed(), (0x0, 1));
+        assert!(inspector.type_digit(&mut chip8, 0x9).unwrap());
+        assert_eq!(chip8.registers()[3], 9);
+        // Ready for the next value
+        assert_eq!(inspector.typed(), (0, 0));
+        assert_eq!(inspector.selected(), Some(Field::V(3)));
+    }
+
+    #[test]
+    fn addresses_take_as_many_digits_as_memory_needs() {
+        assert_eq!(Inspector::new(4096).digits(Field::Pc), 3);
+        assert_eq!(Inspector::new(0x10000).digits(Field::I), 4);
+
+        let mut chip8 = Chip8::new();
+        let mut inspector = paused();
+        inspector.select(Field::Pc);
+        for digit in [0x3, 0x4, 0x6] {
+            inspector.type_digit(&mut chip8, digit).unwrap();
+        }
+        assert_eq!(chip8.pc(), 0x346);
+    }
+
+    #[test]
+    fn nothing_is_edited_while_running() {
+        let mut chip8 = Chip8::new();
+        let mut inspector = Inspector::new(4096);
+        assert!(!inspector.select(Field::V(0)));
+        assert!(!inspector.select_next(true));
+
+        inspector.set_paused(true);
+        inspector.select(Field::V(0));
+        inspector.type_digit(&mut chip8, 0xA).unwrap();
+        inspector.set_paused(false);
+        assert_eq!(inspector.selected(), None);
+        assert!(!inspector.type_digit(&mut chip8, 0xB).unwrap());
+        assert_eq!(chip8.registers()[0], 0);
+    }
+
+    #[test]
+    fn tab_skips_fields_that_cant_be_edited() {
+        let mut inspector = paused();
+        assert!(!inspector.select(Field::Dt));
+        inspector.select_next(true);
+        assert_eq!(inspector.selected(), Some(Field::V(0)));
+        inspector.select_next(false);
+        assert_eq!(inspector.selected(), Some(Field::Pc));
+        inspector.select_next(false);
+        assert_eq!(inspector.selected(), Some(Field::I));
+        inspector.select_next(true);
+        inspector.select_next(true);
+        assert_eq!(inspector.selected(), Some(Field::V(0)));
+    }
+
+    #[test]
+    fn escape_drops_digits_then_the_selection() {
+        let mut chip8 = Chip8::new();
+        let mut inspector = paused();
+        inspector.select(Field::V(1));
+        inspector.type_digit(&mut chip8, 0x4).unwrap();
+        inspector.escape();
+        assert_eq!(inspector.typed(), (0, 0));
+        assert_eq!(inspector.selected(), Some(Field::V(1)));
+        inspector.escape();
+        assert_eq!(inspector.selected(), None);
+    }
+
+    #[test]
+    fn refused_values_are_errors() {
+        // 4KB of memory takes 3-digit addresses, so a bad PC
+        // needs a machine smaller than the inspector thinks
+        let mut chip8 = Chip8::with_layout(0x400, 0x200);
+        let mut inspector = paused();
+        inspector.select(Field::Pc);
+        inspector.type_digit(&mut chip8, 0xF).unwrap();
+        inspector.type_digit(&mut chip8, 0x0).unwrap();
+        assert!(inspector.type_digit(&mut chip8, 0x0).is_err());
+        assert_eq!(chip8.pc(), 0x200);
+    }
+}
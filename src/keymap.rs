@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::KeyCode;
+
+// Same layout as the web frontend:
+// 1 2 3 C → 1 2 3 4
+// 4 5 6 D → Q W E R
+// 7 8 9 E → A S D F
+// A 0 B F → Z X C V
+const STANDARD: [(char, usize); 16] = [
+    ('1', 0x1),
+    ('2', 0x2),
+    ('3', 0x3),
+    ('4', 0xC),
+    ('q', 0x4),
+    ('w', 0x5),
+    ('e', 0x6),
+    ('r', 0xD),
+    ('a', 0x7),
+    ('s', 0x8),
+    ('d', 0x9),
+    ('f', 0xE),
+    ('z', 0xA),
+    ('x', 0x0),
+    ('c', 0xB),
+    ('v', 0xF),
+];
+
+// With --split-keys the right half of the keypad can also be
+// played from the right of the keyboard, so a second player
+// doesn't have to crowd the first:
+// 3 C → 8 9
+// 6 D → I O
+// 9 E → K L
+// B F → , .
+// Up and Down are C and D too, the right paddle in Pong.
+const SPLIT: [(KeyCode, usize); 10] = [
+    (KeyCode::Char('8'), 0x3),
+    (KeyCode::Char('9'), 0xC),
+    (KeyCode::Char('i'), 0x6),
+    (KeyCode::Char('o'), 0xD),
+    (KeyCode::Char('k'), 0x9),
+    (KeyCode::Char('l'), 0xE),
+    (KeyCode::Char(','), 0xB),
+    (KeyCode::Char('.'), 0xF),
+    (KeyCode::Up, 0xC),
+    (KeyCode::Down, 0xD),
+];
+
+// Which keyboard keys press which keypad keys. Several keyboard
+// keys can be bound to one keypad key, which then stays down
+// until the last of them is let go.
+pub struct KeyMap {
+    bindings: HashMap<KeyCode, usize>,
+    // Keyboard keys held down, and when each was last pressed
+    // or repeated
+    held: HashMap<KeyCode, Instant>,
+    // How many held keyboard keys are pressing each keypad key
+    pressing: [u8; 16],
+    // Keypad keys that go up and down by themselves while held,
+    // how many times a second (0 for keys without autofire)
+    autofire: [u32; 16],
+    // Frames each keypad key has been held for
+    held_frames: [u32; 16],
+}
+
+impl KeyMap {
+    pub fn new(split: bool) -> Self {
+        let mut keys = KeyMap {
+            bindings: HashMap::new(),
+            held: HashMap::new(),
+            pressing: [0; 16],
+            autofire: [0; 16],
+            held_frames: [0; 16],
+        };
+        for (c, key) in STANDARD {
+            keys.bind(KeyCode::Char(c), key);
+        }
+        if split {
+            for (code, key) in SPLIT {
+                keys.bind(code, key);
+            }
+        }
+        keys
+    }
+
+    pub fn bind(&mut self, code: KeyCode, key: usize) {
+        self.bindings.insert(normalize(code), key);
+    }
+
+    // Makes a keypad key go up and down `hz` times a second while
+    // it's held (--autofire), for games that want it tapped fast
+    pub fn set_autofire(&mut self, key: usize, hz: u32) {
+        self.autofire[key] = hz;
+    }
+
+    // A keyboard key went down (or repeated). Returns the keypad
+    // key if this pressed it.
+    pub fn press(&mut self, code: KeyCode) -> Option<usize> {
+        let code = normalize(code);
+        let key = *self.bindings.get(&code)?;
+        if self.held.insert(code, Instant::now()).is_some() {
+            return None;
+        }
+        self.pressing[key] += 1;
+        if self.pressing[key] > 1 {
+            return None;
+        }
+        self.held_frames[key] = 0;
+        Some(key)
+    }
+
+    // A keyboard key came up. Returns the keypad key if nothing
+    // else is holding it down.
+    pub fn release(&mut self, code: KeyCode) -> Option<usize> {
+        let code = normalize(code);
+        self.held.remove(&code)?;
+        let key = self.bindings[&code];
+        self.pressing[key] -= 1;
+        (self.pressing[key] == 0).then_some(key)
+    }
+
+    // Releases the keys that haven't repeated for `hold`, for
+    // terminals that don't report releases. Returns the keypad
+    // keys that came up.
+    pub fn release_stale(&mut self, hold: Duration) -> Vec<usize> {
+        let stale: Vec<KeyCode> = self
+            .held
+            .iter()
+            .filter(|(_, pressed)| pressed.elapsed() >= hold)
+            .map(|(&code, _)| code)
+            .collect();
+        stale
+            .into_iter()
+            .filter_map(|code| self.release(code))
+            .collect()
+    }
+
+    // Moves autofire on by a frame. Returns the keypad keys it
+    // put down (true) or let up (false).
+    pub fn tick(&mut self) -> Vec<(usize, bool)> {
+        let mut changes = Vec::new();
+        for key in 0..16 {
+            let hz = self.autofire[key];
+            if hz == 0 || self.pressing[key] == 0 {
+                continue;
+            }
+            let was_down = autofire_down(hz, self.held_frames[key]);
+            self.held_frames[key] += 1;
+            let down = autofire_down(hz, self.held_frames[key]);
+            if down != was_down {
+                changes.push((key, down));
+            }
+        }
+        changes
+    }
+
+    // The keypad keys held down, bit N for key N
+    pub fn down(&self) -> u16 {
+        (0..16)
+            .filter(|&key| self.pressing[key] > 0)
+            .fold(0, |mask, key| mask | 1 << key)
+    }
+
+    // Forgets the held keys, for a fresh machine whose keypad
+    // starts all up
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.pressing = [0; 16];
+    }
+}
+
+// Whether an autofire key is down after being held for `frames`
+// 60Hz frames: down for the first half of each period, from the
+// moment it's pressed
+fn autofire_down(hz: u32, frames: u32) -> bool {
+    (frames * hz / 30).is_multiple_of(2)
+}
+
+// Letters are bound lower case, so Caps Lock doesn't matter
+fn normalize(code: KeyCode) -> KeyCode {
+    match code {
+        KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+        code => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Frames at which autofire changed the key, over `frames`
+    fn toggles(keys: &mut KeyMap, frames: u32) -> Vec<(u32, bool)> {
+        (1..=frames)
+            .flat_map(|frame| keys.tick().into_iter().map(move |(_, down)| (frame, down)))
+            .collect()
+    }
+
+    #[test]
+    fn autofire_toggles_at_its_rate() {
+        let mut keys = KeyMap::new(false);
+        keys.set_autofire(0x5, 10);
+        assert_eq!(keys.press(KeyCode::Char('w')), Some(0x5));
+        // 10Hz at 60 frames a second: 3 frames down, 3 up
+        assert_eq!(
+            toggles(&mut keys, 12),
+            [(3, false), (6, true), (9, false), (12, true)]
+        );
+    }
+
+    #[test]
+    fn autofire_at_30hz_toggles_every_frame() {
+        let mut keys = KeyMap::new(false);
+        keys.set_autofire(0x5, 30);
+        keys.press(KeyCode::Char('w'));
+        assert_eq!(
+            toggles(&mut keys, 4),
+            [(1, false), (2, true), (3, false), (4, true)]
+        );
+    }
+
+    #[test]
+    fn autofire_stops_on_release_and_restarts_down() {
+        let mut keys = KeyMap::new(false);
+        keys.set_autofire(0x5, 10);
+        keys.press(KeyCode::Char('w'));
+        toggles(&mut keys, 4);
+        assert_eq!(keys.release(KeyCode::Char('w')), Some(0x5));
+        assert!(toggles(&mut keys, 10).is_empty());
+
+        // A new press starts a new period
+        keys.press(KeyCode::Char('w'));
+        assert_eq!(toggles(&mut keys, 3), [(3, false)]);
+    }
+
+    #[test]
+    fn keys_without_autofire_stay_down() {
+        let mut keys = KeyMap::new(false);
+        keys.set_autofire(0x5, 10);
+        keys.press(KeyCode::Char('q'));
+        assert!(toggles(&mut keys, 60).is_empty());
+    }
+
+    #[test]
+    fn a_key_held_twice_comes_up_with_the_last_release() {
+        let mut keys = KeyMap::new(true);
+        // 4 and 9 are both C
+        assert_eq!(keys.press(KeyCode::Char('4')), Some(0xC));
+        assert_eq!(keys.press(KeyCode::Char('9')), None);
+        assert_eq!(keys.release(KeyCode::Char('4')), None);
+        assert_eq!(keys.down(), 1 << 0xC);
+        assert_eq!(keys.release(KeyCode::Char('9')), Some(0xC));
+        assert_eq!(keys.down(), 0);
+    }
+
+    #[test]
+    fn repeats_and_double_releases_count_once() {
+        let mut keys = KeyMap::new(true);
+        keys.press(KeyCode::Up);
+        keys.press(KeyCode::Char('w'));
+        // Auto-repeats, one with Caps Lock turned on meanwhile
+        assert_eq!(keys.press(KeyCode::Up), None);
+        assert_eq!(keys.press(KeyCode::Char('W')), None);
+        assert_eq!(keys.release(KeyCode::Up), Some(0xC));
+        assert_eq!(keys.release(KeyCode::Up), None);
+        assert_eq!(keys.release(KeyCode::Char('W')), Some(0x5));
+        assert_eq!(keys.release(KeyCode::Char('w')), None);
+        assert_eq!(keys.down(), 0);
+    }
+
+    #[test]
+    fn stale_keys_time_out_one_by_one() {
+        let mut keys = KeyMap::new(true);
+        keys.press(KeyCode::Char('4'));
+        keys.press(KeyCode::Char('w'));
+        std::thread::sleep(Duration::from_millis(20));
+        // 9 was pressed since, so C stays down
+        keys.press(KeyCode::Char('9'));
+        let mut up = keys.release_stale(Duration::from_millis(10));
+        up.sort();
+        assert_eq!(up, [0x5]);
+        assert_eq!(keys.down(), 1 << 0xC);
+        assert_eq!(keys.release_stale(Duration::ZERO), [0xC]);
+        assert_eq!(keys.down(), 0);
+    }
+
+    #[test]
+    fn split_keys_are_only_bound_when_asked_for() {
+        let mut keys = KeyMap::new(false);
+        assert_eq!(keys.press(KeyCode::Char('9')), None);
+        assert_eq!(keys.press(KeyCode::Up), None);
+        assert_eq!(keys.down(), 0);
+    }
+}
@@ -1,54 +1,874 @@
+mod assembler;
+mod builder;
+mod clock;
+mod error;
+mod frontend;
+mod inspector;
+mod opcode;
+mod pacing;
 mod wasm;
 
+pub use assembler::{AsmError, assemble};
+pub use builder::{BuildError, Chip8Builder};
+pub use clock::TickClock;
+pub use error::Chip8Error;
+pub use frontend::{
+    AudioSink, DisplaySink, Framebuffer, InputSource, KeyEvent, NoAudio, NoDisplay, NoInput,
+    RunConfig, run_emulator,
+};
+pub use inspector::{Field, Inspector};
+pub use opcode::{Opcode, cycles_for, decode, opcode_class, schip_cycles_for};
+pub use pacing::{FramePacer, Pacing};
+
+use log::{debug, info, warn};
 use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Write as _},
     fs::File,
     io::{self, Read, Result},
+    ops::Range,
 };
 
 const FONTSET_START_ADDR: usize = 0x50;
 const PROGRAM_START_ADDR: usize = 0x200;
 const CHIP8_WIDTH: usize = 64;
 const CHIP8_HEIGHT: usize = 32;
+// SUPER-CHIP's high resolution (00FF)
+const HIRES_WIDTH: usize = 128;
+const HIRES_HEIGHT: usize = 64;
+const MEMORY_SIZE: usize = 4096;
+const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+const TRACE_LEN: usize = 256;
+// Rows of memory shown either side of PC in crash_report()
+const CRASH_ROWS: usize = 2;
+const STACK_DEPTH: usize = 16;
+const REWIND_LEN: usize = 600; // ~10 seconds of frames
+// Instructions undo() can step back through
+const UNDO_LEN: usize = 1024;
+const SNAPSHOT_VERSION: u8 = 7;
+// Version 5 snapshots replay the random numbers drawn to get the
+// generator back; more than this is a corrupt file, not a long game
+const MAX_REPLAYED_DRAWS: u64 = 1 << 24;
+// Most frames the flicker filter can blend
+const MAX_FLICKER_WINDOW: usize = 3;
+// XO-CHIP's FX3A pitch until a ROM sets one, which plays the
+// audio pattern at 4000 samples a second
+const DEFAULT_PITCH: u8 = 64;
+// Instructions without drawing or reading the keys or the delay
+// timer before is_looping() says the ROM is stuck. That's about
+// 11 seconds at 15 instructions a frame.
+const LOOP_CYCLES: u64 = 10_000;
+// The frames step_frame() runs, and the delay and sound timers'
+// standard rate
+const FRAME_HZ: u32 = 60;
+
+// Display heights a machine can have, 64 pixels wide: the
+// standard 32 rows, and the taller displays some COSMAC VIP
+// interpreters had (see Chip8::set_display_height())
+pub const DISPLAY_HEIGHTS: [usize; 3] = [CHIP8_HEIGHT, 48, 64];
+
+// Hi-res CHIP-8 ROMs, for the VIP's 64x64 display, start with
+// 1260: a jump into a patch to the interpreter, at 0x260-0x2BF in
+// the ROM, that switched the display over. The program proper
+// starts at HIRES_ENTRY. Here the patch is skipped, and the ROM
+// run from there on a 64-row display instead.
+pub const HIRES_ENTRY: u16 = 0x2C0;
+
+// Whether a ROM loaded at 0x200 is one of those
+pub fn is_hires_rom(rom: &[u8]) -> bool {
+    rom.starts_with(&[0x12, 0x60]) && rom.len() > HIRES_ENTRY as usize - PROGRAM_START_ADDR
+}
+
+// The standard 4x5 hex digit sprites, 0 through F
+pub const FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+// The large 8x10 digit sprites FX30 points at, right after
+// FONTSET in memory. 0-9 are SUPER-CHIP's; A-F, which XO-CHIP
+// added, are drawn to match.
+pub const BIG_FONTSET: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xE7, 0xC0, 0xC0, 0xC0, 0xC0, 0xE7, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC7, 0xC3, 0xC3, 0xC3, 0xC3, 0xC7, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// Something an instruction can write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Memory(u16),
+    Register(usize),
+}
+
+// Reported when an instruction writes to something being
+// watched. `pc` and `opcode` are the instruction that did it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchpointHit {
+    pub target: Watch,
+    pub pc: u16,
+    pub opcode: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+// e.g. "V3 0x00 -> 0x01 at 0x0204 (ADD V3, 0x01)"
+impl fmt::Display for WatchpointHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.target {
+            Watch::Memory(addr) => write!(f, "[{:#05X}]", addr)?,
+            Watch::Register(x) => write!(f, "V{:X}", x)?,
+        }
+        write!(
+            f,
+            " {:#04X} -> {:#04X} at {:#06X} ({})",
+            self.old,
+            self.new,
+            self.pc,
+            decode(self.opcode)
+        )
+    }
+}
+
+// What one instruction changed, for Chip8::undo(): the registers
+// as they were before it, and the old value of each memory and
+// display byte it wrote, in the order it wrote them. An
+// instruction pushes or pops at most one return address, so the
+// stack's depth and top are enough to put it back.
+#[allow(non_snake_case)]
+struct Undo {
+    PC: u16,
+    I: u16,
+    V: [u8; 16],
+    stack_depth: usize,
+    stack_top: Option<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    rng: Rng,
+    waiting_for_vblank: bool,
+    vblank_draw: bool,
+    new_presses: u16,
+    old_presses: u16,
+    seen_keys: u16,
+    key_wait: u16,
+    planes: u8,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    memory: Vec<(usize, u8)>,
+    display: Vec<(usize, u8)>,
+}
+
+// The CXNN random number generator, xoshiro256++ written out here
+// rather than taken from rand so its state can be saved in
+// snapshots. It's seeded the way rand's Xoshiro256PlusPlus is, and
+// bytes are the top of the upper half of a draw like rand's
+// random::<u8>(), so seeds give the same numbers they always have.
+#[derive(Clone)]
+struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    // Fills the state with SplitMix64, which never gives all zeros
+    fn new(mut seed: u64) -> Self {
+        let mut s = [0; 4];
+        for word in &mut s {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            *word = z ^ (z >> 31);
+        }
+        Rng { s }
+    }
+
+    // Where a generator seeded with `seed` is after `draws` bytes.
+    // Version 5 snapshots saved that instead of the state, so
+    // they're replayed, up to MAX_REPLAYED_DRAWS.
+    fn resume(seed: u64, draws: u64) -> Result<Self> {
+        if draws > MAX_REPLAYED_DRAWS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Too many random numbers drawn ({}) in snapshot", draws),
+            ));
+        }
+        let mut rng = Rng::new(seed);
+        for _ in 0..draws {
+            rng.next();
+        }
+        Ok(rng)
+    }
+
+    fn next(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn byte(&mut self) -> u8 {
+        (self.next() >> 32) as u8
+    }
+}
+
+// A copy of the machine state, taken with Chip8::snapshot()
+// and put back with Chip8::restore(). The random number
+// generator and the keypad, with the presses the ROM hasn't seen
+// yet, are included, so a run restored from one goes exactly as
+// it did the first time.
+#[derive(Clone)]
+#[allow(non_snake_case)]
+pub struct Snapshot {
+    stack: Vec<u16>,
+    PC: u16,
+    V: [u8; 16],
+    memory: Vec<u8>,
+    I: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+    rng: Rng,
+    waiting_for_vblank: bool,
+    vblank_draw: bool,
+    hires: bool,
+    display: Vec<u8>,
+    planes: u8,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    keypad: [u8; 16],
+    new_presses: u16,
+    old_presses: u16,
+    seen_keys: u16,
+    key_wait: u16,
+}
+
+impl Snapshot {
+    // Serializes the snapshot for saving to disk
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![SNAPSHOT_VERSION];
+        buf.extend_from_slice(&self.PC.to_le_bytes());
+        buf.extend_from_slice(&self.I.to_le_bytes());
+        buf.extend_from_slice(&self.V);
+        buf.push(self.delay_timer);
+        buf.push(self.sound_timer);
+        buf.push(self.waiting_for_vblank as u8);
+        buf.push(self.vblank_draw as u8);
+        buf.extend_from_slice(&(self.stack.len() as u16).to_le_bytes());
+        for addr in &self.stack {
+            buf.extend_from_slice(&addr.to_le_bytes());
+        }
+        buf.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.memory);
+        let width = if self.hires { HIRES_WIDTH } else { CHIP8_WIDTH };
+        buf.push(self.hires as u8);
+        buf.push((self.display.len() / width) as u8);
+        buf.extend_from_slice(&self.display);
+        buf.push(self.planes);
+        buf.push(self.audio_pattern.is_some() as u8);
+        buf.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        buf.push(self.pitch);
+        for word in self.rng.s {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        let keys = (0..16)
+            .filter(|&key| self.keypad[key] != 0)
+            .fold(0u16, |mask, key| mask | 1 << key);
+        for word in [
+            keys,
+            self.new_presses,
+            self.old_presses,
+            self.seen_keys,
+            self.key_wait,
+        ] {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Snapshot> {
+        let mut reader = SnapshotReader { buf };
+
+        let version = reader.take(1)?[0];
+        // Version 1 is the same minus the plane mask at the end,
+        // version 2 minus the audio pattern and pitch after it,
+        // version 3 minus the display height before the display
+        // (always 32 rows before then), and version 4 minus the
+        // random number generator and keys at the end. Those older
+        // ones get a random seed and no keys down. Version 5 saved
+        // the generator as its seed and the numbers drawn since,
+        // where version 6 saves its state. Version 6 is the same
+        // minus the high resolution flag before the display height.
+        if !(1..=SNAPSHOT_VERSION).contains(&version) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported snapshot version {}", version),
+            ));
+        }
+
+        let pc = reader.u16()?;
+        let i = reader.u16()?;
+        let mut v = [0; 16];
+        v.copy_from_slice(reader.take(16)?);
+        let flags = reader.take(4)?;
+        let (delay_timer, sound_timer) = (flags[0], flags[1]);
+        let (waiting_for_vblank, vblank_draw) = (flags[2] != 0, flags[3] != 0);
+
+        let depth = reader.u16()? as usize;
+        let mut stack = Vec::with_capacity(depth);
+        for _ in 0..depth {
+            stack.push(reader.u16()?);
+        }
+
+        let memory_size = u32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+        // Addresses wrap at the size, and I can only reach 64KB
+        if !(1..=XO_CHIP_MEMORY_SIZE).contains(&memory_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported memory size {:#X} in snapshot", memory_size),
+            ));
+        }
+        let memory = reader.take(memory_size)?.to_vec();
+        let hires = version >= 7 && reader.take(1)?[0] != 0;
+        let rows = if version < 4 {
+            CHIP8_HEIGHT
+        } else {
+            reader.take(1)?[0] as usize
+        };
+        let (width, heights) = match hires {
+            true => (HIRES_WIDTH, &[HIRES_HEIGHT][..]),
+            false => (CHIP8_WIDTH, &DISPLAY_HEIGHTS[..]),
+        };
+        if !heights.contains(&rows) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported display height {} in snapshot", rows),
+            ));
+        }
+        let display = reader.take(width * rows)?.to_vec();
+        let planes = if version == 1 { 1 } else { reader.take(1)?[0] };
+        let (audio_pattern, pitch) = if version < 3 {
+            (None, DEFAULT_PITCH)
+        } else {
+            let has_pattern = reader.take(1)?[0] != 0;
+            let pattern: [u8; 16] = reader.take(16)?.try_into().unwrap();
+            (has_pattern.then_some(pattern), reader.take(1)?[0])
+        };
+        let mut rng = Rng::new(rand::random());
+        let mut keys = [0; 5];
+        if version == 5 {
+            let (seed, draws) = (reader.u64()?, reader.u64()?);
+            rng = Rng::resume(seed, draws)?;
+        } else if version >= 6 {
+            for word in &mut rng.s {
+                *word = reader.u64()?;
+            }
+        }
+        if version >= 5 {
+            for word in &mut keys {
+                *word = reader.u16()?;
+            }
+        }
+        let [keypad, new_presses, old_presses, seen_keys, key_wait] = keys;
+
+        Ok(Snapshot {
+            stack,
+            PC: pc,
+            V: v,
+            memory,
+            I: i,
+            delay_timer,
+            sound_timer,
+            rng,
+            waiting_for_vblank,
+            vblank_draw,
+            hires,
+            display,
+            planes,
+            audio_pattern,
+            pitch,
+            keypad: std::array::from_fn(|key| (keypad >> key & 1) as u8),
+            new_presses,
+            old_presses,
+            seen_keys,
+            key_wait,
+        })
+    }
+}
+
+// Reads a snapshot field by field, failing on truncated data
+struct SnapshotReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Snapshot data is truncated",
+            ));
+        }
+        let (head, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+// Which instruction set a machine runs. Extension opcodes are
+// treated as invalid unless their variant is selected, so
+// standard ROMs behave the same either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    #[default]
+    Chip8,
+    // SUPER-CHIP: CHIP-8's 4KB of memory, plus FX30 for the large
+    // 8x10 digits
+    SuperChip,
+    // 64KB of memory, F000 NNNN (I = NNNN) and a second
+    // display plane selected with FN01
+    XoChip,
+}
+
+// COSMAC VIP machine cycles per 60Hz frame (1.7609MHz clock,
+// 8 clocks per machine cycle)
+pub const VIP_CYCLES_PER_FRAME: u32 = 1_760_900 / 8 / 60;
+
+// How many instructions make up a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    // Every instruction costs the same, CPF of them a frame
+    #[default]
+    Simple,
+    // Instructions cost what they did on the VIP (see
+    // cycles_for()) out of VIP_CYCLES_PER_FRAME
+    Vip,
+    // Instructions cost what they did on SUPER-CHIP (see
+    // schip_cycles_for()) out of CPF simple ones
+    Schip,
+}
+
+impl Timing {
+    // What a frame can spend
+    pub fn frame_budget(self, cpf: usize) -> u32 {
+        match self {
+            Timing::Simple | Timing::Schip => cpf as u32,
+            Timing::Vip => VIP_CYCLES_PER_FRAME,
+        }
+    }
+
+    // What running `op` spends of the budget
+    pub fn cycle_cost(self, op: Opcode) -> u32 {
+        match self {
+            Timing::Simple => 1,
+            Timing::Vip => cycles_for(op),
+            Timing::Schip => schip_cycles_for(op),
+        }
+    }
+}
+
+// What happened during Chip8::step_frame()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameOutput {
+    // The display was drawn to or cleared, or the flicker filter
+    // changed what display_composited() shows, so it needs showing
+    pub display_changed: bool,
+    // The buzzer is sounding (see Chip8::buzzer())
+    pub sound: bool,
+    // The ROM is stopped on FX0A until a key is pressed
+    pub waiting_for_key: bool,
+    // Instructions run, not counting display wait stalls
+    pub instructions: u32,
+    // 00E0 (or hi-res 0230) ran, or 00FE/00FF switched the
+    // resolution, which clears the display too
+    pub cleared: bool,
+    // Pixels DXYN turned on, in any plane
+    pub pixels_drawn: u32,
+}
+
+// Behaviours that differ between interpreters. Games written
+// for one often break on another, so these can be toggled per
+// ROM. The defaults match what this emulator has always done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // DXYN wraps sprite pixels past the right edge around to the
+    // left (wrap_x) and past the bottom edge around to the top
+    // (wrap_y). When off, those pixels are clipped (only the
+    // starting coordinate wraps), like the original VIP.
+    pub wrap_x: bool,
+    pub wrap_y: bool,
+    // DXYN waits for the next vertical blank, like the VIP did.
+    // This limits draws to one per frame, which stops flicker in
+    // games written for the slow original.
+    pub display_wait: bool,
+    // 8XY6/8XYE shift V[Y] into V[X] (original CHIP-8) instead
+    // of shifting V[X] in place (CHIP-48/SCHIP)
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I pointing past the last register they
+    // touched (I += X + 1), like the original interpreter
+    pub index_increment_on_store: bool,
+    // 8XY1/8XY2/8XY3 clear VF as a side effect, as on the VIP
+    pub vf_reset_on_logic: bool,
+    // 0NNN is skipped instead of stopping with
+    // Chip8Error::MachineCodeCall. Some ROMs only use it as
+    // padding or a harmless prefix.
+    pub ignore_machine_code: bool,
+    // BNNN is read as BXNN and jumps to XNN + V[X] (CHIP-48/SCHIP)
+    // instead of NNN + V[0]
+    pub jump_with_vx: bool,
+    // 00CN/00DN/00FB/00FC move the display half as far in low
+    // resolution: SUPER-CHIP 1.1 scrolled its 128x64 screen by
+    // high-resolution pixels either way. Odd distances round down.
+    // XO-CHIP and later SUPER-CHIPs move whole pixels.
+    pub half_scroll: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            wrap_x: true,
+            wrap_y: true,
+            display_wait: false,
+            shift_uses_vy: false,
+            index_increment_on_store: false,
+            vf_reset_on_logic: false,
+            ignore_machine_code: false,
+            jump_with_vx: false,
+            half_scroll: false,
+        }
+    }
+}
 
 #[allow(non_snake_case)]
 pub struct Chip8 {
     stack: Vec<u16>,
+    stack_limit: usize,
     PC: u16,
     V: [u8; 16],
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    program_start: usize,
+    // Where the small font starts, with the large one after it
+    font_start: usize,
     I: u16,
     delay_timer: u8,
     sound_timer: u8,
+    // Whether the buzzer is sounding, as of the last timer tick,
+    // and whether that tick turned it on or off
+    buzzer: bool,
+    beep_started: bool,
+    beep_ended: bool,
+    watchpoints: Vec<u16>,
+    // Bit N set = V[N] is watched
+    register_watches: u16,
+    watchpoint_hits: Vec<WatchpointHit>,
+    trace: [(u16, u16); TRACE_LEN],
+    trace_pos: usize,
+    trace_len: usize,
+    trace_enabled: bool,
+    trace_log: String,
+    // Instructions executed since the machine was created
+    cycles: u64,
+    // Of those, how many there were of each family (the top
+    // nibble of the opcode), while counting is on
+    family_counts: Option<[u64; 16]>,
+    // Instructions executed by class (see opcode_class()) while
+    // profiling
+    opcode_counts: Option<HashMap<u16, u64>>,
+    // The deepest the stack has been
+    peak_stack_depth: usize,
+    // Instructions since the last one that drew or read the keys
+    // or the delay timer (see is_looping())
+    idle_cycles: u64,
+    rng: Rng,
+    waiting_for_vblank: bool,
+    vblank_draw: bool,
+    // Keys pressed (bit N = key N) since the last vblank() and in
+    // the frame before. EX9E, EXA1 and FX0A count these as down
+    // until one of them sees it, so a tap shorter than a frame
+    // isn't missed.
+    new_presses: u16,
+    old_presses: u16,
+    // With the key repeat guard, EX9E/EXA1/FX0A see a held key
+    // down only once per press. Bit N is set once key N has been
+    // seen, and cleared when it's pressed again.
+    key_repeat_guard: bool,
+    seen_keys: u16,
+    // Keys FX0A has seen down while waiting, bit N for key N; it
+    // finishes when one of them is let go
+    key_wait: u16,
+    // With halt_past_rom, the machine stops where PC reaches
+    // memory that neither the ROM nor the program has written;
+    // one flag per byte of memory
+    halt_past_rom: bool,
+    written: Vec<bool>,
+    // Bytes the last load_rom_bytes() put in memory
+    rom_len: usize,
+    // Load only what fits of a ROM too large for memory
+    truncate_oversize: bool,
+    rewind_buffer: VecDeque<Snapshot>,
+    // The last UNDO_LEN instructions' changes, newest last, while
+    // undo is on
+    undo_log: Option<VecDeque<Undo>>,
+    timing: Timing,
+    // Budget the last step_frame() overspent, taken out of the
+    // next one
+    overspent: u32,
+    // Timer ticks due as frames run, FRAME_HZ frames to the
+    // second; one a frame unless set_timer_hz() changed the rate
+    timer_clock: TickClock,
+    // What the current step_frame() has done to the display, for
+    // FrameOutput
+    frame_cleared: bool,
+    frame_pixels_drawn: u32,
+    collision_hook: Option<Box<dyn FnMut(usize)>>,
+    variant: Variant,
+    // SUPER-CHIP's 128x64 high resolution, switched on with 00FF,
+    // and the rows the display goes back to with 00FE
+    hires: bool,
+    lores_rows: usize,
+    // Display planes DXYN and 00E0 act on (XO-CHIP), bit 0 for
+    // the first plane and bit 1 for the second
+    planes: u8,
+    // XO-CHIP audio: the 128 1-bit samples F002 loaded (None until
+    // a ROM loads some, and the buzzer is a plain beep until then)
+    // and the FX3A pitch they play at
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+
+    // The flicker filter: the last `flicker_window` frames passed
+    // to present_frame(), newest last, and those frames ORed
+    // together for display_composited()
+    flicker_window: usize,
+    recent_frames: VecDeque<Vec<u8>>,
+    composited: Vec<u8>,
 
     // Public members to make them accessible later
     // in the main function
     pub keypad: [u8; 16],
-    pub display: [u8; CHIP8_WIDTH * CHIP8_HEIGHT],
+    // One byte per pixel, display_width() by display_height(),
+    // row by row. Bit 0 is the first plane and bit 1 the second,
+    // so outside of XO-CHIP pixels are just 0 or 1.
+    pub display: Vec<u8>,
     pub draw_flag: bool,
+    pub quirks: Quirks,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chip8 {
     pub fn new() -> Self {
+        Self::with_layout(MEMORY_SIZE, PROGRAM_START_ADDR)
+    }
+
+    // Starts configuring a machine; see Chip8Builder. Unlike
+    // new(), machines from the builder come with the fontset
+    // already loaded.
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+
+    // Creates a machine that loads and starts programs at
+    // `addr` instead of 0x200 (ETI-660 programs use 0x600)
+    pub fn new_with_start(addr: u16) -> Result<Self> {
+        if addr as usize >= 0xE00 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Load address {:#X} out of range (0x000..0xE00)", addr),
+            ));
+        }
+
+        Ok(Self::with_layout(MEMORY_SIZE, addr as usize))
+    }
+
+    // Creates a machine with a custom amount of RAM and program
+    // start address (e.g. 0x600 for ETI-660 programs). new() uses
+    // the standard 4096 bytes and 0x200.
+    pub fn with_layout(memory_size: usize, program_start: usize) -> Self {
+        assert!(
+            program_start < memory_size,
+            "program start must be inside memory"
+        );
+
         Chip8 {
             stack: Vec::new(),
-            PC: 0x200,
+            stack_limit: STACK_DEPTH,
+            PC: program_start as u16,
             V: [0; 16],
-            memory: [0; 4096],
+            memory: vec![0; memory_size],
+            written: vec![false; memory_size],
+            program_start,
+            font_start: FONTSET_START_ADDR,
             I: 0,
             delay_timer: 0,
             sound_timer: 0,
+            buzzer: false,
+            beep_started: false,
+            beep_ended: false,
+            watchpoints: Vec::new(),
+            register_watches: 0,
+            watchpoint_hits: Vec::new(),
+            trace: [(0, 0); TRACE_LEN],
+            trace_pos: 0,
+            trace_len: 0,
+            trace_enabled: false,
+            trace_log: String::new(),
+            cycles: 0,
+            family_counts: None,
+            opcode_counts: None,
+            peak_stack_depth: 0,
+            idle_cycles: 0,
+            rng: Rng::new(rand::random()),
+            waiting_for_vblank: false,
+            vblank_draw: false,
+            new_presses: 0,
+            old_presses: 0,
+            key_repeat_guard: false,
+            seen_keys: 0,
+            key_wait: 0,
+            halt_past_rom: false,
+            rom_len: 0,
+            truncate_oversize: false,
+            rewind_buffer: VecDeque::new(),
+            undo_log: None,
+            timing: Timing::Simple,
+            overspent: 0,
+            timer_clock: TickClock::new(FRAME_HZ),
+            frame_cleared: false,
+            frame_pixels_drawn: 0,
+            collision_hook: None,
+            variant: Variant::Chip8,
+            hires: false,
+            lores_rows: CHIP8_HEIGHT,
+            planes: 1,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            flicker_window: 1,
+            recent_frames: VecDeque::new(),
+            composited: vec![0; CHIP8_WIDTH * CHIP8_HEIGHT],
             keypad: [0; 16],
-            display: [0; CHIP8_WIDTH * CHIP8_HEIGHT],
+            display: vec![0; CHIP8_WIDTH * CHIP8_HEIGHT],
             draw_flag: false,
+            quirks: Quirks::default(),
+        }
+    }
+
+    // Copies a font to the font address (0x50 by default): the 16
+    // small hex digits FX29 points at (80 bytes), optionally
+    // followed by the 16 large ones FX30 points at (160 more).
+    // With only the small digits the large ones are the built-in
+    // BIG_FONTSET. Either way it has to end before the program:
+    // 0x200, or the start address if that's lower.
+    pub fn load_fontset(&mut self, fontset: Vec<u8>) -> std::result::Result<(), BuildError> {
+        let max = self.font_end() - self.font_start;
+        let (small, large) = fontset.split_at(fontset.len().min(FONTSET.len()));
+        match (<&[u8; 80]>::try_from(small), <&[u8; 160]>::try_from(large)) {
+            (Ok(small), _) if large.is_empty() => {
+                self.load_fontset_small(small);
+                self.load_fontset_large(&BIG_FONTSET);
+            }
+            (Ok(small), Ok(large)) if fontset.len() <= max => {
+                self.load_fontset_small(small);
+                self.load_fontset_large(large);
+            }
+            _ => {
+                return Err(BuildError::InvalidFontset {
+                    len: fontset.len(),
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Copies the small 4x5 digits to the font address, where FX29
+    // points.
+    // Only the left four bits of each row are meant to be used,
+    // and it's logged if a glyph uses more, since it would run
+    // into the next character.
+    pub fn load_fontset_small(&mut self, font: &[u8; 80]) {
+        if let Some(row) = font.iter().position(|row| row & 0x0F != 0) {
+            warn!(
+                "Font glyph {:X} is wider than 4 pixels (row {:#04X})",
+                row / 5,
+                font[row]
+            );
         }
+        let small = self.small_font();
+        self.memory[small].copy_from_slice(font);
     }
 
-    fn load_fontset(&mut self, fontset: Vec<u8>) {
-        for (i, &byte) in fontset.iter().enumerate() {
-            self.memory[FONTSET_START_ADDR + i] = byte;
+    // Copies the large 8x10 digits after the small ones, where
+    // FX30 points, if there's room before the program.
+    pub fn load_fontset_large(&mut self, font: &[u8; 160]) {
+        let large = self.large_font();
+        if large.end <= self.font_end() {
+            self.memory[large].copy_from_slice(font);
         }
     }
 
+    // Loads the built-in fonts (FONTSET and BIG_FONTSET)
+    pub fn load_default_fontset(&mut self) {
+        self.load_fontset_small(&FONTSET);
+        self.load_fontset_large(&BIG_FONTSET);
+    }
+
+    // Where the small (FX29) digits start, 0x50 unless the builder
+    // was given another font address
+    pub fn font_address(&self) -> u16 {
+        self.font_start as u16
+    }
+
+    fn small_font(&self) -> Range<usize> {
+        self.font_start..self.font_start + FONTSET.len()
+    }
+
+    fn large_font(&self) -> Range<usize> {
+        let start = self.small_font().end;
+        start..start + BIG_FONTSET.len()
+    }
+
+    // Where fonts have to end so programs don't overwrite them
+    fn font_end(&self) -> usize {
+        let end = if self.program_start > self.font_start {
+            self.program_start.min(PROGRAM_START_ADDR)
+        } else {
+            PROGRAM_START_ADDR
+        };
+        end.min(self.memory.len())
+    }
+
     // ROM Loader
     fn load_rom(&mut self, path: &str) -> Result<()> {
         let mut file = File::open(path)?;
@@ -58,35 +878,377 @@ impl Chip8 {
         let mut buf = Vec::new();
         file.read_to_end(&mut buf)?;
 
-        let end = PROGRAM_START_ADDR + buf.len(); // Would be the index of the last
+        // Octo source is assembled first
+        if path.ends_with(".8o") {
+            let source = String::from_utf8(buf)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            buf = assemble(&source)?;
+        }
+
+        self.load_rom_bytes(&buf)
+    }
 
-        // Returns an error if the index goes beyond
-        // bounds
-        if end > self.memory.len() {
+    // Copies a ROM image into memory at the program start address.
+    // A ROM too large for the memory after it is refused, or with
+    // truncate_oversize, cut down to what fits.
+    pub fn load_rom_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        let room = self.memory.len() - self.program_start;
+        let buf = if buf.len() <= room {
+            buf
+        } else if self.truncate_oversize {
+            warn!(
+                "ROM is {} bytes, only the first {} fit after {:#05X}; the rest is left out",
+                buf.len(),
+                room,
+                self.program_start
+            );
+            &buf[..room]
+        } else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "ROM too large to fit in memory",
             ));
+        };
+        let end = self.program_start + buf.len();
+
+        // Unusual load addresses can put the ROM on top
+        // of the fontset, which would corrupt FX29 sprites
+        let small_font = self.small_font();
+        if self.program_start < small_font.end && end > small_font.start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "ROM would overwrite the fontset ({:#X}..{:#X})",
+                    small_font.start, small_font.end
+                ),
+            ));
         }
 
         // The ROM is ok, store it to memory starting from
-        // 0x200 up to end
-        self.memory[PROGRAM_START_ADDR..end].copy_from_slice(&buf);
+        // the program start (0x200 by default) up to end
+        self.memory[self.program_start..end].copy_from_slice(buf);
+        self.written[self.program_start..end].fill(true);
+        self.rom_len = buf.len();
+
+        info!(
+            "ROM at {:#05X}..{:#05X} ({} bytes)",
+            self.program_start,
+            end,
+            buf.len()
+        );
+        // Loaded below 0x200, it can take the place of the large
+        // digits FX30 points at
+        let large_font = self.large_font();
+        if self.program_start < large_font.end && end > large_font.start {
+            warn!(
+                "ROM at {:#05X}..{:#05X} overlaps the large font ({:#05X}..{:#05X}), so FX30 won't find its digits",
+                self.program_start, end, large_font.start, large_font.end
+            );
+        }
 
         Ok(())
     }
 
+    // How many bytes of the last ROM loaded were put in memory
+    pub fn rom_len(&self) -> usize {
+        self.rom_len
+    }
+
+    // Where the last ROM loaded is in memory, so disassembly can
+    // stop at its end instead of decoding the empty memory after
+    pub fn rom_range(&self) -> Range<usize> {
+        self.program_start..self.program_start + self.rom_len
+    }
+
+    // Loads just the start of a ROM too large for memory (with a
+    // warning) instead of refusing it. Off by default.
+    pub fn set_truncate_oversize(&mut self, on: bool) {
+        self.truncate_oversize = on;
+    }
+
+    // Memory accessors. Addresses past the end of memory wrap
+    // back around to 0x000 (like most interpreters do) instead of
+    // panicking, so a ROM that points I or PC near 0xFFF can't
+    // take down the emulator.
+    fn read_mem(&self, addr: usize) -> u8 {
+        self.memory[addr % self.memory.len()]
+    }
+
+    fn write_mem(&mut self, addr: usize, val: u8) {
+        let addr = addr % self.memory.len();
+
+        // Only pay for the lookup when something is being watched
+        if !self.watchpoints.is_empty() && self.watchpoints.contains(&(addr as u16)) {
+            self.record_hit(Watch::Memory(addr as u16), self.memory[addr], val);
+        }
+
+        if let Some(entry) = self.undo_log.as_mut().and_then(|log| log.back_mut()) {
+            entry.memory.push((addr, self.memory[addr]));
+        }
+        self.memory[addr] = val;
+        self.written[addr] = true;
+    }
+
+    // All V[X] writes by instructions go through here so they
+    // can be watched
+    fn write_reg(&mut self, x: usize, val: u8) {
+        if self.register_watches & (1 << x) != 0 {
+            self.record_hit(Watch::Register(x), self.V[x], val);
+        }
+
+        self.V[x] = val;
+    }
+
+    fn record_hit(&mut self, target: Watch, old: u8, new: u8) {
+        // The instruction being executed is the newest trace entry
+        let (pc, opcode) = self.trace[(self.trace_pos + TRACE_LEN - 1) % TRACE_LEN];
+        self.watchpoint_hits.push(WatchpointHit {
+            target,
+            pc,
+            opcode,
+            old,
+            new,
+        });
+    }
+
+    // Watch a memory address (FX33, FX55) or register for
+    // writes. Hits are collected and can be drained with
+    // take_watchpoint_hits()
+    pub fn add_watchpoint(&mut self, watch: Watch) {
+        match watch {
+            Watch::Memory(addr) => {
+                let addr = (addr as usize % self.memory.len()) as u16;
+                if !self.watchpoints.contains(&addr) {
+                    self.watchpoints.push(addr);
+                }
+            }
+            Watch::Register(x) => self.register_watches |= 1 << (x & 0xF),
+        }
+    }
+
+    pub fn remove_watchpoint(&mut self, watch: Watch) {
+        match watch {
+            Watch::Memory(addr) => {
+                let addr = (addr as usize % self.memory.len()) as u16;
+                self.watchpoints.retain(|&a| a != addr);
+            }
+            Watch::Register(x) => self.register_watches &= !(1 << (x & 0xF)),
+        }
+    }
+
+    // Returns every watchpoint hit since the last call, oldest first
+    pub fn take_watchpoint_hits(&mut self) -> Vec<WatchpointHit> {
+        std::mem::take(&mut self.watchpoint_hits)
+    }
+
+    // Calls `hook` with the display index (y * display_width() + x)
+    // of every pixel a DXYN turns off, i.e. every pixel that
+    // collided. Without a hook, DXYN does no extra work.
+    pub fn set_collision_hook(&mut self, hook: impl FnMut(usize) + 'static) {
+        self.collision_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_collision_hook(&mut self) {
+        self.collision_hook = None;
+    }
+
+    // Full instruction trace. While enabled, every executed
+    // instruction is formatted with its operands into a log that
+    // the frontend drains with take_trace_log(). This is slow, so
+    // it's off by default.
+    pub fn enable_trace(&mut self) {
+        self.trace_enabled = true;
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.trace_enabled = false;
+        self.trace_log.clear();
+    }
+
+    // Counts the instructions executed by family, 0NNN to FXNN,
+    // for family_counts(). Off by default; turning it off drops
+    // the counts.
+    pub fn count_families(&mut self, on: bool) {
+        self.family_counts = on.then(|| self.family_counts.unwrap_or([0; 16]));
+    }
+
+    // Instructions executed of each family (by the top nibble of
+    // the opcode) since counting was turned on, or None if it's off
+    pub fn family_counts(&self) -> Option<&[u64; 16]> {
+        self.family_counts.as_ref()
+    }
+
+    // Counts every instruction executed by class, for
+    // opcode_stats(). Slower than family counting, so it's off by
+    // default; turning it off drops the counts.
+    pub fn set_profiling(&mut self, on: bool) {
+        if !on {
+            self.opcode_counts = None;
+        } else if self.opcode_counts.is_none() {
+            self.opcode_counts = Some(HashMap::new());
+        }
+    }
+
+    // Each instruction class executed while profiling, with how
+    // many times, the most frequent first. Empty when profiling is
+    // off.
+    pub fn opcode_stats(&self) -> Vec<(u16, u64)> {
+        let mut stats: Vec<(u16, u64)> = self
+            .opcode_counts
+            .iter()
+            .flatten()
+            .map(|(&class, &count)| (class, count))
+            .collect();
+        stats.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        stats
+    }
+
+    // Returns the traced lines since the last call
+    pub fn take_trace_log(&mut self) -> String {
+        std::mem::take(&mut self.trace_log)
+    }
+
+    // Returns the last TRACE_LEN executed (PC, opcode) pairs,
+    // oldest first. These are always recorded, tracing or not.
+    pub fn trace(&self) -> Vec<(u16, u16)> {
+        self.trace_iter().collect()
+    }
+
+    // Walks the history ring buffer from oldest to newest
+    fn trace_iter(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let start = (self.trace_pos + TRACE_LEN - self.trace_len) % TRACE_LEN;
+        (0..self.trace_len).map(move |i| self.trace[(start + i) % TRACE_LEN])
+    }
+
+    // The recent instruction history as text, for bug reports
+    pub fn dump_trace(&self) -> String {
+        let mut out = String::new();
+        for (pc, opcode) in self.trace_iter() {
+            let _ = writeln!(out, "PC={:#06X} OP={:#06X}  {}", pc, opcode, decode(opcode));
+        }
+        out
+    }
+
+    // The CPU state and the memory around PC as text, for when the
+    // emulator itself has gone wrong (a panic) rather than the ROM
+    pub fn crash_report(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(out, "PC={:#06X}", self.PC);
+        // The instruction being executed is the newest trace entry
+        if self.trace_len > 0 {
+            let (pc, opcode) = self.trace[(self.trace_pos + TRACE_LEN - 1) % TRACE_LEN];
+            let _ = write!(
+                out,
+                "  last OP={:#06X} at {:#06X}  {}",
+                opcode,
+                pc,
+                decode(opcode)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "\nI={:#06X} SP={} DT={} ST={} cycles={}",
+            self.I,
+            self.stack.len(),
+            self.delay_timer,
+            self.sound_timer,
+            self.cycles
+        );
+        // Two rows of eight
+        for (row, regs) in self.V.chunks(8).enumerate() {
+            let regs: Vec<String> = (regs.iter().enumerate())
+                .map(|(x, v)| format!("V{:X}={:02X}", row * 8 + x, v))
+                .collect();
+            let _ = writeln!(out, "{}", regs.join(" "));
+        }
+        // PC's row of 16 bytes, marked, and CRASH_ROWS either side.
+        // The last row is short if the memory size isn't a multiple
+        // of 16.
+        let row = (self.PC as usize).min(self.memory.len().saturating_sub(1)) & !0xF;
+        let start = row.saturating_sub(CRASH_ROWS * 16);
+        let end = (row + (CRASH_ROWS + 1) * 16).min(self.memory.len());
+        for addr in (start..end).step_by(16) {
+            let mark = if addr == row { '>' } else { ' ' };
+            let _ = write!(out, "{}{:04X}:", mark, addr);
+            for byte in &self.memory[addr..(addr + 16).min(end)] {
+                let _ = write!(out, " {:02X}", byte);
+            }
+            let _ = writeln!(out);
+        }
+        out
+    }
+
+    // Appends a line like
+    // PC=0x0246 OP=0x8124 V1=0A V2=03 I=0x0300 SP=2  ADD V1, V2
+    // describing the state right before the instruction executes
+    fn log_trace(&mut self, opcode: u16, op: Opcode) {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let _ = writeln!(
+            self.trace_log,
+            "PC={:#06X} OP={:#06X} V{:X}={:02X} V{:X}={:02X} I={:#06X} SP={}  {}",
+            self.PC,
+            opcode,
+            x,
+            self.V[x],
+            y,
+            self.V[y],
+            self.I,
+            self.stack.len(),
+            op
+        );
+    }
+
+    #[deprecated(note = "use Chip8::builder().build() and load_rom_bytes() instead")]
     pub fn init(&mut self, path: &str, fontset: Vec<u8>) -> Result<()> {
         self.load_rom(path)?;
-        self.load_fontset(fontset);
+        self.load_fontset(fontset)?;
 
         Ok(())
     }
 
     // Emulates the chip8 cycle.
     // Fetch -> Decode -> Execute
+    // Returns what the instruction spent of the frame budget (see
+    // Timing::cycle_cost()). A cycle stalled on the display wait
+    // quirk spends an instruction's worth with Timing::Simple, and
+    // nothing otherwise.
+    pub fn emulate_cycle(&mut self) -> std::result::Result<u32, Chip8Error> {
+        // With the display wait quirk, nothing runs between a
+        // DXYN and the next vblank()
+        if self.waiting_for_vblank {
+            return Ok((self.timing == Timing::Simple) as u32);
+        }
+
+        // A ROM that has run off the end of its program stays put,
+        // as if on a jump to itself, instead of running whatever
+        // is in empty memory (see set_halt_past_rom())
+        if self.ran_past_rom() {
+            return Ok(self.timing.cycle_cost(Opcode::Jump(self.PC)));
+        }
+
+        if self.undo_log.is_some() {
+            let entry = self.undo_entry();
+            if let Some(log) = &mut self.undo_log {
+                if log.len() == UNDO_LEN {
+                    log.pop_front();
+                }
+                log.push_back(entry);
+            }
+        }
+        let result = self.execute();
+        // A failed instruction leaves PC on it, with nothing to undo
+        if result.is_err()
+            && let Some(log) = &mut self.undo_log
+        {
+            log.pop_back();
+        }
+        result
+    }
+
+    // Fetches, decodes and runs the instruction at PC
     #[allow(non_snake_case)]
-    pub fn emulate_cycle(&mut self) -> &[u8] {
+    fn execute(&mut self) -> std::result::Result<u32, Chip8Error> {
         /* Fetch opcode from memory.
             Opcode is from memory[PC] to memory[PC + 1] as a u16
             Combines memory[PC] and memory[PC + 1] by first casting
@@ -95,325 +1257,834 @@ impl Chip8 {
             the left. The right half of the opcode is now zeroed out.
             Then we set the right half with memory[PC + 1].
         */
-        let opcode = ((self.memory[self.PC as usize] as u16) << 8)
-            | (self.memory[(self.PC + 1) as usize] as u16);
-
-        // The X and Y from the opcode is always at the second
-        // and third nibble of the opcode. We can assign it to a
-        // variable. They are casted as usize because they are often
-        // used in indexing with the V registers.
-        let X = ((opcode & 0x0F00) >> 8) as usize;
-        let Y = ((opcode & 0x00F0) >> 4) as usize;
-
-        // NN and NNN are also in a consistent position in the
-        // given opcode, so I made a variable.
-        let NN = opcode & 0x00FF;
-        let NNN = opcode & 0x0FFF;
-
-        match opcode & 0xF000 {
-            0x0000 => {
-                match opcode & 0x00FF {
-                    0x00E0 => {
-                        // 00E0: Clears the display
-                        self.display = [0; CHIP8_WIDTH * CHIP8_HEIGHT];
-                        self.PC += 2;
-                    }
-                    0x00EE => {
-                        // 00EE: Returns from a subroutine
-                        self.PC = self.stack.pop().unwrap();
-                        self.PC += 2;
-                    }
-                    _ => {
-                        eprintln!("Invalid opcode: {:#X}", opcode);
-                    }
+        let opcode = ((self.read_mem(self.PC as usize) as u16) << 8)
+            | (self.read_mem(self.PC as usize + 1) as u16);
+
+        // Always remember recent instructions for crash reports.
+        // This is a fixed-size ring, so it never allocates.
+        self.trace[self.trace_pos] = (self.PC, opcode);
+        self.trace_pos = (self.trace_pos + 1) % TRACE_LEN;
+        self.trace_len = (self.trace_len + 1).min(TRACE_LEN);
+
+        let op = decode(opcode);
+        if self.trace_enabled {
+            self.log_trace(opcode, op);
+        }
+
+        // Execute the decoded instruction
+        match op {
+            Opcode::ScrollDown(n) if self.variant != Variant::Chip8 => {
+                // 00CN: Scrolls the selected planes down N pixels
+                self.scroll(0, self.scroll_distance(n));
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::ScrollUp(n) if self.variant == Variant::XoChip => {
+                // 00DN: Scrolls the selected planes up N pixels
+                self.scroll(0, -self.scroll_distance(n));
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::ScrollRight if self.variant != Variant::Chip8 => {
+                // 00FB: Scrolls the selected planes right 4 pixels
+                self.scroll(self.scroll_distance(4), 0);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::ScrollLeft if self.variant != Variant::Chip8 => {
+                // 00FC: Scrolls the selected planes left 4 pixels
+                self.scroll(-self.scroll_distance(4), 0);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Lores | Opcode::Hires if self.variant != Variant::Chip8 => {
+                // 00FE/00FF: Switches to low (64x32) or high
+                // (128x64) resolution, which clears the display
+                self.set_hires(op == Opcode::Hires);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Sys(0x230) if !self.hires && self.display_height() > CHIP8_HEIGHT => {
+                // 0230: Clears the display on the VIP's hi-res
+                // interpreters, which used it instead of 00E0
+                self.clear_display();
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Sys(_)
+            | Opcode::ScrollDown(_)
+            | Opcode::ScrollUp(_)
+            | Opcode::ScrollRight
+            | Opcode::ScrollLeft
+            | Opcode::Lores
+            | Opcode::Hires => {
+                // 0NNN: Calls a machine code routine at NNN, which
+                // only the VIP itself could run. Without SUPER-CHIP
+                // or XO-CHIP the scrolls and resolution switches are
+                // 0NNN too (and 00DN without XO-CHIP).
+                if !self.quirks.ignore_machine_code {
+                    return Err(Chip8Error::MachineCodeCall {
+                        pc: self.PC,
+                        addr: opcode & 0x0FFF,
+                    });
                 }
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Cls => {
+                // 00E0: Clears the display (the selected planes)
+                self.clear_display();
+                self.PC = self.PC.wrapping_add(2);
             }
-            0x1000 => {
+            Opcode::Ret => {
+                // 00EE: Returns from a subroutine
+                self.PC = match self.stack.pop() {
+                    Some(addr) => addr,
+                    None => {
+                        debug!("{}", self.dump_trace().trim_end());
+                        return Err(Chip8Error::StackUnderflow { pc: self.PC });
+                    }
+                };
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Jump(NNN) => {
                 // 1NNN: Jumps to location NNN
                 self.PC = NNN;
             }
-            0x2000 => {
+            Opcode::Call(NNN) => {
                 // 2NNN: Calls subroutine from NNN
+                if self.stack.len() >= self.stack_limit {
+                    return Err(Chip8Error::StackOverflow {
+                        pc: self.PC,
+                        depth: self.stack_limit,
+                    });
+                }
                 self.stack.push(self.PC);
+                self.peak_stack_depth = self.peak_stack_depth.max(self.stack.len());
                 self.PC = NNN;
             }
-            0x3000 => {
+            Opcode::SeVxByte(X, NN) => {
                 // 3XNN: Skips next instruction if
                 // V[X] == NN
-                if (self.V[X] as u16) == NN {
-                    self.PC += 4; // Skips next instruction
+                if self.V[X] == NN {
+                    self.skip_next();
                 } else {
-                    self.PC += 2;
+                    self.PC = self.PC.wrapping_add(2);
                 }
             }
-            0x4000 => {
+            Opcode::SneVxByte(X, NN) => {
                 // 4XNN: Skips next instruction if
                 // V[X] != NN
-                if (self.V[X] as u16) != NN {
-                    self.PC += 4; // Skips next instruction
+                if self.V[X] != NN {
+                    self.skip_next();
                 } else {
-                    self.PC += 2;
+                    self.PC = self.PC.wrapping_add(2);
                 }
             }
-            0x5000 => {
-                // 5XNN: Skips next instruction if V[X] == V[Y]
+            Opcode::SeVxVy(X, Y) => {
+                // 5XY0: Skips next instruction if V[X] == V[Y]
                 if self.V[X] == self.V[Y] {
-                    self.PC += 4;
+                    self.skip_next();
                 } else {
-                    self.PC += 2;
+                    self.PC = self.PC.wrapping_add(2);
                 }
             }
-            0x6000 => {
+            Opcode::LdVxByte(X, NN) => {
                 // 6XNN: Sets V[X] to NN
-                self.V[X] = NN as u8;
-                self.PC += 2;
+                self.write_reg(X, NN);
+                self.PC = self.PC.wrapping_add(2);
             }
-            0x7000 => {
+            Opcode::AddVxByte(X, NN) => {
                 // 7XNN: Adds NN to V[X]
-                self.V[X] = self.V[X].wrapping_add(NN as u8);
-                self.PC += 2;
-            }
-            0x8000 => {
-                match opcode & 0x000F {
-                    0x0000 => {
-                        // 8XY0: Sets V[X] to V[Y]
-                        self.V[X] = self.V[Y];
-                        self.PC += 2;
-                    }
-                    0x0001 => {
-                        // 8XY1: OR V[X] and V[Y] and
-                        // store the result to V[X]
-                        self.V[X] |= self.V[Y];
-                        self.PC += 2;
-                    }
-                    0x0002 => {
-                        // 8XY2: AND V[X] and V[Y] and
-                        // store the result to V[X]
-                        self.V[X] &= self.V[Y];
-                        self.PC += 2;
-                    }
-                    0x0003 => {
-                        // 8XY3: XOR V[X] and V[Y] and
-                        // store the result to V[X]
-                        self.V[X] ^= self.V[Y];
-                        self.PC += 2;
-                    }
-                    0x0004 => {
-                        // 8XY4: Add with carry
-                        // if the sum is greater than 255 (size of u8),
-                        // then we set the carry flag to V[0xF] (V[15])
-                        let (sum, carry) = self.V[X].overflowing_add(self.V[Y]);
-                        self.V[0xF] = if carry { 1 } else { 0 };
-                        self.V[X] = sum;
-                        self.PC += 2;
-                    }
-                    0x0005 => {
-                        // 8XY5: Sub with borrow
-                        // if V[X] is greater than V[Y], then
-                        // set V[0xF] to 1, otherwise set to 0
-                        let (diff, borrow) = self.V[X].overflowing_sub(self.V[Y]);
-                        self.V[0xF] = if borrow { 0 } else { 1 }; // if borrow = true, then V[X] must be lesser than V[Y]
-                        self.V[X] = diff;
-                        self.PC += 2;
-                    }
-                    0x0006 => {
-                        // 8XY6: If the least significant bit
-                        // of V[X] is 1, then set V[0xF] to 1,
-                        // otherwise 0. Then V[X] is right-shifted once
-                        self.V[0xF] = self.V[X] & 0x1;
-                        self.V[X] >>= 1;
-                        self.PC += 2;
-                    }
-                    0x0007 => {
-                        // 8XY7: Sub with borrow
-                        // if V[Y] is greater than V[X], then
-                        // set V[0xF] to 1, otherwise set to 0
-                        let (diff, borrow) = self.V[Y].overflowing_sub(self.V[X]);
-                        self.V[0xF] = if borrow { 0 } else { 1 };
-                        self.V[X] = diff;
-                        self.PC += 2;
-                    }
-                    0x000E => {
-                        // 8XYE: If the most significant bit
-                        // of V[X] is 1, then set V[0xF] to 1,
-                        // otherwise 0. Then V[X] is left-shifted once
-                        self.V[0xF] = (self.V[X] & 0x80) >> 7;
-                        self.V[X] <<= 1;
-                        self.PC += 2;
-                    }
-                    _ => {
-                        eprintln!("Invalid opcode: {:#X}", opcode);
-                    }
+                self.write_reg(X, self.V[X].wrapping_add(NN));
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdVxVy(X, Y) => {
+                // 8XY0: Sets V[X] to V[Y]
+                self.write_reg(X, self.V[Y]);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::OrVxVy(X, Y) => {
+                // 8XY1: OR V[X] and V[Y] and
+                // store the result to V[X]
+                self.write_reg(X, self.V[X] | self.V[Y]);
+                if self.quirks.vf_reset_on_logic {
+                    self.write_reg(0xF, 0);
                 }
+                self.PC = self.PC.wrapping_add(2);
             }
-            0x9000 => {
+            Opcode::AndVxVy(X, Y) => {
+                // 8XY2: AND V[X] and V[Y] and
+                // store the result to V[X]
+                self.write_reg(X, self.V[X] & self.V[Y]);
+                if self.quirks.vf_reset_on_logic {
+                    self.write_reg(0xF, 0);
+                }
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::XorVxVy(X, Y) => {
+                // 8XY3: XOR V[X] and V[Y] and
+                // store the result to V[X]
+                self.write_reg(X, self.V[X] ^ self.V[Y]);
+                if self.quirks.vf_reset_on_logic {
+                    self.write_reg(0xF, 0);
+                }
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::AddVxVy(X, Y) => {
+                // 8XY4: Add with carry
+                // if the sum is greater than 255 (size of u8),
+                // then we set the carry flag to V[0xF] (V[15])
+                // The flag is written last so it wins when X is F
+                let (sum, carry) = self.V[X].overflowing_add(self.V[Y]);
+                self.write_reg(X, sum);
+                self.write_reg(0xF, if carry { 1 } else { 0 });
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::SubVxVy(X, Y) => {
+                // 8XY5: Sub with borrow
+                // if V[X] is greater than V[Y], then
+                // set V[0xF] to 1, otherwise set to 0
+                let (diff, borrow) = self.V[X].overflowing_sub(self.V[Y]);
+                self.write_reg(X, diff);
+                self.write_reg(0xF, if borrow { 0 } else { 1 }); // if borrow = true, then V[X] must be lesser than V[Y]
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::ShrVx(X, Y) => {
+                // 8XY6: If the least significant bit
+                // of V[X] is 1, then set V[0xF] to 1,
+                // otherwise 0. Then V[X] is right-shifted once
+                // (with shift_uses_vy, V[X] = V[Y] >> 1 instead)
+                let value = if self.quirks.shift_uses_vy {
+                    self.V[Y]
+                } else {
+                    self.V[X]
+                };
+                self.write_reg(X, value >> 1);
+                self.write_reg(0xF, value & 0x1);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::SubnVxVy(X, Y) => {
+                // 8XY7: Sub with borrow
+                // if V[Y] is greater than V[X], then
+                // set V[0xF] to 1, otherwise set to 0
+                let (diff, borrow) = self.V[Y].overflowing_sub(self.V[X]);
+                self.write_reg(X, diff);
+                self.write_reg(0xF, if borrow { 0 } else { 1 });
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::ShlVx(X, Y) => {
+                // 8XYE: If the most significant bit
+                // of V[X] is 1, then set V[0xF] to 1,
+                // otherwise 0. Then V[X] is left-shifted once
+                // (with shift_uses_vy, V[X] = V[Y] << 1 instead)
+                let value = if self.quirks.shift_uses_vy {
+                    self.V[Y]
+                } else {
+                    self.V[X]
+                };
+                self.write_reg(X, value << 1);
+                self.write_reg(0xF, (value & 0x80) >> 7);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::SneVxVy(X, Y) => {
                 // 9XY0: Skips next instruction if V[X] is not
                 // equal to V[Y]
                 if self.V[X] != self.V[Y] {
-                    self.PC += 4;
+                    self.skip_next();
                 } else {
-                    self.PC += 2;
+                    self.PC = self.PC.wrapping_add(2);
                 }
             }
-            0xA000 => {
+            Opcode::LdI(NNN) => {
                 // ANNN: Set index register I to address NNN
                 self.I = NNN;
-                self.PC += 2;
+                self.PC = self.PC.wrapping_add(2);
             }
-            0xB000 => {
-                // BNNN: Jump to address NNN plus V[0]
-                self.PC = NNN + self.V[0] as u16;
+            Opcode::JumpV0(NNN) => {
+                // BNNN: Jump to address NNN plus V[0], or with the
+                // CHIP-48 quirk BXNN: XNN plus V[X]
+                let x = if self.quirks.jump_with_vx {
+                    (NNN >> 8) as usize
+                } else {
+                    0
+                };
+                self.PC = NNN + self.V[x] as u16;
             }
-            0xC000 => {
+            Opcode::Rnd(X, NN) => {
                 // CXNN: Generates a random byte (0 - 255) and ANDs
                 // it to NN, V[X] is then set to the result
-                let rand_byte: u8 = rand::random();
-                self.V[X] = rand_byte & NN as u8;
-                self.PC += 2;
+                let rand_byte = self.rng.byte();
+                self.write_reg(X, rand_byte & NN);
+                self.PC = self.PC.wrapping_add(2);
             }
-            0xD000 => {
+            Opcode::Drw(X, Y, N) => {
                 // DXYN: Draw sprite at coordinate (V[X], V[Y])
                 // with N bytes from memory I
-                let x = self.V[X] as usize;
-                let y = self.V[Y] as usize;
-                let height = (opcode & 0x000F) as usize;
-                self.V[0xF] = 0;
-
-                for row in 0..height {
-                    let sprite = self.memory[self.I as usize + row];
-                    for col in 0..8 {
-                        let pixel = (sprite >> (7 - col)) & 1;
-
-                        let xcord = (x + col) % CHIP8_WIDTH;
-                        let ycord = (y + row) % CHIP8_HEIGHT;
-                        let index = ycord * CHIP8_WIDTH + xcord;
-
-                        if pixel == 1 {
-                            if self.display[index] == 1 {
-                                self.V[0xF] = 1;
-                            }
-                            self.display[index] ^= 1;
-                        }
-                    }
+
+                // Display wait: stall without drawing until the next
+                // vblank, then this DXYN runs again and draws
+                if self.quirks.display_wait && !self.vblank_draw {
+                    self.waiting_for_vblank = true;
+                    return Ok(0);
                 }
+                self.vblank_draw = false;
 
-                self.draw_flag = true;
-                self.PC += 2;
-            }
-            0xE000 => {
-                match opcode & 0x00FF {
-                    0x009E => {
-                        // EX9E: Skip next instruction if keypad[V[X]] is
-                        // pressed
-                        if self.keypad[self.V[X] as usize] == 1 {
-                            self.PC += 4;
-                        } else {
-                            self.PC += 2;
-                        }
-                    }
-                    0x00A1 => {
-                        // EXA1: Skip next instruction if keypad[V[X]] is
-                        // not pressed
-                        if self.keypad[self.V[X] as usize] == 0 {
-                            self.PC += 4;
-                        } else {
-                            self.PC += 2;
-                        }
-                    }
-                    _ => {
-                        eprintln!("Invalid opcode: {:#X}", opcode);
+                // The starting coordinate always wraps
+                let x = self.V[X] as usize % self.display_width();
+                let y = self.V[Y] as usize % self.display_height();
+                // DXY0 is a 16x16 sprite (two bytes a row) on
+                // XO-CHIP, and on SUPER-CHIP in high resolution
+                let (height, width) = match N {
+                    0 if self.variant == Variant::XoChip || self.hires => (16, 16),
+                    n => (n as usize, 8),
+                };
+                let mut hit_rows = 0;
+                let mut changed = false;
+
+                // With both XO-CHIP planes selected, the sprite
+                // for the second one follows the first in memory
+                let mut addr = self.I as usize;
+                for plane in [1, 2] {
+                    if self.planes & plane != 0 {
+                        let (hits, drew) = self.draw_sprite(x, y, addr, height, width, plane);
+                        hit_rows += hits;
+                        changed |= drew;
+                        addr += height * width / 8;
                     }
                 }
+
+                // SUPER-CHIP in high resolution sets VF to the
+                // number of rows that collided, plus the rows
+                // clipped off the bottom; everything else sets it
+                // to 1 for any collision
+                let collision = if self.variant == Variant::SuperChip && self.hires {
+                    let clipped = match self.quirks.wrap_y {
+                        true => 0,
+                        false => (y + height).saturating_sub(self.display_height()),
+                    };
+                    (hit_rows + clipped) as u8
+                } else {
+                    (hit_rows > 0) as u8
+                };
+
+                // A zero-height (DXY0 in CHIP-8, or SUPER-CHIP's low
+                // resolution), blank or fully clipped sprite leaves
+                // VF at 0 and the display untouched, so there's
+                // nothing to redraw
+                self.write_reg(0xF, collision);
+                self.draw_flag |= changed;
+                self.PC = self.PC.wrapping_add(2);
             }
-            0xF000 => {
-                match opcode & 0x00FF {
-                    0x0007 => {
-                        // FX07: Set V[X] to the delay timer
-                        self.V[X] = self.delay_timer;
-                        self.PC += 2;
+            Opcode::Skp(X) => {
+                // EX9E: Skip next instruction if keypad[V[X]] is
+                // pressed. Only the low nibble of V[X] picks the
+                // key, like on hardware, so bad values can't panic.
+                if self.take_key((self.V[X] & 0x0F) as usize) {
+                    self.skip_next();
+                } else {
+                    self.PC = self.PC.wrapping_add(2);
+                }
+            }
+            Opcode::Sknp(X) => {
+                // EXA1: Skip next instruction if keypad[V[X]] is
+                // not pressed (low nibble only, as in EX9E)
+                if !self.take_key((self.V[X] & 0x0F) as usize) {
+                    self.skip_next();
+                } else {
+                    self.PC = self.PC.wrapping_add(2);
+                }
+            }
+            Opcode::LdVxDt(X) => {
+                // FX07: Set V[X] to the delay timer
+                self.write_reg(X, self.delay_timer);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdVxK(X) => {
+                // FX0A: Waits for a key to be pressed and let go,
+                // like the VIP, and sets V[X] to it. Every key
+                // seen down while waiting is remembered, so with
+                // several held it's the one let go first, not the
+                // lowest. (PC only moves on once one is let go.)
+                for i in 0..16 {
+                    if self.take_key(i) {
+                        self.key_wait |= 1 << i;
                     }
-                    0x000A => {
-                        // FX0A: Stop emulator until a key
-                        // is pressed
-                        let mut key_pressed = false;
-
-                        for i in 0..16 {
-                            if self.keypad[i] == 1 {
-                                self.V[X] = i as u8;
-                                key_pressed = true;
-                                break;
-                            }
-                        }
+                }
+                let released = self.key_wait & !self.keys();
+                if released != 0 {
+                    self.write_reg(X, released.trailing_zeros() as u8);
+                    self.key_wait = 0;
+                    self.PC = self.PC.wrapping_add(2);
+                }
+            }
+            Opcode::LdDtVx(X) => {
+                // FX15: Set delay timer to V[X]
+                self.delay_timer = self.V[X];
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdStVx(X) => {
+                // FX18: Set sound timer to V[X]
+                self.sound_timer = self.V[X];
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::AddIVx(X) => {
+                // FX1E: Sets I to I + V[X]
+                self.I = self.I.wrapping_add(self.V[X] as u16);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdFVx(X) => {
+                // FX29: Set I to the location of
+                // sprite for digit V[X]. Digits past F would
+                // point outside the font, so only the low nibble
+                // is used.
+                let digit = self.V[X] & 0x0F;
 
-                        // Effectively stops emulator
-                        // until a key is pressed. (PC is
-                        // only added when a key is pressed)
-                        if key_pressed {
-                            self.PC += 2;
-                        }
-                    }
-                    0x0015 => {
-                        // FX15: Set delay timer to V[X]
-                        self.delay_timer = self.V[X];
-                        self.PC += 2;
-                    }
-                    0x0018 => {
-                        // FX18: Set sound timer to V[X]
-                        self.sound_timer = self.V[X];
-                        self.PC += 2;
-                    }
-                    0x001E => {
-                        // FX1E: Sets I to I + V[X]
-                        self.I = self.I.wrapping_add(self.V[X] as u16);
-                        self.PC += 2;
-                    }
-                    0x0029 => {
-                        // FX29: Set I to the location of
-                        // sprite for digit V[X]
-                        let digit = self.V[X];
+                self.I = self.font_start as u16 + (digit as u16 * 5);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdHfVx(X) if self.variant != Variant::Chip8 => {
+                // FX30: Set I to the large (8x10) sprite for digit
+                // V[X], using only the low nibble like FX29
+                // (SUPER-CHIP, XO-CHIP)
+                let digit = self.V[X] & 0x0F;
 
-                        self.I = FONTSET_START_ADDR as u16 + (digit as u16 * 5);
-                        self.PC += 2;
-                    }
-                    0x0033 => {
-                        // FX33: Store BCD (Binary-Coded Decimal) representation
-                        // of V[X] to memory[I], memory[I + 1], and memory[I + 2]
-                        let value = self.V[X];
+                self.I = self.large_font().start as u16 + (digit as u16 * 10);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdBVx(X) => {
+                // FX33: Store BCD (Binary-Coded Decimal) representation
+                // of V[X] to memory[I], memory[I + 1], and memory[I + 2]
+                let value = self.V[X];
 
-                        self.memory[self.I as usize] = value / 100;
-                        self.memory[self.I as usize + 1] = (value % 100) / 10;
-                        self.memory[self.I as usize + 2] = value % 10;
+                self.write_mem(self.I as usize, value / 100);
+                self.write_mem(self.I as usize + 1, (value % 100) / 10);
+                self.write_mem(self.I as usize + 2, value % 10);
 
-                        self.PC += 2;
-                    }
-                    0x0055 => {
-                        // FX55: Stores V[i] to V[X] into memory[I + i]
-                        for i in 0..=X {
-                            self.memory[self.I as usize + i] = self.V[i];
-                        }
-                        self.PC += 2;
-                    }
-                    0x0065 => {
-                        // FX65: Stores memory[I + i] into V[i] to V[X]
-                        for i in 0..=X {
-                            self.V[i] = self.memory[self.I as usize + i];
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdIVx(X) => {
+                // FX55: Stores V[i] to V[X] into memory[I + i]
+                for i in 0..=X {
+                    self.write_mem(self.I as usize + i, self.V[i]);
+                }
+                if self.quirks.index_increment_on_store {
+                    self.I = self.I.wrapping_add(X as u16 + 1);
+                }
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdVxI(X) => {
+                // FX65: Stores memory[I + i] into V[i] to V[X]
+                for i in 0..=X {
+                    self.write_reg(i, self.read_mem(self.I as usize + i));
+                }
+                if self.quirks.index_increment_on_store {
+                    self.I = self.I.wrapping_add(X as u16 + 1);
+                }
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdILong if self.variant == Variant::XoChip => {
+                // F000 NNNN: Set I to the 16-bit address in the
+                // word after this one
+                let pc = self.PC as usize;
+                self.I = ((self.read_mem(pc + 2) as u16) << 8) | self.read_mem(pc + 3) as u16;
+                self.PC = self.PC.wrapping_add(4);
+            }
+            Opcode::Plane(n) if self.variant == Variant::XoChip => {
+                // FN01: Select the display planes for DXYN and
+                // 00E0 (0 = none, 1, 2, 3 = both)
+                self.planes = n & 0x3;
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Audio if self.variant == Variant::XoChip => {
+                // F002: Load the 16-byte audio pattern at I
+                let mut pattern = [0; 16];
+                for (n, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read_mem(self.I as usize + n);
+                }
+                self.audio_pattern = Some(pattern);
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::Pitch(X) if self.variant == Variant::XoChip => {
+                // FX3A: Set the pitch the audio pattern plays at
+                self.pitch = self.V[X];
+                self.PC = self.PC.wrapping_add(2);
+            }
+            Opcode::LdILong
+            | Opcode::Plane(_)
+            | Opcode::Audio
+            | Opcode::Pitch(_)
+            | Opcode::LdHfVx(_)
+            | Opcode::Invalid(_) => {
+                return Err(self.invalid_opcode(opcode));
+            }
+        };
+
+        // Jumping or running past the end of memory wraps around
+        // to the start, like every other address. Odd addresses
+        // are left alone: the VIP fetched whichever two bytes were
+        // there, and some ROMs rely on it.
+        self.PC = (self.PC as usize % self.memory.len()) as u16;
+        self.cycles += 1;
+        if let Some(counts) = &mut self.family_counts {
+            counts[opcode as usize >> 12] += 1;
+        }
+        if let Some(counts) = &mut self.opcode_counts {
+            *counts.entry(opcode_class(opcode)).or_default() += 1;
+        }
+        match op {
+            Opcode::Cls
+            | Opcode::ScrollDown(_)
+            | Opcode::ScrollUp(_)
+            | Opcode::ScrollRight
+            | Opcode::ScrollLeft
+            | Opcode::Drw(..)
+            | Opcode::Skp(_)
+            | Opcode::Sknp(_)
+            | Opcode::LdVxDt(_)
+            | Opcode::LdVxK(_) => self.idle_cycles = 0,
+            _ => self.idle_cycles += 1,
+        }
+
+        Ok(self.timing.cycle_cost(op))
+    }
+
+    // XORs one sprite, 8 or 16 pixels wide, into a display plane
+    // (bit 1 or 2). Returns how many of its rows turned a pixel
+    // off, and whether it toggled any pixel at all. Pixels clipped
+    // off the edge never count.
+    fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        addr: usize,
+        height: usize,
+        width: usize,
+        plane: u8,
+    ) -> (usize, bool) {
+        let Quirks { wrap_x, wrap_y, .. } = self.quirks;
+        let bytes = width / 8;
+        let (cols, rows) = (self.display_width(), self.display_height());
+        let mut hit_rows = 0;
+        let mut changed = false;
+
+        for row in 0..height {
+            if !wrap_y && y + row >= rows {
+                break;
+            }
+
+            // Rows are read into the top bits of a u16, whether
+            // they're one byte wide or two. Empty rows can't change
+            // anything, skip them.
+            let at = addr + row * bytes;
+            let mut sprite = (self.read_mem(at) as u16) << 8;
+            if bytes == 2 {
+                sprite |= self.read_mem(at + 1) as u16;
+            }
+            if sprite == 0 {
+                continue;
+            }
+
+            let row_start = ((y + row) % rows) * cols;
+            let mut hit = false;
+            for col in 0..width {
+                if !wrap_x && x + col >= cols {
+                    break;
+                }
+                if sprite & (0x8000 >> col) != 0 {
+                    let index = row_start + (x + col) % cols;
+
+                    // A pixel that was already on in this plane
+                    // is a collision
+                    if self.display[index] & plane != 0 {
+                        hit = true;
+                        if let Some(hook) = &mut self.collision_hook {
+                            hook(index);
                         }
-                        self.PC += 2;
-                    }
-                    _ => {
-                        eprintln!("Invalid opcode: {:#X}", opcode);
+                    } else {
+                        self.frame_pixels_drawn += 1;
                     }
+                    self.set_pixel(index, self.display[index] ^ plane);
+                    changed = true;
                 }
             }
-            _ => {
-                eprintln!("Invalid opcode: {:#X}", opcode);
+            hit_rows += hit as usize;
+        }
+
+        (hit_rows, changed)
+    }
+
+    // What every clearing instruction (00E0, and 0230 on hi-res)
+    // does: the selected planes are cleared across the whole
+    // display, whatever its height, and the other plane is left
+    // alone. It goes pixel by pixel so it can be undone, and only
+    // flags a redraw if something was lit. The flicker filter's
+    // past frames are kept, since blending a clear with what came
+    // before it is what the filter is for.
+    fn clear_display(&mut self) {
+        self.frame_cleared = true;
+        for index in 0..self.display.len() {
+            let pixel = self.display[index];
+            if pixel & self.planes != 0 {
+                self.set_pixel(index, pixel & !self.planes);
+                self.draw_flag = true;
             }
-        };
+        }
+    }
+
+    // All display writes by instructions go through here so they
+    // can be undone
+    fn set_pixel(&mut self, index: usize, val: u8) {
+        if let Some(entry) = self.undo_log.as_mut().and_then(|log| log.back_mut()) {
+            entry.display.push((index, self.display[index]));
+        }
+        self.display[index] = val;
+    }
+
+    // How far a scroll of `n` pixels moves the display
+    fn scroll_distance(&self, n: u8) -> isize {
+        if self.quirks.half_scroll && !self.hires {
+            n as isize / 2
+        } else {
+            n as isize
+        }
+    }
+
+    // Moves the selected planes `dx` pixels right and `dy` down
+    // (negative for left and up). What scrolls off the edge is
+    // lost and the area it leaves is cleared; the other plane
+    // stays put. Like any other drawing this only sets draw_flag,
+    // so however many scrolls a frame has, step_frame() reports
+    // one change.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let old = self.display.clone();
+        let (cols, rows) = (self.display_width(), self.display_height());
+        for y in 0..rows {
+            for x in 0..cols {
+                let (from_x, from_y) = (x as isize - dx, y as isize - dy);
+                let from = if (0..cols as isize).contains(&from_x)
+                    && (0..rows as isize).contains(&from_y)
+                {
+                    old[from_y as usize * cols + from_x as usize] & self.planes
+                } else {
+                    0
+                };
+                let index = y * cols + x;
+                let scrolled = self.display[index] & !self.planes | from;
+                if scrolled != self.display[index] {
+                    self.set_pixel(index, scrolled);
+                    self.draw_flag = true;
+                }
+            }
+        }
+    }
+
+    // Stops on an opcode that can't be run. PC stays on it, so a
+    // ROM that wandered into data can be looked at from there, and
+    // the history that led there is logged.
+    fn invalid_opcode(&self, opcode: u16) -> Chip8Error {
+        debug!("{}", self.dump_trace().trim_end());
+        Chip8Error::InvalidOpcode {
+            pc: self.PC,
+            opcode,
+        }
+    }
+
+    // Steps over the next instruction. On XO-CHIP that can be
+    // the two-word F000 NNNN.
+    fn skip_next(&mut self) {
+        let next = self.PC as usize + 2;
+        let long = self.variant == Variant::XoChip
+            && self.read_mem(next) == 0xF0
+            && self.read_mem(next + 1) == 0x00;
+        self.PC = self.PC.wrapping_add(if long { 6 } else { 4 });
+    }
+
+    // Runs one 60Hz frame: vblank(), then instructions until
+    // `cycles` of the frame budget are spent (instructions, VIP
+    // machine cycles with Timing::Vip, or SUPER-CHIP costs with
+    // Timing::Schip), then update_timers() (as many times as are
+    // due at another rate, see set_timer_hz()).
+    //
+    // The frame ends early on a display wait stall, or as soon as
+    // a watchpoint is hit so the debugger can stop right there
+    // (hits from earlier frames that haven't been taken don't
+    // count). An instruction that runs past the end of the budget
+    // is paid for out of the next frame. On an error the frame
+    // stops at the failing instruction and the timers don't tick.
+    pub fn step_frame(&mut self, cycles: u32) -> std::result::Result<FrameOutput, Chip8Error> {
+        self.step_frame_upto(cycles, u64::MAX)
+    }
+
+    // Like step_frame(), but stops after `limit` instructions
+    // (stalls included), for a run of a fixed number of them. A
+    // frame cut short that way is left unfinished, and the timers
+    // don't tick.
+    pub fn step_frame_upto(
+        &mut self,
+        cycles: u32,
+        limit: u64,
+    ) -> std::result::Result<FrameOutput, Chip8Error> {
+        (self.frame_cleared, self.frame_pixels_drawn) = (false, 0);
+        self.vblank();
+        let mut spent = self.overspent;
+        self.overspent = 0;
+        let mut instructions = 0;
+        let hits = self.watchpoint_hits.len();
+        while spent < cycles {
+            if instructions as u64 >= limit {
+                return Ok(self.frame_output(instructions));
+            }
+            spent += self.emulate_cycle()?;
+            instructions += 1;
+            if self.waiting_for_vblank || self.watchpoint_hits.len() > hits {
+                spent = cycles;
+            }
+        }
+        self.overspent = spent - cycles;
+        // A 60th of a second, rounded up to the nanosecond, or at
+        // 30Hz two frames would come up a nanosecond short of a tick
+        let frame = std::time::Duration::from_nanos(1_000_000_000_u64.div_ceil(FRAME_HZ as u64));
+        for _ in 0..self.timer_clock.take(frame) {
+            self.update_timers();
+        }
+        let mut output = self.frame_output(instructions);
+        output.display_changed |= self.present_frame();
+        Ok(output)
+    }
+
+    fn frame_output(&mut self, instructions: u32) -> FrameOutput {
+        FrameOutput {
+            display_changed: self.take_draw_flag(),
+            sound: self.buzzer,
+            waiting_for_key: matches!(self.next_op(), Opcode::LdVxK(_)),
+            instructions,
+            cleared: self.frame_cleared,
+            pixels_drawn: self.frame_pixels_drawn,
+        }
+    }
+
+    // Sets how many frames the flicker filter blends (1 to 3; 1
+    // turns it off). Games that erase a sprite and draw it again
+    // in the next frame flicker with XOR drawing; blending the
+    // last few frames hides that at the cost of some smearing.
+    pub fn set_flicker_filter(&mut self, frames: usize) {
+        self.flicker_window = frames.clamp(1, MAX_FLICKER_WINDOW);
+        self.recent_frames.clear();
+        self.composited.clone_from(&self.display);
+    }
+
+    // Whether the display changed since the last call, clearing
+    // the flag in the same step. Reading draw_flag and clearing it
+    // after drawing would lose a change made in between.
+    pub fn take_draw_flag(&mut self) -> bool {
+        std::mem::take(&mut self.draw_flag)
+    }
+
+    // Hands the display to the flicker filter once per shown
+    // frame; step_frame() does this itself. Returns whether
+    // display_composited() changed.
+    pub fn present_frame(&mut self) -> bool {
+        if self.flicker_window == 1 {
+            return false;
+        }
+        if self.recent_frames.len() == self.flicker_window {
+            self.recent_frames.pop_front();
+        }
+        self.recent_frames.push_back(self.display.clone());
+
+        let mut composited = vec![0; self.display.len()];
+        for frame in &self.recent_frames {
+            for (out, &pixel) in composited.iter_mut().zip(frame) {
+                *out |= pixel;
+            }
+        }
+        let changed = composited != self.composited;
+        self.composited = composited;
+        changed
+    }
 
-        &self.display
+    // What to show: the display, with a pixel lit if it was lit
+    // in any of the frames the flicker filter blends. Collisions
+    // and everything else still use the plain `display`.
+    pub fn display_composited(&self) -> &[u8] {
+        if self.flicker_window == 1 {
+            &self.display
+        } else {
+            &self.composited
+        }
+    }
+
+    // 64 pixels, or 128 in SUPER-CHIP's high resolution
+    pub fn display_width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { CHIP8_WIDTH }
+    }
+
+    pub fn display_height(&self) -> usize {
+        self.display.len() / self.display_width()
+    }
+
+    // Whether 00FF has switched to SUPER-CHIP's 128x64 high
+    // resolution
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    // Gives the display 32, 48 or 64 rows (see DISPLAY_HEIGHTS).
+    // The COSMAC VIP's hi-res CHIP-8 interpreters drew on a 64x64
+    // display, and a few on 64x48; their ROMs draw past row 32
+    // and wrap at the bottom of the taller display. The display
+    // is cleared, and back in low resolution if 00FF had left
+    // it in high. Other heights are refused.
+    pub fn set_display_height(&mut self, rows: usize) -> Result<()> {
+        if !DISPLAY_HEIGHTS.contains(&rows) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Display height {} isn't 32, 48 or 64", rows),
+            ));
+        }
+        self.hires = false;
+        self.lores_rows = rows;
+        self.reset_display(rows);
+        Ok(())
+    }
+
+    // 00FE and 00FF: a blank display 64 pixels wide, as tall as
+    // set_display_height() made it, or 128x64. Like the other
+    // clears it counts for FrameOutput::cleared, but it can't be
+    // undone (see reset_display()).
+    fn set_hires(&mut self, on: bool) {
+        self.hires = on;
+        self.frame_cleared = true;
+        self.reset_display(if on { HIRES_HEIGHT } else { self.lores_rows });
+    }
+
+    // Starts over with a blank display `rows` high, every plane
+    // and the flicker filter's frames included. Unlike
+    // clear_display() this can't be undone, as the display's size
+    // may have changed. It only needs a redraw if something was
+    // lit, so a new machine doesn't start with one pending.
+    fn reset_display(&mut self, rows: usize) {
+        self.draw_flag |= self.composited.iter().any(|&p| p != 0);
+        self.display = vec![0; self.display_width() * rows];
+        self.composited.clone_from(&self.display);
+        self.recent_frames.clear();
+        if let Some(log) = &mut self.undo_log {
+            log.clear();
+        }
     }
 
+    // Ticks the timers `hz` times a second (at least once) instead
+    // of once a frame in step_frame(), counting frames as 1/60 of
+    // a second. The time between ticks carries over from frame to
+    // frame (see TickClock), so rates that don't divide into 60
+    // don't drift.
+    pub fn set_timer_hz(&mut self, hz: u32) {
+        self.timer_clock.set_hz(hz);
+    }
+
+    // One 60Hz timer tick. The buzzer only turns on or off here:
+    // it sounds from the first tick after FX18 for as many ticks as
+    // the timer was set to, so a beep's length is exact. See
+    // beep_started() and beep_ended().
     pub fn update_timers(&mut self) {
+        let was = self.buzzer;
+        self.buzzer = self.sound_timer > 0;
+        self.beep_started = self.buzzer && !was;
+        self.beep_ended = was && !self.buzzer;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -421,4 +2092,472 @@ impl Chip8 {
             self.sound_timer -= 1;
         }
     }
+
+    // Marks the start of a display frame. Frontends call this
+    // once per 60Hz frame; a DXYN that is waiting on the display
+    // wait quirk draws on the next emulate_cycle().
+    pub fn vblank(&mut self) {
+        if self.waiting_for_vblank {
+            self.waiting_for_vblank = false;
+            self.vblank_draw = true;
+        }
+        // Taps nobody looked at for a whole frame are dropped
+        self.old_presses = self.new_presses;
+        self.new_presses = 0;
+    }
+
+    // Presses or releases a key (0x0-0xF). Prefer this to writing
+    // `keypad` directly: a press is remembered until the ROM checks
+    // for it, even if the key is let go again before then.
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if key >= 16 {
+            return;
+        }
+        if pressed && self.keypad[key] == 0 {
+            self.seen_keys &= !(1 << key);
+        }
+        self.keypad[key] = pressed as u8;
+        if pressed {
+            self.new_presses |= 1 << key;
+        }
+    }
+
+    // Makes each key press count once: after EX9E, EXA1 or FX0A
+    // has seen a key down, it reads as up until it's let go and
+    // pressed again. This is for ROMs that poll the keys many
+    // times a frame and take one press for several (menus
+    // skipping entries, pieces rotating twice), and breaks games
+    // that expect a held key to keep acting, so it's off by
+    // default. EXA1 is still the opposite of EX9E: both see the
+    // key down the first time and up after that.
+    pub fn set_key_repeat_guard(&mut self, on: bool) {
+        self.key_repeat_guard = on;
+        self.seen_keys = 0;
+    }
+
+    // Sets all 16 keys at once, bit N for key N, e.g. from a
+    // recorded replay. Presses are remembered as with set_key().
+    pub fn set_keys(&mut self, mask: u16) {
+        for key in 0..16 {
+            self.set_key(key, mask & (1 << key) != 0);
+        }
+    }
+
+    // The keys held down, bit N for key N
+    pub fn keys(&self) -> u16 {
+        (0..16)
+            .filter(|&key| self.keypad[key] != 0)
+            .fold(0, |mask, key| mask | 1 << key)
+    }
+
+    // Whether a key counts as down for EX9E/EXA1/FX0A: held now,
+    // or tapped since it was last checked. Checking uses up the tap,
+    // and with the key repeat guard the press too.
+    fn take_key(&mut self, key: usize) -> bool {
+        let bit = 1 << key;
+        let tapped = (self.new_presses | self.old_presses) & bit != 0;
+        self.new_presses &= !bit;
+        self.old_presses &= !bit;
+        let down = self.keypad[key] == 1 || tapped;
+        if !self.key_repeat_guard {
+            return down;
+        }
+        let fresh = down && self.seen_keys & bit == 0;
+        if down {
+            self.seen_keys |= bit;
+        }
+        fresh
+    }
+
+    // True while the CPU is stalled until the next vblank(), so
+    // frontends can stop spending cycles on this frame
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            stack: self.stack.clone(),
+            PC: self.PC,
+            V: self.V,
+            memory: self.memory.clone(),
+            I: self.I,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            rng: self.rng.clone(),
+            waiting_for_vblank: self.waiting_for_vblank,
+            vblank_draw: self.vblank_draw,
+            hires: self.hires,
+            display: self.display.clone(),
+            planes: self.planes,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            keypad: self.keypad,
+            new_presses: self.new_presses,
+            old_presses: self.old_presses,
+            seen_keys: self.seen_keys,
+            key_wait: self.key_wait,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.stack.clone_from(&snapshot.stack);
+        self.peak_stack_depth = self.peak_stack_depth.max(self.stack.len());
+        self.PC = snapshot.PC;
+        self.V = snapshot.V;
+        self.memory.clone_from(&snapshot.memory);
+        // A snapshot from a machine with more memory brings it along.
+        // What this machine didn't have came from the snapshot, so it
+        // counts as written.
+        self.written.resize(self.memory.len(), true);
+        self.I = snapshot.I;
+        self.delay_timer = snapshot.delay_timer;
+        self.sound_timer = snapshot.sound_timer;
+        self.rng = snapshot.rng.clone();
+        self.waiting_for_vblank = snapshot.waiting_for_vblank;
+        self.vblank_draw = snapshot.vblank_draw;
+        self.keypad = snapshot.keypad;
+        self.new_presses = snapshot.new_presses;
+        self.old_presses = snapshot.old_presses;
+        self.seen_keys = snapshot.seen_keys;
+        self.key_wait = snapshot.key_wait;
+        if self.display.len() != snapshot.display.len() {
+            self.recent_frames.clear();
+            self.composited.clone_from(&snapshot.display);
+        }
+        self.display.clone_from(&snapshot.display);
+        self.hires = snapshot.hires;
+        if !self.hires {
+            self.lores_rows = self.display_height();
+        }
+        self.planes = snapshot.planes;
+        self.audio_pattern = snapshot.audio_pattern;
+        self.pitch = snapshot.pitch;
+        self.idle_cycles = 0;
+        self.draw_flag = true;
+        // The changes logged were made to another state
+        if let Some(log) = &mut self.undo_log {
+            log.clear();
+        }
+    }
+
+    // Records a rewind point. Frontends call this once per frame;
+    // only the last REWIND_LEN frames are kept.
+    pub fn push_rewind(&mut self) {
+        if self.rewind_buffer.len() == REWIND_LEN {
+            self.rewind_buffer.pop_front();
+        }
+        let snapshot = self.snapshot();
+        self.rewind_buffer.push_back(snapshot);
+    }
+
+    // Steps back to the last rewind point, returning false once
+    // the buffer has run out
+    pub fn rewind(&mut self) -> bool {
+        match self.rewind_buffer.pop_back() {
+            Some(snapshot) => {
+                // The keys are the player's, as held now rather than
+                // back then, so none are left stuck down
+                let keys = (
+                    self.keypad,
+                    self.new_presses,
+                    self.old_presses,
+                    self.seen_keys,
+                );
+                self.restore(&snapshot);
+                (
+                    self.keypad,
+                    self.new_presses,
+                    self.old_presses,
+                    self.seen_keys,
+                ) = keys;
+                self.key_wait = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Turns on logging what each instruction changes, so undo()
+    // can step back through the last UNDO_LEN of them. This is
+    // much lighter than a snapshot per instruction, but still
+    // costs something on every instruction, so it's off by
+    // default; turning it off drops the log.
+    pub fn set_undo(&mut self, on: bool) {
+        self.undo_log = on.then(VecDeque::new);
+    }
+
+    fn undo_entry(&self) -> Undo {
+        Undo {
+            PC: self.PC,
+            I: self.I,
+            V: self.V,
+            stack_depth: self.stack.len(),
+            stack_top: self.stack.last().copied(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            rng: self.rng.clone(),
+            waiting_for_vblank: self.waiting_for_vblank,
+            vblank_draw: self.vblank_draw,
+            new_presses: self.new_presses,
+            old_presses: self.old_presses,
+            seen_keys: self.seen_keys,
+            key_wait: self.key_wait,
+            planes: self.planes,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            memory: Vec::new(),
+            display: Vec::new(),
+        }
+    }
+
+    // Steps back one instruction, as if it had never run. Returns
+    // false when there's nothing to undo: undo is off (see
+    // set_undo()), the log has run out, or a state was restored
+    // since. The timers go back to what they were before the
+    // instruction too, even if a frame ticked them since.
+    pub fn undo(&mut self) -> bool {
+        let Some(entry) = self.undo_log.as_mut().and_then(|log| log.pop_back()) else {
+            return false;
+        };
+        // Newest first, so a byte written twice gets its first
+        // old value
+        for &(addr, old) in entry.memory.iter().rev() {
+            self.memory[addr] = old;
+        }
+        for &(index, old) in entry.display.iter().rev() {
+            self.display[index] = old;
+        }
+        self.stack.truncate(entry.stack_depth);
+        if let Some(top) = entry.stack_top
+            && self.stack.len() < entry.stack_depth
+        {
+            self.stack.push(top);
+        }
+        self.PC = entry.PC;
+        self.I = entry.I;
+        self.V = entry.V;
+        self.delay_timer = entry.delay_timer;
+        self.sound_timer = entry.sound_timer;
+        self.rng = entry.rng;
+        self.waiting_for_vblank = entry.waiting_for_vblank;
+        self.vblank_draw = entry.vblank_draw;
+        self.new_presses = entry.new_presses;
+        self.old_presses = entry.old_presses;
+        self.seen_keys = entry.seen_keys;
+        self.key_wait = entry.key_wait;
+        self.planes = entry.planes;
+        self.audio_pattern = entry.audio_pattern;
+        self.pitch = entry.pitch;
+        self.idle_cycles = 0;
+        self.draw_flag = true;
+        true
+    }
+
+    // Reseeds the CXNN random number generator. Machines are
+    // randomly seeded by default; a fixed seed makes runs
+    // reproducible. The generator is xoshiro256++ on every
+    // target, so a seed gives the same numbers everywhere.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    // Instructions executed so far, not counting display wait
+    // stalls or failed instructions. Rewinding and loading states
+    // don't change it, so it can be used to measure speed.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.PC
+    }
+
+    // The instruction at PC, which the next emulate_cycle() runs
+    fn next_op(&self) -> Opcode {
+        let pc = self.PC as usize;
+        decode(u16::from_be_bytes([
+            self.read_mem(pc),
+            self.read_mem(pc + 1),
+        ]))
+    }
+
+    // Whether the ROM has stopped for good on a jump to itself,
+    // which is how most ROMs end. It still runs, but nothing but a
+    // reset will change what it does.
+    pub fn is_halted(&self) -> bool {
+        self.ran_past_rom() || self.next_op() == Opcode::Jump(self.PC)
+    }
+
+    // Stops the machine once PC reaches memory that nothing was
+    // ever put in: not loaded from the ROM, and not written by
+    // the program since. Empty memory is all 0000, which would
+    // otherwise stop with a machine code call (or with
+    // ignore_machine_code, run on through memory for ever). Once
+    // stopped, is_halted() and ran_past_rom() say so. Memory
+    // that loading a state puts back still counts as empty if the
+    // program hadn't written it here. Off by default.
+    pub fn set_halt_past_rom(&mut self, on: bool) {
+        self.halt_past_rom = on;
+    }
+
+    // Whether PC is in empty memory with halt_past_rom on, so the
+    // machine has stopped
+    pub fn ran_past_rom(&self) -> bool {
+        let len = self.written.len();
+        let pc = self.PC as usize;
+        self.halt_past_rom && !self.written[pc % len] && !self.written[(pc + 1) % len]
+    }
+
+    // Whether the ROM has gone LOOP_CYCLES instructions without
+    // drawing or reading the keys or the delay timer. Nothing from
+    // outside can change what it does next, so unless it's in the
+    // middle of a long calculation it's stuck. A ROM waiting for a
+    // key or for the delay timer doesn't count.
+    pub fn is_looping(&self) -> bool {
+        self.idle_cycles >= LOOP_CYCLES
+    }
+
+    pub fn i(&self) -> u16 {
+        self.I
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.V
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // The most nested calls there have been at once
+    pub fn peak_stack_depth(&self) -> usize {
+        self.peak_stack_depth
+    }
+
+    // Return addresses, the outermost call first
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    // How deep calls can go before 2NNN stops with
+    // Chip8Error::StackOverflow (see Chip8Builder::stack_depth()),
+    // for showing how close stack() is to it
+    pub fn stack_limit(&self) -> usize {
+        self.stack_limit
+    }
+
+    // For debuggers changing the machine's state, e.g. the web
+    // UI's register inspector. Values the machine couldn't hold
+    // are refused: a register past VF, or an address past the
+    // end of memory.
+    pub fn set_register(&mut self, x: usize, val: u8) -> Result<()> {
+        if x >= self.V.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("There's no register {} (V0-VF are 0-15)", x),
+            ));
+        }
+        self.V[x] = val;
+        Ok(())
+    }
+
+    pub fn set_i(&mut self, addr: u16) -> Result<()> {
+        self.check_addr(addr)?;
+        self.I = addr;
+        Ok(())
+    }
+
+    pub fn set_pc(&mut self, addr: u16) -> Result<()> {
+        self.check_addr(addr)?;
+        self.PC = addr;
+        Ok(())
+    }
+
+    fn check_addr(&self, addr: u16) -> Result<()> {
+        if addr as usize >= self.memory.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{:#05X} is outside memory (0x000..{:#X})",
+                    addr,
+                    self.memory.len()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    // All of RAM, fontset and program included
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    // The XO-CHIP audio pattern: 128 1-bit samples, the most
+    // significant bit of each byte first, looped while the sound
+    // timer runs. None until the ROM loads one with F002, in which
+    // case frontends play their usual beep.
+    pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+        self.audio_pattern.as_ref()
+    }
+
+    // Samples per second the audio pattern plays at: 4000 at the
+    // default pitch of 64, and an octave up or down every 48
+    pub fn audio_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    // 64-bit FNV-1a hash of the display buffer. It's stable across
+    // platforms and Rust versions (unlike std's DefaultHasher), so
+    // it can be compared against golden values from a seeded run.
+    pub fn display_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for &b in self.display.iter() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    // Both timers count down by one per update_timers(), which
+    // frontends call at 60Hz, and stop at 0
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    // The buzzer sounds while this is above 0, so it's how many
+    // 60ths of a second of beep are left. A frontend can fade the
+    // tone out as it nears 0, or flash something while it runs.
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    // Whether the buzzer is sounding until the next timer tick
+    pub fn buzzer(&self) -> bool {
+        self.buzzer
+    }
+
+    // Whether the last update_timers() turned the buzzer on, or
+    // off. Frontends that start and stop the tone on these, at the
+    // time the tick was due, get beeps of the right length
+    // wherever in a frame they were set.
+    pub fn beep_started(&self) -> bool {
+        self.beep_started
+    }
+
+    pub fn beep_ended(&self) -> bool {
+        self.beep_ended
+    }
 }
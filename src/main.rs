@@ -0,0 +1,777 @@
+// Native frontends. The main way to play is the web UI, but
+// the core runs fine in a terminal or with no UI at all.
+mod config;
+mod control;
+mod crash;
+mod flash;
+mod headless;
+mod info;
+mod keymap;
+mod netplay;
+mod practice;
+mod reload;
+mod replay;
+mod romdb;
+mod savestate;
+mod scan;
+mod trace;
+mod tui;
+mod video;
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use chip8_emu::{
+    BIG_FONTSET, Chip8, Chip8Error, DISPLAY_HEIGHTS, FONTSET, Pacing, Timing, Variant, Watch,
+    assemble,
+};
+use config::{Config, QUIRK_FLAGS};
+use crash::WithTrace;
+use log::{LevelFilter, error, info};
+use trace::Trace;
+
+const USAGE: &str = "Usage: chip8-emu [--romdir DIR] [options]
+       chip8-emu --tui [--record-input FILE | --replay FILE] [options] <rom>
+       chip8-emu --tui (--host PORT | --connect ADDR) [--input-delay N] [options] <rom>
+       chip8-emu --headless [--cycles N] [--report FILE] [--profile] [--seed N] [options] <rom>
+       chip8-emu --control-pipe FILE [--seed N] [options] <rom>
+       chip8-emu --info [--json] <rom>
+       chip8-emu asm <source.8o> [-o <rom.ch8>]
+       chip8-emu config --print [options]
+       chip8-emu --help
+
+ROMs can also be Octo source files (.8o), which are assembled
+first. asm just assembles one, by default to the same name
+with .ch8.
+
+Without a ROM, a list of the ROMs in DIR (default: the current
+directory) is shown in the terminal.
+
+Known ROMs get the settings they need from a built-in database;
+options given here take precedence.
+
+Settings are also read from chip8-emu.toml in the current
+directory, or else in the config directory (e.g.
+~/.config/chip8-emu/); options given here take precedence.
+config --print shows the settings in effect as such a file.
+
+Config:
+  --config FILE   Read settings from FILE instead
+  --no-config     Don't read a config file
+
+Options:
+  --cpf N         Instructions per 60Hz frame (default 15)
+  --timer-hz N    Delay and sound timer ticks a second (default 60)
+  --timing MODE   simple: CPF instructions a frame (default)
+                  vip: instructions take as long as on the COSMAC
+                  VIP, so draws and FX33/FX55/FX65 cost more
+                  schip: as on SUPER-CHIP, so draws, scrolls
+                  and clears cost more out of CPF
+  --seed N        Seed for CXNN random numbers (headless and
+                  scripts default to 0, otherwise random)
+  --font FILE     Use the font in FILE: 80 bytes of 4x5 hex digits
+                  (FX29), optionally followed by 160 bytes of 8x10
+                  ones (FX30)
+  --load-addr ADDR
+                  Load and start the ROM at ADDR instead of 0x200
+                  (e.g. 0x600 for ETI-660 programs)
+  --font-address ADDR
+                  Put the font at ADDR instead of 0x50, for ROMs
+                  that expect it elsewhere (e.g. 0x000)
+  --no-romdb      Don't use the built-in settings for known ROMs
+                  or the ones in <rom>.toml next to the ROM
+  --no-detect     Don't turn on XO-CHIP or SUPER-CHIP for ROMs whose
+                  code looks like it needs it, or the 64x64 display
+                  for hi-res ROMs (which start with 1260)
+  --hires         Use the COSMAC VIP's 64x64 hi-res display
+  --display-height N
+                  Rows on the display: 32 (default), 48 or 64
+  --schip         Enable SUPER-CHIP extensions (FX30 large digits)
+  --xo-chip       Enable XO-CHIP extensions (64KB memory, F000 NNNN)
+  --clip-sprites  Clip sprites at the screen edges instead of wrapping
+  --clip-x        Clip sprites at the right edge only
+  --clip-y        Clip sprites at the bottom edge only
+  --display-wait  Limit sprite drawing to once per 60Hz frame
+  --shift-vy      8XY6/8XYE shift VY into VX (original CHIP-8)
+  --increment-i   FX55/FX65 advance I past the last register
+  --vf-reset      8XY1/8XY2/8XY3 clear VF
+  --ignore-0nnn   Skip 0NNN machine code calls instead of stopping
+  --jump-vx       BXNN jumps to XNN + VX (CHIP-48/SCHIP)
+  --half-scroll   00CN/00DN/00FB/00FC scroll half as far (SCHIP 1.1)
+  --quirk NAME    Any of the quirk flags above by name, e.g.
+                  --quirk vf-reset
+
+Scripting:
+  --report FILE        With --headless, write what happened to FILE as
+                       JSON: cycles run, final PC, why it stopped, the
+                       display hash, instructions by family (top
+                       nibble) and the deepest the stack got
+  --profile            With --headless, list the instructions run most
+                       often (e.g. 8XY4), after the final state
+  --control-pipe FILE  Run the ROM one command from FILE (a named
+                       pipe, or - for stdin) a frame: key down K,
+                       key up K, wait FRAMES, screenshot PATH,
+                       savestate SLOT, loadstate SLOT, reset, quit.
+                       Each is answered with ok or err on stdout
+
+Exit status: 0 when the ROM ran, 2 for bad arguments, 3 when it
+stopped on an invalid opcode, 4 on a stack overflow or a return
+with an empty stack, 5 on a 0NNN machine code call, 1 for anything
+else.
+
+Logging (to stderr; RUST_LOG, e.g. chip8_emu::tui=debug, overrides these):
+  -v, --verbose   Log ROM loads, settings, save states, key
+                  presses and emulation speed
+  -q, --quiet     Don't log anything, not even errors
+
+Debugging:
+  --entry ADDR    Start running at ADDR instead of the load address,
+                  where the ROM is still loaded (e.g. 0x2A4)
+  --watch ADDR    Report writes to a memory address (e.g. 0x300)
+  --watch-reg VX  Report writes to a register (e.g. VA)
+                  The terminal pauses on each write; headless
+                  mode prints them
+  --halt-past-rom
+                  Stop once PC reaches memory the ROM didn't load
+                  and the program never wrote, instead of running
+                  the empty memory after it
+  --truncate-oversize
+                  Load what fits of a ROM too large for memory
+                  (with a warning) instead of refusing it
+  --trace         With --headless, print each instruction run
+                  ahead of the final state, e.g.
+                  PC=0x0246 OP=0x8124 V1=0A V2=03 I=0x0300 SP=2
+                  ADD V1, V2
+  --trace-file FILE
+                  Write the trace to FILE instead, headless or in
+                  the terminal
+                  A ROM that stops on an error is reported with
+                  the last 256 instructions it ran either way
+
+Terminal options:
+  --fg RRGGBB          Color of lit pixels, in hex (also in recordings
+                       and screenshots)
+  --bg RRGGBB          Color of the background
+  --palette BG,FG,PLANE2,BOTH
+                       All four colors: the background, lit pixels,
+                       and XO-CHIP pixels in the second plane and in
+                       both (default 000000,00CD00,CD0000,CDCD00)
+  --record-video PATH  Record the game to PATH (.gif, or else a
+                       directory of PPM frames); F10 toggles recording
+  --scale N            Pixel size of recordings (default 4)
+  --flicker-filter N   Show a pixel as lit if it was in any of the
+                       last N frames (1-3, default 1), so sprites
+                       redrawn every frame don't flicker
+  --watch-rom          Restart the game whenever the ROM file changes
+  --show-speed         Show instructions and frames per second in the
+                       window title
+  --split-keys         Also play 3 C / 6 D / 9 E / B F from 8 9 / I O /
+                       K L / , . and C D from Up and Down, so two
+                       players fit on one keyboard
+  --key-repeat-guard   Count each key press once: after the ROM sees a
+                       key down, it reads as up until pressed again,
+                       for ROMs that poll so fast one press does
+                       several things. Breaks games that expect held
+                       keys to keep acting
+  --sticky-keys        Held keys read as down for as long as they're
+                       held (the default; overrides the config file)
+  --autofire K=HZ      While keypad key K (0-F) is held, let it up and
+                       press it again HZ times a second (1-30); can
+                       be given for several keys
+  --flash-guard        Show big changes to the display over a few
+                       frames, so screens that flash don't strobe
+  --record-input FILE  Save every key press, and the random seed, to
+                       FILE
+  --replay FILE        Play the key presses saved with --record-input
+                       back instead of reading the keyboard
+  --host PORT          Wait for a second player to join on PORT; both
+                       players' keys go on the one keypad
+  --connect ADDR       Join a game hosted at ADDR (HOST:PORT), with
+                       the same ROM
+  --input-delay N      Frames a key press takes to reach both games
+                       when hosting, 2-4 (default 2)
+  --practice           Take a checkpoint whenever the game moves on to
+                       a new screen; F6 goes back to the latest, and
+                       again to the one before
+  --pacing MODE        sleep: wait for input until each frame is due
+                       (default); spin: stop waiting 2ms early and
+                       busy-wait the rest, so frames start on time at
+                       the cost of a CPU core
+  --unlimited          Run frames as fast as possible instead of 60 a
+                       second, and show the speed in the title (see
+                       --show-speed), for benchmarking";
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+    let asm_mode = args.next_if(|arg| arg == "asm").is_some();
+    let config_mode = !asm_mode && args.next_if(|arg| arg == "config").is_some();
+    let mut output = None;
+    let mut print_config = false;
+
+    let mut tui_mode = false;
+    let mut headless_mode = false;
+    let mut info_mode = false;
+    let mut json = false;
+    let mut cycles: u64 = 100_000;
+    let mut report = None;
+    let mut profile = false;
+    // What the command line sets, laid over the config file
+    let mut cli = Config::default();
+    let mut config_path = None;
+    let mut no_config = false;
+    let mut timer_hz = 60;
+    let mut trace = false;
+    let mut trace_file = None;
+    let mut load_address = None;
+    let mut entry = None;
+    let mut font_address = None;
+    let mut watches = Vec::new();
+    let mut romdir = None;
+    let mut record_video = None;
+    let mut watch_rom = false;
+    let mut pacing = Pacing::Sleep;
+    let mut latency_test = false;
+    let mut practice = false;
+    let mut control = None;
+    let mut input = replay::Options {
+        record: None,
+        replay: None,
+    };
+    let mut net_role = None;
+    let mut net_delay = netplay::MIN_DELAY;
+    // Errors only, unless asked for more or less
+    let mut log_level = LevelFilter::Error;
+    let mut rom = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--print" if config_mode => print_config = true,
+            "--config" => match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => usage_error("--config expects a path"),
+            },
+            "--no-config" => no_config = true,
+            "--tui" => tui_mode = true,
+            "--headless" => headless_mode = true,
+            "--info" => info_mode = true,
+            "--json" => json = true,
+            "-v" | "--verbose" => log_level = LevelFilter::Debug,
+            "-q" | "--quiet" => log_level = LevelFilter::Off,
+            "--cycles" => cycles = parse_value(&arg, args.next()),
+            "--profile" => profile = true,
+            "--trace" => trace = true,
+            "--trace-file" => match args.next() {
+                Some(path) => trace_file = Some(PathBuf::from(path)),
+                None => usage_error("--trace-file expects a path"),
+            },
+            "--report" => match args.next() {
+                Some(path) => report = Some(PathBuf::from(path)),
+                None => usage_error("--report expects a path"),
+            },
+            "--seed" => cli.seed = Some(parse_value(&arg, args.next())),
+            "--romdir" => match args.next() {
+                Some(dir) => romdir = Some(PathBuf::from(dir)),
+                None => usage_error("--romdir expects a directory"),
+            },
+            "--record-video" => match args.next() {
+                Some(path) => record_video = Some(PathBuf::from(path)),
+                None => usage_error("--record-video expects a path"),
+            },
+            "--watch-rom" => watch_rom = true,
+            "--practice" => practice = true,
+            "--pacing" => match args.next().as_deref() {
+                Some("sleep") => pacing = Pacing::Sleep,
+                Some("spin") => pacing = Pacing::Spin,
+                _ => usage_error("--pacing expects sleep or spin"),
+            },
+            "--unlimited" => pacing = Pacing::Unlimited,
+            // Not in the usage: for measuring pacing changes
+            "--latency-test" => latency_test = true,
+            "--show-speed" => cli.show_speed = Some(true),
+            "--split-keys" => cli.split_keys = Some(true),
+            "--key-repeat-guard" => cli.key_repeat_guard = Some(true),
+            "--sticky-keys" => cli.key_repeat_guard = Some(false),
+            "--halt-past-rom" => cli.halt_past_rom = Some(true),
+            "--truncate-oversize" => cli.truncate_oversize = Some(true),
+            "--hires" => cli.display_height = Some(64),
+            "--display-height" => match parse_value(&arg, args.next()) as usize {
+                rows if DISPLAY_HEIGHTS.contains(&rows) => cli.display_height = Some(rows),
+                _ => usage_error("--display-height must be 32, 48 or 64"),
+            },
+            "--autofire" => match args.next().as_deref().and_then(config::parse_autofire) {
+                Some(key) => cli.autofire.push(key),
+                None => usage_error("--autofire expects KEY=HZ, e.g. 5=10 (1-30 Hz)"),
+            },
+            "--flash-guard" => cli.flash_guard = Some(true),
+            "--fg" => match args.next().as_deref().and_then(config::parse_color) {
+                Some(color) => cli.fg = Some(color),
+                None => usage_error("--fg expects a color like 00CD00"),
+            },
+            "--bg" => match args.next().as_deref().and_then(config::parse_color) {
+                Some(color) => cli.bg = Some(color),
+                None => usage_error("--bg expects a color like 000000"),
+            },
+            "--palette" => match args.next().as_deref().and_then(parse_palette) {
+                Some([bg, fg, plane2, both]) => {
+                    (cli.bg, cli.fg) = (Some(bg), Some(fg));
+                    (cli.plane2, cli.both) = (Some(plane2), Some(both));
+                }
+                None => {
+                    usage_error("--palette expects four colors like 000000,00CD00,CD0000,CDCD00")
+                }
+            },
+            "--record-input" => match args.next() {
+                Some(path) => input.record = Some(PathBuf::from(path)),
+                None => usage_error("--record-input expects a path"),
+            },
+            "--replay" => match args.next() {
+                Some(path) => input.replay = Some(PathBuf::from(path)),
+                None => usage_error("--replay expects a path"),
+            },
+            "--host" => match parse_value(&arg, args.next()) {
+                port @ 1..=65535 => net_role = Some(netplay::Role::Host(port as u16)),
+                _ => usage_error("--host expects a port"),
+            },
+            "--connect" => match args.next() {
+                Some(addr) => net_role = Some(netplay::Role::Connect(addr)),
+                None => usage_error("--connect expects an address like 192.168.1.20:7777"),
+            },
+            "--input-delay" => match parse_value(&arg, args.next()) {
+                frames
+                    if (netplay::MIN_DELAY as u64..=netplay::MAX_DELAY as u64)
+                        .contains(&frames) =>
+                {
+                    net_delay = frames as u8
+                }
+                _ => usage_error("--input-delay must be between 2 and 4"),
+            },
+            "--control-pipe" => match args.next() {
+                Some(path) => control = Some(PathBuf::from(path)),
+                None => usage_error("--control-pipe expects a file, or - for stdin"),
+            },
+            "--scale" => match parse_value(&arg, args.next()) {
+                scale @ 1..=64 => cli.scale = Some(scale as u16),
+                _ => usage_error("--scale must be between 1 and 64"),
+            },
+            "--load-addr" => match args.next().as_deref().and_then(parse_addr) {
+                Some(addr) => load_address = Some(addr),
+                None => usage_error("--load-addr expects an address"),
+            },
+            "--entry" => match args.next().as_deref().and_then(parse_addr) {
+                Some(addr) => entry = Some(addr),
+                None => usage_error("--entry expects an address"),
+            },
+            "--watch" => match args.next().as_deref().and_then(parse_addr) {
+                Some(addr) => watches.push(Watch::Memory(addr)),
+                None => usage_error("--watch expects an address"),
+            },
+            "--watch-reg" => match args.next().as_deref().and_then(parse_register) {
+                Some(x) => watches.push(Watch::Register(x)),
+                None => usage_error("--watch-reg expects a register (V0-VF)"),
+            },
+            "--cpf" => match parse_value(&arg, args.next()) {
+                cpf @ 1..=1000 => cli.cpf = Some(cpf as usize),
+                _ => usage_error("--cpf must be between 1 and 1000"),
+            },
+            "--timer-hz" => match parse_value(&arg, args.next()) {
+                hz @ 1..=240 => timer_hz = hz as u32,
+                _ => usage_error("--timer-hz must be between 1 and 240"),
+            },
+            "--timing" => match args.next().as_deref() {
+                Some("simple") => cli.timing = Some(Timing::Simple),
+                Some("vip") => cli.timing = Some(Timing::Vip),
+                Some("schip") => cli.timing = Some(Timing::Schip),
+                _ => usage_error("--timing expects simple, vip or schip"),
+            },
+            "--flicker-filter" => match parse_value(&arg, args.next()) {
+                frames @ 1..=3 => cli.flicker_filter = Some(frames as usize),
+                _ => usage_error("--flicker-filter must be between 1 and 3"),
+            },
+            "--font-address" => match args.next().as_deref().and_then(parse_addr) {
+                Some(addr) => font_address = Some(addr),
+                None => usage_error("--font-address expects an address"),
+            },
+            "--font" => match args.next() {
+                Some(path) => cli.font = Some(PathBuf::from(path)),
+                None => usage_error("--font expects a path"),
+            },
+            "--no-romdb" => cli.romdb = Some(false),
+            "--no-detect" => cli.detect = Some(false),
+            "--schip" => cli.schip = Some(true),
+            "--xo-chip" => cli.xo_chip = Some(true),
+            "--quirk" => match args.next().as_deref().and_then(config::quirk_flag) {
+                Some((name, _)) => cli.quirks.push(name),
+                None => usage_error(&format!(
+                    "--quirk expects one of {}",
+                    QUIRK_FLAGS.map(|(name, _)| name).join(", ")
+                )),
+            },
+            flag if let Some((name, _)) = flag.strip_prefix("--").and_then(config::quirk_flag) => {
+                cli.quirks.push(name)
+            }
+            "-o" if asm_mode => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => usage_error("-o expects a path"),
+            },
+            _ if rom.is_none() && !arg.starts_with("--") && !config_mode => rom = Some(arg),
+            _ => usage_error(&format!("Unknown argument: {}", arg)),
+        }
+    }
+    if config_mode && !print_config {
+        usage_error("config expects --print");
+    }
+
+    // Other crates' logs are only wanted at error level
+    env_logger::Builder::new()
+        .filter_level(log_level.min(LevelFilter::Error))
+        .filter_module("chip8_emu", log_level)
+        .parse_default_env()
+        .init();
+
+    let path = match config_path {
+        Some(path) => Some(path),
+        None if no_config => None,
+        None => config::find(),
+    };
+    let file = match &path {
+        Some(path) => match config::load(path) {
+            Ok((file, warnings)) => {
+                info!("Using the config in {}", path.display());
+                if log_level != LevelFilter::Off {
+                    for warning in warnings {
+                        eprintln!("warning: {}", warning);
+                    }
+                }
+                file
+            }
+            Err(err) => {
+                error!("{}", err);
+                process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+    let config = file.merge(cli);
+
+    if print_config {
+        match &path {
+            Some(path) => println!("# From {} and the command line\n", path.display()),
+            None => println!("# No config file; defaults and the command line\n"),
+        }
+        print!("{}", config);
+        return;
+    }
+
+    if input.record.is_some() || input.replay.is_some() {
+        if !tui_mode || rom.is_none() {
+            usage_error("--record-input and --replay need --tui and a ROM");
+        }
+        if input.record.is_some() && input.replay.is_some() {
+            usage_error("--record-input and --replay can't be combined");
+        }
+    }
+    if net_role.is_some() {
+        if !tui_mode || rom.is_none() {
+            usage_error("--host and --connect need --tui and a ROM");
+        }
+        if input.record.is_some() || input.replay.is_some() {
+            usage_error("--host and --connect can't be combined with --record-input or --replay");
+        }
+        if practice {
+            usage_error("--practice can't be combined with --host or --connect");
+        }
+        if pacing == Pacing::Unlimited {
+            usage_error("--unlimited can't be combined with --host or --connect");
+        }
+    }
+    let trace = trace || trace_file.is_some();
+    if trace {
+        if asm_mode || info_mode || (control.is_some() && !headless_mode) {
+            usage_error("--trace and --trace-file need --headless or the terminal");
+        }
+        if !headless_mode && trace_file.is_none() {
+            usage_error("--trace needs --trace-file in the terminal, which is using stdout");
+        }
+    }
+    if let Some(addr) = load_address
+        && let Err(err) = check_load_address(addr, font_address)
+    {
+        usage_error(&err);
+    }
+    let font = match &config.font {
+        Some(path) => match read_font(path) {
+            Ok(font) => Some(font),
+            Err(err) => {
+                error!("{}", err);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let machine = romdb::Options {
+        quirks: config.quirk_flags(),
+        // xo_chip = false (or schip) leaves it to the ROM's profile
+        // and detection
+        variant: match (config.xo_chip, config.schip) {
+            (Some(true), _) => Some(Variant::XoChip),
+            (_, Some(true)) => Some(Variant::SuperChip),
+            _ => None,
+        },
+        cpf: config.cpf,
+        timing: config.timing.unwrap_or(Timing::Simple),
+        timer_hz,
+        load_address,
+        entry,
+        seed: config.seed,
+        flicker_filter: config.flicker_filter.unwrap_or(1),
+        font,
+        font_address,
+        watches,
+        romdb: config.romdb.unwrap_or(true),
+        detect: config.detect.unwrap_or(true),
+        key_repeat_guard: config.key_repeat_guard.unwrap_or(false),
+        halt_past_rom: config.halt_past_rom.unwrap_or(false),
+        truncate_oversize: config.truncate_oversize.unwrap_or(false),
+        trace,
+        display_height: config.display_height,
+    };
+    let trace = match trace.then(|| Trace::open(trace_file.as_deref())) {
+        Some(Ok(trace)) => Some(trace),
+        Some(Err(err)) => {
+            error!("{}", err);
+            process::exit(1);
+        }
+        None => None,
+    };
+    // Headless runs and scripts are repeatable unless told otherwise
+    let seed = machine.seed.unwrap_or(0);
+
+    let options = tui::Options {
+        video: video::Options {
+            path: record_video,
+            scale: config.scale.unwrap_or(4),
+        },
+        watch_rom,
+        // Uncapped runs are for measuring the speed
+        show_speed: config.show_speed.unwrap_or(false) || pacing == Pacing::Unlimited,
+        split_keys: config.split_keys.unwrap_or(false),
+        autofire: config.autofire,
+        flash_guard: config.flash_guard.unwrap_or(false),
+        fg: config.fg,
+        bg: config.bg,
+        plane2: config.plane2,
+        both: config.both,
+        input,
+        netplay: net_role.map(|role| netplay::Options {
+            role,
+            delay: net_delay,
+        }),
+        pacing,
+        latency_test,
+        practice,
+        trace,
+    };
+
+    let result = match rom {
+        Some(rom) if asm_mode => assemble_file(Path::new(&rom), output),
+        Some(rom) if info_mode => info::run(&rom, &machine, json),
+        Some(rom) if headless_mode => {
+            let trace = options.trace.as_ref();
+            headless::run(
+                &rom,
+                &machine,
+                cycles,
+                seed,
+                report.as_deref(),
+                profile,
+                trace,
+            )
+        }
+        Some(rom) if let Some(source) = &control => control::run(
+            &rom,
+            source,
+            &machine,
+            seed,
+            options.video.scale,
+            options.palette(),
+        ),
+        Some(rom) if tui_mode => tui::run(&rom, &machine, options),
+        Some(_) => usage_error("No frontend selected (open index.html for the web UI)"),
+        None if headless_mode || info_mode || asm_mode || control.is_some() => {
+            usage_error("Missing ROM path")
+        }
+        None => tui::browse(
+            &romdir.unwrap_or_else(|| PathBuf::from(".")),
+            &machine,
+            options,
+        ),
+    };
+
+    if let Err(err) = result {
+        error!("{}", err);
+        // What it ran up to a crash or the ROM stopping
+        if let Some(crash) = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<WithTrace>())
+        {
+            error!(
+                "Last instructions, oldest first:\n{}",
+                crash.trace.trim_end()
+            );
+        }
+        process::exit(exit_code(&err));
+    }
+}
+
+// 1 for anything that went wrong, or 3-5 for a ROM stopping on
+// an error, so scripts can tell them apart
+fn exit_code(err: &io::Error) -> i32 {
+    let err = err
+        .get_ref()
+        .map(|err| match err.downcast_ref::<WithTrace>() {
+            Some(crash) => &*crash.error,
+            None => err,
+        });
+    match err.and_then(|err| err.downcast_ref()) {
+        Some(Chip8Error::InvalidOpcode { .. }) => 3,
+        Some(Chip8Error::StackOverflow { .. } | Chip8Error::StackUnderflow { .. }) => 4,
+        Some(Chip8Error::MachineCodeCall { .. }) => 5,
+        None => 1,
+    }
+}
+
+// Four comma-separated colors
+fn parse_palette(s: &str) -> Option<[[u8; 3]; 4]> {
+    let colors: Vec<[u8; 3]> = s
+        .split(',')
+        .map(config::parse_color)
+        .collect::<Option<_>>()?;
+    colors.try_into().ok()
+}
+
+fn parse_value(flag: &str, value: Option<String>) -> u64 {
+    match value.map(|v| v.parse()) {
+        Some(Ok(n)) => n,
+        _ => usage_error(&format!("{} expects a number", flag)),
+    }
+}
+
+// fs::read, but the error says which file it was about. Loads
+// are logged with the hash romdb looks ROMs up by.
+fn read_rom(path: &Path) -> io::Result<Vec<u8>> {
+    let data = fs::read(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't read ROM {}: {}", path.display(), err),
+        )
+    })?;
+    let rom = rom_from_file(path, data)?;
+    info!(
+        "Loaded {} ({} bytes, SHA-256 {})",
+        path.display(),
+        rom.len(),
+        romdb::sha256(&rom)
+    );
+    Ok(rom)
+}
+
+// The ROM in a file's contents: Octo source (.8o) is assembled,
+// anything else is used as it is
+fn rom_from_file(path: &Path, data: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !is_octo_source(path) {
+        return Ok(data);
+    }
+    let source = String::from_utf8(data)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Source isn't UTF-8 text"))?;
+    assemble(&source).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Can't assemble {}: {}", path.display(), err),
+        )
+    })
+}
+
+// A --font file: the 16 small digits, optionally followed by
+// the 16 large ones
+fn read_font(path: &Path) -> io::Result<Vec<u8>> {
+    let font = fs::read(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't read font {}: {}", path.display(), err),
+        )
+    })?;
+    let sizes = [FONTSET.len(), FONTSET.len() + BIG_FONTSET.len()];
+    if !sizes.contains(&font.len()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Font {} must be {} bytes, or {} with the large digits, not {}",
+                path.display(),
+                sizes[0],
+                sizes[1],
+                font.len()
+            ),
+        ));
+    }
+    Ok(font)
+}
+
+fn is_octo_source(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("8o"))
+}
+
+// chip8-emu asm: writes the assembled ROM next to the source,
+// or to `output`
+fn assemble_file(source: &Path, output: Option<PathBuf>) -> io::Result<()> {
+    let rom = read_rom(source)?;
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+    fs::write(&output, &rom).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't write {}: {}", output.display(), err),
+        )
+    })?;
+    info!("Wrote {} ({} bytes)", output.display(), rom.len());
+    Ok(())
+}
+
+// Hex with a 0x prefix, otherwise decimal
+fn parse_addr(s: &str) -> Option<u16> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+// --load-addr has to leave room for a program below 0xE00, as
+// for Chip8::new_with_start(), and stay clear of the font,
+// wherever --font-address puts it
+fn check_load_address(addr: u16, font_address: Option<u16>) -> Result<(), String> {
+    Chip8::new_with_start(addr).map_err(|err| err.to_string())?;
+    let mut builder = Chip8::builder().start_address(addr);
+    if let Some(font) = font_address {
+        builder = builder.font_address(font);
+    }
+    builder.build().map(drop).map_err(|err| err.to_string())
+}
+
+// VA, va or just A
+fn parse_register(s: &str) -> Option<usize> {
+    let s = s.strip_prefix(['V', 'v']).unwrap_or(s);
+    match usize::from_str_radix(s, 16) {
+        Ok(x) if x < 16 && s.len() == 1 => Some(x),
+        _ => None,
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    process::exit(2);
+}
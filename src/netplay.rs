@@ -0,0 +1,585 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::savestate::RomHash;
+use log::info;
+
+// Two players on one keypad over TCP, in lockstep: a frame only
+// runs once both sides' keys for it are known, and both keypads
+// get both players' keys. Keys pressed now are sent for a frame
+// a little way ahead (the input delay), so on a LAN the other
+// side's keys are usually in before they're needed.
+//
+// Messages are a big-endian u16 length, a tag byte and the
+// fields, also big-endian:
+//
+//     1 hello      version u8, ROM SHA-256 [u8; 32], seed u64, delay u8
+//     2 input      frame u64, keys u16
+//     3 hash       frame u64, display hash u64
+//     4 heartbeat
+//
+// Each side sends hello first. The host's seed and delay are the
+// ones both use; a ROM that isn't the same on both ends is
+// refused. Every DIVERGENCE_CHECK frames each side sends the hash
+// of its display, and a mismatch ends the game: the two machines
+// are no longer playing the same game.
+const VERSION: u8 = 1;
+
+pub const MIN_DELAY: u8 = 2;
+pub const MAX_DELAY: u8 = 4;
+
+const DIVERGENCE_CHECK: u64 = 60;
+// Sent when nothing else has been for this long, so a quiet but
+// connected player (paused, say) isn't taken for a lost one
+const HEARTBEAT: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(10);
+// Longer than any message: hello is 43 bytes
+const MAX_MESSAGE: usize = 64;
+
+// Set from the command line (--host or --connect, --input-delay)
+pub struct Options {
+    pub role: Role,
+    pub delay: u8,
+}
+
+pub enum Role {
+    // Wait for the other player on this port
+    Host(u16),
+    // Join a host at this address, e.g. 192.168.1.20:7777
+    Connect(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Message {
+    Hello {
+        version: u8,
+        rom_hash: RomHash,
+        seed: u64,
+        delay: u8,
+    },
+    Input {
+        frame: u64,
+        keys: u16,
+    },
+    Hash {
+        frame: u64,
+        hash: u64,
+    },
+    Heartbeat,
+}
+
+impl Message {
+    // The message with its length in front
+    fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        match self {
+            Message::Hello {
+                version,
+                rom_hash,
+                seed,
+                delay,
+            } => {
+                body.extend([1, *version]);
+                body.extend(rom_hash);
+                body.extend(seed.to_be_bytes());
+                body.push(*delay);
+            }
+            Message::Input { frame, keys } => {
+                body.push(2);
+                body.extend(frame.to_be_bytes());
+                body.extend(keys.to_be_bytes());
+            }
+            Message::Hash { frame, hash } => {
+                body.push(3);
+                body.extend(frame.to_be_bytes());
+                body.extend(hash.to_be_bytes());
+            }
+            Message::Heartbeat => body.push(4),
+        }
+        let mut out = (body.len() as u16).to_be_bytes().to_vec();
+        out.extend(body);
+        out
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Message> {
+        let mut len = [0; 2];
+        reader.read_exact(&mut len)?;
+        let len = u16::from_be_bytes(len) as usize;
+        if len == 0 || len > MAX_MESSAGE {
+            return Err(invalid(format!("netplay message of {} bytes", len)));
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+
+        let u64_at = |at: usize| body[at..at + 8].try_into().map(u64::from_be_bytes);
+        let message = match (body[0], len) {
+            (1, 43) => Message::Hello {
+                version: body[1],
+                rom_hash: body[2..34].try_into().unwrap(),
+                seed: u64_at(34).unwrap(),
+                delay: body[42],
+            },
+            (2, 11) => Message::Input {
+                frame: u64_at(1).unwrap(),
+                keys: u16::from_be_bytes([body[9], body[10]]),
+            },
+            (3, 17) => Message::Hash {
+                frame: u64_at(1).unwrap(),
+                hash: u64_at(9).unwrap(),
+            },
+            (4, 1) => Message::Heartbeat,
+            (tag, _) => {
+                return Err(invalid(format!("netplay message {} of {} bytes", tag, len)));
+            }
+        };
+        Ok(message)
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// A game with the other player. The socket is read and written
+// on threads of their own, so the frame loop never blocks on the
+// network; it only stops running frames while it waits for keys.
+pub struct Session {
+    outgoing: Sender<Message>,
+    incoming: Receiver<io::Result<Message>>,
+    seed: u64,
+    delay: u64,
+    // The keypad here, as the keyboard has it now
+    keys: u16,
+    // The next frame to send this side's keys for
+    next_send: u64,
+    // Keys for frames that haven't run yet, from each side
+    local: HashMap<u64, u16>,
+    remote: HashMap<u64, u16>,
+    // Display hashes for frames the other side hasn't matched yet
+    local_hashes: HashMap<u64, u64>,
+    remote_hashes: HashMap<u64, u64>,
+    last_sent: Instant,
+    last_heard: Instant,
+}
+
+impl Session {
+    // Waits for one player to connect on `port`. They play with
+    // this side's seed and input delay.
+    pub fn host(port: u16, rom_hash: RomHash, seed: u64, delay: u8) -> io::Result<Session> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        Self::accept(listener, rom_hash, seed, delay)
+    }
+
+    fn accept(
+        listener: TcpListener,
+        rom_hash: RomHash,
+        seed: u64,
+        delay: u8,
+    ) -> io::Result<Session> {
+        let (stream, peer) = listener.accept()?;
+        info!("Netplay: {} connected", peer);
+        let hello = Message::Hello {
+            version: VERSION,
+            rom_hash,
+            seed,
+            delay,
+        };
+        let (stream, _) = handshake(stream, hello)?;
+        Self::start(stream, seed, delay)
+    }
+
+    // Joins the game hosted at `addr`, taking its seed and input
+    // delay
+    pub fn connect(addr: &str, rom_hash: RomHash) -> io::Result<Session> {
+        let stream = TcpStream::connect(addr)
+            .map_err(|err| io::Error::new(err.kind(), format!("Can't reach {}: {}", addr, err)))?;
+        let hello = Message::Hello {
+            version: VERSION,
+            rom_hash,
+            seed: 0,
+            delay: 0,
+        };
+        let (stream, (seed, delay)) = handshake(stream, hello)?;
+        Self::start(stream, seed, delay)
+    }
+
+    fn start(stream: TcpStream, seed: u64, delay: u8) -> io::Result<Session> {
+        let (outgoing, to_send) = mpsc::channel::<Message>();
+        let (received, incoming) = mpsc::channel();
+
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for message in to_send {
+                if writer.write_all(&message.encode()).is_err() {
+                    break;
+                }
+            }
+            // The other side sees the game end
+            let _ = writer.shutdown(Shutdown::Both);
+        });
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stream);
+            loop {
+                let message = Message::read(&mut reader);
+                let failed = message.is_err();
+                if received.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        let delay = delay as u64;
+        Ok(Session {
+            outgoing,
+            incoming,
+            seed,
+            delay,
+            keys: 0,
+            next_send: delay,
+            local: HashMap::new(),
+            remote: HashMap::new(),
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+            last_sent: Instant::now(),
+            last_heard: Instant::now(),
+        })
+    }
+
+    // The CXNN seed both machines use
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // A keypad key going down or up on this side. It reaches both
+    // machines `delay` frames from now.
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if key < 16 {
+            self.keys = match pressed {
+                true => self.keys | 1 << key,
+                false => self.keys & !(1 << key),
+            };
+        }
+    }
+
+    // Both players' keys for `frame`, or None while the other
+    // side's aren't in yet. This side's keys for `frame` plus the
+    // delay are sent first. The first `delay` frames have no keys.
+    pub fn keys_for(&mut self, frame: u64) -> io::Result<Option<u16>> {
+        while self.next_send <= frame + self.delay {
+            self.local.insert(self.next_send, self.keys);
+            self.send(Message::Input {
+                frame: self.next_send,
+                keys: self.keys,
+            });
+            self.next_send += 1;
+        }
+        self.poll()?;
+
+        if frame < self.delay {
+            return Ok(Some(0));
+        }
+        match self.remote.remove(&frame) {
+            Some(remote) => Ok(Some(self.local.remove(&frame).unwrap_or(0) | remote)),
+            None => Ok(None),
+        }
+    }
+
+    // Called once `frame` has run, with the hash of the display
+    // it left. Every DIVERGENCE_CHECK frames it's compared with
+    // the other side's.
+    pub fn frame_done(&mut self, frame: u64, display_hash: u64) -> io::Result<()> {
+        if !(frame + 1).is_multiple_of(DIVERGENCE_CHECK) {
+            return Ok(());
+        }
+        self.send(Message::Hash {
+            frame,
+            hash: display_hash,
+        });
+        self.local_hashes.insert(frame, display_hash);
+        self.check_hashes()
+    }
+
+    // Takes in what the other side has sent, and keeps the
+    // connection alive. Fails once the other side is gone or out
+    // of sync.
+    pub fn poll(&mut self) -> io::Result<()> {
+        loop {
+            let message = match self.incoming.try_recv() {
+                Ok(Ok(message)) => message,
+                Ok(Err(err)) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "The other player left",
+                    ));
+                }
+                Ok(Err(err)) => {
+                    return Err(io::Error::new(
+                        err.kind(),
+                        format!("Lost the other player: {}", err),
+                    ));
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "The other player left",
+                    ));
+                }
+            };
+            self.last_heard = Instant::now();
+            match message {
+                Message::Input { frame, keys } => {
+                    self.remote.insert(frame, keys);
+                }
+                Message::Hash { frame, hash } => {
+                    self.remote_hashes.insert(frame, hash);
+                    self.check_hashes()?;
+                }
+                Message::Heartbeat => {}
+                Message::Hello { .. } => return Err(invalid("netplay hello mid-game".into())),
+            }
+        }
+
+        if self.last_heard.elapsed() >= TIMEOUT {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Lost the other player: nothing heard for 10 seconds",
+            ));
+        }
+        if self.last_sent.elapsed() >= HEARTBEAT {
+            self.send(Message::Heartbeat);
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: Message) {
+        // A send only fails once the writer has stopped, and the
+        // reader reports why
+        let _ = self.outgoing.send(message);
+        self.last_sent = Instant::now();
+    }
+
+    fn check_hashes(&mut self) -> io::Result<()> {
+        let matched: Vec<u64> = self
+            .remote_hashes
+            .keys()
+            .filter(|frame| self.local_hashes.contains_key(frame))
+            .copied()
+            .collect();
+        for frame in matched {
+            let local = self.local_hashes.remove(&frame);
+            if self.remote_hashes.remove(&frame) != local {
+                return Err(invalid(format!(
+                    "Out of sync with the other player at frame {}",
+                    frame
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Swaps hellos, checking the other side is playing the same ROM
+// with the same protocol. Returns the host's seed and delay.
+fn handshake(mut stream: TcpStream, hello: Message) -> io::Result<(TcpStream, (u64, u8))> {
+    stream.set_nodelay(true)?;
+    stream.write_all(&hello.encode())?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let theirs = Message::read(&mut stream)?;
+    stream.set_read_timeout(None)?;
+
+    let (
+        Message::Hello {
+            rom_hash: ours,
+            seed,
+            delay,
+            ..
+        },
+        Message::Hello {
+            version,
+            rom_hash,
+            seed: their_seed,
+            delay: their_delay,
+        },
+    ) = (hello, theirs)
+    else {
+        return Err(invalid("netplay game didn't start with hello".into()));
+    };
+    if version != VERSION {
+        return Err(invalid(format!(
+            "The other player has netplay version {}, this is version {}",
+            version, VERSION
+        )));
+    }
+    if rom_hash != ours {
+        return Err(invalid("The other player has a different ROM".into()));
+    }
+    // Only the host's hello has a delay in it
+    Ok(match delay {
+        0 => (stream, (their_seed, their_delay)),
+        _ => (stream, (seed, delay)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_emu::{Chip8, assemble};
+
+    fn pair(host_rom: RomHash, guest_rom: RomHash) -> (io::Result<Session>, io::Result<Session>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let host = thread::spawn(move || Session::accept(listener, host_rom, 1234, 3));
+        let guest = Session::connect(&addr, guest_rom);
+        (host.join().unwrap(), guest)
+    }
+
+    fn machine(rom: &[u8], seed: u64) -> Chip8 {
+        let mut chip8 = Chip8::builder().seed(seed).build().unwrap();
+        chip8.load_rom_bytes(rom).unwrap();
+        chip8
+    }
+
+    // Runs a frame on both machines once both sides have its keys
+    fn lockstep(
+        sessions: &mut [Session; 2],
+        machines: &mut [Chip8; 2],
+        frame: u64,
+    ) -> io::Result<()> {
+        let mut keys = [None, None];
+        let start = Instant::now();
+        while keys.contains(&None) {
+            for side in 0..2 {
+                if keys[side].is_none() {
+                    keys[side] = sessions[side].keys_for(frame)?;
+                }
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "stuck at {}",
+                frame
+            );
+            thread::sleep(Duration::from_micros(100));
+        }
+        assert_eq!(keys[0], keys[1], "keys for frame {}", frame);
+        for side in 0..2 {
+            machines[side].set_keys(keys[side].unwrap());
+            machines[side].step_frame(1000).unwrap();
+            sessions[side].frame_done(frame, machines[side].display_hash())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Message::Hello {
+                version: VERSION,
+                rom_hash: [7; 32],
+                seed: u64::MAX - 1,
+                delay: 3,
+            },
+            Message::Input {
+                frame: 1 << 40,
+                keys: 0x8421,
+            },
+            Message::Hash {
+                frame: 59,
+                hash: 0x0123_4567_89AB_CDEF,
+            },
+            Message::Heartbeat,
+        ];
+        for message in messages {
+            let bytes = message.encode();
+            assert_eq!(Message::read(&mut &bytes[..]).unwrap(), message);
+        }
+        // Too short for an input, and an unknown tag
+        assert!(Message::read(&mut &[0, 2, 2, 0][..]).is_err());
+        assert!(Message::read(&mut &[0, 1, 9][..]).is_err());
+    }
+
+    #[test]
+    fn players_stay_in_step() {
+        // Draws a random sprite wherever the keys held say, so
+        // both the RNG and the keys show up on the display
+        let rom = assemble(
+            "i := 0x300 loop v0 := random 0xFF save v0 \
+             v1 := 0 v2 := 0 \
+             if v1 key then v1 := 10 \
+             v3 := 2 if v3 key then v2 := 9 \
+             v4 := random 0x3F v5 := random 0x1F \
+             v4 += v1 v5 += v2 sprite v4 v5 1 again",
+        )
+        .unwrap();
+        let hash = crate::savestate::rom_hash(&rom);
+        let (host, guest) = pair(hash, hash);
+        let mut sessions = [host.unwrap(), guest.unwrap()];
+        assert_eq!(sessions[1].seed(), 1234);
+        assert_eq!(sessions[1].delay, 3);
+        let mut machines = [machine(&rom, 1234), machine(&rom, sessions[1].seed())];
+
+        for frame in 0..600 {
+            // Each player holds one key, on and off
+            sessions[0].set_key(0, frame % 40 < 15);
+            sessions[1].set_key(2, frame % 25 < 5);
+            lockstep(&mut sessions, &mut machines, frame).unwrap();
+        }
+        assert_eq!(machines[0].display_hash(), machines[1].display_hash());
+        // Every display hash sent was matched, the last one too
+        let start = Instant::now();
+        while sessions.iter().any(|s| !s.local_hashes.is_empty()) {
+            sessions.iter_mut().for_each(|s| s.poll().unwrap());
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_micros(100));
+        }
+    }
+
+    #[test]
+    fn a_different_display_ends_the_game() {
+        let rom = assemble("loop v0 := random 0xFF i := hex v0 sprite v0 v0 5 again").unwrap();
+        let hash = crate::savestate::rom_hash(&rom);
+        let (host, guest) = pair(hash, hash);
+        let mut sessions = [host.unwrap(), guest.unwrap()];
+        // Different seeds draw different sprites
+        let mut machines = [machine(&rom, 1), machine(&rom, 2)];
+
+        let result = (0..120).try_for_each(|frame| lockstep(&mut sessions, &mut machines, frame));
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "Out of sync with the other player at frame 59"
+        );
+    }
+
+    #[test]
+    fn different_roms_are_refused() {
+        let (host, guest) = pair([1; 32], [2; 32]);
+        for result in [host, guest] {
+            let err = result.err().expect("different ROMs");
+            assert_eq!(err.to_string(), "The other player has a different ROM");
+        }
+    }
+
+    #[test]
+    fn a_player_leaving_ends_the_game() {
+        let (host, guest) = pair([1; 32], [1; 32]);
+        let mut host = host.unwrap();
+        drop(guest);
+        let start = Instant::now();
+        let err = loop {
+            if let Err(err) = host.keys_for(10) {
+                break err;
+            }
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        };
+        assert_eq!(err.to_string(), "The other player left");
+    }
+}
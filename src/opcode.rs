@@ -0,0 +1,328 @@
+use std::fmt;
+
+// A decoded chip8 instruction. X and Y are register indices,
+// so they're stored as usize for indexing into V.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Sys(u16),               // 0NNN
+    ScrollDown(u8),         // 00CN (SUPER-CHIP, XO-CHIP)
+    ScrollUp(u8),           // 00DN (XO-CHIP)
+    Cls,                    // 00E0
+    Ret,                    // 00EE
+    ScrollRight,            // 00FB (SUPER-CHIP, XO-CHIP)
+    ScrollLeft,             // 00FC (SUPER-CHIP, XO-CHIP)
+    Lores,                  // 00FE (SUPER-CHIP, XO-CHIP)
+    Hires,                  // 00FF (SUPER-CHIP, XO-CHIP)
+    Jump(u16),              // 1NNN
+    Call(u16),              // 2NNN
+    SeVxByte(usize, u8),    // 3XNN
+    SneVxByte(usize, u8),   // 4XNN
+    SeVxVy(usize, usize),   // 5XY0
+    LdVxByte(usize, u8),    // 6XNN
+    AddVxByte(usize, u8),   // 7XNN
+    LdVxVy(usize, usize),   // 8XY0
+    OrVxVy(usize, usize),   // 8XY1
+    AndVxVy(usize, usize),  // 8XY2
+    XorVxVy(usize, usize),  // 8XY3
+    AddVxVy(usize, usize),  // 8XY4
+    SubVxVy(usize, usize),  // 8XY5
+    ShrVx(usize, usize),    // 8XY6
+    SubnVxVy(usize, usize), // 8XY7
+    ShlVx(usize, usize),    // 8XYE
+    SneVxVy(usize, usize),  // 9XY0
+    LdI(u16),               // ANNN
+    JumpV0(u16),            // BNNN
+    Rnd(usize, u8),         // CXNN
+    Drw(usize, usize, u8),  // DXYN
+    Skp(usize),             // EX9E
+    Sknp(usize),            // EXA1
+    LdVxDt(usize),          // FX07
+    LdVxK(usize),           // FX0A
+    LdDtVx(usize),          // FX15
+    LdStVx(usize),          // FX18
+    AddIVx(usize),          // FX1E
+    LdFVx(usize),           // FX29
+    LdHfVx(usize),          // FX30 (SUPER-CHIP, XO-CHIP)
+    LdBVx(usize),           // FX33
+    LdIVx(usize),           // FX55
+    LdVxI(usize),           // FX65
+    LdILong,                // F000 NNNN (XO-CHIP)
+    Plane(u8),              // FN01 (XO-CHIP)
+    Audio,                  // F002 (XO-CHIP)
+    Pitch(usize),           // FX3A (XO-CHIP)
+    Invalid(u16),
+}
+
+// Splits the raw opcode into its parts and matches
+// it against the known instructions.
+#[allow(non_snake_case)]
+pub fn decode(opcode: u16) -> Opcode {
+    // The X and Y from the opcode is always at the second
+    // and third nibble of the opcode.
+    let X = ((opcode & 0x0F00) >> 8) as usize;
+    let Y = ((opcode & 0x00F0) >> 4) as usize;
+    let N = (opcode & 0x000F) as u8;
+    let NN = (opcode & 0x00FF) as u8;
+    let NNN = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00C0..=0x00CF => Opcode::ScrollDown(N),
+            0x00D0..=0x00DF => Opcode::ScrollUp(N),
+            0x00E0 => Opcode::Cls,
+            0x00EE => Opcode::Ret,
+            0x00FB => Opcode::ScrollRight,
+            0x00FC => Opcode::ScrollLeft,
+            0x00FE => Opcode::Lores,
+            0x00FF => Opcode::Hires,
+            _ => Opcode::Sys(NNN),
+        },
+        0x1000 => Opcode::Jump(NNN),
+        0x2000 => Opcode::Call(NNN),
+        0x3000 => Opcode::SeVxByte(X, NN),
+        0x4000 => Opcode::SneVxByte(X, NN),
+        0x5000 => Opcode::SeVxVy(X, Y),
+        0x6000 => Opcode::LdVxByte(X, NN),
+        0x7000 => Opcode::AddVxByte(X, NN),
+        0x8000 => match N {
+            0x0 => Opcode::LdVxVy(X, Y),
+            0x1 => Opcode::OrVxVy(X, Y),
+            0x2 => Opcode::AndVxVy(X, Y),
+            0x3 => Opcode::XorVxVy(X, Y),
+            0x4 => Opcode::AddVxVy(X, Y),
+            0x5 => Opcode::SubVxVy(X, Y),
+            0x6 => Opcode::ShrVx(X, Y),
+            0x7 => Opcode::SubnVxVy(X, Y),
+            0xE => Opcode::ShlVx(X, Y),
+            _ => Opcode::Invalid(opcode),
+        },
+        0x9000 => Opcode::SneVxVy(X, Y),
+        0xA000 => Opcode::LdI(NNN),
+        0xB000 => Opcode::JumpV0(NNN),
+        0xC000 => Opcode::Rnd(X, NN),
+        0xD000 => Opcode::Drw(X, Y, N),
+        0xE000 => match NN {
+            0x9E => Opcode::Skp(X),
+            0xA1 => Opcode::Sknp(X),
+            _ => Opcode::Invalid(opcode),
+        },
+        0xF000 => match NN {
+            0x00 if X == 0 => Opcode::LdILong,
+            0x01 => Opcode::Plane(X as u8),
+            0x02 if X == 0 => Opcode::Audio,
+            0x07 => Opcode::LdVxDt(X),
+            0x0A => Opcode::LdVxK(X),
+            0x15 => Opcode::LdDtVx(X),
+            0x18 => Opcode::LdStVx(X),
+            0x1E => Opcode::AddIVx(X),
+            0x29 => Opcode::LdFVx(X),
+            0x30 => Opcode::LdHfVx(X),
+            0x33 => Opcode::LdBVx(X),
+            0x3A => Opcode::Pitch(X),
+            0x55 => Opcode::LdIVx(X),
+            0x65 => Opcode::LdVxI(X),
+            _ => Opcode::Invalid(opcode),
+        },
+        _ => Opcode::Invalid(opcode),
+    }
+}
+
+impl Opcode {
+    // The instruction's form, with X, Y and N for its operands
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Opcode::Sys(_) => "0NNN",
+            Opcode::ScrollDown(_) => "00CN",
+            Opcode::ScrollUp(_) => "00DN",
+            Opcode::Cls => "00E0",
+            Opcode::Ret => "00EE",
+            Opcode::ScrollRight => "00FB",
+            Opcode::ScrollLeft => "00FC",
+            Opcode::Lores => "00FE",
+            Opcode::Hires => "00FF",
+            Opcode::Jump(_) => "1NNN",
+            Opcode::Call(_) => "2NNN",
+            Opcode::SeVxByte(..) => "3XNN",
+            Opcode::SneVxByte(..) => "4XNN",
+            Opcode::SeVxVy(..) => "5XY0",
+            Opcode::LdVxByte(..) => "6XNN",
+            Opcode::AddVxByte(..) => "7XNN",
+            Opcode::LdVxVy(..) => "8XY0",
+            Opcode::OrVxVy(..) => "8XY1",
+            Opcode::AndVxVy(..) => "8XY2",
+            Opcode::XorVxVy(..) => "8XY3",
+            Opcode::AddVxVy(..) => "8XY4",
+            Opcode::SubVxVy(..) => "8XY5",
+            Opcode::ShrVx(..) => "8XY6",
+            Opcode::SubnVxVy(..) => "8XY7",
+            Opcode::ShlVx(..) => "8XYE",
+            Opcode::SneVxVy(..) => "9XY0",
+            Opcode::LdI(_) => "ANNN",
+            Opcode::JumpV0(_) => "BNNN",
+            Opcode::Rnd(..) => "CXNN",
+            Opcode::Drw(..) => "DXYN",
+            Opcode::Skp(_) => "EX9E",
+            Opcode::Sknp(_) => "EXA1",
+            Opcode::LdVxDt(_) => "FX07",
+            Opcode::LdVxK(_) => "FX0A",
+            Opcode::LdDtVx(_) => "FX15",
+            Opcode::LdStVx(_) => "FX18",
+            Opcode::AddIVx(_) => "FX1E",
+            Opcode::LdFVx(_) => "FX29",
+            Opcode::LdHfVx(_) => "FX30",
+            Opcode::LdBVx(_) => "FX33",
+            Opcode::LdIVx(_) => "FX55",
+            Opcode::LdVxI(_) => "FX65",
+            Opcode::LdILong => "F000",
+            Opcode::Plane(_) => "FN01",
+            Opcode::Audio => "F002",
+            Opcode::Pitch(_) => "FX3A",
+            Opcode::Invalid(_) => "????",
+        }
+    }
+}
+
+// The opcode with its operands cleared, e.g. 0x8004 for every
+// 8XY4, which is how Chip8::opcode_stats() counts instructions.
+// Opcodes that aren't instructions are their own class.
+pub fn opcode_class(opcode: u16) -> u16 {
+    let op = decode(opcode);
+    if let Opcode::Invalid(_) = op {
+        return opcode;
+    }
+    op.pattern()
+        .chars()
+        .zip([12, 8, 4, 0])
+        .filter(|(c, _)| c.is_ascii_hexdigit())
+        .fold(0, |class, (_, shift)| class | opcode & 0xF << shift)
+}
+
+// Roughly what an instruction took on the COSMAC VIP, in machine
+// cycles (8 clocks each), including the interpreter's fetch and
+// decode. These are approximate: the real cost also depended on
+// operands, skips being taken and page crossings. Extension
+// opcodes never ran on a VIP and get the cost of a simple one.
+pub fn cycles_for(op: Opcode) -> u32 {
+    const FETCH: u32 = 40;
+    FETCH
+        + match op {
+            Opcode::Sys(_) => 10,
+            Opcode::Cls => 3078,
+            Opcode::Ret => 10,
+            Opcode::Jump(_) => 12,
+            Opcode::Call(_) => 26,
+            Opcode::SeVxByte(..) | Opcode::SneVxByte(..) => 10,
+            Opcode::SeVxVy(..) | Opcode::SneVxVy(..) => 14,
+            Opcode::LdVxByte(..) => 6,
+            Opcode::AddVxByte(..) => 10,
+            Opcode::LdVxVy(..)
+            | Opcode::OrVxVy(..)
+            | Opcode::AndVxVy(..)
+            | Opcode::XorVxVy(..)
+            | Opcode::AddVxVy(..)
+            | Opcode::SubVxVy(..)
+            | Opcode::ShrVx(..)
+            | Opcode::SubnVxVy(..)
+            | Opcode::ShlVx(..) => 44,
+            Opcode::LdI(_) => 12,
+            Opcode::JumpV0(_) => 22,
+            Opcode::Rnd(..) => 36,
+            // Shifting each row into place dominates
+            Opcode::Drw(_, _, n) => 26 + 136 * n.max(1) as u32,
+            Opcode::Skp(_) | Opcode::Sknp(_) => 14,
+            Opcode::LdVxDt(_) | Opcode::LdDtVx(_) | Opcode::LdStVx(_) => 10,
+            Opcode::LdVxK(_) => 18,
+            Opcode::AddIVx(_) | Opcode::LdFVx(_) | Opcode::LdHfVx(_) => 16,
+            // Repeated subtraction of 100s and 10s
+            Opcode::LdBVx(_) => 364,
+            Opcode::LdIVx(x) | Opcode::LdVxI(x) => 14 + 28 * (x as u32 + 1),
+            Opcode::LdILong
+            | Opcode::Plane(_)
+            | Opcode::Audio
+            | Opcode::Pitch(_)
+            | Opcode::ScrollDown(_)
+            | Opcode::ScrollUp(_)
+            | Opcode::ScrollRight
+            | Opcode::ScrollLeft
+            | Opcode::Lores
+            | Opcode::Hires => 12,
+            Opcode::Invalid(_) => 0,
+        }
+}
+
+// Roughly what an instruction took on SUPER-CHIP (HP48), in units
+// of a simple instruction, so a frame's budget is still CPF. The
+// interpreter's own overhead swamped most instructions, but
+// drawing, scrolling and clearing touched the whole display (or
+// many rows of it) and took several times as long.
+pub fn schip_cycles_for(op: Opcode) -> u32 {
+    match op {
+        // Switching resolution clears the display too
+        Opcode::Cls | Opcode::Lores | Opcode::Hires => 4,
+        // A 16x16 sprite is two bytes a row for 16 rows
+        Opcode::Drw(_, _, 0) => 9,
+        Opcode::Drw(_, _, n) => 1 + (n as u32).div_ceil(4),
+        Opcode::ScrollDown(_) | Opcode::ScrollUp(_) | Opcode::ScrollRight | Opcode::ScrollLeft => 6,
+        Opcode::LdBVx(_) => 2,
+        Opcode::LdIVx(x) | Opcode::LdVxI(x) => 1 + (x as u32 + 1) / 8,
+        Opcode::Invalid(_) => 0,
+        _ => 1,
+    }
+}
+
+// Disassembly, using the common Cowgod-style mnemonics
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Opcode::Sys(nnn) => write!(f, "SYS {:#05X}", nnn),
+            Opcode::ScrollDown(n) => write!(f, "SCD {}", n),
+            Opcode::ScrollUp(n) => write!(f, "SCU {}", n),
+            Opcode::Cls => write!(f, "CLS"),
+            Opcode::Ret => write!(f, "RET"),
+            Opcode::ScrollRight => write!(f, "SCR"),
+            Opcode::ScrollLeft => write!(f, "SCL"),
+            Opcode::Lores => write!(f, "LOW"),
+            Opcode::Hires => write!(f, "HIGH"),
+            Opcode::Jump(nnn) => write!(f, "JP {:#05X}", nnn),
+            Opcode::Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Opcode::SeVxByte(x, nn) => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Opcode::SneVxByte(x, nn) => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Opcode::SeVxVy(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Opcode::LdVxByte(x, nn) => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Opcode::AddVxByte(x, nn) => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Opcode::LdVxVy(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Opcode::OrVxVy(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Opcode::AndVxVy(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Opcode::XorVxVy(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Opcode::AddVxVy(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Opcode::SubVxVy(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Opcode::ShrVx(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Opcode::SubnVxVy(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Opcode::ShlVx(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Opcode::SneVxVy(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Opcode::LdI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Opcode::JumpV0(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Opcode::Rnd(x, nn) => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Opcode::Drw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Opcode::Skp(x) => write!(f, "SKP V{:X}", x),
+            Opcode::Sknp(x) => write!(f, "SKNP V{:X}", x),
+            Opcode::LdVxDt(x) => write!(f, "LD V{:X}, DT", x),
+            Opcode::LdVxK(x) => write!(f, "LD V{:X}, K", x),
+            Opcode::LdDtVx(x) => write!(f, "LD DT, V{:X}", x),
+            Opcode::LdStVx(x) => write!(f, "LD ST, V{:X}", x),
+            Opcode::AddIVx(x) => write!(f, "ADD I, V{:X}", x),
+            Opcode::LdFVx(x) => write!(f, "LD F, V{:X}", x),
+            Opcode::LdHfVx(x) => write!(f, "LD HF, V{:X}", x),
+            Opcode::LdBVx(x) => write!(f, "LD B, V{:X}", x),
+            Opcode::LdIVx(x) => write!(f, "LD [I], V{:X}", x),
+            Opcode::LdVxI(x) => write!(f, "LD V{:X}, [I]", x),
+            // The address is in the next word, which decode()
+            // doesn't see
+            Opcode::LdILong => write!(f, "LD I, LONG"),
+            Opcode::Plane(n) => write!(f, "PLANE {}", n),
+            Opcode::Audio => write!(f, "AUDIO"),
+            Opcode::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Opcode::Invalid(op) => write!(f, "DW {:#06X}", op),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    hint,
+    time::{Duration, Instant},
+};
+
+// When a native frontend's frames run (the terminal's, and
+// run_emulator()'s). Time is measured out in whole 60Hz frames,
+// and each time round the loop runs as many as have come due,
+// then draws once; a frontend that's slow to draw gets fewer
+// redraws rather than a slower game, and deadlines don't drift.
+// Between frames the loop waits for input with a timeout, which
+// the OS can overrun by a millisecond or more; with Pacing::Spin
+// the wait stops short of the deadline and the rest is spent
+// spinning, so frames start on time at the cost of some CPU.
+//
+// The deadline math takes the time as an argument, so the tests
+// don't depend on the wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pacing {
+    // Wait for input right up to the deadline (--pacing sleep)
+    #[default]
+    Sleep,
+    // Wait until SPIN_MARGIN before it, then spin (--pacing spin)
+    Spin,
+    // Don't wait at all (--unlimited): frames run back to back,
+    // in batches sized so the display is still drawn about once
+    // every frame's worth of real time
+    Unlimited,
+}
+
+pub const SPIN_MARGIN: Duration = Duration::from_millis(2);
+// At most this many frames run at once. Time beyond that (a
+// long stall) is dropped rather than caught up on, so it doesn't
+// spiral.
+const MAX_BEHIND: u32 = 6;
+// The most frames Pacing::Unlimited runs between redraws
+const MAX_BATCH: u32 = 10_000;
+
+pub struct FramePacer {
+    pacing: Pacing,
+    frame: Duration,
+    // When the next frame is due, or with Pacing::Unlimited, when
+    // the last batch was taken
+    next: Instant,
+    // Frames in the next batch, with Pacing::Unlimited
+    batch: u32,
+}
+
+impl FramePacer {
+    // The first frame is due at `now`
+    pub fn new(pacing: Pacing, frame: Duration, now: Instant) -> Self {
+        FramePacer {
+            pacing,
+            frame,
+            next: now,
+            batch: 1,
+        }
+    }
+
+    // How long to wait for input from `now`
+    pub fn timeout(&self, now: Instant) -> Duration {
+        let left = self.next.saturating_duration_since(now);
+        match self.pacing {
+            Pacing::Sleep => left,
+            Pacing::Spin => left.saturating_sub(SPIN_MARGIN),
+            Pacing::Unlimited => Duration::ZERO,
+        }
+    }
+
+    // Whether the frame is due at `now`
+    pub fn due(&self, now: Instant) -> bool {
+        self.pacing == Pacing::Unlimited || now >= self.next
+    }
+
+    // Once the wait for input has timed out: spins out the rest
+    // of the margin with Pacing::Spin
+    pub fn finish_wait(&self) {
+        if self.pacing == Pacing::Spin {
+            while !self.due(Instant::now()) {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    // How many frames have come due by `now`, which are taken:
+    // the next one is due a frame after the last of them
+    pub fn take_due(&mut self, now: Instant) -> u32 {
+        if self.pacing == Pacing::Unlimited {
+            return self.take_batch(now);
+        }
+        if now < self.next {
+            return 0;
+        }
+        let behind = (now - self.next).as_nanos() / self.frame.as_nanos();
+        if behind >= MAX_BEHIND as u128 {
+            self.next = now + self.frame;
+            return MAX_BEHIND;
+        }
+        let due = behind as u32 + 1;
+        self.next += self.frame * due;
+        due
+    }
+
+    // The next batch for Pacing::Unlimited. How long the last
+    // time round the loop took sizes it, halfway towards a frame's
+    // worth of real time so one slow redraw doesn't swing it.
+    fn take_batch(&mut self, now: Instant) -> u32 {
+        let took = (now - self.next).as_nanos().max(1);
+        let fits = self.batch as u128 * self.frame.as_nanos() / took;
+        let batch = (self.batch as u128 + fits) / 2;
+        self.batch = batch.clamp(1, MAX_BATCH as u128) as u32;
+        self.next = now;
+        self.batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_micros(16_667);
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn sleep_waits_until_the_deadline() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Sleep, FRAME, start);
+        assert!(pacer.due(start));
+        assert_eq!(pacer.timeout(start), Duration::ZERO);
+
+        assert_eq!(pacer.take_due(start), 1);
+        assert!(!pacer.due(start + ms(16)));
+        assert_eq!(pacer.timeout(start + ms(10)), FRAME - ms(10));
+        assert!(pacer.due(start + FRAME));
+        // Already late
+        assert_eq!(pacer.timeout(start + ms(20)), Duration::ZERO);
+    }
+
+    #[test]
+    fn spin_stops_waiting_short_of_the_deadline() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Spin, FRAME, start);
+        pacer.take_due(start);
+        assert_eq!(pacer.timeout(start), FRAME - SPIN_MARGIN);
+        assert_eq!(pacer.timeout(start + ms(15)), Duration::ZERO);
+        assert!(!pacer.due(start + ms(15)));
+    }
+
+    #[test]
+    fn deadlines_dont_drift() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Sleep, FRAME, start);
+        // Each frame starting a little late doesn't push the
+        // ones after it back
+        for n in 0..60 {
+            assert_eq!(pacer.take_due(start + FRAME * n + ms(1)), 1);
+        }
+        assert_eq!(pacer.next, start + FRAME * 60);
+    }
+
+    #[test]
+    fn slow_redraws_run_more_frames_at_once() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Sleep, FRAME, start);
+        // Going round the loop every 40ms still runs 60 frames a
+        // second, two or three at a time
+        let ran: Vec<u32> = (0..25)
+            .map(|n| pacer.take_due(start + ms(40) * n))
+            .collect();
+        assert!(ran.iter().all(|&n| n <= 3));
+        assert_eq!(ran.iter().sum::<u32>(), 58);
+        assert_eq!(pacer.take_due(start + ms(1000)), 2);
+        // Nothing more until the next is due
+        assert_eq!(pacer.take_due(start + ms(1000)), 0);
+    }
+
+    #[test]
+    fn unlimited_batches_fill_a_frame() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Unlimited, FRAME, start);
+        assert_eq!(pacer.timeout(start), Duration::ZERO);
+        assert!(pacer.due(start - ms(5)));
+
+        // Frames that take 0.1ms each, plus a 1ms redraw
+        let mut now = start + ms(1);
+        let mut batch = 0;
+        for _ in 0..40 {
+            batch = pacer.take_due(now);
+            now += Duration::from_micros(100) * batch + ms(1);
+        }
+        assert!((150..=160).contains(&batch), "{}", batch);
+
+        // A sudden slow redraw halves it at most
+        assert!(pacer.take_due(now + Duration::from_secs(1)) >= batch / 2);
+    }
+
+    #[test]
+    fn unlimited_batches_stay_in_bounds() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Unlimited, FRAME, start);
+        let mut now = start;
+        for _ in 0..40 {
+            assert!((1..=MAX_BATCH).contains(&pacer.take_due(now)));
+        }
+        // Frames that each take longer than a frame should
+        let mut batch = MAX_BATCH;
+        for _ in 0..40 {
+            now += ms(20) * batch;
+            batch = pacer.take_due(now);
+            assert!((1..=MAX_BATCH).contains(&batch));
+        }
+        assert_eq!(batch, 1);
+    }
+
+    #[test]
+    fn a_long_stall_is_skipped() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(Pacing::Sleep, FRAME, start);
+        let later = start + Duration::from_secs(1);
+        assert_eq!(pacer.take_due(later), MAX_BEHIND);
+        assert_eq!(pacer.next, later + FRAME);
+
+        // A few frames behind is caught up on
+        let mut pacer = FramePacer::new(Pacing::Sleep, FRAME, start);
+        assert_eq!(pacer.take_due(start + FRAME * 4), 5);
+        assert_eq!(pacer.next, start + FRAME * 5);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+
+use chip8_emu::{Chip8, FrameOutput, Snapshot};
+
+// With --practice the terminal takes checkpoints by itself as a
+// game moves on to a new screen (the next level, say), and F6
+// goes back to the latest, for games with no continues.
+//
+// A new screen is a clear followed within SETTLE frames by at
+// least MIN_PIXELS pixels being drawn, and the checkpoint is
+// taken once they have been. Games that clear and redraw every
+// frame look like that all the time, so checkpoints are at least
+// MIN_INTERVAL frames apart. Checkpoints are kept in memory as
+// save state bytes, the last CHECKPOINTS of them.
+const SETTLE: u64 = 10;
+const MIN_PIXELS: u32 = 128;
+const MIN_INTERVAL: u64 = 300;
+const CHECKPOINTS: usize = 8;
+
+pub struct Practice {
+    // Oldest first
+    checkpoints: VecDeque<Vec<u8>>,
+    // The frame of the last clear not yet followed by a new
+    // screen, and the pixels drawn since
+    clear: Option<(u64, u32)>,
+    last_taken: Option<u64>,
+    // Set once F6 has gone back, so another press goes further
+    walking: bool,
+}
+
+impl Practice {
+    pub fn new() -> Self {
+        Practice {
+            checkpoints: VecDeque::new(),
+            clear: None,
+            last_taken: None,
+            walking: false,
+        }
+    }
+
+    // Called after each frame runs, with what it did. Returns
+    // whether it took a checkpoint.
+    pub fn frame(&mut self, frame: u64, output: &FrameOutput, chip8: &Chip8) -> bool {
+        if output.cleared {
+            self.clear = Some((frame, 0));
+        }
+        let Some((cleared_at, drawn)) = &mut self.clear else {
+            return false;
+        };
+        *drawn += output.pixels_drawn;
+        if *drawn < MIN_PIXELS {
+            if frame >= *cleared_at + SETTLE {
+                self.clear = None;
+            }
+            return false;
+        }
+
+        self.clear = None;
+        if self
+            .last_taken
+            .is_some_and(|last| frame < last + MIN_INTERVAL)
+        {
+            return false;
+        }
+        if self.checkpoints.len() == CHECKPOINTS {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back(chip8.snapshot().to_bytes());
+        self.last_taken = Some(frame);
+        self.walking = false;
+        true
+    }
+
+    // The latest checkpoint, or pressed again, the one before
+    // (the later one is dropped). None before the first.
+    pub fn back(&mut self) -> Option<Snapshot> {
+        if self.walking && self.checkpoints.len() > 1 {
+            self.checkpoints.pop_back();
+        }
+        let bytes = self.checkpoints.back()?;
+        self.walking = true;
+        self.clear = None;
+        Snapshot::from_bytes(bytes).ok()
+    }
+
+    // Checkpoints there are to go back to
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_emu::assemble;
+
+    fn output(cleared: bool, pixels_drawn: u32) -> FrameOutput {
+        FrameOutput {
+            cleared,
+            pixels_drawn,
+            ..FrameOutput::default()
+        }
+    }
+
+    fn restored(snapshot: Snapshot) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.restore(&snapshot);
+        chip8
+    }
+
+    // Frames where a checkpoint was taken
+    fn taken(practice: &mut Practice, frames: &[FrameOutput]) -> Vec<u64> {
+        let chip8 = Chip8::new();
+        (0..frames.len() as u64)
+            .filter(|&n| practice.frame(n, &frames[n as usize], &chip8))
+            .collect()
+    }
+
+    #[test]
+    fn a_clear_then_a_big_draw_is_a_new_screen() {
+        let mut frames = vec![output(false, 20); 20];
+        frames[3] = output(true, 0);
+        frames[5] = output(false, 100);
+        // 20 + 100 + 20 makes it on frame 6
+        assert_eq!(taken(&mut Practice::new(), &frames), [6]);
+    }
+
+    #[test]
+    fn small_draws_after_a_clear_arent() {
+        let mut frames = vec![output(false, 0); 40];
+        frames[0] = output(true, 50);
+        // Too late: SETTLE frames have passed
+        frames[12] = output(false, 200);
+        assert!(taken(&mut Practice::new(), &frames).is_empty());
+    }
+
+    #[test]
+    fn clearing_every_frame_is_checkpointed_rarely() {
+        let frames = vec![output(true, 500); 1000];
+        assert_eq!(taken(&mut Practice::new(), &frames), [0, 300, 600, 900]);
+    }
+
+    #[test]
+    fn f6_walks_back_through_the_checkpoints() {
+        let mut practice = Practice::new();
+        assert!(practice.back().is_none());
+
+        let mut chip8 = Chip8::new();
+        for frame in 0..3 {
+            chip8.set_register(0, frame as u8).unwrap();
+            assert!(practice.frame(frame * 1000, &output(true, 200), &chip8));
+        }
+        assert_eq!(restored(practice.back().unwrap()).registers()[0], 2);
+        // Each press after goes one further back, down to the first
+        assert_eq!(restored(practice.back().unwrap()).registers()[0], 1);
+        assert_eq!(restored(practice.back().unwrap()).registers()[0], 0);
+        assert_eq!(restored(practice.back().unwrap()).registers()[0], 0);
+        assert_eq!(practice.len(), 1);
+    }
+
+    #[test]
+    fn only_the_latest_are_kept() {
+        let mut practice = Practice::new();
+        let chip8 = Chip8::new();
+        for frame in 0..20 {
+            practice.frame(frame * MIN_INTERVAL, &output(true, 200), &chip8);
+        }
+        assert_eq!(practice.len(), CHECKPOINTS);
+    }
+
+    #[test]
+    fn a_rom_moving_to_a_new_screen() {
+        // Waits about 30 frames, then clears and fills most of
+        // the screen with 0s, then waits for good
+        let rom = assemble(
+            "v0 := 30 delay := v0 \
+             loop v0 := delay if v0 != 0 then again \
+             clear i := hex v1 \
+             v2 := 0 loop v3 := 0 loop sprite v2 v3 5 v3 += 6 if v3 != 30 then again \
+             v2 += 5 if v2 != 60 then again \
+             : end jump end",
+        )
+        .unwrap();
+        let mut chip8 = Chip8::builder().build().unwrap();
+        chip8.load_rom_bytes(&rom).unwrap();
+        let mut practice = Practice::new();
+        let taken: Vec<u64> = (0..120)
+            .filter(|&frame| {
+                let output = chip8.step_frame(15).unwrap();
+                practice.frame(frame, &output, &chip8)
+            })
+            .collect();
+        assert_eq!(taken.len(), 1);
+        assert!((30..40).contains(&taken[0]), "{:?}", taken);
+        // The checkpoint has the new screen on it
+        let restored = restored(practice.back().unwrap());
+        assert!(restored.display.iter().filter(|&&p| p != 0).count() >= 128);
+    }
+}
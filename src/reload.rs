@@ -0,0 +1,72 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+const POLL: Duration = Duration::from_millis(250);
+
+// Notices when a ROM file changes on disk, so it can be reloaded
+// while the game is being written (e.g. exported again from Octo).
+// The file's modification time is polled, which is plenty at
+// this rate and needs no platform file watching.
+pub struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    next_poll: Instant,
+    // The changed file as last read. It's only trusted once the
+    // next poll reads the same bytes, so a ROM caught halfway
+    // through being written isn't loaded.
+    pending: Option<Vec<u8>>,
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path) -> Self {
+        RomWatcher {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            next_poll: Instant::now() + POLL,
+            pending: None,
+        }
+    }
+
+    // The new ROM, once the file has changed and settled. Cheap
+    // to call every frame; the file is only looked at every POLL.
+    pub fn poll(&mut self) -> Option<Vec<u8>> {
+        let now = Instant::now();
+        if now < self.next_poll {
+            return None;
+        }
+        self.next_poll = now + POLL;
+        self.check()
+    }
+
+    fn check(&mut self) -> Option<Vec<u8>> {
+        let modified = modified(&self.path);
+        if modified == self.modified && self.pending.is_none() {
+            return None;
+        }
+
+        // Empty or unreadable while it's being written; try
+        // again next time
+        let rom = match fs::read(&self.path) {
+            Ok(rom) if !rom.is_empty() => rom,
+            _ => {
+                self.pending = None;
+                return None;
+            }
+        };
+        if self.pending.as_ref() == Some(&rom) {
+            self.pending = None;
+            self.modified = modified;
+            Some(rom)
+        } else {
+            self.pending = Some(rom);
+            None
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
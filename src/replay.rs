@@ -0,0 +1,130 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::netplay::Session;
+
+// Input recordings are text: this header, the CXNN seed, then
+// one `<frame> <key> down|up` line per key event, e.g.
+//
+//     chip8-emu input 1
+//     seed 8114219378513950911
+//     120 5 down
+//     126 5 up
+//
+// Frames count the 60Hz frames emulated since the game started,
+// and an event applies just before that frame runs.
+const HEADER: &str = "chip8-emu input 1";
+
+// Set from the command line, for the terminal
+pub struct Options {
+    // Record the keys pressed to this file
+    pub record: Option<PathBuf>,
+    // Take the keys from this recording instead of the keyboard
+    pub replay: Option<PathBuf>,
+}
+
+// Where the keypad gets its input from
+pub enum Input {
+    Keyboard,
+    // The keyboard, with every key event written to a file
+    Record(InputRecorder),
+    // A recording; the keyboard's keypad keys are ignored
+    Replay(Replay),
+    // The keyboard here and another player's over the network,
+    // both on the one keypad (see netplay)
+    Netplay(Box<Session>),
+}
+
+pub struct InputRecorder {
+    file: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn start(path: &Path, seed: u64) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "{}\nseed {}", HEADER, seed)?;
+        Ok(InputRecorder { file })
+    }
+
+    pub fn record(&mut self, frame: u64, key: usize, pressed: bool) -> io::Result<()> {
+        let state = if pressed { "down" } else { "up" };
+        writeln!(self.file, "{} {:X} {}", frame, key, state)
+    }
+}
+
+pub struct Replay {
+    seed: u64,
+    // (frame, key, pressed), in the order they happened
+    events: Vec<(u64, usize, bool)>,
+    next: usize,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("Can't read {}: {}", path.display(), err),
+            )
+        })?;
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: not an input recording line: {}", path.display(), line),
+            )
+        };
+
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not an input recording", path.display()),
+            ));
+        }
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| invalid("missing seed"))?;
+
+        let mut events = Vec::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let event = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [frame, key, state @ ("down" | "up")] => frame
+                    .parse()
+                    .ok()
+                    .zip(usize::from_str_radix(key, 16).ok().filter(|&k| k < 16))
+                    .map(|(frame, key)| (frame, key, state == "down")),
+                _ => None,
+            };
+            events.push(event.ok_or_else(|| invalid(line))?);
+        }
+
+        Ok(Replay {
+            seed,
+            events,
+            next: 0,
+        })
+    }
+
+    // The seed the recording was made with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // The key events due before `frame` runs
+    pub fn take(&mut self, frame: u64) -> &[(u64, usize, bool)] {
+        let start = self.next;
+        while self.events.get(self.next).is_some_and(|e| e.0 <= frame) {
+            self.next += 1;
+        }
+        &self.events[start..self.next]
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
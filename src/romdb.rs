@@ -0,0 +1,326 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{self, Config},
+    scan::{self, Extension},
+};
+use chip8_emu::{
+    Chip8, Chip8Builder, DISPLAY_HEIGHTS, HIRES_ENTRY, Quirks, Timing, Variant, Watch, is_hires_rom,
+};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+
+// Instructions per 60Hz frame when neither the command line nor
+// a profile says otherwise
+pub const DEFAULT_CPF: usize = 15;
+
+// Settings known to make a particular ROM run right
+pub struct Profile {
+    pub name: &'static str,
+    pub quirks: Quirks,
+    pub variant: Variant,
+    pub cpf: usize,
+}
+
+// Quirks::default(), usable in consts
+const DEFAULT: Quirks = Quirks {
+    wrap_x: true,
+    wrap_y: true,
+    display_wait: false,
+    shift_uses_vy: false,
+    index_increment_on_store: false,
+    vf_reset_on_logic: false,
+    ignore_machine_code: false,
+    jump_with_vx: false,
+    half_scroll: false,
+};
+
+// Keyed by the SHA-256 of the ROM file, as printed by --info.
+// Entries look like
+//
+//     (
+//         "<sha256>",
+//         Profile {
+//             name: "Blinky",
+//             quirks: Quirks { index_increment_on_store: true, ..DEFAULT },
+//             variant: Variant::Chip8,
+//             cpf: 15,
+//         },
+//     ),
+const PROFILES: &[(&str, Profile)] = &[];
+
+// The ROM's SHA-256 in hex, as --info prints it
+pub fn sha256(rom: &[u8]) -> String {
+    Sha256::digest(rom)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn lookup(rom: &[u8]) -> Option<&'static Profile> {
+    let hash = sha256(rom);
+    PROFILES
+        .iter()
+        .find(|(sha256, _)| *sha256 == hash)
+        .map(|(_, profile)| profile)
+}
+
+// Settings for one ROM in a file next to it, game.ch8.toml for
+// game.ch8. It's in the config file's format, and [emulation]'s
+// cpf, schip, xo_chip and quirks apply; they take the place of a
+// built-in profile, so the command line and config file still win.
+pub struct Sidecar {
+    // The file's name, for the notice
+    pub name: String,
+    // From the defaults, with the quirks listed
+    pub quirks: Option<Quirks>,
+    pub variant: Option<Variant>,
+    pub cpf: Option<usize>,
+}
+
+pub fn sidecar_path(rom: &Path) -> PathBuf {
+    let mut name = rom.as_os_str().to_owned();
+    name.push(".toml");
+    PathBuf::from(name)
+}
+
+// The sidecar for the ROM at `rom`, None if it has none. Other
+// settings in it are skipped with a warning, as are keys the
+// config file doesn't know.
+pub fn load_sidecar(rom: &Path) -> io::Result<Option<Sidecar>> {
+    let path = sidecar_path(rom);
+    if let Err(err) = fs::metadata(&path) {
+        return match err.kind() {
+            io::ErrorKind::NotFound => Ok(None),
+            _ => Err(err),
+        };
+    }
+    let (config, warnings) = config::load(&path)?;
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+    let others = Config {
+        cpf: None,
+        schip: None,
+        xo_chip: None,
+        quirks: Vec::new(),
+        ..config.clone()
+    };
+    if others != Config::default() {
+        warn!(
+            "{}: only cpf, schip, xo_chip and quirks apply to one ROM; the rest is skipped",
+            path.display()
+        );
+    }
+
+    let quirks = (!config.quirks.is_empty()).then(|| {
+        let mut quirks = DEFAULT;
+        for name in &config.quirks {
+            if let Some((_, flag)) = config::quirk_flag(name) {
+                flag(&mut quirks);
+            }
+        }
+        quirks
+    });
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    Ok(Some(Sidecar {
+        name: name.into_owned(),
+        quirks,
+        variant: match (config.xo_chip, config.schip) {
+            (Some(true), _) => Some(Variant::XoChip),
+            (_, Some(true)) => Some(Variant::SuperChip),
+            (None, None) => None,
+            _ => Some(Variant::Chip8),
+        },
+        cpf: config.cpf,
+    }))
+}
+
+// Machine settings from the command line. Anything given here
+// wins over the ROM's profile, which wins over the defaults.
+#[derive(Clone)]
+pub struct Options {
+    // Each quirk flag, applied in order on top of the profile
+    pub quirks: Vec<fn(&mut Quirks)>,
+    pub variant: Option<Variant>,
+    pub cpf: Option<usize>,
+    pub timing: Timing,
+    // Timer ticks a second (--timer-hz)
+    pub timer_hz: u32,
+    // Where programs are loaded and started instead of 0x200
+    // (--load-addr)
+    pub load_address: Option<u16>,
+    // Where PC starts instead of the load address (--entry)
+    pub entry: Option<u16>,
+    // Fixed CXNN seed (--seed); random if not given
+    pub seed: Option<u64>,
+    // Frames blended against flicker (--flicker-filter)
+    pub flicker_filter: usize,
+    // Replaces the built-in font (--font)
+    pub font: Option<Vec<u8>>,
+    // Where the font goes instead of 0x50 (--font-address)
+    pub font_address: Option<u16>,
+    pub watches: Vec<Watch>,
+    // Look ROMs up in PROFILES, and read their sidecars (off with
+    // --no-romdb)
+    pub romdb: bool,
+    // Turn on XO-CHIP or SUPER-CHIP for ROMs that look like they
+    // need it, unless a variant was given, and the 64x64 display
+    // for hi-res ROMs (off with --no-detect)
+    pub detect: bool,
+    // Rows on the display (--hires, --display-height); 32 unless
+    // the ROM is detected as hi-res
+    pub display_height: Option<usize>,
+    // Count each key press once (--key-repeat-guard)
+    pub key_repeat_guard: bool,
+    // Stop where the program runs out (--halt-past-rom)
+    pub halt_past_rom: bool,
+    // Load what fits of a ROM too large for memory
+    // (--truncate-oversize)
+    pub truncate_oversize: bool,
+    // Log every instruction for the frontend to write out
+    // (--trace)
+    pub trace: bool,
+}
+
+// How to run one particular ROM
+pub struct Setup {
+    pub builder: Chip8Builder,
+    pub cpf: usize,
+    pub timing: Timing,
+    // For Chip8::set_timer_hz(), which frontends call on the
+    // machines they run
+    pub timer_hz: u32,
+    // The profile that was used, or what variant detection
+    // found, for the terminal to show
+    pub notice: Option<String>,
+}
+
+impl Options {
+    // How to run `rom`, read from `path`. Fails if the ROM's
+    // sidecar can't be read.
+    pub fn setup(&self, path: &Path, rom: &[u8]) -> io::Result<Setup> {
+        let profile = if self.romdb { lookup(rom) } else { None };
+        let sidecar = if self.romdb {
+            load_sidecar(path)?
+        } else {
+            None
+        };
+        let sidecar = sidecar.as_ref();
+
+        let mut quirks = sidecar
+            .and_then(|s| s.quirks)
+            .or(profile.map(|p| p.quirks))
+            .unwrap_or(DEFAULT);
+        for flag in &self.quirks {
+            flag(&mut quirks);
+        }
+        let mut variant = self
+            .variant
+            .or(sidecar.and_then(|s| s.variant))
+            .or(profile.map(|p| p.variant));
+        let cpf = self
+            .cpf
+            .or(sidecar.and_then(|s| s.cpf))
+            .or(profile.map(|p| p.cpf))
+            .unwrap_or(DEFAULT_CPF);
+        let mut notice = match (sidecar, profile) {
+            (Some(sidecar), _) => Some(format!("Using the settings in {}", sidecar.name)),
+            (None, Some(profile)) => Some(format!("Using the settings for {}", profile.name)),
+            (None, None) => None,
+        };
+        if let Some(msg) = &notice {
+            info!("{}", msg);
+        }
+
+        // Without this a ROM using an extension just stops on its
+        // first extension opcode, which isn't much of a hint
+        if variant.is_none()
+            && let Some(extension) = scan::detect(rom)
+        {
+            let msg = match extension {
+                Extension::XoChip if self.detect => {
+                    variant = Some(Variant::XoChip);
+                    "This ROM appears to require XO-CHIP; it's turned on"
+                }
+                Extension::XoChip => "This ROM appears to require XO-CHIP; run with --xo-chip",
+                Extension::SuperChip if self.detect => {
+                    variant = Some(Variant::SuperChip);
+                    "This ROM appears to require SUPER-CHIP (partly supported); it's turned on"
+                }
+                Extension::SuperChip => {
+                    "This ROM appears to require SUPER-CHIP (partly supported); run with --schip"
+                }
+            };
+            match variant {
+                Some(_) => info!("{}", msg),
+                None => warn!("{}", msg),
+            }
+            notice = Some(msg.to_string());
+        }
+        let variant = variant.unwrap_or_default();
+
+        // Hi-res ROMs start by jumping into an interpreter patch
+        // that can't run here, so they start past it instead. Only
+        // at 0x200, where the patch expects them.
+        let mut entry = self.entry;
+        let mut rows = self.display_height.unwrap_or(DISPLAY_HEIGHTS[0]);
+        let hires = match self.display_height {
+            None => self.detect,
+            Some(rows) => rows == 64,
+        };
+        let at_0x200 = self.load_address.is_none_or(|addr| addr == 0x200);
+        if hires && at_0x200 && is_hires_rom(rom) {
+            rows = 64;
+            entry.get_or_insert(HIRES_ENTRY);
+            let msg = "This looks like a hi-res ROM for the VIP's 64x64 display; it's turned on";
+            info!("{}", msg);
+            notice.get_or_insert(msg.to_string());
+        }
+        debug!(
+            "{:?}, {:?}, {} instructions per frame, {:?} timing",
+            variant, quirks, cpf, self.timing
+        );
+
+        let mut builder = self.watches.iter().fold(
+            Chip8::builder()
+                .quirks(quirks)
+                .variant(variant)
+                .timing(self.timing),
+            |b, &w| b.watch(w),
+        );
+        if let Some(addr) = self.load_address {
+            builder = builder.start_address(addr);
+        }
+        if let Some(addr) = entry {
+            builder = builder.entry(addr);
+        }
+        builder = builder
+            .display_height(rows)
+            .flicker_filter(self.flicker_filter)
+            .key_repeat_guard(self.key_repeat_guard)
+            .halt_past_rom(self.halt_past_rom)
+            .truncate_oversize(self.truncate_oversize)
+            .trace(self.trace);
+        if let Some(font) = &self.font {
+            builder = builder.fontset(font);
+        }
+        if let Some(addr) = self.font_address {
+            builder = builder.font_address(addr);
+        }
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+
+        Ok(Setup {
+            builder,
+            cpf,
+            timing: self.timing,
+            timer_hz: self.timer_hz,
+            notice,
+        })
+    }
+}
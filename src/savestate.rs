@@ -0,0 +1,124 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chip8_emu::Snapshot;
+use log::info;
+use sha2::{Digest, Sha256};
+
+// Save state files are this magic, the SHA-256 of the ROM they
+// were made with, then the snapshot itself
+const MAGIC: &[u8; 4] = b"C8SS";
+
+pub const SLOTS: u8 = 10;
+
+pub type RomHash = [u8; 32];
+
+pub fn rom_hash(rom: &[u8]) -> RomHash {
+    Sha256::digest(rom).into()
+}
+
+// Slots live next to the ROM: game.ch8 -> game.ch8.slot3.state
+pub fn slot_path(rom: &Path, slot: u8) -> PathBuf {
+    let mut name = rom.as_os_str().to_owned();
+    name.push(format!(".slot{}.state", slot));
+    PathBuf::from(name)
+}
+
+pub fn save(path: &Path, hash: &RomHash, snapshot: &Snapshot) -> io::Result<()> {
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(hash);
+    buf.extend_from_slice(&snapshot.to_bytes());
+    fs::write(path, buf)?;
+    info!("Saved state to {}", path.display());
+    Ok(())
+}
+
+// Returns None if nothing has been saved to `path` yet. States
+// saved from a different ROM are refused.
+pub fn load(path: &Path, hash: &RomHash) -> io::Result<Option<Snapshot>> {
+    let buf = match fs::read(path) {
+        Ok(buf) => buf,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let header_len = MAGIC.len() + hash.len();
+    if buf.len() < header_len || &buf[..MAGIC.len()] != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a save state file",
+        ));
+    }
+    if &buf[MAGIC.len()..header_len] != hash {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Save state was made with a different ROM",
+        ));
+    }
+
+    let snapshot = Snapshot::from_bytes(&buf[header_len..])?;
+    info!("Loaded state from {}", path.display());
+    Ok(Some(snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use chip8_emu::Chip8;
+
+    use super::*;
+
+    // A path in the temp directory no other test uses
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8-emu-{}-{}", process::id(), name))
+    }
+
+    #[test]
+    fn slots_are_named_after_the_rom() {
+        assert_eq!(
+            slot_path(Path::new("game.ch8"), 3),
+            Path::new("game.ch8.slot3.state")
+        );
+        assert_eq!(
+            slot_path(Path::new("roms/Pong (1 player).ch8"), 0),
+            Path::new("roms/Pong (1 player).ch8.slot0.state")
+        );
+        assert_eq!(
+            slot_path(Path::new("/tmp/noext"), 9),
+            Path::new("/tmp/noext.slot9.state")
+        );
+    }
+
+    #[test]
+    fn states_load_only_for_their_rom() {
+        let path = temp_path("hash.state");
+        let mut chip8 = Chip8::new();
+        chip8.set_register(3, 0x2A).unwrap();
+        save(&path, &rom_hash(b"one"), &chip8.snapshot()).unwrap();
+
+        let err = load(&path, &rom_hash(b"two")).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Save state was made with a different ROM");
+
+        let snapshot = load(&path, &rom_hash(b"one")).unwrap().unwrap();
+        let mut other = Chip8::new();
+        other.restore(&snapshot);
+        assert_eq!(other.registers()[3], 0x2A);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_slots_and_other_files() {
+        let path = temp_path("missing.state");
+        assert!(load(&path, &rom_hash(b"one")).unwrap().is_none());
+
+        let path = temp_path("other.state");
+        fs::write(&path, b"not a save state").unwrap();
+        let err = load(&path, &rom_hash(b"one")).err().unwrap();
+        assert_eq!(err.to_string(), "Not a save state file");
+        fs::remove_file(&path).unwrap();
+    }
+}
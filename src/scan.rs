@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+
+use chip8_emu::{Opcode, decode};
+
+// Where ROMs are loaded unless told otherwise
+const LOAD_ADDR: usize = 0x200;
+
+// Instruction set extensions a ROM can be written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extension {
+    SuperChip,
+    XoChip,
+}
+
+// Looks for instructions that only exist in the extensions.
+// Only code that can be reached from the start is looked at, by
+// following jumps, calls and both sides of skips, so sprite data
+// (full of bytes like F0 00) isn't taken for instructions. Jumps
+// through BNNN can't be followed, so this is only a hint.
+pub fn detect(rom: &[u8]) -> Option<Extension> {
+    let code = reachable(rom);
+    let xo_chip = code.iter().any(|&w| {
+        w == 0xF000 // long I
+            || w == 0xF002 // audio pattern
+            || (w & 0xF0FF) == 0xF001 // plane select
+            || (w & 0xF0FF) == 0xF03A // pitch
+            || (w & 0xF00E) == 0x5002 // 5XY2/5XY3 register ranges
+            || (w & 0xFFF0) == 0x00D0 // scroll up
+    });
+    if xo_chip {
+        return Some(Extension::XoChip);
+    }
+
+    let schip = code.iter().any(|&w| {
+        (w & 0xFFF0) == 0x00C0 // scroll down
+            || (0x00FB..=0x00FF).contains(&w) // scroll, exit, hires
+            || (w & 0xF00F) == 0xD000 // 16x16 sprites
+            || matches!(w & 0xF0FF, 0xF030 | 0xF075 | 0xF085) // big font, RPL flags
+    });
+    schip.then_some(Extension::SuperChip)
+}
+
+// The instructions reachable from the load address
+fn reachable(rom: &[u8]) -> Vec<u16> {
+    let word = |addr: usize| {
+        let at = addr.checked_sub(LOAD_ADDR)?;
+        Some(u16::from_be_bytes([*rom.get(at)?, *rom.get(at + 1)?]))
+    };
+
+    let mut code = Vec::new();
+    let mut seen = HashSet::new();
+    let mut todo = vec![LOAD_ADDR];
+    while let Some(addr) = todo.pop() {
+        if !seen.insert(addr) {
+            continue;
+        }
+        let Some(w) = word(addr) else { continue };
+        code.push(w);
+
+        // Skipping over F000 NNNN skips all four bytes
+        let after_next = match word(addr + 2) {
+            Some(0xF000) => addr + 6,
+            _ => addr + 4,
+        };
+        match decode(w) {
+            Opcode::Jump(nnn) => todo.push(nnn as usize),
+            Opcode::Call(nnn) => todo.extend([nnn as usize, addr + 2]),
+            Opcode::SeVxByte(..)
+            | Opcode::SneVxByte(..)
+            | Opcode::SeVxVy(..)
+            | Opcode::SneVxVy(..)
+            | Opcode::Skp(_)
+            | Opcode::Sknp(_) => todo.extend([addr + 2, after_next]),
+            Opcode::LdILong => todo.push(addr + 4),
+            // Returns, computed jumps, SUPER-CHIP's exit and
+            // anything that isn't an instruction end the path
+            Opcode::Ret | Opcode::JumpV0(_) | Opcode::Sys(0x0FD) | Opcode::Invalid(_) => {}
+            _ => todo.push(addr + 2),
+        }
+    }
+    code
+}
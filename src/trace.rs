@@ -0,0 +1,47 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+use chip8_emu::Chip8;
+
+// Where --trace writes the lines the machine logs for each
+// instruction (see Chip8::enable_trace()), like
+// PC=0x0246 OP=0x8124 V1=0A V2=03 I=0x0300 SP=2  ADD V1, V2
+// The frontends write out what was logged once a frame, so the
+// log doesn't pile up in memory (headless mode, once the run is
+// over).
+pub enum Trace {
+    Stdout,
+    // --trace-file
+    File(File),
+}
+
+impl Trace {
+    // Stdout, or a new file at `path`
+    pub fn open(path: Option<&Path>) -> io::Result<Trace> {
+        match path {
+            None => Ok(Trace::Stdout),
+            Some(path) => File::create(path).map(Trace::File).map_err(|err| {
+                io::Error::new(
+                    err.kind(),
+                    format!("Can't write trace {}: {}", path.display(), err),
+                )
+            }),
+        }
+    }
+
+    // Writes what `chip8` logged since the last time
+    pub fn write(&self, chip8: &mut Chip8) -> io::Result<()> {
+        let log = chip8.take_trace_log();
+        match self {
+            Trace::Stdout => io::stdout().lock().write_all(log.as_bytes()),
+            // &File writes too, so a shared Trace will do
+            Trace::File(file) => {
+                let mut file: &File = file;
+                file.write_all(log.as_bytes())
+            }
+        }
+    }
+}
@@ -0,0 +1,1164 @@
+use std::{
+    fs,
+    io::{self, Stdout, Write},
+    panic,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    crash,
+    flash::FlashGuard,
+    keymap::KeyMap,
+    netplay::{self, Role, Session},
+    practice::Practice,
+    read_rom,
+    reload::RomWatcher,
+    replay::{self, Input, InputRecorder, Replay},
+    rom_from_file, romdb,
+    savestate::{self, SLOTS},
+    trace::Trace,
+    video::{self, Recorder},
+};
+use chip8_emu::{Chip8, FramePacer, Pacing};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self, Event, KeyCode, KeyEventKind, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+};
+use log::{debug, info};
+
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// Pixel colors by value: off, first plane, second plane (XO-CHIP)
+// and both
+const COLORS: [Color; 4] = [Color::Black, Color::Green, Color::Red, Color::Yellow];
+
+// Roughly what COLORS look like in most terminals, for recordings
+const PALETTE: video::Palette = [
+    [0x00, 0x00, 0x00],
+    [0x00, 0xCD, 0x00],
+    [0xCD, 0x00, 0x00],
+    [0xCD, 0xCD, 0x00],
+];
+
+// Most terminals only report key presses, never releases, so
+// without the keyboard enhancement protocol a key is treated
+// as released once it stops auto-repeating for this long.
+const KEY_HOLD: Duration = Duration::from_millis(150);
+
+// Netplay only says it's waiting for the other player once the
+// game has been held up this long, not for every late packet
+const NET_WAIT_NOTICE: Duration = Duration::from_millis(500);
+
+// Octo source (.8o) is assembled when it's loaded
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "rom", "8o"];
+
+// Puts the terminal into raw/alternate-screen mode and restores
+// it when dropped, which also happens while unwinding a panic.
+struct TerminalGuard {
+    enhanced_keys: bool,
+}
+
+impl TerminalGuard {
+    fn new(stdout: &mut Stdout) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+
+        let enhanced_keys = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if enhanced_keys {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(TerminalGuard { enhanced_keys })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal(self.enhanced_keys);
+    }
+}
+
+fn restore_terminal(enhanced_keys: bool) {
+    let mut stdout = io::stdout();
+    if enhanced_keys {
+        let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+    }
+    let _ = execute!(stdout, ResetColor, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+// Frontend settings from the command line
+pub struct Options {
+    pub video: video::Options,
+    // Restart the game whenever the ROM file changes
+    pub watch_rom: bool,
+    // Show instructions and frames per second in the title
+    pub show_speed: bool,
+    // Also map the right half of the keypad to the right of the
+    // keyboard (--split-keys)
+    pub split_keys: bool,
+    // Keypad keys that go up and down while held (--autofire),
+    // and how many times a second
+    pub autofire: Vec<(usize, u32)>,
+    // Show big display changes over a few frames (--flash-guard)
+    pub flash_guard: bool,
+    // Lit pixels and the background (--fg and --bg), in place of
+    // the terminal's own green and black
+    pub fg: Option<[u8; 3]>,
+    pub bg: Option<[u8; 3]>,
+    // XO-CHIP pixels in the second plane and in both (--palette),
+    // in place of red and yellow
+    pub plane2: Option<[u8; 3]>,
+    pub both: Option<[u8; 3]>,
+    pub input: replay::Options,
+    // Play with someone else over the network (--host, --connect)
+    pub netplay: Option<netplay::Options>,
+    // How to wait for the next frame (--pacing)
+    pub pacing: Pacing,
+    // Light the display on each key press and log how long it
+    // took to show (--latency-test)
+    pub latency_test: bool,
+    // Take checkpoints on new screens for F6 to go back to
+    // (--practice)
+    pub practice: bool,
+    // Where the instructions run go (--trace-file)
+    pub trace: Option<Trace>,
+}
+
+impl Options {
+    // The colors set, by pixel value like PALETTE
+    fn custom(&self) -> [Option<[u8; 3]>; 4] {
+        [self.bg, self.fg, self.plane2, self.both]
+    }
+
+    // PALETTE with the colors set, for recordings and screenshots
+    pub fn palette(&self) -> video::Palette {
+        let mut palette = PALETTE;
+        for (color, custom) in palette.iter_mut().zip(self.custom()) {
+            *color = custom.unwrap_or(*color);
+        }
+        palette
+    }
+
+    // COLORS with the colors set
+    fn colors(&self) -> [Color; 4] {
+        let rgb = |[r, g, b]: [u8; 3]| Color::Rgb { r, g, b };
+        let mut colors = COLORS;
+        for (color, custom) in colors.iter_mut().zip(self.custom()) {
+            *color = custom.map_or(*color, rgb);
+        }
+        colors
+    }
+}
+
+// Plays a ROM in the terminal. Each text cell shows two
+// vertically stacked pixels using half-block characters, so
+// the 64x32 display needs a 64x16 terminal, and SUPER-CHIP's
+// 128x64 one a 128x32 terminal.
+pub fn run(path: &str, machine: &romdb::Options, mut options: Options) -> io::Result<()> {
+    // A replay only plays out the same with the seed it was
+    // recorded with, so a recording has to pick one up front
+    let mut machine = machine.clone();
+    let mut input = match (options.input.record.take(), options.input.replay.take()) {
+        (_, Some(path)) => {
+            let replay = Replay::load(&path)?;
+            machine.seed = Some(replay.seed());
+            Input::Replay(replay)
+        }
+        (Some(path), None) => {
+            let seed = *machine.seed.get_or_insert_with(rand::random);
+            Input::Record(InputRecorder::start(&path, seed)?)
+        }
+        (None, None) => Input::Keyboard,
+    };
+    // Both machines have to draw the same random numbers, so the
+    // host picks the seed. The ROM is read first to check both
+    // players have the same one.
+    let rom = read_rom(Path::new(path))?;
+    if let Some(net) = options.netplay.take() {
+        let rom_hash = savestate::rom_hash(&rom);
+        let session = match net.role {
+            Role::Host(port) => {
+                let seed = *machine.seed.get_or_insert_with(rand::random);
+                eprintln!("Waiting for the other player on port {}...", port);
+                Session::host(port, rom_hash, seed, net.delay)?
+            }
+            Role::Connect(addr) => Session::connect(&addr, rom_hash)?,
+        };
+        machine.seed = Some(session.seed());
+        input = Input::Netplay(Box::new(session));
+    }
+    let machine = &machine;
+    let (mut game, mut chip8) = start(machine, Path::new(path), rom)?;
+
+    let mut stdout = io::stdout();
+    let guard = setup(&mut stdout)?;
+
+    // F3 moves on to the next ROM in the same directory that
+    // loads, or carries on with this one if there isn't any
+    while let Exit::NextRom = play(
+        &mut stdout,
+        &guard,
+        machine,
+        &mut chip8,
+        &mut game,
+        &options,
+        &mut input,
+    )? {
+        // Recordings and replays are of the first game only
+        options.video.path = None;
+        input = Input::Keyboard;
+
+        let dir = match game.path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let roms = find_roms(dir)?;
+        let current = roms
+            .iter()
+            .position(|p| p.file_name() == game.path.file_name());
+        // Every other ROM, starting after this one
+        let (start, others) = match current {
+            Some(i) => (i + 1, roms.len() - 1),
+            None => (0, roms.len()),
+        };
+        let next = roms
+            .iter()
+            .cycle()
+            .skip(start)
+            .take(others)
+            .find_map(|p| load(machine, p).ok());
+        if let Some(next) = next {
+            (game, chip8) = next;
+        }
+    }
+    Ok(())
+}
+
+// The ROM being played and how to run it
+struct Game {
+    path: PathBuf,
+    rom: Vec<u8>,
+    // What a frame can spend, see Timing
+    budget: u32,
+    // Shown when the game starts, see romdb::Setup
+    notice: Option<String>,
+}
+
+// How play() ended
+enum Exit {
+    Quit,
+    NextRom,
+}
+
+// Reads a ROM and sets up a machine for it
+fn load(machine: &romdb::Options, path: &Path) -> io::Result<(Game, Chip8)> {
+    start(machine, path, read_rom(path)?)
+}
+
+// Sets up a machine for a ROM that's already been read
+fn start(machine: &romdb::Options, path: &Path, rom: Vec<u8>) -> io::Result<(Game, Chip8)> {
+    let settings = machine.setup(path, &rom)?;
+    let mut chip8 = settings.builder.build()?;
+    chip8.set_timer_hz(settings.timer_hz);
+    chip8.load_rom_bytes(&rom)?;
+
+    let game = Game {
+        path: path.to_path_buf(),
+        rom,
+        budget: settings.timing.frame_budget(settings.cpf),
+        notice: settings.notice,
+    };
+    Ok((game, chip8))
+}
+
+// Lists the ROMs in `dir` and plays the one picked. Esc in a
+// game goes back to the list, Esc in the list quits, and F3
+// plays the next ROM in the list. A recording path only applies
+// to the first game.
+pub fn browse(dir: &Path, machine: &romdb::Options, mut options: Options) -> io::Result<()> {
+    let roms = find_roms(dir).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Can't list ROMs in {}: {}", dir.display(), err),
+        )
+    })?;
+
+    let mut stdout = io::stdout();
+    let guard = setup(&mut stdout)?;
+
+    let mut selected = 0;
+    let mut message = if roms.is_empty() {
+        Some(format!("No ROMs found in {}", dir.display()))
+    } else {
+        None
+    };
+    let mut redraw = true;
+
+    loop {
+        if redraw {
+            queue!(stdout, SetTitle("chip8-emu"))?;
+            draw_menu(&mut stdout, &roms, selected, message.as_deref())?;
+            redraw = false;
+        }
+
+        match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Up => {
+                    selected = selected.saturating_sub(1);
+                    redraw = true;
+                }
+                KeyCode::Down => {
+                    selected = (selected + 1).min(roms.len().saturating_sub(1));
+                    redraw = true;
+                }
+                KeyCode::Enter if !roms.is_empty() => {
+                    message = None;
+                    loop {
+                        let path = &roms[selected];
+                        let (mut game, mut chip8) = match load(machine, path) {
+                            Ok(loaded) => loaded,
+                            Err(err) => {
+                                message = Some(format!("Can't load {}: {}", file_name(path), err));
+                                break;
+                            }
+                        };
+                        let exit = play(
+                            &mut stdout,
+                            &guard,
+                            machine,
+                            &mut chip8,
+                            &mut game,
+                            &options,
+                            &mut Input::Keyboard,
+                        )?;
+                        // Only the first game is recorded from the start
+                        options.video.path = None;
+                        match exit {
+                            Exit::Quit => break,
+                            Exit::NextRom => selected = (selected + 1) % roms.len(),
+                        }
+                    }
+                    redraw = true;
+                }
+                _ => {}
+            },
+            Event::Resize(..) => redraw = true,
+            _ => {}
+        }
+    }
+}
+
+fn setup(stdout: &mut Stdout) -> io::Result<TerminalGuard> {
+    let guard = TerminalGuard::new(stdout)?;
+
+    // The panic message would be printed into the alternate
+    // screen and lost, so restore the terminal before reporting
+    let enhanced_keys = guard.enhanced_keys;
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal(enhanced_keys);
+        default_hook(info);
+    }));
+
+    Ok(guard)
+}
+
+// Runs the machine until Esc (or F3, for the next ROM) is
+// pressed. F5 saves to the current save state slot and F8 loads
+// it; [ and ] pick the slot. F10 starts and stops recording a
+// video. When a watchpoint is hit the machine pauses until
+// Enter is pressed. With `watch_rom`, the game restarts whenever
+// the ROM file changes. Keypad keys come from `input`.
+fn play(
+    stdout: &mut Stdout,
+    guard: &TerminalGuard,
+    machine: &romdb::Options,
+    chip8: &mut Chip8,
+    game: &mut Game,
+    options: &Options,
+    input: &mut Input,
+) -> io::Result<Exit> {
+    let (rom_path, scale) = (game.path.clone(), options.video.scale);
+    let colors = options.colors();
+    let rom_path = rom_path.as_path();
+    let mut keys = KeyMap::new(options.split_keys);
+    for &(key, hz) in &options.autofire {
+        keys.set_autofire(key, hz);
+    }
+    let mut flash_guard = options.flash_guard.then(FlashGuard::new);
+    let mut rewind_press = None::<Instant>;
+    let mut size = terminal::size()?;
+    let mut redraw = true;
+    // What the terminal shows, so only changes are drawn; None
+    // after it's cleared, to draw it all again
+    let mut shown = None::<Vec<u8>>;
+    let mut pacer = FramePacer::new(options.pacing, FRAME, Instant::now());
+
+    let mut rom_hash = savestate::rom_hash(&game.rom);
+    let mut watcher = options.watch_rom.then(|| RomWatcher::new(rom_path));
+    // When the title goes back from saying "reloaded" or
+    // "stopped"
+    let mut title_reset = None::<Instant>;
+    let mut slot = 0;
+    let mut status = game.notice.clone();
+    // Set when the ROM hits an error; the machine stays stopped
+    // until it's rewound or a state is loaded
+    let mut halted = false;
+    // Set while the ROM is still running but has stopped doing
+    // anything (Chip8::is_halted() or is_looping())
+    let mut stuck = false;
+    let mut paused = false;
+    let mut recorder = match &options.video.path {
+        Some(path) => Some(Recorder::start(
+            path,
+            options.palette(),
+            scale,
+            dims(chip8),
+        )?),
+        None => None,
+    };
+    // Frames emulated, which input recordings are timed by
+    let mut frame: u64 = 0;
+    // Logged once a second
+    let (mut frames, mut stats_start) = (0, Instant::now());
+    let mut stats_cycles = chip8.cycle_count();
+    // For the title with show_speed, e.g. " - 900 IPS, 60 FPS"
+    let mut speed = String::new();
+    // Since when netplay has been waiting for the other player,
+    // and whether the status line says so
+    let mut net_wait = None::<Instant>;
+    let mut net_wait_shown = false;
+    // F1's registers over the top of the display
+    let mut overlay = false;
+    // Whether the beep light is on the display
+    let mut beep_light = false;
+    // When the key the latency test is showing was read
+    let mut latency_press = None::<Instant>;
+    let mut practice = options.practice.then(Practice::new);
+
+    queue!(stdout, Clear(ClearType::All))?;
+    set_title(stdout, rom_path, slot, &speed)?;
+
+    loop {
+        // Handle input until it's time for the next frame
+        while event::poll(pacer.timeout(Instant::now()))? {
+            let read_at = Instant::now();
+            match event::read()? {
+                Event::Key(key) => {
+                    let exit = match key.code {
+                        KeyCode::Esc => Some(Exit::Quit),
+                        // The other player would be left on the old one
+                        KeyCode::F(3)
+                            if key.kind == KeyEventKind::Press
+                                && !matches!(input, Input::Netplay(_)) =>
+                        {
+                            Some(Exit::NextRom)
+                        }
+                        _ => None,
+                    };
+                    if let Some(exit) = exit {
+                        if let Some(recorder) = recorder {
+                            recorder.finish()?;
+                        }
+                        return Ok(exit);
+                    }
+                    if key.kind == KeyEventKind::Press {
+                        let path = savestate::slot_path(rom_path, slot);
+                        match key.code {
+                            KeyCode::F(5) => {
+                                status = Some(
+                                    match savestate::save(&path, &rom_hash, &chip8.snapshot()) {
+                                        Ok(()) => format!("Saved slot {}", slot),
+                                        Err(err) => format!("Can't save slot {}: {}", slot, err),
+                                    },
+                                );
+                            }
+                            // Loading a state or rewinding here alone
+                            // would put the machines out of step
+                            KeyCode::F(8) if matches!(input, Input::Netplay(_)) => {
+                                status = Some("Can't load a state during netplay".to_string());
+                            }
+                            KeyCode::F(8) => {
+                                status = Some(match savestate::load(&path, &rom_hash) {
+                                    Ok(Some(snapshot)) => {
+                                        chip8.restore(&snapshot);
+                                        resync_keys(chip8, input, frame, &keys)?;
+                                        if halted {
+                                            (halted, title_reset) = (false, Some(Instant::now()));
+                                        }
+                                        format!("Loaded slot {}", slot)
+                                    }
+                                    Ok(None) => format!("Slot {} is empty", slot),
+                                    Err(err) => format!("Can't load slot {}: {}", slot, err),
+                                });
+                            }
+                            KeyCode::F(6) if let Some(practice) = &mut practice => {
+                                status = Some(match practice.back() {
+                                    Some(snapshot) => {
+                                        chip8.restore(&snapshot);
+                                        resync_keys(chip8, input, frame, &keys)?;
+                                        if halted {
+                                            (halted, title_reset) = (false, Some(Instant::now()));
+                                        }
+                                        format!("Back to checkpoint {}", practice.len())
+                                    }
+                                    None => "No checkpoints yet".to_string(),
+                                });
+                            }
+                            KeyCode::Char('[') | KeyCode::Char(']') => {
+                                slot = if key.code == KeyCode::Char(']') {
+                                    (slot + 1) % SLOTS
+                                } else {
+                                    (slot + SLOTS - 1) % SLOTS
+                                };
+                                set_title(stdout, rom_path, slot, &speed)?;
+                                status = Some(format!("Slot {}", slot));
+                            }
+                            KeyCode::F(1) => {
+                                overlay = !overlay;
+                                // What it covered comes back
+                                shown = None;
+                                redraw = true;
+                            }
+                            KeyCode::Enter if paused => {
+                                paused = false;
+                                status = Some("Resumed".to_string());
+                            }
+                            KeyCode::F(10) => {
+                                status = Some(match recorder.take() {
+                                    Some(recorder) => {
+                                        let path = recorder.path().display().to_string();
+                                        match recorder.finish() {
+                                            Ok(()) => format!("Saved {}", path),
+                                            Err(err) => format!("Recording failed: {}", err),
+                                        }
+                                    }
+                                    None => {
+                                        let path = video::next_path(rom_path);
+                                        let dims = dims(chip8);
+                                        match Recorder::start(&path, options.palette(), scale, dims)
+                                        {
+                                            Ok(started) => {
+                                                recorder = Some(started);
+                                                format!("Recording to {}", path.display())
+                                            }
+                                            Err(err) => format!("Can't record: {}", err),
+                                        }
+                                    }
+                                });
+                            }
+                            _ => {}
+                        }
+                    }
+                    if key.code == KeyCode::Backspace && !matches!(input, Input::Netplay(_)) {
+                        rewind_press = match key.kind {
+                            KeyEventKind::Release => None,
+                            _ => Some(Instant::now()),
+                        };
+                    }
+                    if !matches!(input, Input::Replay(_)) {
+                        let pressed = key.kind != KeyEventKind::Release;
+                        let changed = match pressed {
+                            true => keys.press(key.code),
+                            false => keys.release(key.code),
+                        };
+                        if let Some(k) = changed {
+                            debug!(
+                                "Key {:X} {}",
+                                k,
+                                if pressed { "pressed" } else { "released" }
+                            );
+                            press(chip8, input, frame, k, pressed)?;
+                            if pressed && options.latency_test {
+                                latency_press = Some(read_at);
+                            }
+                        }
+                    }
+                }
+                Event::Resize(cols, rows) => {
+                    // The display moves, so the old one has to go
+                    size = (cols, rows);
+                    queue!(stdout, ResetColor, Clear(ClearType::All))?;
+                    shown = None;
+                    redraw = true;
+                }
+                _ => {}
+            }
+        }
+        pacer.finish_wait();
+        let due = pacer.take_due(Instant::now());
+
+        // A rebuilt ROM starts over on a fresh machine with the
+        // same settings; the slot and any recording carry on
+        if let Some(rom) = watcher.as_mut().and_then(|w| w.poll()) {
+            let restarted =
+                rom_from_file(rom_path, rom).and_then(|rom| start(machine, rom_path, rom));
+            status = Some(match restarted {
+                Ok((reloaded, fresh)) => {
+                    (*game, *chip8) = (reloaded, fresh);
+                    rom_hash = savestate::rom_hash(&game.rom);
+                    (halted, paused) = (false, false);
+                    keys.clear();
+                    queue!(stdout, ResetColor, Clear(ClearType::All))?;
+                    shown = None;
+                    redraw = true;
+                    execute!(
+                        stdout,
+                        SetTitle(format!("chip8-emu - {} - reloaded", file_name(rom_path)))
+                    )?;
+                    title_reset = Some(Instant::now() + Duration::from_secs(1));
+                    info!("Restarted {} after it changed", rom_path.display());
+                    format!("Reloaded {}", file_name(rom_path))
+                }
+                Err(err) => format!("Can't reload: {}", err),
+            });
+        }
+        if title_reset.is_some_and(|t| t <= Instant::now()) {
+            title_reset = None;
+            set_title(stdout, rom_path, slot, &speed)?;
+        }
+
+        if !guard.enhanced_keys {
+            for k in keys.release_stale(KEY_HOLD) {
+                debug!("Key {:X} released (no repeat)", k);
+                press(chip8, input, frame, k, false)?;
+            }
+            if rewind_press.is_some_and(|t| t.elapsed() >= KEY_HOLD) {
+                rewind_press = None;
+            }
+        }
+        // Runs the frames that have come due (more than one when
+        // the terminal is slow to draw), then draws once. With
+        // --unlimited a batch can be thousands of frames, and only
+        // its last is kept for rewinding and recorded, which would
+        // otherwise cost more than running them.
+        for n in 0..due {
+            let kept = options.pacing != Pacing::Unlimited || n + 1 == due;
+            if !matches!(input, Input::Replay(_)) {
+                for (k, pressed) in keys.tick() {
+                    press(chip8, input, frame, k, pressed)?;
+                }
+            }
+
+            // With netplay a frame waits for the other player's keys,
+            // which go on the keypad along with this side's
+            let mut ready = true;
+            if let Input::Netplay(session) = input {
+                session.poll()?;
+                if !halted && !paused {
+                    match session.keys_for(frame)? {
+                        Some(keys) => chip8.set_keys(keys),
+                        None => ready = false,
+                    }
+                }
+                if !ready {
+                    let since = *net_wait.get_or_insert_with(Instant::now);
+                    if since.elapsed() >= NET_WAIT_NOTICE && !net_wait_shown {
+                        status = Some("Waiting for the other player...".to_string());
+                        net_wait_shown = true;
+                    }
+                } else if net_wait.take().is_some() && net_wait_shown {
+                    status.get_or_insert_with(String::new);
+                    net_wait_shown = false;
+                }
+            }
+
+            // Holding Backspace plays the recorded frames backwards
+            if rewind_press.is_some() {
+                if chip8.rewind() && halted {
+                    (halted, title_reset) = (false, Some(Instant::now()));
+                }
+                redraw |= chip8.present_frame();
+            } else if !halted && !paused && ready {
+                if let Input::Replay(replay) = input {
+                    for &(_, k, pressed) in replay.take(frame) {
+                        chip8.set_key(k, pressed);
+                    }
+                    if replay.finished() {
+                        status = Some("Replay finished".to_string());
+                        *input = Input::Keyboard;
+                    }
+                }
+                if kept {
+                    chip8.push_rewind();
+                }
+                match crash::guard(chip8, |chip8| chip8.step_frame(game.budget))? {
+                    Ok(output) => {
+                        redraw |= output.display_changed;
+                        if let Some(practice) = &mut practice
+                            && practice.frame(frame, &output, chip8)
+                        {
+                            status = Some(format!("Checkpoint {} taken", practice.len()));
+                        }
+                        // Otherwise it looks like the emulator froze
+                        let now_stuck = chip8.is_halted() || chip8.is_looping();
+                        if now_stuck && !stuck {
+                            status = Some(if chip8.ran_past_rom() {
+                                format!("Halted: ran past the program at {:#05X}", chip8.pc())
+                            } else if chip8.is_halted() {
+                                format!("Halted: jump to itself at {:#05X}", chip8.pc())
+                            } else {
+                                "Looping without drawing or reading the keys".to_string()
+                            });
+                        } else if stuck && !now_stuck {
+                            status.get_or_insert_with(String::new);
+                        }
+                        stuck = now_stuck;
+                    }
+                    Err(err) => {
+                        // Kept in the title until the game moves again
+                        execute!(
+                            stdout,
+                            SetTitle(format!(
+                                "chip8-emu - {} - stopped: {}",
+                                file_name(rom_path),
+                                err
+                            ))
+                        )?;
+                        // A ROM that stops straight away most likely
+                        // needs what the start-up notice says
+                        status.get_or_insert_with(|| format!("Stopped: {}", err));
+                        (halted, title_reset) = (true, None);
+                    }
+                }
+                if let Some(trace) = &options.trace {
+                    trace.write(chip8)?;
+                }
+                let hits = chip8.take_watchpoint_hits();
+                if let Some(hit) = hits.first() {
+                    let more = match hits.len() {
+                        1 => String::new(),
+                        n => format!(" (+{} more)", n - 1),
+                    };
+                    status = Some(format!("{}{}, Enter resumes", hit, more));
+                    paused = true;
+                }
+                if let Input::Netplay(session) = input {
+                    session.frame_done(frame, chip8.display_hash())?;
+                }
+                frames += 1;
+                frame += 1;
+            }
+            if let Some(recorder) = &recorder
+                && kept
+            {
+                recorder.frame(chip8.display_composited(), chip8.display_width());
+            }
+        }
+        if stats_start.elapsed() >= Duration::from_secs(1) {
+            let secs = stats_start.elapsed().as_secs_f64();
+            // A reload starts the count over
+            let instructions = chip8.cycle_count().saturating_sub(stats_cycles);
+            let (ips, fps) = (instructions as f64 / secs, frames as f64 / secs);
+            debug!("{:.0} instructions/s, {:.1} frames/s", ips, fps);
+            if options.show_speed {
+                speed = format!(" - {:.0} IPS, {:.0} FPS", ips, fps);
+                if title_reset.is_none() && !halted {
+                    set_title(stdout, rom_path, slot, &speed)?;
+                }
+            }
+            (frames, stats_cycles, stats_start) = (0, chip8.cycle_count(), Instant::now());
+        }
+
+        // The flash guard may still be catching up with a frame
+        // the machine drew a while ago
+        let catching_up = flash_guard
+            .as_ref()
+            .is_some_and(|g| !g.settled(chip8.display_composited()));
+        if chip8.take_draw_flag() || redraw || catching_up || latency_press.is_some() {
+            redraw = false;
+            let dims = dims(chip8);
+            if !fits(size, dims) {
+                draw_too_small(stdout, dims)?;
+                shown = None;
+            } else {
+                let lit;
+                let display = match (latency_press, &mut flash_guard) {
+                    // The latency test shows a key press as a fully
+                    // lit display
+                    (Some(_), _) => {
+                        lit = vec![1; chip8.display.len()];
+                        &lit
+                    }
+                    (None, Some(guard)) => guard.present(chip8.display_composited()),
+                    (None, None) => chip8.display_composited(),
+                };
+                // A display of another size (a reloaded ROM, or
+                // 00FE/00FF) is drawn in full, over a cleared
+                // terminal in case it's smaller
+                let previous = shown.as_deref().filter(|s| s.len() == display.len());
+                if shown.is_some() && previous.is_none() {
+                    queue!(stdout, ResetColor, Clear(ClearType::All))?;
+                }
+                draw(stdout, display, previous, &layout(size, dims), &colors)?;
+                shown = Some(display.to_vec());
+            }
+            // The time from reading the key to the flushed frame
+            // showing it. The game's own display comes back on
+            // the next frame.
+            if let Some(read_at) = latency_press.take() {
+                let ms = read_at.elapsed().as_secs_f64() * 1000.0;
+                info!("Latency test: key to display in {:.2}ms", ms);
+                status = Some(format!("Key to display: {:.2}ms", ms));
+                redraw = true;
+            }
+        }
+        if overlay && fits(size, dims(chip8)) {
+            draw_overlay(stdout, chip8, &layout(size, dims(chip8)))?;
+        }
+        // The terminal has no sound, so a beep lights a cell in the
+        // top right corner instead. Turning it off redraws the
+        // display under it.
+        if fits(size, dims(chip8)) {
+            if chip8.buzzer() {
+                draw_beep_light(stdout, &layout(size, dims(chip8)))?;
+            } else if beep_light {
+                (shown, redraw) = (None, true);
+            }
+            beep_light = chip8.buzzer();
+        }
+
+        // Messages go on the line under the display, if the
+        // terminal has one
+        if let Some(msg) = status.take()
+            && fits(size, dims(chip8))
+        {
+            let layout = layout(size, dims(chip8));
+            if (layout.top as usize + layout.scale * layout.rows / 2) < size.1 as usize {
+                draw_status(stdout, &msg, &layout)?;
+            }
+        }
+    }
+}
+
+// ROM files in `dir`, sorted by name
+// A keypad key from the keyboard, saved if input is being recorded
+fn press(
+    chip8: &mut Chip8,
+    input: &mut Input,
+    frame: u64,
+    key: usize,
+    pressed: bool,
+) -> io::Result<()> {
+    match input {
+        Input::Record(recorder) => recorder.record(frame, key, pressed)?,
+        // Keys only reach the keypad in lockstep with the other
+        // player's, see play()
+        Input::Netplay(session) => {
+            session.set_key(key, pressed);
+            return Ok(());
+        }
+        _ => {}
+    }
+    chip8.set_key(key, pressed);
+    Ok(())
+}
+
+// A restored state has the keys that were down when it was
+// saved; this puts back the ones held now
+fn resync_keys(chip8: &mut Chip8, input: &mut Input, frame: u64, keys: &KeyMap) -> io::Result<()> {
+    if matches!(input, Input::Replay(_)) {
+        return Ok(());
+    }
+    let (held, down) = (keys.down(), chip8.keys());
+    for key in (0..16).filter(|key| (held ^ down) & 1 << key != 0) {
+        press(chip8, input, frame, key, held & 1 << key != 0)?;
+    }
+    Ok(())
+}
+
+fn find_roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    roms.sort();
+    Ok(roms)
+}
+
+// File name with anything unprintable replaced, so odd names
+// can't mess up the terminal
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_control() { '?' } else { c })
+        .collect()
+}
+
+fn draw_menu(
+    stdout: &mut Stdout,
+    roms: &[PathBuf],
+    selected: usize,
+    message: Option<&str>,
+) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let width = cols as usize;
+
+    queue!(
+        stdout,
+        ResetColor,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(truncate("Up/Down select, Enter plays, Esc quits", width))
+    )?;
+
+    // Keep the selection in view when the list is taller
+    // than the terminal
+    let visible = (rows as usize).saturating_sub(3).max(1);
+    let first = selected.saturating_sub(visible - 1);
+    for (i, path) in roms.iter().enumerate().skip(first).take(visible) {
+        let line = truncate(&format!("  {}", file_name(path)), width);
+        queue!(stdout, MoveTo(0, (i - first + 2) as u16))?;
+        if i == selected {
+            queue!(
+                stdout,
+                SetForegroundColor(Color::Black),
+                SetBackgroundColor(Color::Green),
+                Print(line),
+                ResetColor
+            )?;
+        } else {
+            queue!(stdout, Print(line))?;
+        }
+    }
+
+    if let Some(message) = message {
+        queue!(
+            stdout,
+            MoveTo(0, rows.saturating_sub(1)),
+            SetForegroundColor(Color::Red),
+            Print(truncate(message, width)),
+            ResetColor
+        )?;
+    }
+
+    stdout.flush()
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+// The display's width and height in pixels
+fn dims(chip8: &Chip8) -> (usize, usize) {
+    (chip8.display_width(), chip8.display_height())
+}
+
+// Whether a display of `width` by `height` pixels fits in the
+// terminal
+fn fits((cols, rows): (u16, u16), (width, height): (usize, usize)) -> bool {
+    cols as usize >= width && rows as usize >= height / 2
+}
+
+// Where the display goes: the biggest whole number of cells per
+// pixel that fits, centred, so a 64x64 display is a square with
+// blank space either side. A row is kept free for messages when
+// the terminal is only just big enough for a scale.
+struct Layout {
+    scale: usize,
+    left: u16,
+    top: u16,
+    // The display's width and height in pixels
+    cols: usize,
+    rows: usize,
+}
+
+fn layout((cols, rows): (u16, u16), (width, height): (usize, usize)) -> Layout {
+    let (cols, rows) = (cols as usize, rows as usize);
+    let scale = (cols / width)
+        .min(rows.saturating_sub(1) / (height / 2))
+        .max(1);
+    Layout {
+        scale,
+        left: (cols.saturating_sub(width * scale) / 2) as u16,
+        top: (rows.saturating_sub(height / 2 * scale + 1) / 2) as u16,
+        cols: width,
+        rows: height,
+    }
+}
+
+// Draws the display, or with `shown` (what's on the terminal
+// now) only the part of each line that changed since
+fn draw(
+    stdout: &mut Stdout,
+    display: &[u8],
+    shown: Option<&[u8]>,
+    layout: &Layout,
+    palette: &[Color; 4],
+) -> io::Result<()> {
+    let (scale, w) = (layout.scale, layout.cols);
+    for row in 0..layout.rows / 2 * scale {
+        // Each cell is two half-height pixels, which makes them
+        // square, so scaling both ways by the same amount keeps
+        // the 2:1 shape
+        let (top_y, bottom_y) = (row * 2 / scale, (row * 2 + 1) / scale);
+        let pixels = |d: &[u8], x: usize| {
+            (
+                (d[top_y * w + x] & 3) as usize,
+                (d[bottom_y * w + x] & 3) as usize,
+            )
+        };
+
+        // Most frames only move a sprite or two, so most lines
+        // are skipped and the rest only print from the first to
+        // the last pixel that changed
+        let (first, last) = match shown {
+            None => (0, w - 1),
+            Some(shown) => {
+                let mut changed = (0..w).filter(|&x| pixels(display, x) != pixels(shown, x));
+                let Some(first) = changed.next() else {
+                    continue;
+                };
+                (first, changed.next_back().unwrap_or(first))
+            }
+        };
+        queue!(
+            stdout,
+            MoveTo(
+                layout.left + (first * scale) as u16,
+                layout.top + row as u16
+            )
+        )?;
+
+        // Colors only change between runs of cells, so plain
+        // CHIP-8 games still get one Print per line
+        let mut colors = None;
+        let mut line = String::new();
+        for col in first * scale..(last + 1) * scale {
+            let (c, fg, bg) = match pixels(display, col / scale) {
+                (0, 0) => (' ', 1, 0),
+                (t, b) if t == b => ('█', t, 0),
+                (t, 0) => ('▀', t, 0),
+                (0, b) => ('▄', b, 0),
+                (t, b) => ('▀', t, b),
+            };
+            if colors != Some((fg, bg)) {
+                if !line.is_empty() {
+                    queue!(stdout, Print(&line))?;
+                    line.clear();
+                }
+                queue!(
+                    stdout,
+                    SetForegroundColor(palette[fg]),
+                    SetBackgroundColor(palette[bg])
+                )?;
+                colors = Some((fg, bg));
+            }
+            line.push(c);
+        }
+        queue!(stdout, Print(line))?;
+    }
+
+    queue!(stdout, ResetColor)?;
+    stdout.flush()
+}
+
+// PC, I, the V registers and the top of the stack, on the first
+// lines of the display. Redrawn every frame, over whatever the
+// display drew there.
+fn draw_overlay(stdout: &mut Stdout, chip8: &Chip8, layout: &Layout) -> io::Result<()> {
+    let hex = |v: &[u8]| v.iter().map(|v| format!("{:02X}", v)).collect::<Vec<_>>();
+    let v = chip8.registers();
+    let top = match chip8.stack().last() {
+        Some(addr) => format!(
+            "{:03X} ({} of {} deep)",
+            addr,
+            chip8.stack_depth(),
+            chip8.stack_limit()
+        ),
+        None => "empty".to_string(),
+    };
+    let lines = [
+        format!("PC {:03X}  I {:03X}  stack {}", chip8.pc(), chip8.i(), top),
+        format!("V0-V7 {}", hex(&v[..8]).join(" ")),
+        format!("V8-VF {}", hex(&v[8..]).join(" ")),
+    ];
+    queue!(
+        stdout,
+        SetForegroundColor(Color::White),
+        SetBackgroundColor(Color::DarkBlue)
+    )?;
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            MoveTo(layout.left, layout.top + row as u16),
+            Print(truncate(line, layout.cols * layout.scale))
+        )?;
+    }
+    queue!(stdout, ResetColor)?;
+    stdout.flush()
+}
+
+fn draw_beep_light(stdout: &mut Stdout, layout: &Layout) -> io::Result<()> {
+    queue!(
+        stdout,
+        MoveTo(
+            layout.left + (layout.cols * layout.scale) as u16 - 1,
+            layout.top
+        ),
+        SetForegroundColor(Color::Yellow),
+        Print('█'),
+        ResetColor
+    )?;
+    stdout.flush()
+}
+
+fn draw_status(stdout: &mut Stdout, msg: &str, layout: &Layout) -> io::Result<()> {
+    let row = layout.top + (layout.rows / 2 * layout.scale) as u16;
+    queue!(
+        stdout,
+        ResetColor,
+        MoveTo(0, row),
+        Clear(ClearType::CurrentLine),
+        MoveTo(layout.left, row),
+        Print(truncate(msg, layout.cols * layout.scale))
+    )?;
+    stdout.flush()
+}
+
+fn set_title(stdout: &mut Stdout, rom_path: &Path, slot: u8, speed: &str) -> io::Result<()> {
+    execute!(
+        stdout,
+        SetTitle(format!(
+            "chip8-emu - {} - slot {}{}",
+            file_name(rom_path),
+            slot,
+            speed
+        ))
+    )
+}
+
+fn draw_too_small(stdout: &mut Stdout, (width, height): (usize, usize)) -> io::Result<()> {
+    queue!(
+        stdout,
+        ResetColor,
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(format!(
+            "Terminal too small (need {}x{})",
+            width,
+            height / 2
+        ))
+    )?;
+    stdout.flush()
+}
@@ -0,0 +1,310 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use gif::{Encoder, Frame, Repeat};
+
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// Colors for pixel values 0-3 (off, and the XO-CHIP planes)
+pub type Palette = [[u8; 3]; 4];
+
+// Set from the command line
+pub struct Options {
+    // Start recording to this file as soon as a game starts
+    pub path: Option<PathBuf>,
+    // Image pixels per CHIP-8 pixel
+    pub scale: u16,
+}
+
+// Records the display to an animated GIF, or to a directory of
+// numbered PPM images if `path` doesn't end in .gif. Encoding
+// happens on a worker thread so it doesn't cost frame time.
+//
+// Frames are placed on the wall clock's 60Hz ticks, so if the
+// host stalls the previous frame is held (repeated) and if two
+// frames land on the same tick only the last one is kept.
+pub struct Recorder {
+    path: PathBuf,
+    // The display's size when recording started, which every
+    // frame is fitted to
+    width: usize,
+    rows: usize,
+    start: Instant,
+    frames: Option<Sender<(u64, Vec<u8>)>>,
+    worker: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Recorder {
+    // `width` and `rows` are the display's size, which a GIF
+    // can't change once started
+    pub fn start(
+        path: &Path,
+        palette: Palette,
+        scale: u16,
+        (width, rows): (usize, usize),
+    ) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let mut sink: Box<dyn Sink> = if is_gif(path) {
+            Box::new(GifSink::new(path, palette, scale, width, rows)?)
+        } else {
+            fs::create_dir_all(path)?;
+            Box::new(PpmSink {
+                dir: path.to_path_buf(),
+                palette,
+                scale: scale as usize,
+                width,
+                count: 0,
+            })
+        };
+
+        let (frames, received) = mpsc::channel::<(u64, Vec<u8>)>();
+        let worker = thread::spawn(move || {
+            let mut pending: Option<(u64, Vec<u8>)> = None;
+            for (tick, display) in received {
+                match pending.take() {
+                    // Same tick: the newer frame replaces it
+                    Some((start, _)) if tick <= start => pending = Some((start, display)),
+                    // Nothing changed, keep holding the frame
+                    Some((start, prev)) if prev == display => pending = Some((start, prev)),
+                    Some((start, prev)) => {
+                        sink.write(&prev, start, tick)?;
+                        pending = Some((tick, display));
+                    }
+                    None => pending = Some((tick, display)),
+                }
+            }
+            // The recording stopped, so the last frame gets one tick
+            if let Some((start, display)) = pending {
+                sink.write(&display, start, start + 1)?;
+            }
+            sink.finish()
+        });
+
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            width,
+            rows,
+            start: Instant::now(),
+            frames: Some(frames),
+            worker: Some(worker),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Records `display`, `width` pixels wide
+    pub fn frame(&self, display: &[u8], width: usize) {
+        let tick = (self.start.elapsed().as_nanos() / TICK.as_nanos()) as u64;
+        if let Some(frames) = &self.frames {
+            // A send only fails if the worker already gave up;
+            // finish() reports why
+            let _ = frames.send((tick, self.fit(display, width)));
+        }
+    }
+
+    // A reloaded ROM can change the display's height, which is cut
+    // off or padded to fit the recording, and 00FE/00FF its
+    // resolution, which is scaled to fit
+    fn fit(&self, display: &[u8], width: usize) -> Vec<u8> {
+        if width == self.width {
+            let mut display = display.to_vec();
+            display.resize(self.width * self.rows, 0);
+            return display;
+        }
+        let height = display.len() / width;
+        let mut out = Vec::with_capacity(self.width * self.rows);
+        for y in 0..self.rows {
+            let row = y * height / self.rows * width;
+            out.extend((0..self.width).map(|x| display[row + x * width / self.width]));
+        }
+        out
+    }
+
+    // Waits for the queued frames to be written and closes the file
+    pub fn finish(mut self) -> io::Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        self.frames = None;
+        match self.worker.take().map(|worker| worker.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("video encoder panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+// Quitting mid-recording still writes a complete file
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+// First `<rom>.rec<N>.gif` that doesn't exist yet
+pub fn next_path(rom: &Path) -> PathBuf {
+    (0..)
+        .map(|n| {
+            let mut name = rom.as_os_str().to_owned();
+            name.push(format!(".rec{}.gif", n));
+            PathBuf::from(name)
+        })
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn is_gif(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+}
+
+trait Sink: Send {
+    // Shows `display` from tick `start` until tick `end`
+    fn write(&mut self, display: &[u8], start: u64, end: u64) -> io::Result<()>;
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+struct GifSink {
+    encoder: Option<Encoder<BufWriter<File>>>,
+    scale: usize,
+    width: usize,
+    rows: usize,
+}
+
+impl GifSink {
+    fn new(
+        path: &Path,
+        palette: Palette,
+        scale: u16,
+        width: usize,
+        rows: usize,
+    ) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(
+            file,
+            width as u16 * scale,
+            rows as u16 * scale,
+            palette.as_flattened(),
+        )
+        .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        Ok(GifSink {
+            encoder: Some(encoder),
+            scale: scale as usize,
+            width,
+            rows,
+        })
+    }
+}
+
+impl Sink for GifSink {
+    fn write(&mut self, display: &[u8], start: u64, end: u64) -> io::Result<()> {
+        let Some(encoder) = &mut self.encoder else {
+            return Ok(());
+        };
+
+        // GIF delays are in hundredths of a second, which 60Hz
+        // doesn't divide. Rounding the end points instead of the
+        // length keeps the total in sync.
+        let centis = |tick: u64| tick * 100 / 60;
+        let mut delay = centis(end) - centis(start);
+
+        let pixels = scaled(display, self.width, self.scale);
+        // Long holds are split, the delay field is only 16 bits
+        while delay > 0 {
+            let frame = Frame {
+                width: (self.width * self.scale) as u16,
+                height: (self.rows * self.scale) as u16,
+                delay: delay.min(u16::MAX as u64) as u16,
+                buffer: pixels.as_slice().into(),
+                ..Frame::default()
+            };
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+            delay -= frame.delay as u64;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.into_inner()?.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+struct PpmSink {
+    dir: PathBuf,
+    palette: Palette,
+    scale: usize,
+    width: usize,
+    count: u64,
+}
+
+impl Sink for PpmSink {
+    fn write(&mut self, display: &[u8], start: u64, end: u64) -> io::Result<()> {
+        let image = ppm(display, self.width, self.palette, self.scale);
+
+        // One image per tick, so a held frame is repeated
+        for _ in start..end {
+            let path = self.dir.join(format!("frame{:06}.ppm", self.count));
+            fs::write(path, &image)?;
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Saves a single frame, `width` pixels wide, as a PPM image
+pub fn screenshot(
+    path: &Path,
+    display: &[u8],
+    width: usize,
+    palette: Palette,
+    scale: u16,
+) -> io::Result<()> {
+    fs::write(path, ppm(display, width, palette, scale.max(1) as usize))
+}
+
+fn ppm(display: &[u8], width: usize, palette: Palette, scale: usize) -> Vec<u8> {
+    let (w, h) = (width * scale, display.len() / width * scale);
+    let mut image = format!("P6\n{} {}\n255\n", w, h).into_bytes();
+    image.extend(
+        scaled(display, width, scale)
+            .iter()
+            .flat_map(|&p| palette[p as usize]),
+    );
+    image
+}
+
+// Blows a display `width` pixels wide up to `scale` pixels per
+// CHIP-8 pixel
+fn scaled(display: &[u8], width: usize, scale: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(display.len() * scale * scale);
+    for row in display.chunks(width) {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|&p| std::iter::repeat_n(p & 3, scale))
+            .collect();
+        for _ in 0..scale {
+            out.extend_from_slice(&line);
+        }
+    }
+    out
+}
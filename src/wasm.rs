@@ -1,13 +1,22 @@
-use crate::Chip8;
+use crate::{
+    Chip8, FONTSET, HIRES_ENTRY, Inspector, MEMORY_SIZE, PROGRAM_START_ADDR, TickClock, Variant,
+    XO_CHIP_MEMORY_SIZE, decode, is_hires_rom,
+};
+use std::time::Duration;
 use wasm_bindgen::prelude::*;
 
-const FONTSET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
-    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
-    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
-    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
-    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
-];
+// The built-in 4x5 hex font, so the frontend can draw text
+// with the same glyphs the ROMs use
+#[wasm_bindgen]
+pub fn fontset() -> Vec<u8> {
+    FONTSET.to_vec()
+}
+
+// Mnemonic for a raw opcode, e.g. 0x8124 -> "ADD V1, V2"
+#[wasm_bindgen]
+pub fn disassemble(opcode: u16) -> String {
+    decode(opcode).to_string()
+}
 
 #[wasm_bindgen]
 pub struct WasmChip8 {
@@ -19,47 +28,428 @@ impl WasmChip8 {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
         let mut chip8 = Chip8::new();
-        chip8.load_fontset(FONTSET.to_vec());
+        chip8.load_default_fontset();
+        chip8.set_undo(true);
         WasmChip8 { inner: chip8 }
     }
 
-    // Load ROM from raw bytes (JS passes a Uint8Array)
-    pub fn load_rom(&mut self, rom: &[u8]) {
-        let end = 0x200 + rom.len();
-        self.inner.memory[0x200..end].copy_from_slice(rom);
+    // Like new(), but programs are loaded and started at `addr`
+    pub fn with_start(addr: u16) -> Result<WasmChip8, JsError> {
+        let mut chip8 = Chip8::new_with_start(addr)?;
+        chip8.load_default_fontset();
+        chip8.set_undo(true);
+        Ok(WasmChip8 { inner: chip8 })
+    }
+
+    // Load ROM from raw bytes (JS passes a Uint8Array). Hi-res
+    // ROMs loaded at 0x200 get the 64x64 display and start past
+    // their interpreter patch (see HIRES_ENTRY), so check
+    // display_height() afterwards.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        self.inner.load_rom_bytes(rom)?;
+        if self.inner.program_start == PROGRAM_START_ADDR && is_hires_rom(rom) {
+            self.inner.set_display_height(64)?;
+            self.inner.set_pc(HIRES_ENTRY)?;
+        }
+        Ok(())
+    }
+
+    // Throws if the ROM did something the machine can't continue
+    // from. The display is read with framebuffer_ptr() instead of
+    // being copied out after every instruction.
+    pub fn emulate_cycle(&mut self) -> Result<(), JsError> {
+        self.inner.emulate_cycle()?;
+        Ok(())
+    }
+
+    // Runs up to `cycles` instructions, stopping early if the
+    // display wait quirk stalls the CPU until the next vblank
+    pub fn tick(&mut self, cycles: u32) -> Result<(), JsError> {
+        for _ in 0..cycles {
+            self.inner.emulate_cycle()?;
+            if self.inner.waiting_for_vblank() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn display(&self) -> Vec<u8> {
+        self.inner.display.to_vec()
+    }
+
+    // Where the display (one byte a pixel, display_width() by
+    // display_height()) lives in wasm
+    // memory, so JS can read it without a copy:
+    //
+    //     new Uint8Array(wasm.memory.buffer, ptr, len)
+    //
+    // The view is only good until memory grows, so make a new one
+    // each time it's read.
+    pub fn framebuffer_ptr(&self) -> *const u8 {
+        self.inner.display.as_ptr()
+    }
+
+    pub fn framebuffer_len(&self) -> usize {
+        self.inner.display.len()
+    }
+
+    pub fn display_width(&self) -> usize {
+        self.inner.display_width()
+    }
+
+    pub fn display_height(&self) -> usize {
+        self.inner.display_height()
+    }
+
+    pub fn push_rewind(&mut self) {
+        self.inner.push_rewind();
+    }
+
+    pub fn rewind(&mut self) -> bool {
+        self.inner.rewind()
     }
 
-    // Returns owned Vec<u8> — wasm-bindgen can cross the boundary with this
-    pub fn emulate_cycle(&mut self) -> Vec<u8> {
-        self.inner.emulate_cycle().to_vec()
+    // Takes back the last instruction, for stepping backward while
+    // paused. The undo log is always on here; false once it runs
+    // out.
+    pub fn undo(&mut self) -> bool {
+        self.inner.undo()
     }
 
     pub fn update_timers(&mut self) {
         self.inner.update_timers();
     }
 
-    pub fn draw_flag(&self) -> bool {
-        self.inner.draw_flag
+    // The display as white-on-black RGBA pixels, ready for
+    // new ImageData(...) and putImageData. XO-CHIP's second plane
+    // is light grey and pixels in both planes dark grey.
+    pub fn display_rgba(&self) -> Vec<u8> {
+        const GREYS: [u8; 4] = [0x00, 0xFF, 0xAA, 0x55];
+        self.inner
+            .display
+            .iter()
+            .flat_map(|&p| {
+                let c = GREYS[(p & 3) as usize];
+                [c, c, c, 0xFF]
+            })
+            .collect()
+    }
+
+    // True while the buzzer sounds (see Chip8::buzzer())
+    pub fn beeping(&self) -> bool {
+        self.inner.buzzer()
+    }
+
+    // Whether the last update_timers() turned the buzzer on, or
+    // off
+    pub fn beep_started(&self) -> bool {
+        self.inner.beep_started()
+    }
+
+    pub fn beep_ended(&self) -> bool {
+        self.inner.beep_ended()
+    }
+
+    // Calls in progress, and how many there can be
+    pub fn stack_depth(&self) -> usize {
+        self.inner.stack_depth()
+    }
+
+    pub fn stack_limit(&self) -> usize {
+        self.inner.stack_limit()
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.inner.pc()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.inner.is_halted()
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.inner.is_looping()
+    }
+
+    pub fn i(&self) -> u16 {
+        self.inner.i()
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.inner.memory().len()
+    }
+
+    // 256 bytes of RAM starting at page * 0x100, for the memory
+    // viewer. Only the visible page crosses into JS each frame.
+    pub fn memory_page(&self, page: usize) -> Vec<u8> {
+        let start = (page * 0x100).min(self.inner.memory().len());
+        let end = (start + 0x100).min(self.inner.memory().len());
+        self.inner.memory()[start..end].to_vec()
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.inner.delay_timer()
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.inner.sound_timer()
+    }
+
+    // Instructions run so far (see Chip8::cycle_count()), as a
+    // number rather than a BigInt
+    pub fn cycle_count(&self) -> f64 {
+        self.inner.cycle_count() as f64
+    }
+
+    // See Chip8::take_draw_flag(); the flag has no separate
+    // getter, so a change can't be lost between reading and
+    // clearing it
+    pub fn take_draw_flag(&mut self) -> bool {
+        self.inner.take_draw_flag()
+    }
+
+    pub fn enable_trace(&mut self) {
+        self.inner.enable_trace();
+    }
+
+    pub fn disable_trace(&mut self) {
+        self.inner.disable_trace();
+    }
+
+    // Newline-separated trace lines since the last call
+    pub fn take_trace_log(&mut self) -> String {
+        self.inner.take_trace_log()
+    }
+
+    // Recent (PC, opcode) pairs flattened as [pc, op, pc, op, ...]
+    pub fn trace(&self) -> Vec<u16> {
+        self.inner
+            .trace()
+            .into_iter()
+            .flat_map(|(pc, op)| [pc, op])
+            .collect()
+    }
+
+    // Wrap sprites around the left and right edges (on) or clip
+    // them at the right (off)
+    pub fn set_wrap_x(&mut self, on: bool) {
+        self.inner.quirks.wrap_x = on;
+    }
+
+    // Wrap sprites around the top and bottom edges (on) or clip
+    // them at the bottom (off)
+    pub fn set_wrap_y(&mut self, on: bool) {
+        self.inner.quirks.wrap_y = on;
+    }
+
+    // Limit DXYN to one draw per frame
+    pub fn set_display_wait(&mut self, on: bool) {
+        self.inner.quirks.display_wait = on;
     }
 
-    pub fn clear_draw_flag(&mut self) {
-        self.inner.draw_flag = false;
+    // Shift V[Y] into V[X] in 8XY6/8XYE
+    pub fn set_shift_uses_vy(&mut self, on: bool) {
+        self.inner.quirks.shift_uses_vy = on;
+    }
+
+    // Advance I past the registers in FX55/FX65
+    pub fn set_index_increment_on_store(&mut self, on: bool) {
+        self.inner.quirks.index_increment_on_store = on;
+    }
+
+    // Clear VF after 8XY1/8XY2/8XY3
+    pub fn set_vf_reset_on_logic(&mut self, on: bool) {
+        self.inner.quirks.vf_reset_on_logic = on;
+    }
+
+    // Skip 0NNN instead of stopping
+    pub fn set_ignore_machine_code(&mut self, on: bool) {
+        self.inner.quirks.ignore_machine_code = on;
+    }
+
+    // BXNN jumps to XNN + V[X] (CHIP-48/SCHIP)
+    pub fn set_jump_with_vx(&mut self, on: bool) {
+        self.inner.quirks.jump_with_vx = on;
+    }
+
+    // Scroll half as far, like SUPER-CHIP 1.1 in low resolution
+    pub fn set_half_scroll(&mut self, on: bool) {
+        self.inner.quirks.half_scroll = on;
+    }
+
+    // Count each key press once; see Chip8::set_key_repeat_guard()
+    pub fn set_key_repeat_guard(&mut self, on: bool) {
+        self.inner.set_key_repeat_guard(on);
+    }
+
+    pub fn vblank(&mut self) {
+        self.inner.vblank();
+    }
+
+    pub fn waiting_for_vblank(&self) -> bool {
+        self.inner.waiting_for_vblank()
     }
 
     pub fn key_down(&mut self, key: u8) {
-        if (key as usize) < 16 {
-            self.inner.keypad[key as usize] = 1;
-        }
+        self.inner.set_key(key as usize, true);
     }
 
     pub fn key_up(&mut self, key: u8) {
-        if (key as usize) < 16 {
-            self.inner.keypad[key as usize] = 0;
+        self.inner.set_key(key as usize, false);
+    }
+
+    // Resets the machine, keeping the configured load address,
+    // variant and quirks
+    pub fn reset(&mut self) {
+        self.rebuild(self.inner.variant, self.inner.memory.len());
+    }
+
+    // Turns the XO-CHIP extensions on or off. XO-CHIP has 64KB of
+    // memory, so this starts a fresh machine like reset(); load
+    // the ROM afterwards.
+    pub fn set_xo_chip(&mut self, on: bool) {
+        match on {
+            true => self.rebuild(Variant::XoChip, XO_CHIP_MEMORY_SIZE),
+            false => self.rebuild(Variant::Chip8, MEMORY_SIZE),
+        }
+    }
+
+    // The XO-CHIP audio pattern (16 bytes, 128 1-bit samples), or
+    // undefined until the ROM loads one and the beep should be
+    // played as usual
+    pub fn audio_pattern(&self) -> Option<Vec<u8>> {
+        self.inner.audio_pattern().map(|p| p.to_vec())
+    }
+
+    // Samples per second to play the audio pattern at
+    pub fn audio_rate(&self) -> f64 {
+        self.inner.audio_rate()
+    }
+}
+
+impl WasmChip8 {
+    // A fresh machine with the same load address and quirks
+    fn rebuild(&mut self, variant: Variant, memory_size: usize) {
+        let quirks = self.inner.quirks;
+        self.inner = Chip8::with_layout(memory_size, self.inner.program_start);
+        self.inner.quirks = quirks;
+        self.inner.variant = variant;
+        self.inner.load_default_fontset();
+        self.inner.set_undo(true);
+    }
+}
+
+// How many frames or timer ticks have come due; see TickClock.
+// Times are in milliseconds, as performance.now() gives them.
+#[wasm_bindgen]
+pub struct WasmTickClock {
+    inner: TickClock,
+}
+
+#[wasm_bindgen]
+impl WasmTickClock {
+    #[wasm_bindgen(constructor)]
+    pub fn new(hz: u32) -> Self {
+        WasmTickClock {
+            inner: TickClock::new(hz),
         }
     }
 
+    pub fn set_hz(&mut self, hz: u32) {
+        self.inner.set_hz(hz);
+    }
+
+    // Adds `elapsed_ms` (negative counts as none) and returns the
+    // ticks that came due
+    pub fn take(&mut self, elapsed_ms: f64) -> u32 {
+        let elapsed = Duration::from_secs_f64(elapsed_ms.max(0.0) / 1000.0);
+        self.inner.take(elapsed)
+    }
+
+    pub fn carried_ms(&self) -> f64 {
+        self.inner.carried().as_secs_f64() * 1000.0
+    }
+
     pub fn reset(&mut self) {
-        self.inner = Chip8::new();
-        self.inner.load_fontset(FONTSET.to_vec());
+        self.inner.reset();
+    }
+}
+
+// The register inspector panel (F4). JS draws it from cells() and
+// labels() and passes on clicks and keys; see Inspector.
+#[wasm_bindgen]
+pub struct WasmInspector {
+    inner: Inspector,
+}
+
+#[wasm_bindgen]
+impl WasmInspector {
+    // Make a new one whenever the machine's memory size changes
+    #[wasm_bindgen(constructor)]
+    pub fn new(memory_size: usize) -> Self {
+        WasmInspector {
+            inner: Inspector::new(memory_size),
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.inner.set_paused(paused);
+    }
+
+    // Each cell flattened as [col, row, value, digits, state], in
+    // character cells. State is 0 for a value that can only be
+    // looked at, 1 for one that can be edited and 2 for the
+    // selected one; while digits are being typed into it, value
+    // and digits are what's been typed so far.
+    pub fn cells(&self, chip8: &WasmChip8) -> Vec<u16> {
+        let chip8 = &chip8.inner;
+        Inspector::layout(chip8.stack_depth())
+            .into_iter()
+            .flat_map(|(field, col, row)| {
+                let selected = self.inner.selected() == Some(field);
+                let (value, digits) = match self.inner.typed() {
+                    (typed, count) if selected && count > 0 => (typed, count),
+                    _ => (field.read(chip8), self.inner.digits(field)),
+                };
+                let state = if selected { 2 } else { field.editable() as u16 };
+                [col as u16, row as u16, value, digits as u16, state]
+            })
+            .collect()
+    }
+
+    // Labels for the cells, in the same order
+    pub fn labels(&self, chip8: &WasmChip8) -> Vec<String> {
+        Inspector::layout(chip8.inner.stack_depth())
+            .into_iter()
+            .map(|(field, _, _)| field.label())
+            .collect()
+    }
+
+    // Selects the field at a character position. False while
+    // running, or if there's nothing editable there.
+    pub fn click(&mut self, chip8: &WasmChip8, col: usize, row: usize) -> bool {
+        Inspector::field_at(chip8.inner.stack_depth(), col, row)
+            .is_some_and(|field| self.inner.select(field))
+    }
+
+    // Tab and Shift+Tab
+    pub fn select_next(&mut self, forward: bool) -> bool {
+        self.inner.select_next(forward)
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.inner.selected().is_some()
+    }
+
+    // Esc: drops what's been typed, or the selection if nothing
+    // has
+    pub fn escape(&mut self) {
+        self.inner.escape();
+    }
+
+    // Throws if the machine refuses the finished value
+    pub fn type_digit(&mut self, chip8: &mut WasmChip8, digit: u8) -> Result<bool, JsError> {
+        Ok(self.inner.type_digit(&mut chip8.inner, digit)?)
     }
 }
@@ -0,0 +1,157 @@
+// The Octo assembler: small programs against bytes assembled by
+// hand, labels used before they're defined, where errors are
+// reported, and the disassembler reading the output back.
+
+use chip8_emu::{AsmError, assemble, decode};
+
+// The error assembling `source`
+fn error(source: &str) -> AsmError {
+    assemble(source).unwrap_err()
+}
+
+#[test]
+fn programs_match_hand_assembly() {
+    let source = "
+        : main
+            clear
+            v0 := 0x0A
+            v1 += 3
+            v2 -= 1      # added as 0xFF
+            v3 := v4
+            v5 ^= v6
+            v7 >>= v8
+            v9 =- va
+            i := 0x300
+            i += vb
+            i := hex vc
+            sprite vd ve 5
+            vf := random 0x3F
+            v1 := delay
+            v2 := key
+            delay := v3
+            buzzer := v4
+            bcd v5
+            save v6
+            load v7
+            return
+    ";
+    let expected = [
+        0x00, 0xE0, 0x60, 0x0A, 0x71, 0x03, 0x72, 0xFF, 0x83, 0x40, 0x85, 0x63, 0x87, 0x86, 0x89,
+        0xA7, 0xA3, 0x00, 0xFB, 0x1E, 0xFC, 0x29, 0xDD, 0xE5, 0xCF, 0x3F, 0xF1, 0x07, 0xF2, 0x0A,
+        0xF3, 0x15, 0xF4, 0x18, 0xF5, 0x33, 0xF6, 0x55, 0xF7, 0x65, 0x00, 0xEE,
+    ];
+    assert_eq!(assemble(source).unwrap(), expected);
+}
+
+#[test]
+fn conditions_skip_when_they_dont_hold() {
+    // `if` assembles to the opposite skip
+    let source = "
+        if v1 == 5 then v0 := 1
+        if v1 != v2 then v0 := 2
+        if v3 key then v0 := 3
+        if v3 -key then v0 := 4
+    ";
+    let expected = [
+        0x41, 0x05, 0x60, 0x01, 0x51, 0x20, 0x60, 0x02, 0xE3, 0xA1, 0x60, 0x03, 0xE3, 0x9E, 0x60,
+        0x04,
+    ];
+    assert_eq!(assemble(source).unwrap(), expected);
+}
+
+#[test]
+fn labels_can_be_used_before_theyre_defined() {
+    let source = "
+        :const SPEED 4
+        jump start
+        : data 0xF0 0x90
+        : start
+            i := data
+            loop
+                v0 += SPEED
+                sub
+            again
+        : sub return
+    ";
+    // 0x200 jump, 0x202 data, 0x204 start, loop at 0x206
+    let expected = [
+        0x12, 0x04, 0xF0, 0x90, 0xA2, 0x02, 0x70, 0x04, 0x22, 0x0C, 0x12, 0x06, 0x00, 0xEE,
+    ];
+    assert_eq!(assemble(source).unwrap(), expected);
+}
+
+#[test]
+fn main_gets_a_jump_when_its_not_first() {
+    let rom = assemble(": sub return : main sub").unwrap();
+    assert_eq!(rom, [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+    // Already at 0x200, it doesn't
+    assert_eq!(assemble(": main clear").unwrap(), [0x00, 0xE0]);
+}
+
+#[test]
+fn errors_give_the_line_and_token() {
+    let err = error("clear\n\n  v0 := 1\n  jump nowhere # oops\n");
+    assert_eq!((err.line, err.token.as_str()), (4, "nowhere"));
+    assert_eq!(err.to_string(), "Line 4: undefined label ('nowhere')");
+
+    let err = error("v0 := 1\nv1 += 256");
+    assert_eq!((err.line, err.token.as_str()), (2, "256"));
+    assert_eq!(err.message, "value out of range");
+
+    let err = error(": a\nclear\n: a");
+    assert_eq!((err.line, err.message.as_str()), (3, "label defined twice"));
+
+    let err = error("loop\n  clear\nagain\nagain");
+    assert_eq!((err.line, err.message.as_str()), (4, "again without loop"));
+
+    let err = error("clear\nloop clear");
+    assert_eq!((err.line, err.message.as_str()), (2, "loop without again"));
+
+    // `vx` is taken for a call, so it's the := that's out of place
+    let err = error("vx := 1");
+    assert_eq!((err.line, err.token.as_str()), (1, ":="));
+    assert_eq!(err.message, "unknown statement");
+
+    let err = error("if v0 < 3 then clear");
+    assert_eq!(err.message, "expected ==, !=, key or -key");
+}
+
+#[test]
+fn the_disassembler_reads_it_back() {
+    let source = "
+        : main
+            clear
+            v1 := 0x2A
+            v2 += 1
+            v1 += v2
+            v3 <<= v4
+            i := 0x345
+            sprite v1 v2 15
+            if v1 key then jump main
+            v5 := random 0xFF
+            i := hex v6
+            return
+    ";
+    let rom = assemble(source).unwrap();
+    let lines: Vec<String> = rom
+        .chunks(2)
+        .map(|word| decode(u16::from_be_bytes([word[0], word[1]])).to_string())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "CLS",
+            "LD V1, 0x2A",
+            "ADD V2, 0x01",
+            "ADD V1, V2",
+            "SHL V3, V4",
+            "LD I, 0x345",
+            "DRW V1, V2, 15",
+            "SKNP V1",
+            "JP 0x200",
+            "RND V5, 0xFF",
+            "LD F, V6",
+            "RET",
+        ]
+    );
+}
@@ -0,0 +1,62 @@
+// XO-CHIP's audio: F002 loads a 16-byte pattern from I and FX3A
+// sets the pitch it plays at. Both are kept in snapshots, and
+// other variants stop on them as invalid opcodes.
+
+mod common;
+
+use chip8_emu::{Chip8, Snapshot, Variant};
+use common::{ending, load, run_to_end};
+
+const PATTERN: &str = ": pattern 0x00 0xFF 0x0F 0xF0 0x33 0xCC 0x55 0xAA \
+    0x01 0x02 0x04 0x08 0x10 0x20 0x40 0x80";
+
+fn xo_chip(source: &str) -> Chip8 {
+    let mut chip8 = Chip8::builder().variant(Variant::XoChip).build().unwrap();
+    load(&mut chip8, &format!("{} {}", ending(source), PATTERN));
+    chip8
+}
+
+#[test]
+fn f002_loads_the_pattern_from_i() {
+    assert!(xo_chip("").audio_pattern().is_none());
+
+    let mut chip8 = xo_chip("i := pattern audio");
+    run_to_end(&mut chip8);
+    let pattern = chip8.audio_pattern().unwrap();
+    assert_eq!(pattern[..4], [0x00, 0xFF, 0x0F, 0xF0]);
+    assert_eq!(pattern[15], 0x80);
+}
+
+#[test]
+fn fx3a_sets_the_pitch() {
+    // 4000 samples a second at 64, an octave for every 48
+    for (pitch, rate) in [(64, 4000.0), (112, 8000.0), (16, 2000.0)] {
+        let mut chip8 = xo_chip(&format!("v1 := {} pitch := v1", pitch));
+        run_to_end(&mut chip8);
+        assert!((chip8.audio_rate() - rate).abs() < 1e-6, "{}", pitch);
+    }
+    assert_eq!(xo_chip("").audio_rate(), 4000.0);
+}
+
+#[test]
+fn snapshots_keep_the_pattern_and_pitch() {
+    let mut chip8 = xo_chip("i := pattern audio v1 := 112 pitch := v1");
+    run_to_end(&mut chip8);
+    let bytes = chip8.snapshot().to_bytes();
+
+    let mut other = Chip8::builder().variant(Variant::XoChip).build().unwrap();
+    other.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    assert_eq!(other.audio_pattern(), chip8.audio_pattern());
+    assert_eq!(other.audio_rate(), 8000.0);
+}
+
+#[test]
+fn only_xo_chip_has_them() {
+    for opcode in [0xF002u16, 0xF13A] {
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+        assert!(chip8.emulate_cycle().is_err(), "{:04X}", opcode);
+        assert!(chip8.audio_pattern().is_none());
+        assert_eq!(chip8.audio_rate(), 4000.0);
+    }
+}
@@ -0,0 +1,145 @@
+// What Chip8Builder::build() refuses: each BuildError, from the
+// options that cause it, with the message a frontend shows.
+
+use std::io;
+
+use chip8_emu::{BuildError, Chip8, FONTSET};
+
+#[test]
+fn the_defaults_build() {
+    let chip8 = Chip8::builder().build().unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    assert_eq!(chip8.memory().len(), 4096);
+    assert_eq!(&chip8.memory()[0x50..0xA0], &FONTSET);
+}
+
+#[test]
+fn memory_too_small() {
+    let err = Chip8::builder().memory_size(0x90).build().err().unwrap();
+    assert_eq!(
+        err,
+        BuildError::MemoryTooSmall {
+            size: 0x90,
+            need: 0xA0
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Memory size 0x90 is too small to hold the fontset (need 0xA0)"
+    );
+}
+
+#[test]
+fn start_address_out_of_range() {
+    let err = Chip8::builder()
+        .start_address(0x1000)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        BuildError::StartAddressOutOfRange {
+            addr: 0x1000,
+            memory_size: 0x1000
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Start address 0x1000 is outside memory (0x000..0x1000)"
+    );
+}
+
+#[test]
+fn start_address_in_fontset() {
+    let err = Chip8::builder().start_address(0x60).build().err().unwrap();
+    assert_eq!(
+        err,
+        BuildError::StartAddressInFontset {
+            addr: 0x60,
+            font: 0x50
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Start address 0x60 overlaps the fontset (0x50..0xA0)"
+    );
+}
+
+#[test]
+fn font_address() {
+    let err = Chip8::builder().font_address(0x300).build().err().unwrap();
+    assert_eq!(
+        err,
+        BuildError::FontAddress {
+            addr: 0x300,
+            end: 0x200
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Font address 0x300 leaves no room for the 80-byte font before 0x200"
+    );
+}
+
+#[test]
+fn entry_out_of_range() {
+    let err = Chip8::builder().entry(0x1000).build().err().unwrap();
+    assert_eq!(
+        err,
+        BuildError::EntryOutOfRange {
+            addr: 0x1000,
+            memory_size: 0x1000
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Entry point 0x1000 is outside memory (0x000..0x1000)"
+    );
+}
+
+#[test]
+fn invalid_fontset() {
+    let err = Chip8::builder().fontset(&[0xF0; 79]).build().err().unwrap();
+    assert_eq!(
+        err,
+        BuildError::InvalidFontset {
+            len: 79,
+            max: 0x1B0
+        }
+    );
+
+    // Both sets, with a start address that leaves room for 112
+    let err = Chip8::builder()
+        .start_address(0xC0)
+        .fontset(&[0xF0; 240])
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        BuildError::InvalidFontset {
+            len: 240,
+            max: 0x70
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "Fontset must be 80 bytes, or 240 with the large digits (at most 112 fit before the program), got 240"
+    );
+}
+
+#[test]
+fn display_height() {
+    let err = Chip8::builder().display_height(40).build().err().unwrap();
+    assert_eq!(err, BuildError::DisplayHeight(40));
+    assert_eq!(
+        err.to_string(),
+        "Display height must be one of [32, 48, 64] rows, got 40"
+    );
+}
+
+#[test]
+fn errors_become_invalid_input() {
+    let err = Chip8::builder().display_height(40).build().err().unwrap();
+    assert_eq!(io::Error::from(err).kind(), io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,91 @@
+// 00E0 on every kind of display: the whole of a tall one, only
+// the selected XO-CHIP planes, and a redraw only when something
+// was actually lit.
+
+mod common;
+
+use chip8_emu::{Chip8, Variant};
+use common::{ending, lit, load, machine, run, run_to_end};
+
+#[test]
+fn the_whole_of_a_tall_display_is_cleared() {
+    for rows in [48, 64] {
+        let mut chip8 = Chip8::builder().display_height(rows).build().unwrap();
+        load(
+            &mut chip8,
+            &ending(&format!(
+                "v1 := {} i := hex v0 sprite v0 v1 5 clear",
+                rows - 5
+            )),
+        );
+        run_to_end(&mut chip8);
+        assert_eq!(chip8.display.len(), 64 * rows);
+        assert!(lit(&chip8, 0xFF).is_empty());
+    }
+}
+
+#[test]
+fn only_the_selected_planes_are_cleared() {
+    // A pixel in both planes (F301), then clear with only plane 2
+    // selected (F201)
+    let mut chip8 = Chip8::builder().variant(Variant::XoChip).build().unwrap();
+    load(
+        &mut chip8,
+        &ending("i := dot 0xF3 0x01 sprite v0 v0 1 0xF2 0x01 clear jump end : dot 0x80 0x80"),
+    );
+    run_to_end(&mut chip8);
+    assert_eq!((lit(&chip8, 1).len(), lit(&chip8, 2).len()), (1, 0));
+
+    // Both selected, both cleared
+    let mut chip8 = Chip8::builder().variant(Variant::XoChip).build().unwrap();
+    load(
+        &mut chip8,
+        &ending("i := dot 0xF3 0x01 sprite v0 v0 1 clear jump end : dot 0x80 0x80"),
+    );
+    run_to_end(&mut chip8);
+    assert!(lit(&chip8, 0xFF).is_empty());
+}
+
+#[test]
+fn clearing_a_blank_display_needs_no_redraw() {
+    let mut chip8 = Chip8::builder().build().unwrap();
+    load(&mut chip8, ": top clear jump top");
+    chip8.step_frame(15).unwrap();
+    let output = chip8.step_frame(15).unwrap();
+    assert!(output.cleared);
+    assert!(!output.display_changed);
+
+    let mut chip8 = Chip8::builder().build().unwrap();
+    load(
+        &mut chip8,
+        "i := hex v0 : top clear sprite v0 v0 1 jump top",
+    );
+    chip8.step_frame(15).unwrap();
+    assert!(chip8.step_frame(15).unwrap().display_changed);
+}
+
+#[test]
+fn the_flicker_filter_still_blends_across_a_clear() {
+    // Draws the top of the 0 (4 pixels) on the first frame and
+    // clears on the second
+    let mut chip8 = Chip8::builder().flicker_filter(2).build().unwrap();
+    load(&mut chip8, &ending("i := hex v0 sprite v0 v0 1 clear"));
+    let mut frames = Vec::new();
+    for budget in [2, 1, 1] {
+        chip8.step_frame(budget).unwrap();
+        let shown = chip8.display_composited().iter().filter(|&&p| p != 0);
+        frames.push((lit(&chip8, 1).len(), shown.count()));
+    }
+    // What was cleared lingers for a frame, then goes
+    assert_eq!(frames, [(4, 4), (0, 4), (0, 0)]);
+}
+
+#[test]
+fn a_clear_is_taken_as_a_change_only_when_it_erases() {
+    let mut chip8 = machine("clear i := hex v0 sprite v0 v0 5 clear clear sprite v0 v0 0");
+    // (instructions, redraw needed)
+    for (cycles, changed) in [(1, false), (2, true), (1, true), (1, false), (1, false)] {
+        run(&mut chip8, cycles);
+        assert_eq!(chip8.take_draw_flag(), changed, "PC={:#X}", chip8.pc());
+    }
+}
@@ -0,0 +1,135 @@
+// The chip8-emu binary itself, for what only the command line
+// does: each test writes its ROM to a temporary file and runs
+// it headless.
+
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{self, Command, Output},
+};
+
+fn rom(name: &str, bytes: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("chip8-emu-cli-{}-{}.ch8", process::id(), name));
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn chip8_emu(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8-emu"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+// 6105 1602: V1 := 5, then jump to itself at 0x602, so it only
+// runs where it's loaded at 0x600
+const AT_0X600: [u8; 4] = [0x61, 0x05, 0x16, 0x02];
+
+#[test]
+fn load_addr_loads_and_starts_the_rom_there() {
+    let path = rom("load-addr", &AT_0X600);
+    let output = chip8_emu(&["--headless", "--load-addr", "0x600", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("pc: 0x0602"), "{}", out);
+    assert!(out.contains("v1: 0x05"), "{}", out);
+}
+
+#[test]
+fn bad_load_addrs_are_usage_errors() {
+    let path = rom("bad-load-addr", &AT_0X600);
+    let path = path.to_str().unwrap();
+    for (addr, msg) in [
+        ("0xE00", "Load address 0xE00 out of range (0x000..0xE00)"),
+        (
+            "0x60",
+            "Start address 0x60 overlaps the fontset (0x50..0xA0)",
+        ),
+        ("six", "--load-addr expects an address"),
+    ] {
+        let output = chip8_emu(&["--headless", "--load-addr", addr, path]);
+        assert_eq!(output.status.code(), Some(2), "{}", addr);
+        assert!(stderr(&output).starts_with(msg), "{}", stderr(&output));
+    }
+    // Clear of a moved font is fine
+    let path = rom("load-addr-0x60", &[0x61, 0x05, 0x10, 0x62]);
+    let path = path.to_str().unwrap();
+    let output = chip8_emu(&[
+        "--headless",
+        "--font-address",
+        "0x000",
+        "--load-addr",
+        "0x60",
+        path,
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+// 6105 7101 FFFF: two instructions, then an invalid opcode
+const STOPS: [u8; 6] = [0x61, 0x05, 0x71, 0x01, 0xFF, 0xFF];
+
+#[test]
+fn trace_prints_each_instruction_ahead_of_the_state() {
+    let path = rom("trace", &STOPS);
+    let output = chip8_emu(&["--headless", "--trace", path.to_str().unwrap()]);
+    let out = stdout(&output);
+    let lines: Vec<&str> = out.lines().take(3).collect();
+    assert_eq!(
+        lines,
+        [
+            "PC=0x0200 OP=0x6105 V1=00 V0=00 I=0x0000 SP=0  LD V1, 0x05",
+            "PC=0x0202 OP=0x7101 V1=05 V0=00 I=0x0000 SP=0  ADD V1, 0x01",
+            "PC=0x0204 OP=0xFFFF VF=00 VF=00 I=0x0000 SP=0  DW 0xFFFF",
+        ]
+    );
+    assert!(out.contains("\ncycles: 2\n"), "{}", out);
+}
+
+#[test]
+fn trace_file_takes_the_trace_off_stdout() {
+    let path = rom("trace-file", &STOPS);
+    let log = env::temp_dir().join(format!("chip8-emu-cli-{}-trace.log", process::id()));
+    let output = chip8_emu(&[
+        "--headless",
+        "--trace-file",
+        log.to_str().unwrap(),
+        path.to_str().unwrap(),
+    ]);
+    assert!(stdout(&output).starts_with("cycles: 2\n"));
+    let trace = fs::read_to_string(&log).unwrap();
+    assert_eq!(trace.lines().count(), 3);
+    assert!(trace.starts_with("PC=0x0200 OP=0x6105"), "{}", trace);
+
+    // The terminal's stdout is the display
+    let output = chip8_emu(&["--tui", "--trace", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn a_stopped_rom_is_reported_with_its_last_instructions() {
+    let path = rom("stopped", &STOPS);
+    let output = chip8_emu(&["--headless", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(3));
+    let err = stderr(&output);
+    assert!(err.contains("Invalid opcode 0xFFFF at 0x204"), "{}", err);
+    assert!(err.contains("PC=0x0202 OP=0x7101  ADD V1, 0x01"), "{}", err);
+}
+
+#[test]
+fn timer_hz_has_to_be_at_least_1() {
+    let path = rom("timer-hz", &AT_0X600);
+    let path = path.to_str().unwrap();
+    let output = chip8_emu(&["--headless", "--timer-hz", "0", path]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).starts_with("--timer-hz must be between 1 and 240"));
+    let output = chip8_emu(&["--headless", "--timer-hz", "30", path]);
+    assert_eq!(output.status.code(), Some(5));
+}
@@ -0,0 +1,48 @@
+// Helpers shared by the test files: machines with an Octo program
+// loaded, running them, and reading the display back. Each file
+// uses only some of them.
+#![allow(dead_code)]
+
+use chip8_emu::{Chip8, assemble};
+
+// Assembles `source` and loads it into `chip8`
+pub fn load(chip8: &mut Chip8, source: &str) {
+    let rom = assemble(source).unwrap_or_else(|err| panic!("{}: {}", source, err));
+    chip8.load_rom_bytes(&rom).unwrap();
+}
+
+// A machine with the default settings and `source` loaded
+pub fn machine(source: &str) -> Chip8 {
+    let mut chip8 = Chip8::builder().build().unwrap();
+    load(&mut chip8, source);
+    chip8
+}
+
+// `source` followed by `: end jump end`, so it can `jump end` to
+// stop, and stops there when it runs out (see run_to_end())
+pub fn ending(source: &str) -> String {
+    format!("{} : end jump end", source)
+}
+
+// Runs `cycles` instructions
+pub fn run(chip8: &mut Chip8, cycles: usize) {
+    for _ in 0..cycles {
+        chip8.emulate_cycle().unwrap();
+    }
+}
+
+// Runs until the program stops in a jump to itself
+pub fn run_to_end(chip8: &mut Chip8) {
+    while !chip8.is_halted() {
+        chip8.emulate_cycle().unwrap();
+    }
+}
+
+// The pixels lit in any of `planes`, as (x, y)
+pub fn lit(chip8: &Chip8, planes: u8) -> Vec<(usize, usize)> {
+    let width = chip8.display_width();
+    (0..chip8.display.len())
+        .filter(|&n| chip8.display[n] & planes != 0)
+        .map(|n| (n % width, n / width))
+        .collect()
+}
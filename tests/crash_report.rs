@@ -0,0 +1,50 @@
+// The machine state dumped when the emulator panics: enough to
+// see what the ROM was doing without a debugger.
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::{machine, run};
+
+#[test]
+fn the_report_shows_the_cpu_and_memory_around_pc() {
+    let mut chip8 = machine("v3 := 0x2A i := 0x345 : top jump top");
+    run(&mut chip8, 3);
+    let report = chip8.crash_report();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines[0], "PC=0x0204  last OP=0x1204 at 0x0204  JP 0x204");
+    assert_eq!(lines[1], "I=0x0345 SP=0 DT=0 ST=0 cycles=3");
+    assert!(lines[2].contains("V3=2A"));
+    // PC's row is marked, with two rows either side
+    let rows: Vec<&str> = lines[4..].to_vec();
+    assert_eq!(rows.len(), 5);
+    assert_eq!(
+        rows[2],
+        ">0200: 63 2A A3 45 12 04 00 00 00 00 00 00 00 00 00 00"
+    );
+    assert!(rows[0].starts_with(" 01E0:"));
+}
+
+#[test]
+fn the_window_stays_inside_memory() {
+    // Nothing has run yet, so there's no last instruction
+    let mut chip8 = machine("jump 0xFFE");
+    assert!(chip8.crash_report().starts_with("PC=0x0200\n"));
+
+    chip8.emulate_cycle().unwrap();
+    let report = chip8.crash_report();
+    let rows: Vec<&str> = report.lines().skip(4).collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[2].starts_with(">0FF0:"));
+}
+
+#[test]
+fn the_last_row_can_be_short() {
+    // 4100 bytes end four bytes into the row at 0x1000
+    let mut chip8 = Chip8::builder().memory_size(4100).build().unwrap();
+    chip8.set_pc(0x1002).unwrap();
+    let report = chip8.crash_report();
+    let rows: Vec<&str> = report.lines().skip(4).collect();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[2], ">1000: 00 00 00 00");
+}
@@ -0,0 +1,58 @@
+// DXYN asks for a redraw only when a pixel actually changed, and
+// pixels clipped off the edge never collide.
+
+mod common;
+
+use chip8_emu::{Chip8, Quirks};
+use common::{lit, load, machine, run};
+
+#[test]
+fn a_sprite_in_the_corner_is_clipped() {
+    // The 0's top row is 0xF0; only its first pixel is on screen
+    let quirks = Quirks {
+        wrap_x: false,
+        wrap_y: false,
+        ..Quirks::default()
+    };
+    let mut chip8 = Chip8::builder().quirks(quirks).build().unwrap();
+    load(
+        &mut chip8,
+        "v1 := 63 v2 := 31 i := hex v0 sprite v1 v2 5 sprite v1 v2 5",
+    );
+    run(&mut chip8, 4);
+    assert_eq!(lit(&chip8, 1), [(63, 31)]);
+    assert_eq!(chip8.registers()[0xF], 0);
+    assert!(chip8.take_draw_flag());
+
+    // Drawn again, it collides and the pixel goes out
+    run(&mut chip8, 1);
+    assert!(lit(&chip8, 1).is_empty());
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert!(chip8.take_draw_flag());
+}
+
+#[test]
+fn draws_that_change_nothing_need_no_redraw() {
+    // Zero rows, then a blank sprite
+    let mut chip8 = machine("i := blank sprite v0 v0 0 sprite v0 v0 1 : blank 0x00");
+    chip8.set_register(0xF, 1).unwrap();
+    run(&mut chip8, 2);
+    assert_eq!(chip8.registers()[0xF], 0);
+    assert!(!chip8.take_draw_flag());
+    run(&mut chip8, 1);
+    assert_eq!(chip8.registers()[0xF], 0);
+    assert!(!chip8.take_draw_flag());
+}
+
+#[test]
+fn an_identical_redraw_erases_and_collides() {
+    let mut chip8 = machine("i := hex v0 sprite v0 v0 5 sprite v0 v0 5");
+    run(&mut chip8, 2);
+    assert_eq!(lit(&chip8, 1).len(), 14);
+    chip8.take_draw_flag();
+
+    run(&mut chip8, 1);
+    assert!(lit(&chip8, 1).is_empty());
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert!(chip8.take_draw_flag());
+}
@@ -0,0 +1,23 @@
+// take_draw_flag() reports each display change once.
+
+mod common;
+
+use common::machine;
+
+#[test]
+fn each_change_is_taken_once() {
+    let mut chip8 = machine("i := hex v0 sprite v0 v0 5 v1 := 1 sprite v0 v0 5");
+    assert!(!chip8.take_draw_flag());
+
+    chip8.emulate_cycle().unwrap();
+    assert!(!chip8.take_draw_flag());
+    chip8.emulate_cycle().unwrap();
+    assert!(chip8.take_draw_flag());
+    assert!(!chip8.take_draw_flag());
+
+    // A change after the last take isn't lost
+    chip8.emulate_cycle().unwrap();
+    chip8.emulate_cycle().unwrap();
+    assert!(chip8.take_draw_flag());
+    assert!(!chip8.draw_flag);
+}
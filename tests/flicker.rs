@@ -0,0 +1,52 @@
+// The flicker filter: display_composited() ORs the last few
+// frames together, so a sprite erased and redrawn every frame
+// stays visible, and what's erased lingers for the rest of the
+// window.
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::load;
+
+// Pixels display_composited() shows after each of `frames` frames
+// of 2 instructions, with I at the 0's top row (4 pixels)
+fn shown(window: usize, source: &str, frames: usize) -> Vec<usize> {
+    let mut chip8 = Chip8::builder().flicker_filter(window).build().unwrap();
+    load(&mut chip8, source);
+    chip8.set_i(0x50).unwrap();
+    (0..frames)
+        .map(|_| {
+            chip8.step_frame(2).unwrap();
+            chip8
+                .display_composited()
+                .iter()
+                .filter(|&&p| p != 0)
+                .count()
+        })
+        .collect()
+}
+
+#[test]
+fn a_sprite_drawn_every_other_frame_stays_lit() {
+    let toggling = ": top sprite v0 v0 1 jump top";
+    assert_eq!(shown(1, toggling, 4), [4, 0, 4, 0]);
+    assert_eq!(shown(2, toggling, 4), [4, 4, 4, 4]);
+    assert_eq!(shown(3, toggling, 4), [4, 4, 4, 4]);
+}
+
+#[test]
+fn erased_pixels_linger_for_the_window() {
+    // Drawn on the first frame, erased on the second
+    let erased = "sprite v0 v0 1 v1 := 0 sprite v0 v0 1 : end jump end";
+    assert_eq!(shown(1, erased, 4), [4, 0, 0, 0]);
+    assert_eq!(shown(2, erased, 4), [4, 4, 0, 0]);
+    assert_eq!(shown(3, erased, 4), [4, 4, 4, 0]);
+}
+
+#[test]
+fn the_window_is_one_to_three_frames() {
+    let toggling = ": top sprite v0 v0 1 jump top";
+    // 0 is off, and more than 3 is 3
+    assert_eq!(shown(0, toggling, 2), [4, 0]);
+    assert_eq!(shown(9, toggling, 4), shown(3, toggling, 4));
+}
@@ -0,0 +1,70 @@
+// Moving the font: FX29 and FX30 follow it wherever it goes, and
+// a font that would overlap the program is refused.
+
+mod common;
+
+use chip8_emu::{BuildError, Chip8, FONTSET, Variant};
+use common::{load, machine, run};
+
+#[test]
+fn fx29_points_at_the_default_font() {
+    let mut chip8 = machine("v0 := 7 i := hex v0");
+    run(&mut chip8, 2);
+    assert_eq!(chip8.font_address(), 0x50);
+    assert_eq!(chip8.i(), 0x50 + 7 * 5);
+}
+
+#[test]
+fn fx29_points_at_a_moved_font() {
+    let mut chip8 = Chip8::builder().font_address(0x000).build().unwrap();
+    load(&mut chip8, "v0 := 3 i := hex v0");
+    run(&mut chip8, 2);
+    assert_eq!(chip8.i(), 3 * 5);
+    assert_eq!(&chip8.memory()[..FONTSET.len()], &FONTSET);
+    // The large digits now end where they used to start
+    assert!(chip8.memory()[0xF0..0x140].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn fx30_follows_the_moved_small_font() {
+    let mut chip8 = Chip8::builder()
+        .variant(Variant::XoChip)
+        .font_address(0x000)
+        .build()
+        .unwrap();
+    load(&mut chip8, "v0 := 2 i := bighex v0");
+    run(&mut chip8, 2);
+    assert_eq!(chip8.i() as usize, FONTSET.len() + 2 * 10);
+}
+
+#[test]
+fn a_font_that_runs_into_the_program_is_refused() {
+    let err = Chip8::builder()
+        .start_address(0x600)
+        .font_address(0x1D0)
+        .build()
+        .err();
+    assert_eq!(
+        err,
+        Some(BuildError::FontAddress {
+            addr: 0x1D0,
+            end: 0x200
+        })
+    );
+}
+
+#[test]
+fn a_program_inside_the_moved_font_is_refused() {
+    let err = Chip8::builder()
+        .font_address(0x100)
+        .start_address(0x120)
+        .build()
+        .err();
+    assert_eq!(
+        err,
+        Some(BuildError::StartAddressInFontset {
+            addr: 0x120,
+            font: 0x100
+        })
+    );
+}
@@ -0,0 +1,119 @@
+// Custom fonts: FX29 and FX30 point at the loaded digits, the
+// built-in large font has all 16, and fonts of the wrong size are
+// refused.
+
+mod common;
+
+use chip8_emu::{BIG_FONTSET, BuildError, Chip8, FONTSET, Variant};
+use common::{load, run};
+
+// Digit N of the small font is five rows of 0x10 + N, and of the
+// large one ten rows of 0x80 + N
+fn custom_font() -> Vec<u8> {
+    let small = (0..16u8).flat_map(|n| [0x10 + n; 5]);
+    let large = (0..16u8).flat_map(|n| [0x80 + n; 10]);
+    small.chain(large).collect()
+}
+
+fn xo_chip(font: &[u8]) -> Chip8 {
+    Chip8::builder()
+        .variant(Variant::XoChip)
+        .fontset(font)
+        .build()
+        .unwrap()
+}
+
+// The bytes at I after `source`
+fn glyph(chip8: &mut Chip8, source: &str, len: usize) -> Vec<u8> {
+    load(chip8, source);
+    run(chip8, 2);
+    let i = chip8.i() as usize;
+    chip8.memory()[i..i + len].to_vec()
+}
+
+#[test]
+fn fx29_points_at_a_custom_fonts_digits() {
+    let mut chip8 = xo_chip(&custom_font()[..80]);
+    assert_eq!(glyph(&mut chip8, "v0 := 0xA i := hex v0", 5), [0x1A; 5]);
+    // Given only the small digits, the large ones are built in
+    let mut chip8 = xo_chip(&custom_font()[..80]);
+    assert_eq!(
+        glyph(&mut chip8, "v0 := 0xA i := bighex v0", 10),
+        BIG_FONTSET[100..110]
+    );
+}
+
+#[test]
+fn fx30_points_at_a_custom_fonts_large_digits() {
+    let mut chip8 = xo_chip(&custom_font());
+    assert_eq!(
+        glyph(&mut chip8, "v0 := 0xF i := bighex v0", 10),
+        [0x8F; 10]
+    );
+}
+
+#[test]
+fn fx30_needs_super_chip_or_xo_chip() {
+    let mut chip8 = Chip8::builder()
+        .variant(Variant::SuperChip)
+        .build()
+        .unwrap();
+    assert_eq!(
+        glyph(&mut chip8, "v0 := 7 i := bighex v0", 10),
+        BIG_FONTSET[70..80]
+    );
+    // SUPER-CHIP keeps CHIP-8's 4KB
+    assert_eq!(chip8.memory().len(), 4096);
+
+    let mut chip8 = Chip8::builder().build().unwrap();
+    load(&mut chip8, "v0 := 7 i := bighex v0");
+    run(&mut chip8, 1);
+    assert!(chip8.emulate_cycle().is_err());
+}
+
+#[test]
+fn the_built_in_large_font_has_every_digit() {
+    for (n, digit) in BIG_FONTSET.chunks(10).enumerate() {
+        assert!(digit.iter().any(|&row| row != 0), "{:X}", n);
+        let mut chip8 = xo_chip(&FONTSET);
+        let source = format!("v0 := {} i := bighex v0", n);
+        assert_eq!(glyph(&mut chip8, &source, 10), digit);
+    }
+}
+
+#[test]
+fn fonts_of_other_sizes_are_refused() {
+    let mut chip8 = Chip8::builder().build().unwrap();
+    for len in [0, 79, 81, 160, 239, 241] {
+        let err = chip8.load_fontset(vec![0xF0; len]).err();
+        assert_eq!(err, Some(BuildError::InvalidFontset { len, max: 0x1B0 }));
+    }
+    // Nothing was loaded
+    assert_eq!(chip8.memory()[0x50..0xA0], FONTSET);
+    assert!(chip8.load_fontset(custom_font()).is_ok());
+}
+
+#[test]
+fn a_full_font_must_fit_before_a_low_program() {
+    // 0x50 + 240 bytes runs to 0x140
+    let err = Chip8::builder()
+        .start_address(0x120)
+        .fontset(&custom_font())
+        .build()
+        .err();
+    assert_eq!(
+        err,
+        Some(BuildError::InvalidFontset {
+            len: 240,
+            max: 0xD0
+        })
+    );
+    // The small digits alone fit
+    assert!(
+        Chip8::builder()
+            .start_address(0x120)
+            .fontset(&custom_font()[..80])
+            .build()
+            .is_ok()
+    );
+}
@@ -0,0 +1,288 @@
+// run_emulator() end to end, with sinks that record what they're
+// given: frames run like step_frame() would run them, input
+// reaches the keypad, and the display and buzzer are passed on.
+
+mod common;
+
+use chip8_emu::{
+    AudioSink, Chip8, DisplaySink, Framebuffer, InputSource, KeyEvent, NoAudio, NoDisplay, NoInput,
+    RunConfig, run_emulator,
+};
+use common::load;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+// A seeded machine `rows` tall with `source` loaded
+fn seeded(source: &str, rows: usize) -> Chip8 {
+    let mut chip8 = Chip8::builder()
+        .display_height(rows)
+        .seed(1)
+        .build()
+        .unwrap();
+    load(&mut chip8, source);
+    chip8
+}
+
+fn frames(n: u64) -> RunConfig {
+    RunConfig {
+        frame_time: None,
+        max_frames: Some(n),
+        ..RunConfig::default()
+    }
+}
+
+// Each frame presented: its size and lit pixels
+#[derive(Default)]
+struct Screens(Vec<(usize, usize, usize)>);
+
+impl DisplaySink for Screens {
+    fn present(&mut self, fb: &Framebuffer) {
+        let lit = fb.pixels.iter().filter(|&&p| p != 0).count();
+        self.0.push((fb.width, fb.height, lit));
+    }
+}
+
+// Hands out `script[n]` on the nth poll, and quits after the last
+struct Script {
+    script: Vec<Vec<KeyEvent>>,
+    polls: usize,
+}
+
+impl Script {
+    fn new(script: Vec<Vec<KeyEvent>>) -> Self {
+        Script { script, polls: 0 }
+    }
+}
+
+impl InputSource for Script {
+    fn poll(&mut self) -> Vec<KeyEvent> {
+        self.polls += 1;
+        self.script.get(self.polls - 1).cloned().unwrap_or_default()
+    }
+
+    fn should_quit(&self) -> bool {
+        self.polls > self.script.len()
+    }
+}
+
+// The buzzer turning on and off
+#[derive(Default)]
+struct Beeps {
+    changes: Vec<bool>,
+}
+
+impl AudioSink for Beeps {
+    fn set_beeping(&mut self, on: bool) {
+        self.changes.push(on);
+    }
+}
+
+const BOUNCE: &str = "i := hex v0 \
+    : top sprite v1 v2 5 sprite v1 v2 5 v1 += 1 v2 += 1 v3 := random 0xFF jump top";
+
+#[test]
+fn frames_run_like_step_frame() {
+    let mut chip8 = seeded(BOUNCE, 32);
+    let ran = run_emulator(
+        &mut chip8,
+        &mut NoDisplay,
+        &mut NoInput,
+        &mut NoAudio,
+        frames(90),
+    );
+    assert_eq!(ran, Ok(90));
+
+    let mut expected = seeded(BOUNCE, 32);
+    for _ in 0..90 {
+        expected.step_frame(15).unwrap();
+    }
+    assert_eq!(chip8.display_hash(), expected.display_hash());
+    assert_eq!(chip8.registers(), expected.registers());
+    assert_eq!(chip8.cycle_count(), 90 * 15);
+}
+
+#[test]
+fn key_presses_reach_the_rom() {
+    // Waits for a key, then draws it
+    let mut chip8 = seeded("v0 := key i := hex v0 sprite v1 v1 5 : end jump end", 32);
+    let mut screens = Screens::default();
+    let mut events = vec![vec![]; 10];
+    events[3] = vec![KeyEvent::Down(8)];
+    events[5] = vec![KeyEvent::Up(8)];
+    let mut script = Script::new(events);
+
+    let ran = run_emulator(
+        &mut chip8,
+        &mut screens,
+        &mut script,
+        &mut NoAudio,
+        frames(100),
+    );
+    // The script ran out first
+    assert_eq!(ran, Ok(10));
+    assert_eq!(chip8.registers()[0], 8);
+    // The blank start, then the 8 once, when it was drawn
+    let lit_8 = 4 + 2 + 4 + 2 + 4;
+    assert_eq!(screens.0, [(64, 32, 0), (64, 32, lit_8)]);
+}
+
+#[test]
+fn the_buzzer_is_passed_on() {
+    let mut chip8 = seeded("v0 := 3 buzzer := v0 : end jump end", 32);
+    let mut beeps = Beeps::default();
+    run_emulator(
+        &mut chip8,
+        &mut NoDisplay,
+        &mut NoInput,
+        &mut beeps,
+        frames(10),
+    )
+    .unwrap();
+    assert_eq!(beeps.changes, [true, false]);
+
+    // Still sounding at the end, it's turned off
+    let mut chip8 = seeded("v0 := 100 buzzer := v0 : end jump end", 32);
+    let mut beeps = Beeps::default();
+    run_emulator(
+        &mut chip8,
+        &mut NoDisplay,
+        &mut NoInput,
+        &mut beeps,
+        frames(10),
+    )
+    .unwrap();
+    assert_eq!(beeps.changes, [true, false]);
+}
+
+#[test]
+fn pausing_and_stepping() {
+    let mut chip8 = seeded(": top v0 += 1 jump top", 32);
+    let mut script = Script::new(vec![
+        vec![],
+        vec![KeyEvent::Pause],
+        vec![],
+        vec![KeyEvent::Step],
+        vec![],
+        vec![KeyEvent::Pause],
+        vec![],
+    ]);
+    let ran = run_emulator(
+        &mut chip8,
+        &mut NoDisplay,
+        &mut script,
+        &mut NoAudio,
+        frames(100),
+    );
+    // Frames 0, 3 (stepped), 5 and 6
+    assert_eq!(ran, Ok(4));
+    assert_eq!(chip8.cycle_count(), 4 * 15);
+}
+
+#[test]
+fn hires_frames_carry_their_size() {
+    let mut chip8 = seeded("v1 := 50 i := hex v0 sprite v0 v1 5 : end jump end", 64);
+    let mut screens = Screens::default();
+    run_emulator(
+        &mut chip8,
+        &mut screens,
+        &mut NoInput,
+        &mut NoAudio,
+        frames(2),
+    )
+    .unwrap();
+    assert_eq!(screens.0.last(), Some(&(64, 64, 14)));
+}
+
+#[test]
+fn errors_stop_the_loop() {
+    let mut chip8 = seeded("return", 32);
+    let ran = run_emulator(
+        &mut chip8,
+        &mut NoDisplay,
+        &mut NoInput,
+        &mut NoAudio,
+        frames(5),
+    );
+    assert!(ran.is_err());
+}
+
+// Takes 10ms to show each frame
+struct SlowDisplay {
+    presents: u64,
+}
+
+impl DisplaySink for SlowDisplay {
+    fn present(&mut self, _: &Framebuffer) {
+        self.presents += 1;
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn a_slow_display_gets_fewer_frames_not_a_slower_game() {
+    // Draws every frame
+    let mut chip8 = seeded(": top clear sprite v0 v0 5 jump top", 32);
+    let mut display = SlowDisplay { presents: 0 };
+    let config = RunConfig {
+        frame_time: Some(Duration::from_millis(2)),
+        max_frames: Some(60),
+        ..RunConfig::default()
+    };
+    let start = Instant::now();
+    let ran = run_emulator(&mut chip8, &mut display, &mut NoInput, &mut NoAudio, config);
+    assert_eq!(ran, Ok(60));
+    // Several frames ran for each one shown
+    assert!(display.presents < 30, "{} presents", display.presents);
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[test]
+fn a_cycle_limit_can_end_partway_through_a_frame() {
+    let mut chip8 = seeded("v0 := 10 delay := v0 : top v1 += 1 jump top", 32);
+    let config = RunConfig {
+        cpf: 10,
+        max_cycles: Some(25),
+        ..frames(100)
+    };
+    let ran = run_emulator(
+        &mut chip8,
+        &mut NoDisplay,
+        &mut NoInput,
+        &mut NoAudio,
+        config,
+    );
+    // Two whole frames and half of a third, whose timer tick
+    // doesn't happen
+    assert_eq!(ran, Ok(3));
+    assert_eq!(chip8.cycle_count(), 25);
+    assert_eq!(chip8.delay_timer(), 8);
+}
+
+#[test]
+fn timers_tick_at_the_configured_rate() {
+    // The delay timer after 60 frames, set to 200 in the first
+    let after_a_second = |timer_hz| {
+        let mut chip8 = seeded("v0 := 200 delay := v0 : top jump top", 32);
+        let config = RunConfig {
+            timer_hz,
+            ..frames(60)
+        };
+        let ran = run_emulator(
+            &mut chip8,
+            &mut NoDisplay,
+            &mut NoInput,
+            &mut NoAudio,
+            config,
+        );
+        assert_eq!(ran, Ok(60));
+        chip8.delay_timer()
+    };
+    // The first frame's tick comes after FX15
+    assert_eq!(after_a_second(60), 140);
+    assert_eq!(after_a_second(30), 170);
+    assert_eq!(after_a_second(120), 80);
+    // A rate that doesn't divide into 60 doesn't drift
+    assert_eq!(after_a_second(45), 155);
+}
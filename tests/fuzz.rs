@@ -0,0 +1,143 @@
+// Whatever a ROM does, the emulator mustn't panic: an instruction
+// either runs or comes back as a Chip8Error. Random ROMs mostly
+// stop on an invalid opcode within a few instructions, so there's
+// also a run of ROMs made only of instructions that exist, which
+// keep going for the whole 10,000 cycles.
+
+mod common;
+
+use chip8_emu::{Chip8, Opcode, Quirks, Variant, assemble, decode};
+use common::load;
+use proptest::prelude::*;
+
+// Everything from 0x200 to the end of 4KB
+const ROM_SIZE: usize = 0x1000 - 0x200;
+const CYCLES: usize = 10_000;
+
+fn configured(variant: Variant, quirks: Quirks) -> Chip8 {
+    Chip8::builder()
+        .variant(variant)
+        .quirks(quirks)
+        .seed(1)
+        .build()
+        .unwrap()
+}
+
+// Runs until CYCLES instructions or the first error, changing the
+// held keys every so often
+fn fuzz(mut chip8: Chip8, rom: &[u8], keys: &[u16]) {
+    chip8.load_rom_bytes(rom).unwrap();
+    for cycle in 0..CYCLES {
+        if cycle % 500 == 0 {
+            chip8.set_keys(keys[cycle / 500 % keys.len()]);
+        }
+        if chip8.emulate_cycle().is_err() {
+            break;
+        }
+    }
+}
+
+// Opcodes that decode to an instruction. decode() only gives up on
+// some 8XYN, EXNN and FXNN, and clearing the top bit turns those
+// into 0NNN, 6XNN and 7XNN.
+fn instruction() -> impl Strategy<Value = u16> {
+    any::<u16>().prop_map(|op| match decode(op) {
+        Opcode::Invalid(_) => op & 0x7FFF,
+        _ => op,
+    })
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    any::<[bool; 9]>().prop_map(|q| Quirks {
+        wrap_x: q[0],
+        wrap_y: q[1],
+        display_wait: q[2],
+        shift_uses_vy: q[3],
+        index_increment_on_store: q[4],
+        vf_reset_on_logic: q[5],
+        ignore_machine_code: q[6],
+        jump_with_vx: q[7],
+        half_scroll: q[8],
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn random_bytes(
+        rom in prop::collection::vec(any::<u8>(), ROM_SIZE),
+        keys in prop::collection::vec(any::<u16>(), 1..20),
+        xo_chip in any::<bool>(),
+    ) {
+        let variant = if xo_chip { Variant::XoChip } else { Variant::Chip8 };
+        fuzz(configured(variant, Quirks::default()), &rom, &keys);
+    }
+
+    #[test]
+    fn random_instructions(
+        ops in prop::collection::vec(instruction(), ROM_SIZE / 2),
+        keys in prop::collection::vec(any::<u16>(), 1..20),
+        quirks in quirks(),
+    ) {
+        let rom: Vec<u8> = ops.iter().flat_map(|op| op.to_be_bytes()).collect();
+        // With 0NNN skipped, every opcode runs
+        let quirks = Quirks { ignore_machine_code: true, ..quirks };
+        fuzz(configured(Variant::XoChip, quirks), &rom, &keys);
+    }
+}
+
+// The corners random ROMs are unlikely to find
+
+fn run_source(variant: Variant, source: &str) {
+    fuzz(
+        configured(variant, Quirks::default()),
+        &assemble(source).unwrap(),
+        &[0],
+    );
+}
+
+#[test]
+fn fetch_at_end_of_memory() {
+    // A jump to 0xFFF fetches its second byte from 0x000
+    run_source(Variant::Chip8, "jump 0xFFF");
+}
+
+#[test]
+fn jump_past_end_of_memory() {
+    // BNNN can reach 0x10FE, past the end of 4KB
+    for variant in [Variant::Chip8, Variant::XoChip] {
+        run_source(variant, "v0 := 0xFF jump0 0xFFF");
+    }
+}
+
+#[test]
+fn index_at_end_of_memory() {
+    for variant in [Variant::Chip8, Variant::XoChip] {
+        run_source(
+            variant,
+            "i := 0xFFF sprite v0 v0 15 sprite v0 v0 0 bcd v0 save vf load vf \
+             v1 := 0xFF i += v1 save vf",
+        );
+    }
+    run_source(Variant::XoChip, "i := 0xFFF audio");
+}
+
+#[test]
+fn recursion_stops_at_the_stack_limit() {
+    let mut chip8 = configured(Variant::Chip8, Quirks::default());
+    load(&mut chip8, ": f f");
+    let err = (0..100).find_map(|_| chip8.emulate_cycle().err()).unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Stack overflow at 0x200 (more than 16 nested calls)"
+    );
+}
+
+#[test]
+fn return_with_empty_stack() {
+    let mut chip8 = configured(Variant::Chip8, Quirks::default());
+    load(&mut chip8, "return");
+    let err = chip8.emulate_cycle().unwrap_err();
+    assert_eq!(err.to_string(), "Return at 0x200 with an empty stack");
+}
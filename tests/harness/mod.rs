@@ -0,0 +1,154 @@
+// Runs scenarios: a ROM played headlessly for a number of frames
+// with scripted key presses, and the display hash expected at
+// some of those frames. A scenario file looks like
+//
+//     {
+//       "rom": "alu.8o",
+//       "seed": 1,
+//       "quirks": ["vf-reset"],
+//       "inputs": [{ "frame": 30, "key": 5, "down": true }],
+//       "checkpoints": [{ "frame": 60, "display_hash": "0x..." }]
+//     }
+//
+// `rom` is relative to tests/roms, and is assembled first if it's
+// Octo source. `quirks` are named as on the command line, on
+// top of the defaults. Inputs are applied before their frame runs, and a
+// checkpoint's hash is the display after that many frames.
+//
+// Frames are a fixed number of instructions with the timers
+// ticking once each, and CXNN is seeded, so the hashes only
+// depend on the emulator. Running with CHIP8_BLESS=1 rewrites the
+// expected hashes with the current ones, for when a change to
+// what the emulator draws is intended.
+
+use std::{env, fs, path::PathBuf};
+
+use chip8_emu::{Chip8, Quirks, Timing, assemble};
+use serde::{Deserialize, Serialize};
+
+// Instructions per frame unless a scenario says otherwise; the
+// terminal's default
+const DEFAULT_CPF: u32 = 15;
+
+#[derive(Serialize, Deserialize)]
+pub struct Scenario {
+    pub rom: String,
+    pub seed: u64,
+    #[serde(default = "default_cpf")]
+    pub cpf: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quirks: Vec<String>,
+    #[serde(default)]
+    pub inputs: Vec<Input>,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Input {
+    pub frame: u32,
+    pub key: usize,
+    pub down: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub frame: u32,
+    // As Chip8::display_hash() returns it, in hex
+    pub display_hash: String,
+}
+
+fn default_cpf() -> u32 {
+    DEFAULT_CPF
+}
+
+fn tests_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn load_rom(name: &str) -> Vec<u8> {
+    let path = tests_dir().join("roms").join(name);
+    if name.ends_with(".8o") {
+        let source =
+            fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        assemble(&source).unwrap_or_else(|err| panic!("{}: {}", name, err))
+    } else {
+        fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+    }
+}
+
+// The quirks --<name> turns on, for the ones these ROMs tell apart
+fn quirks(names: &[String]) -> Quirks {
+    let mut quirks = Quirks::default();
+    for name in names {
+        match name.as_str() {
+            "clip-sprites" => (quirks.wrap_x, quirks.wrap_y) = (false, false),
+            "shift-vy" => quirks.shift_uses_vy = true,
+            "increment-i" => quirks.index_increment_on_store = true,
+            "vf-reset" => quirks.vf_reset_on_logic = true,
+            "jump-vx" => quirks.jump_with_vx = true,
+            _ => panic!("unknown quirk {}", name),
+        }
+    }
+    quirks
+}
+
+impl Scenario {
+    // The display hash at each checkpoint, in order
+    pub fn run(&self) -> Vec<u64> {
+        let mut chip8 = Chip8::builder()
+            .seed(self.seed)
+            .timing(Timing::Simple)
+            .quirks(quirks(&self.quirks))
+            .build()
+            .unwrap();
+        chip8.load_rom_bytes(&load_rom(&self.rom)).unwrap();
+
+        let last = self.checkpoints.iter().map(|c| c.frame).max().unwrap_or(0);
+        let mut hashes = vec![0; self.checkpoints.len()];
+        for frame in 0..=last {
+            for input in self.inputs.iter().filter(|i| i.frame == frame) {
+                chip8.set_key(input.key, input.down);
+            }
+            for (hash, checkpoint) in hashes.iter_mut().zip(&self.checkpoints) {
+                if checkpoint.frame == frame {
+                    *hash = chip8.display_hash();
+                }
+            }
+            if frame < last {
+                chip8
+                    .step_frame(self.cpf)
+                    .unwrap_or_else(|err| panic!("{}: frame {}: {}", self.rom, frame, err));
+            }
+        }
+        hashes
+    }
+}
+
+// Runs tests/scenarios/<name>.json and fails on the first
+// checkpoint whose display differs, or blesses the file instead
+pub fn check(name: &str) {
+    let path = tests_dir().join("scenarios").join(format!("{}.json", name));
+    let json =
+        fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    let mut scenario: Scenario =
+        serde_json::from_str(&json).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    let hashes = scenario.run();
+
+    if env::var_os("CHIP8_BLESS").is_some() {
+        for (checkpoint, hash) in scenario.checkpoints.iter_mut().zip(hashes) {
+            checkpoint.display_hash = format!("{:#018x}", hash);
+        }
+        let json = serde_json::to_string_pretty(&scenario).unwrap() + "\n";
+        fs::write(&path, json).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        return;
+    }
+
+    for (checkpoint, hash) in scenario.checkpoints.iter().zip(hashes) {
+        let actual = format!("{:#018x}", hash);
+        assert_eq!(
+            actual, checkpoint.display_hash,
+            "{}: display differs at frame {} (CHIP8_BLESS=1 to accept)",
+            name, checkpoint.frame
+        );
+    }
+}
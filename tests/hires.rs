@@ -0,0 +1,124 @@
+// The COSMAC VIP's taller displays: 64x64 (and 64x48) hi-res
+// machines draw and wrap on every row, hi-res ROMs are recognised
+// by their 1260 start, and everything else keeps 64x32.
+
+mod common;
+
+use chip8_emu::{Chip8, HIRES_ENTRY, Quirks, Snapshot, is_hires_rom};
+use common::{ending, lit, load, run_to_end};
+
+// A machine `rows` tall that has run `source` to its end
+fn finished(rows: usize, quirks: Quirks, source: &str) -> Chip8 {
+    let mut chip8 = Chip8::builder()
+        .display_height(rows)
+        .quirks(quirks)
+        .build()
+        .unwrap();
+    load(&mut chip8, &ending(source));
+    run_to_end(&mut chip8);
+    chip8
+}
+
+// The font's 0 (F0 90 90 90 F0) with its top left corner at (x, y)
+fn zero_at(x: usize, y: usize) -> Vec<(usize, usize)> {
+    let mut pixels: Vec<_> = (0..4).map(|dx| (x + dx, y)).collect();
+    for dy in 1..4 {
+        pixels.extend([(x, y + dy), (x + 3, y + dy)]);
+    }
+    pixels.extend((0..4).map(|dx| (x + dx, y + 4)));
+    pixels.sort_by_key(|&(x, y)| (y, x));
+    pixels
+}
+
+const DRAW_ZERO_AT_40: &str = "v0 := 10 v1 := 40 v2 := 0 i := hex v2 sprite v0 v1 5";
+
+#[test]
+fn hires_draws_below_row_32() {
+    let chip8 = finished(64, Quirks::default(), DRAW_ZERO_AT_40);
+    assert_eq!((chip8.display_width(), chip8.display_height()), (64, 64));
+    assert_eq!(chip8.display.len(), 64 * 64);
+    assert_eq!(lit(&chip8, 0xFF), zero_at(10, 40));
+}
+
+#[test]
+fn standard_roms_stay_64x32() {
+    let chip8 = finished(32, Quirks::default(), DRAW_ZERO_AT_40);
+    assert_eq!(Chip8::new().display_height(), 32);
+    assert_eq!(chip8.display.len(), 64 * 32);
+    // Row 40 wraps around to row 8
+    assert_eq!(lit(&chip8, 0xFF), zero_at(10, 8));
+}
+
+#[test]
+fn sprites_wrap_or_clip_at_the_taller_bottom_edge() {
+    let source = "v0 := 0 v1 := 62 v2 := 0 i := hex v2 sprite v0 v1 5";
+    let wrapped = finished(64, Quirks::default(), source);
+    let mut expected: Vec<_> = zero_at(0, 62)
+        .into_iter()
+        .map(|(x, y)| (x, y % 64))
+        .collect();
+    expected.sort_by_key(|&(x, y)| (y, x));
+    assert_eq!(lit(&wrapped, 0xFF), expected);
+
+    let clip = Quirks {
+        wrap_y: false,
+        ..Quirks::default()
+    };
+    let clipped = finished(64, clip, source);
+    assert_eq!(lit(&clipped, 0xFF), zero_at(0, 62)[..6].to_vec());
+
+    // 64x48 wraps at row 48
+    let chip8 = finished(48, Quirks::default(), DRAW_ZERO_AT_40);
+    assert_eq!(chip8.display.len(), 64 * 48);
+    assert_eq!(lit(&chip8, 0xFF), zero_at(10, 40));
+    let chip8 = finished(48, Quirks::default(), "v1 := 50 i := hex v1 sprite v0 v1 1");
+    assert_eq!(lit(&chip8, 0xFF)[0].1, 2);
+}
+
+#[test]
+fn hires_0230_clears_the_display() {
+    let chip8 = finished(
+        64,
+        Quirks::default(),
+        &format!("{} 0x02 0x30", DRAW_ZERO_AT_40),
+    );
+    assert!(lit(&chip8, 0xFF).is_empty());
+
+    // Elsewhere it's still a machine code call
+    let mut chip8 = Chip8::new();
+    chip8.load_rom_bytes(&[0x02, 0x30]).unwrap();
+    assert!(chip8.emulate_cycle().is_err());
+}
+
+#[test]
+fn only_other_heights_are_refused() {
+    assert!(Chip8::builder().display_height(40).build().is_err());
+    let mut chip8 = Chip8::new();
+    assert!(chip8.set_display_height(128).is_err());
+    assert_eq!(chip8.display_height(), 32);
+    chip8.set_display_height(48).unwrap();
+    assert_eq!(chip8.display.len(), 64 * 48);
+}
+
+#[test]
+fn hires_roms_start_with_a_jump_to_0x260() {
+    let mut rom = vec![0; 0x100];
+    rom[..2].copy_from_slice(&[0x12, 0x60]);
+    assert!(is_hires_rom(&rom));
+    assert_eq!(HIRES_ENTRY, 0x2C0);
+    // Too short to have a program past the patch
+    assert!(!is_hires_rom(&rom[..0x40]));
+    rom[1] = 0x62;
+    assert!(!is_hires_rom(&rom));
+}
+
+#[test]
+fn snapshots_keep_the_display_height() {
+    let chip8 = finished(64, Quirks::default(), DRAW_ZERO_AT_40);
+    let snapshot = Snapshot::from_bytes(&chip8.snapshot().to_bytes()).unwrap();
+
+    let mut restored = Chip8::new();
+    restored.restore(&snapshot);
+    assert_eq!(restored.display_height(), 64);
+    assert_eq!(lit(&restored, 0xFF), zero_at(10, 40));
+}
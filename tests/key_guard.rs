@@ -0,0 +1,99 @@
+// The key repeat guard: one press held while a ROM polls the key
+// over and over counts once with the guard on, and on every poll
+// without it. EX9E and EXA1 have to agree on what they see.
+
+use std::cell::Cell;
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::{load, run};
+
+// V0 := 5, then 16 passes of the loop: the poll, the count (or
+// its skip) and the jump back
+const POLLS: u8 = 16;
+
+// Polls key 5 POLLS times, counting in V1 the polls that see it
+// down. `test` is the Octo condition that skips the count when
+// the key reads as up. `press` sets the key before every
+// instruction, given how many polls are done (V2).
+fn polls_seeing_key(guard: bool, test: &str, press: impl Fn(&mut Chip8, u8)) -> u8 {
+    let mut chip8 = Chip8::builder().key_repeat_guard(guard).build().unwrap();
+    let source = format!(
+        "v0 := 5 loop if v0 {} then v1 += 1 v2 += 1 if v2 != {} then again : end jump end",
+        test, POLLS
+    );
+    load(&mut chip8, &source);
+    while !chip8.is_halted() {
+        let polls = chip8.registers()[2];
+        press(&mut chip8, polls);
+        chip8.emulate_cycle().unwrap();
+    }
+    chip8.registers()[1]
+}
+
+// Held from the first poll to the last
+fn held(chip8: &mut Chip8, _: u8) {
+    chip8.set_key(5, true);
+}
+
+// Down for 5 polls, up for 5, down again for the rest
+fn pressed_twice(chip8: &mut Chip8, polls: u8) {
+    chip8.set_key(5, !(5..10).contains(&polls));
+}
+
+#[test]
+fn held_key_counts_on_every_poll_without_the_guard() {
+    // `if v0 key then` is EXA1, skipping the count when up
+    assert_eq!(polls_seeing_key(false, "key", held), POLLS);
+    // `if v0 -key then` is EX9E, skipping it when down, so it
+    // counts the polls seeing the key up
+    assert_eq!(polls_seeing_key(false, "-key", held), 0);
+}
+
+#[test]
+fn held_key_counts_once_with_the_guard() {
+    assert_eq!(polls_seeing_key(true, "key", held), 1);
+    assert_eq!(polls_seeing_key(true, "-key", held), POLLS - 1);
+}
+
+#[test]
+fn pressing_again_counts_again() {
+    assert_eq!(polls_seeing_key(true, "key", pressed_twice), 2);
+    assert_eq!(polls_seeing_key(true, "-key", pressed_twice), POLLS - 2);
+}
+
+#[test]
+fn taps_still_count_once() {
+    // Down and up again before the ROM looks, once
+    let tapped = Cell::new(false);
+    let tap = |chip8: &mut Chip8, polls| {
+        if polls == 3 && !tapped.replace(true) {
+            chip8.set_key(5, true);
+            chip8.set_key(5, false);
+        }
+    };
+    assert_eq!(polls_seeing_key(false, "key", tap), 1);
+    tapped.set(false);
+    assert_eq!(polls_seeing_key(true, "key", tap), 1);
+}
+
+#[test]
+fn fx0a_takes_a_held_key_once() {
+    let mut chip8 = Chip8::builder().key_repeat_guard(true).build().unwrap();
+    load(&mut chip8, "v1 := key v2 := key");
+    chip8.set_key(7, true);
+    run(&mut chip8, 10);
+    // The first FX0A saw the press, and waits for it to end
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.set_key(7, false);
+    chip8.emulate_cycle().unwrap();
+    assert_eq!((chip8.registers()[1], chip8.pc()), (7, 0x202));
+
+    // The second needs a press of its own
+    chip8.set_key(7, true);
+    chip8.emulate_cycle().unwrap();
+    chip8.set_key(7, false);
+    chip8.emulate_cycle().unwrap();
+    assert_eq!((chip8.registers()[2], chip8.pc()), (7, 0x204));
+}
@@ -0,0 +1,80 @@
+// FX0A waits for a key to be pressed and let go, as on the VIP.
+// With several keys down it takes the one let go first, not the
+// lowest held.
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::{ending, machine, run};
+
+// Waiting on `v1 := key`, which then stops
+fn waiting() -> Chip8 {
+    machine(&ending("v1 := key"))
+}
+
+fn done(chip8: &Chip8) -> bool {
+    chip8.pc() != 0x200
+}
+
+#[test]
+fn a_press_alone_doesnt_finish_the_wait() {
+    let mut chip8 = waiting();
+    chip8.set_key(0xA, true);
+    run(&mut chip8, 5);
+    assert!(!done(&chip8));
+    chip8.set_key(0xA, false);
+    run(&mut chip8, 1);
+    assert!(done(&chip8));
+    assert_eq!(chip8.registers()[1], 0xA);
+}
+
+#[test]
+fn the_key_let_go_is_the_one_taken() {
+    let mut chip8 = waiting();
+    chip8.set_key(3, true);
+    run(&mut chip8, 2);
+    chip8.set_key(9, true);
+    run(&mut chip8, 2);
+    // 9 goes first, while 3 is still held
+    chip8.set_key(9, false);
+    run(&mut chip8, 1);
+    assert!(done(&chip8));
+    assert_eq!(chip8.registers()[1], 9);
+}
+
+#[test]
+fn keys_held_together_count_from_the_start() {
+    // Both already down when the wait starts
+    let mut chip8 = waiting();
+    chip8.set_keys(1 << 2 | 1 << 0xE);
+    run(&mut chip8, 3);
+    chip8.set_key(0xE, false);
+    run(&mut chip8, 1);
+    assert_eq!(chip8.registers()[1], 0xE);
+}
+
+#[test]
+fn a_tap_between_instructions_still_counts() {
+    let mut chip8 = waiting();
+    chip8.set_key(6, true);
+    chip8.set_key(6, false);
+    run(&mut chip8, 1);
+    assert!(done(&chip8));
+    assert_eq!(chip8.registers()[1], 6);
+}
+
+#[test]
+fn the_next_wait_starts_afresh() {
+    let mut chip8 = machine(&ending("v1 := key v2 := key"));
+    chip8.set_keys(1 << 4 | 1 << 5);
+    run(&mut chip8, 1);
+    chip8.set_key(4, false);
+    run(&mut chip8, 1);
+    assert_eq!(chip8.registers()[1], 4);
+    // 5 is still held; the second wait takes it when it's let go
+    run(&mut chip8, 3);
+    assert_eq!(chip8.pc(), 0x202);
+    chip8.set_key(5, false);
+    run(&mut chip8, 1);
+    assert_eq!((chip8.registers()[2], chip8.pc()), (5, 0x204));
+}
@@ -0,0 +1,59 @@
+// Programs loaded somewhere other than 0x200, such as ETI-660
+// programs at 0x600: the address has to be below 0xE00, the ROM
+// has to fit in memory from there, and it can't cover the font.
+
+use chip8_emu::Chip8;
+
+fn rom(len: usize) -> Vec<u8> {
+    vec![0x12; len]
+}
+
+#[test]
+fn programs_start_where_they_are_loaded() {
+    let mut chip8 = Chip8::new_with_start(0x600).unwrap();
+    chip8.load_rom_bytes(&[0x6A, 0x42]).unwrap();
+    assert_eq!(chip8.pc(), 0x600);
+    assert_eq!(&chip8.memory()[0x600..0x602], [0x6A, 0x42]);
+    chip8.emulate_cycle().unwrap();
+    assert_eq!(chip8.registers()[0xA], 0x42);
+}
+
+#[test]
+fn load_addresses_past_0xe00_are_refused() {
+    assert!(Chip8::new_with_start(0xDFF).is_ok());
+    let err = Chip8::new_with_start(0xE00).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Load address 0xE00 out of range (0x000..0xE00)"
+    );
+    assert!(Chip8::new_with_start(0xFFFF).is_err());
+}
+
+#[test]
+fn the_rom_has_to_fit_below_0x1000() {
+    let mut chip8 = Chip8::new_with_start(0x600).unwrap();
+    assert!(chip8.load_rom_bytes(&rom(0xA01)).is_err());
+    chip8.load_rom_bytes(&rom(0xA00)).unwrap();
+    assert_eq!(chip8.rom_range(), 0x600..0x1000);
+}
+
+#[test]
+fn roms_over_the_font_are_refused() {
+    // 0x40..0x60 covers the start of the font at 0x50
+    let mut chip8 = Chip8::new_with_start(0x40).unwrap();
+    let err = chip8.load_rom_bytes(&rom(0x20)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "ROM would overwrite the fontset (0x50..0xA0)"
+    );
+    assert_eq!(chip8.rom_len(), 0);
+
+    // Up to the font is fine
+    let mut chip8 = Chip8::new_with_start(0x000).unwrap();
+    assert!(chip8.load_rom_bytes(&rom(0x51)).is_err());
+    chip8.load_rom_bytes(&rom(0x50)).unwrap();
+
+    // And so is right after it
+    let mut chip8 = Chip8::new_with_start(0xA0).unwrap();
+    chip8.load_rom_bytes(&rom(0x20)).unwrap();
+}
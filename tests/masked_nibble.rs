@@ -0,0 +1,38 @@
+// EX9E, EXA1 and FX29 only use the low nibble of V[X], so a
+// register past 0xF reads the key or digit it ends in instead of
+// running off the keypad or the font.
+
+use chip8_emu::Chip8;
+
+// The machine after `opcode` runs with V1 = `v1` and only `key`
+// held
+fn after(opcode: u16, v1: u8, key: usize) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+    chip8.set_register(1, v1).unwrap();
+    chip8.set_key(key, true);
+    chip8.emulate_cycle().unwrap();
+    chip8
+}
+
+#[test]
+fn ex9e_skips_on_the_masked_key() {
+    assert_eq!(after(0xE19E, 0x10, 0x0).pc(), 0x204);
+    assert_eq!(after(0xE19E, 0x10, 0x1).pc(), 0x202);
+    assert_eq!(after(0xE19E, 0xFF, 0xF).pc(), 0x204);
+    assert_eq!(after(0xE19E, 0xFF, 0xE).pc(), 0x202);
+}
+
+#[test]
+fn exa1_skips_unless_the_masked_key_is_down() {
+    assert_eq!(after(0xE1A1, 0x10, 0x0).pc(), 0x202);
+    assert_eq!(after(0xE1A1, 0x10, 0x1).pc(), 0x204);
+    assert_eq!(after(0xE1A1, 0xFF, 0xF).pc(), 0x202);
+    assert_eq!(after(0xE1A1, 0xFF, 0xE).pc(), 0x204);
+}
+
+#[test]
+fn fx29_points_at_the_masked_digit() {
+    assert_eq!(after(0xF129, 0x10, 0).i(), 0x50);
+    assert_eq!(after(0xF129, 0xFF, 0).i(), 0x50 + 0xF * 5);
+}
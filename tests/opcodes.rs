@@ -0,0 +1,215 @@
+// One instruction at a time: each opcode family run on a machine
+// with its registers poked beforehand, checking what it changed.
+// The tricky ones get more cases: VF as the destination of the
+// arithmetic (the flag is written last and wins), DXYN collisions,
+// FX33's digits and nested calls.
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::{lit, machine, run, run_to_end};
+
+// A machine that has run the single instruction `opcode`, with
+// `regs` set beforehand
+fn after(opcode: u16, regs: &[(usize, u8)]) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+    for &(x, value) in regs {
+        chip8.set_register(x, value).unwrap();
+    }
+    chip8.emulate_cycle().unwrap();
+    chip8
+}
+
+// V[X] and VF after `opcode`
+fn result(opcode: u16, regs: &[(usize, u8)]) -> (u8, u8) {
+    let chip8 = after(opcode, regs);
+    let x = (opcode >> 8 & 0xF) as usize;
+    (chip8.registers()[x], chip8.registers()[0xF])
+}
+
+#[test]
+fn clear_00e0() {
+    let mut chip8 = machine("i := hex v0 sprite v0 v0 5 clear");
+    run(&mut chip8, 2);
+    assert!(!lit(&chip8, 1).is_empty());
+    run(&mut chip8, 1);
+    assert!(lit(&chip8, 1).is_empty());
+    assert_eq!(chip8.pc(), 0x206);
+}
+
+#[test]
+fn jump_1nnn_and_bnnn() {
+    assert_eq!(after(0x1ABC, &[]).pc(), 0xABC);
+    assert_eq!(after(0xB300, &[(0, 0x10), (3, 0x20)]).pc(), 0x310);
+}
+
+#[test]
+fn call_2nnn_and_return_00ee_nest() {
+    let mut chip8 = machine("a v5 := 1 : end jump end : a b v6 := 2 return : b v7 := 3 return");
+    run(&mut chip8, 2);
+    assert_eq!(chip8.stack(), [0x200, 0x206]);
+    assert_eq!(chip8.pc(), 0x20C);
+
+    run_to_end(&mut chip8);
+    assert!(chip8.stack().is_empty());
+    assert_eq!(chip8.pc(), 0x204);
+    assert_eq!(&chip8.registers()[5..8], [1, 2, 3]);
+}
+
+#[test]
+fn skips_3xnn_4xnn_5xy0_9xy0() {
+    let equal = [(0xA, 0x12), (0xB, 0x12)];
+    let unequal = [(0xA, 0x12), (0xB, 0x13)];
+    assert_eq!(after(0x3A12, &equal).pc(), 0x204);
+    assert_eq!(after(0x3A13, &equal).pc(), 0x202);
+    assert_eq!(after(0x4A12, &equal).pc(), 0x202);
+    assert_eq!(after(0x4A13, &equal).pc(), 0x204);
+    assert_eq!(after(0x5AB0, &equal).pc(), 0x204);
+    assert_eq!(after(0x5AB0, &unequal).pc(), 0x202);
+    assert_eq!(after(0x9AB0, &equal).pc(), 0x202);
+    assert_eq!(after(0x9AB0, &unequal).pc(), 0x204);
+}
+
+#[test]
+fn load_6xnn_and_add_7xnn() {
+    assert_eq!(after(0x6C42, &[]).registers()[0xC], 0x42);
+    // 7XNN wraps and leaves VF alone
+    assert_eq!(result(0x7102, &[(1, 0xFF), (0xF, 9)]), (1, 9));
+}
+
+#[test]
+fn register_ops_8xy0_to_8xy3() {
+    let regs = [(1, 0b1100), (2, 0b1010)];
+    assert_eq!(result(0x8120, &regs).0, 0b1010);
+    assert_eq!(result(0x8121, &regs).0, 0b1110);
+    assert_eq!(result(0x8122, &regs).0, 0b1000);
+    assert_eq!(result(0x8123, &regs).0, 0b0110);
+}
+
+#[test]
+fn arithmetic_sets_the_flag() {
+    // 8XY4: carry
+    assert_eq!(result(0x8124, &[(1, 200), (2, 55)]), (255, 0));
+    assert_eq!(result(0x8124, &[(1, 200), (2, 56)]), (0, 1));
+    // 8XY5: no borrow
+    assert_eq!(result(0x8125, &[(1, 5), (2, 5)]), (0, 1));
+    assert_eq!(result(0x8125, &[(1, 5), (2, 6)]), (0xFF, 0));
+    // 8XY7: V[Y] - V[X], no borrow
+    assert_eq!(result(0x8127, &[(1, 6), (2, 5)]), (0xFF, 0));
+    assert_eq!(result(0x8127, &[(1, 5), (2, 6)]), (1, 1));
+    // 8XY6/8XYE: the bit shifted out
+    assert_eq!(result(0x8106, &[(1, 0b101)]), (0b10, 1));
+    assert_eq!(result(0x810E, &[(1, 0x81)]), (0x02, 1));
+    assert_eq!(result(0x810E, &[(1, 0x41)]), (0x82, 0));
+}
+
+#[test]
+fn the_flag_wins_when_x_is_f() {
+    // The result is written to VF first, then the flag over it
+    assert_eq!(result(0x8F14, &[(0xF, 0xFF), (1, 1)]).1, 1);
+    assert_eq!(result(0x8F15, &[(0xF, 3), (1, 5)]).1, 0);
+    assert_eq!(result(0x8F17, &[(0xF, 3), (1, 5)]).1, 1);
+    assert_eq!(result(0x8F06, &[(0xF, 0b11)]).1, 1);
+    assert_eq!(result(0x8F0E, &[(0xF, 0x40)]).1, 0);
+}
+
+#[test]
+fn index_annn_and_fx1e() {
+    assert_eq!(after(0xA345, &[]).i(), 0x345);
+
+    let mut chip8 = machine("i := 0x300 i += v1");
+    chip8.set_register(1, 0x10).unwrap();
+    chip8.set_register(0xF, 7).unwrap();
+    run(&mut chip8, 2);
+    assert_eq!(chip8.i(), 0x310);
+    assert_eq!(chip8.registers()[0xF], 7);
+}
+
+#[test]
+fn random_cxnn_is_masked() {
+    for seed in 0..20 {
+        let mut chip8 = Chip8::builder().seed(seed).build().unwrap();
+        chip8.load_rom_bytes(&[0xC1, 0x0F]).unwrap();
+        chip8.emulate_cycle().unwrap();
+        assert_eq!(chip8.registers()[1] & 0xF0, 0);
+    }
+}
+
+#[test]
+fn draw_dxyn_collides_only_with_lit_pixels() {
+    // The 0 (14 pixels), the 1 beside it, then the 0 again
+    let mut chip8 = machine(
+        "i := hex v0 sprite v0 v0 5 v1 := 1 i := hex v1 v2 := 8 sprite v2 v0 5 \
+         i := hex v0 sprite v0 v0 5",
+    );
+    run(&mut chip8, 2);
+    assert_eq!(lit(&chip8, 1).len(), 14);
+    assert_eq!(chip8.registers()[0xF], 0);
+
+    run(&mut chip8, 4);
+    assert_eq!(chip8.registers()[0xF], 0);
+
+    // Drawn over itself, the 0 is erased and collides
+    run(&mut chip8, 2);
+    assert_eq!(chip8.registers()[0xF], 1);
+    assert!(lit(&chip8, 1).iter().all(|&(x, _)| x >= 8));
+}
+
+#[test]
+fn keys_ex9e_and_exa1() {
+    for (down, skip_if_down, skip_if_up) in [(true, 0x204, 0x202), (false, 0x202, 0x204)] {
+        for (opcode, expected) in [(0xE19E, skip_if_down), (0xE1A1, skip_if_up)] {
+            let mut chip8 = Chip8::new();
+            chip8.load_rom_bytes(&u16::to_be_bytes(opcode)).unwrap();
+            chip8.set_register(1, 5).unwrap();
+            chip8.set_key(5, down);
+            chip8.emulate_cycle().unwrap();
+            assert_eq!(chip8.pc(), expected, "{:04X} key down {}", opcode, down);
+        }
+    }
+}
+
+#[test]
+fn timers_fx07_fx15_fx18() {
+    let mut chip8 = machine("v0 := 30 delay := v0 buzzer := v0 v1 := delay");
+    run(&mut chip8, 4);
+    assert_eq!(chip8.delay_timer(), 30);
+    assert_eq!(chip8.sound_timer(), 30);
+    assert_eq!(chip8.registers()[1], 30);
+}
+
+#[test]
+fn font_fx29() {
+    assert_eq!(after(0xF129, &[(1, 0xA)]).i(), 0x50 + 0xA * 5);
+}
+
+#[test]
+fn bcd_fx33() {
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (10, [0, 1, 0]),
+        (99, [0, 9, 9]),
+        (255, [2, 5, 5]),
+    ] {
+        let mut chip8 = machine("i := 0x300 bcd v1");
+        chip8.set_register(1, value).unwrap();
+        run(&mut chip8, 2);
+        assert_eq!(&chip8.memory()[0x300..0x303], digits, "{}", value);
+        assert_eq!(chip8.i(), 0x300);
+    }
+}
+
+#[test]
+fn store_fx55_and_load_fx65() {
+    let mut chip8 = machine("i := 0x300 save v2 v0 := 0 v1 := 0 v2 := 0 load v1");
+    for (x, value) in [(0, 4), (1, 5), (2, 6), (3, 7)] {
+        chip8.set_register(x, value).unwrap();
+    }
+    run(&mut chip8, 2);
+    assert_eq!(&chip8.memory()[0x300..0x304], [4, 5, 6, 0]);
+    run(&mut chip8, 4);
+    assert_eq!(&chip8.registers()[..4], [4, 5, 0, 7]);
+    assert_eq!(chip8.i(), 0x300);
+}
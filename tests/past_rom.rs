@@ -0,0 +1,85 @@
+// Running off the end of the program: the empty memory after a
+// ROM is all 0000, which stops on a machine code call, or with
+// halt_past_rom stops the machine where it is.
+
+mod common;
+
+use chip8_emu::{Chip8, Chip8Error, Quirks};
+use common::load;
+
+fn configured(source: &str, halt_past_rom: bool, quirks: Quirks) -> Chip8 {
+    let mut chip8 = Chip8::builder()
+        .halt_past_rom(halt_past_rom)
+        .quirks(quirks)
+        .build()
+        .unwrap();
+    load(&mut chip8, source);
+    chip8
+}
+
+#[test]
+fn empty_memory_is_a_machine_code_call_by_default() {
+    let mut chip8 = configured("v0 := 1 v1 := 2", false, Quirks::default());
+    chip8.emulate_cycle().unwrap();
+    chip8.emulate_cycle().unwrap();
+    assert_eq!(
+        chip8.emulate_cycle(),
+        Err(Chip8Error::MachineCodeCall { pc: 0x204, addr: 0 })
+    );
+    assert!(!chip8.ran_past_rom());
+}
+
+#[test]
+fn the_machine_stops_where_the_program_ends() {
+    let mut chip8 = configured("v0 := 1 v1 := 2", true, Quirks::default());
+    assert!(!chip8.is_halted());
+    for _ in 0..100 {
+        chip8.emulate_cycle().unwrap();
+    }
+    assert_eq!(chip8.pc(), 0x204);
+    assert!(chip8.ran_past_rom());
+    assert!(chip8.is_halted());
+    assert_eq!(chip8.registers()[..2], [1, 2]);
+
+    // Frames still pass, so timers run down as they would on a
+    // jump to itself
+    chip8.step_frame(1000).unwrap();
+    assert_eq!(chip8.pc(), 0x204);
+}
+
+#[test]
+fn skipping_machine_code_still_stops() {
+    let quirks = Quirks {
+        ignore_machine_code: true,
+        ..Quirks::default()
+    };
+    let mut chip8 = configured("v0 := 1", false, quirks);
+    for _ in 0..10 {
+        chip8.emulate_cycle().unwrap();
+    }
+    // Without the guard it wanders on through memory
+    assert_eq!(chip8.pc(), 0x202 + 9 * 2);
+
+    let mut chip8 = configured("v0 := 1", true, quirks);
+    for _ in 0..10 {
+        chip8.emulate_cycle().unwrap();
+    }
+    assert_eq!(chip8.pc(), 0x202);
+    assert!(chip8.is_halted());
+}
+
+#[test]
+fn code_the_program_wrote_runs() {
+    // Writes 1300 (jump to itself) at 0x300 and jumps there
+    let mut chip8 = configured(
+        "i := 0x300 v0 := 0x13 v1 := 0x00 save v1 jump 0x300",
+        true,
+        Quirks::default(),
+    );
+    for _ in 0..20 {
+        chip8.emulate_cycle().unwrap();
+    }
+    assert_eq!(chip8.pc(), 0x300);
+    assert!(chip8.is_halted());
+    assert!(!chip8.ran_past_rom());
+}
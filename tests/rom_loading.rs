@@ -0,0 +1,71 @@
+// Loading ROMs: a ROM too large for memory is refused, or with
+// truncate_oversize cut down to what fits, and the machine knows
+// where in memory the ROM is.
+
+use chip8_emu::Chip8;
+
+// A ROM of `len` bytes, each its offset's low byte
+fn rom(len: usize) -> Vec<u8> {
+    (0..len).map(|n| n as u8).collect()
+}
+
+#[test]
+fn oversized_roms_are_refused_by_default() {
+    let mut chip8 = Chip8::builder().build().unwrap();
+    assert!(chip8.load_rom_bytes(&rom(0xE01)).is_err());
+    assert_eq!(chip8.rom_len(), 0);
+    assert!(chip8.memory()[0x200..].iter().all(|&b| b == 0));
+
+    // Exactly full is fine
+    chip8.load_rom_bytes(&rom(0xE00)).unwrap();
+    assert_eq!(chip8.rom_len(), 0xE00);
+}
+
+#[test]
+fn truncating_loads_what_fits() {
+    let mut chip8 = Chip8::builder().truncate_oversize(true).build().unwrap();
+    let padded = rom(0xE00 + 300);
+    chip8.load_rom_bytes(&padded).unwrap();
+    assert_eq!(chip8.rom_len(), 0xE00);
+    assert_eq!(chip8.rom_range(), 0x200..0x1000);
+    assert_eq!(chip8.memory()[0x200..], padded[..0xE00]);
+
+    // Elsewhere there's less room
+    let mut chip8 = Chip8::builder()
+        .start_address(0x600)
+        .truncate_oversize(true)
+        .build()
+        .unwrap();
+    chip8.load_rom_bytes(&padded).unwrap();
+    assert_eq!(chip8.rom_range(), 0x600..0x1000);
+
+    // Small ROMs aren't touched
+    let mut chip8 = Chip8::builder().truncate_oversize(true).build().unwrap();
+    chip8.load_rom_bytes(&rom(10)).unwrap();
+    assert_eq!(chip8.rom_len(), 10);
+}
+
+#[test]
+fn the_range_is_where_the_rom_went() {
+    let mut chip8 = Chip8::builder().build().unwrap();
+    assert!(chip8.rom_range().is_empty());
+    chip8.load_rom_bytes(&rom(0x46)).unwrap();
+    assert_eq!(chip8.rom_range(), 0x200..0x246);
+
+    // Loaded below 0x200, on top of where the large font goes
+    let mut chip8 = Chip8::builder().start_address(0x100).build().unwrap();
+    chip8.load_rom_bytes(&rom(0x20)).unwrap();
+    assert_eq!(chip8.rom_range(), 0x100..0x120);
+    assert_eq!(chip8.memory()[0x100..0x120], rom(0x20));
+}
+
+#[test]
+fn the_fontset_is_still_off_limits() {
+    let mut chip8 = Chip8::builder()
+        .start_address(0x20)
+        .truncate_oversize(true)
+        .build()
+        .unwrap();
+    assert!(chip8.load_rom_bytes(&rom(0x40)).is_err());
+    assert_eq!(chip8.rom_len(), 0);
+}
@@ -0,0 +1,52 @@
+// Plays the ROMs in tests/roms and compares the display against
+// the hashes recorded in tests/scenarios; see harness/mod.rs
+
+mod harness;
+
+#[test]
+fn alu() {
+    harness::check("alu");
+}
+
+#[test]
+fn draw() {
+    harness::check("draw");
+}
+
+#[test]
+fn keys() {
+    harness::check("keys");
+}
+
+#[test]
+fn memory() {
+    harness::check("memory");
+}
+
+// Tick or cross tests for each instruction, after corax's
+// opcode test ROM, and for the flags the ALU leaves in VF
+#[test]
+fn opcodes() {
+    harness::check("opcodes");
+}
+
+#[test]
+fn flags() {
+    harness::check("flags");
+}
+
+// The same ROM shows which quirks are on
+#[test]
+fn quirks() {
+    harness::check("quirks");
+}
+
+#[test]
+fn quirks_vip() {
+    harness::check("quirks-vip");
+}
+
+#[test]
+fn quirks_schip() {
+    harness::check("quirks-schip");
+}
@@ -0,0 +1,140 @@
+// XO-CHIP's scroll instructions (00CN, 00DN, 00FB, 00FC): a known
+// pattern scrolled each way, what's left at the edges, and how
+// planes and the half-distance quirk change it.
+
+mod common;
+
+use chip8_emu::{Chip8, Quirks, Variant};
+use common::{ending, lit, load, run_to_end};
+
+const WIDTH: usize = 64;
+
+// An XO-CHIP machine with `source` loaded, which can `jump end`
+// to stop
+fn xo_chip(quirks: Quirks, source: &str) -> Chip8 {
+    let mut chip8 = Chip8::builder()
+        .variant(Variant::XoChip)
+        .quirks(quirks)
+        .build()
+        .unwrap();
+    load(&mut chip8, &ending(source));
+    chip8
+}
+
+// Runs `source` on an XO-CHIP machine until it gets to the end
+fn finished(quirks: Quirks, source: &str) -> Chip8 {
+    let mut chip8 = xo_chip(quirks, source);
+    run_to_end(&mut chip8);
+    chip8
+}
+
+// A single pixel at (x, y), then `scroll`
+fn pixel_then(x: u8, y: u8, scroll: &str) -> String {
+    format!(
+        "v0 := {} v1 := {} i := dot sprite v0 v1 1 {} jump end : dot 0x80",
+        x, y, scroll
+    )
+}
+
+fn scrolled(x: u8, y: u8, scroll: &str) -> Vec<(usize, usize)> {
+    lit(&finished(Quirks::default(), &pixel_then(x, y, scroll)), 1)
+}
+
+#[test]
+fn each_direction() {
+    assert_eq!(scrolled(10, 10, "scroll-down 3"), [(10, 13)]);
+    assert_eq!(scrolled(10, 10, "scroll-up 3"), [(10, 7)]);
+    assert_eq!(scrolled(10, 10, "scroll-right"), [(14, 10)]);
+    assert_eq!(scrolled(10, 10, "scroll-left"), [(6, 10)]);
+    assert_eq!(scrolled(10, 10, "scroll-down 0"), [(10, 10)]);
+}
+
+#[test]
+fn pixels_scrolled_off_the_edge_are_lost() {
+    assert_eq!(scrolled(5, 31, "scroll-down 1"), []);
+    assert_eq!(scrolled(5, 0, "scroll-up 1"), []);
+    assert_eq!(scrolled(60, 5, "scroll-right"), []);
+    assert_eq!(scrolled(3, 5, "scroll-left"), []);
+    // Right up to the edge is still on screen
+    assert_eq!(scrolled(5, 16, "scroll-down 15"), [(5, 31)]);
+    assert_eq!(scrolled(59, 5, "scroll-right"), [(63, 5)]);
+    assert_eq!(scrolled(4, 5, "scroll-left"), [(0, 5)]);
+}
+
+#[test]
+fn vacated_area_is_cleared() {
+    // A full-width line scrolled down leaves the top row empty
+    let chip8 = finished(
+        Quirks::default(),
+        "i := bar v1 := 0 v0 := 0 loop sprite v0 v1 1 v0 += 8 if v0 != 64 then again \
+         scroll-down 2 jump end : bar 0xFF",
+    );
+    let row = |y| {
+        (0..WIDTH)
+            .filter(|&x| chip8.display[y * WIDTH + x] != 0)
+            .count()
+    };
+    assert_eq!([row(0), row(1), row(2)], [0, 0, WIDTH]);
+
+    // And scrolled right, the left 4 columns of it
+    let chip8 = finished(
+        Quirks::default(),
+        "i := bar v1 := 0 v0 := 0 loop sprite v0 v1 1 v0 += 8 if v0 != 64 then again \
+         scroll-right jump end : bar 0xFF",
+    );
+    assert_eq!(
+        lit(&chip8, 1),
+        (4..WIDTH).map(|x| (x, 0)).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn only_the_selected_planes_scroll() {
+    // The same pixel in both planes (F301), then only plane 2
+    // (F201) scrolls
+    let chip8 = finished(
+        Quirks::default(),
+        "v0 := 10 v1 := 10 i := dot 0xF3 0x01 sprite v0 v1 1 0xF2 0x01 scroll-down 1 \
+         jump end : dot 0x80 0x80",
+    );
+    assert_eq!(lit(&chip8, 1), [(10, 10)]);
+    assert_eq!(lit(&chip8, 2), [(10, 11)]);
+}
+
+#[test]
+fn half_scroll_moves_half_as_far() {
+    let half = Quirks {
+        half_scroll: true,
+        ..Quirks::default()
+    };
+    let scrolled = |scroll| lit(&finished(half, &pixel_then(10, 10, scroll)), 1);
+    assert_eq!(scrolled("scroll-down 4"), [(10, 12)]);
+    assert_eq!(scrolled("scroll-up 3"), [(10, 9)]);
+    assert_eq!(scrolled("scroll-right"), [(12, 10)]);
+    assert_eq!(scrolled("scroll-left"), [(8, 10)]);
+}
+
+#[test]
+fn many_scrolls_are_one_change_a_frame() {
+    let mut chip8 = xo_chip(
+        Quirks::default(),
+        &pixel_then(10, 10, "loop scroll-right scroll-left again"),
+    );
+    assert!(chip8.step_frame(200).unwrap().display_changed);
+    // Back where it started every other instruction, still a change
+    assert!(chip8.step_frame(200).unwrap().display_changed);
+
+    // A blank screen scrolled has nothing to redraw
+    let mut chip8 = xo_chip(Quirks::default(), "loop scroll-down 1 again");
+    assert!(!chip8.step_frame(200).unwrap().display_changed);
+}
+
+#[test]
+fn without_xo_chip_scrolls_are_machine_code_calls() {
+    let mut chip8 = Chip8::new();
+    load(&mut chip8, "scroll-down 1");
+    assert_eq!(
+        chip8.emulate_cycle().unwrap_err().to_string(),
+        "0x200 calls native COSMAC VIP code at 0x0C1 (0NNN), which can't be emulated"
+    );
+}
@@ -0,0 +1,162 @@
+// Snapshots carry everything a run depends on, the random number
+// generator and the keypad included: a run restored from one, in
+// memory or saved as bytes, goes exactly as it did the first time.
+
+mod common;
+
+use chip8_emu::{Chip8, Snapshot, Variant};
+use common::{ending, load, machine, run};
+
+// Random sprites at random places, counting the frames key 5 is
+// down in V4
+const SCATTER: &str = ": top v0 := random 63 v1 := random 31 v2 := random 15 \
+    i := hex v2 sprite v0 v1 5 v3 := 5 if v3 key then v4 += 1 jump top";
+
+fn scatter() -> Chip8 {
+    let mut chip8 = Chip8::builder().seed(7).build().unwrap();
+    load(&mut chip8, SCATTER);
+    chip8
+}
+
+// The display and registers after 1000 more cycles
+fn outcome(chip8: &mut Chip8) -> (u64, [u8; 16]) {
+    run(chip8, 1000);
+    (chip8.display_hash(), *chip8.registers())
+}
+
+#[test]
+fn a_restored_run_goes_the_same_way() {
+    let mut chip8 = scatter();
+    outcome(&mut chip8);
+    chip8.set_key(5, true);
+    let snapshot = chip8.snapshot();
+    let first = outcome(&mut chip8);
+
+    // Different keys held since then don't matter
+    chip8.set_key(5, false);
+    chip8.restore(&snapshot);
+    assert_eq!(outcome(&mut chip8), first);
+    assert!(first.1[4] > 0);
+}
+
+#[test]
+fn saved_snapshots_go_the_same_way_on_another_machine() {
+    let mut chip8 = scatter();
+    outcome(&mut chip8);
+    chip8.set_key(5, true);
+    let bytes = chip8.snapshot().to_bytes();
+    let first = outcome(&mut chip8);
+
+    // Seeded differently, with nothing held
+    let mut other = Chip8::builder().seed(99).build().unwrap();
+    other.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    assert_eq!(other.keys(), 1 << 5);
+    assert_eq!(outcome(&mut other), first);
+}
+
+#[test]
+fn presses_not_yet_seen_are_kept() {
+    // Waiting on `v1 := key`
+    let mut chip8 = machine(&ending("v1 := key"));
+    chip8.emulate_cycle().unwrap();
+    // Tapped between instructions
+    chip8.set_key(0xB, true);
+    chip8.set_key(0xB, false);
+    let bytes = chip8.snapshot().to_bytes();
+
+    let mut other = Chip8::builder().build().unwrap();
+    other.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    other.emulate_cycle().unwrap();
+    assert_eq!((other.registers()[1], other.pc()), (0xB, 0x202));
+}
+
+#[test]
+fn rewinding_keeps_the_keys_held_now() {
+    let mut chip8 = scatter();
+    chip8.set_key(5, true);
+    chip8.push_rewind();
+    chip8.set_key(5, false);
+    chip8.set_key(2, true);
+    assert!(chip8.rewind());
+    assert_eq!(chip8.keys(), 1 << 2);
+}
+
+#[test]
+fn corrupt_memory_sizes_are_refused() {
+    let mut bytes = Chip8::new().snapshot().to_bytes();
+    // After the version, PC, I, V0-VF, the timers and flags and an
+    // empty stack
+    let size = 1 + 2 + 2 + 16 + 4 + 2;
+    assert_eq!(bytes[size..size + 4], 0x1000u32.to_le_bytes());
+    for bad in [0, 0x10001] {
+        bytes[size..size + 4].copy_from_slice(&u32::to_le_bytes(bad));
+        let err = Snapshot::from_bytes(&bytes).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("Unsupported memory size {:#X} in snapshot", bad)
+        );
+    }
+}
+
+#[test]
+fn snapshots_bring_their_memory_size() {
+    // An XO-CHIP machine about to write past 4KB
+    let mut xo_chip = Chip8::builder().variant(Variant::XoChip).build().unwrap();
+    load(&mut xo_chip, &ending("v0 := 0x2A save v0"));
+    xo_chip.set_i(0x9000).unwrap();
+    let bytes = xo_chip.snapshot().to_bytes();
+
+    let mut chip8 = Chip8::new();
+    chip8.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    run(&mut chip8, 3);
+    assert_eq!(chip8.memory().len(), 0x10000);
+    assert_eq!(chip8.memory()[0x9000], 0x2A);
+    assert!(chip8.is_halted());
+
+    // And back down to 4KB
+    chip8.restore(&Chip8::new().snapshot());
+    chip8.set_i(0xFFF).unwrap();
+    chip8.load_rom_bytes(&[0xF0, 0x55]).unwrap();
+    run(&mut chip8, 1);
+    assert_eq!(chip8.memory().len(), 0x1000);
+}
+
+// `bytes` as version 5 saved them: without the high resolution
+// flag, and with the generator as its seed and the numbers drawn
+// since in place of its state
+fn version_5(bytes: &[u8], seed: u64, draws: u64) -> Vec<u8> {
+    // The flag follows the stack and memory, and the state is
+    // followed by the five key words
+    let depth = u16::from_le_bytes([bytes[25], bytes[26]]) as usize;
+    let at = 27 + 2 * depth;
+    let flag = at + 4 + u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+    let state = bytes.len() - 10 - 32;
+    let mut old = vec![5];
+    old.extend_from_slice(&bytes[1..flag]);
+    old.extend_from_slice(&bytes[flag + 1..state]);
+    old.extend_from_slice(&seed.to_le_bytes());
+    old.extend_from_slice(&draws.to_le_bytes());
+    old.extend_from_slice(&bytes[state + 32..]);
+    old
+}
+
+#[test]
+fn version_5_snapshots_replay_the_draws() {
+    // Past the three `random`s
+    let mut chip8 = scatter();
+    run(&mut chip8, 3);
+    let bytes = version_5(&chip8.snapshot().to_bytes(), 7, 3);
+    let first = outcome(&mut chip8);
+
+    let mut other = Chip8::new();
+    other.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    assert_eq!(outcome(&mut other), first);
+
+    // Without hanging on a corrupt count
+    let bytes = version_5(&scatter().snapshot().to_bytes(), 7, u64::MAX);
+    let err = Snapshot::from_bytes(&bytes).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!("Too many random numbers drawn ({}) in snapshot", u64::MAX)
+    );
+}
@@ -0,0 +1,73 @@
+// The buzzer turns on and off only on timer ticks, and sounds for
+// exactly as many ticks as the sound timer was set to.
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::{ending, load, machine, run};
+
+// (buzzer, started, ended) after each of `ticks` timer ticks
+fn ticks(chip8: &mut Chip8, ticks: usize) -> Vec<(bool, bool, bool)> {
+    (0..ticks)
+        .map(|_| {
+            chip8.update_timers();
+            (chip8.buzzer(), chip8.beep_started(), chip8.beep_ended())
+        })
+        .collect()
+}
+
+// Runs FX18 with the timer set to `n`
+fn set_sound_timer(chip8: &mut Chip8, n: u8) {
+    load(chip8, &format!("v0 := {} buzzer := v0", n));
+    run(chip8, 2);
+    assert_eq!(chip8.sound_timer(), n);
+}
+
+#[test]
+fn a_beep_lasts_as_many_ticks_as_it_was_set_for() {
+    let mut chip8 = Chip8::new();
+    set_sound_timer(&mut chip8, 3);
+    // Setting the timer doesn't start it; the next tick does
+    assert!(!chip8.buzzer());
+    assert_eq!(
+        ticks(&mut chip8, 5),
+        [
+            (true, true, false),
+            (true, false, false),
+            (true, false, false),
+            (false, false, true),
+            (false, false, false),
+        ]
+    );
+    assert_eq!(chip8.sound_timer(), 0);
+}
+
+#[test]
+fn a_one_tick_beep_still_has_both_edges() {
+    let mut chip8 = Chip8::new();
+    set_sound_timer(&mut chip8, 1);
+    assert_eq!(
+        ticks(&mut chip8, 2),
+        [(true, true, false), (false, false, true)]
+    );
+}
+
+#[test]
+fn topping_up_the_timer_doesnt_restart_the_beep() {
+    let mut chip8 = machine("v0 := 2 buzzer := v0 v1 := 4 buzzer := v1");
+    run(&mut chip8, 2);
+    assert_eq!(ticks(&mut chip8, 1), [(true, true, false)]);
+    run(&mut chip8, 2);
+    let after = ticks(&mut chip8, 5);
+    assert_eq!(after.iter().filter(|&&(_, started, _)| started).count(), 0);
+    assert_eq!(after.iter().position(|&(_, _, ended)| ended), Some(4));
+}
+
+#[test]
+fn frames_report_the_buzzer() {
+    let mut chip8 = machine(&ending("v0 := 2 buzzer := v0"));
+    let sound: Vec<bool> = (0..4)
+        .map(|_| chip8.step_frame(15).unwrap().sound)
+        .collect();
+    assert_eq!(sound, [true, true, false, false]);
+}
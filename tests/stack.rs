@@ -0,0 +1,36 @@
+// The call stack as a debugger sees it: the return addresses,
+// outermost first, and the depth limit they run up against.
+
+mod common;
+
+use chip8_emu::{Chip8, Chip8Error};
+use common::{load, machine, run};
+
+// Calls a, which calls b, which calls itself for ever
+const NESTED: &str = ": main a : a b : b b";
+
+#[test]
+fn return_addresses_outermost_first() {
+    let mut chip8 = machine(NESTED);
+    assert!(chip8.stack().is_empty());
+    assert_eq!(chip8.stack_limit(), 16);
+    run(&mut chip8, 3);
+    assert_eq!(chip8.stack(), [0x200, 0x202, 0x204]);
+    assert_eq!(chip8.stack_depth(), 3);
+}
+
+#[test]
+fn the_limit_is_where_calls_overflow() {
+    let mut chip8 = Chip8::builder().stack_depth(12).build().unwrap();
+    load(&mut chip8, NESTED);
+    assert_eq!(chip8.stack_limit(), 12);
+    run(&mut chip8, 12);
+    assert_eq!(chip8.stack().len(), chip8.stack_limit());
+    assert_eq!(
+        chip8.emulate_cycle(),
+        Err(Chip8Error::StackOverflow {
+            pc: 0x204,
+            depth: 12
+        })
+    );
+}
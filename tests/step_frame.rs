@@ -0,0 +1,65 @@
+// step_frame() runs one 60Hz frame: the instructions the budget
+// pays for, then a single tick of the timers, reporting what the
+// frame changed.
+
+mod common;
+
+use chip8_emu::{Chip8, Watch};
+use common::{ending, load, machine};
+
+#[test]
+fn the_display_changes_only_on_frames_that_draw() {
+    // Draws once, then spins
+    let mut chip8 = machine(&ending("i := hex v0 sprite v0 v0 5"));
+    assert!(chip8.step_frame(15).unwrap().display_changed);
+    assert!(!chip8.step_frame(15).unwrap().display_changed);
+    // The flag was taken, not left for the next caller
+    assert!(!chip8.take_draw_flag());
+}
+
+#[test]
+fn timers_tick_once_a_frame() {
+    let mut chip8 = machine(&ending("v0 := 10 delay := v0 buzzer := v0"));
+    // The timers are set partway through the first frame, and
+    // tick at its end however many instructions it ran
+    let output = chip8.step_frame(100).unwrap();
+    assert!(output.sound);
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (9, 9));
+    for budget in [1, 15, 1000] {
+        let before = chip8.delay_timer();
+        chip8.step_frame(budget).unwrap();
+        assert_eq!(chip8.delay_timer(), before - 1, "budget {}", budget);
+    }
+}
+
+#[test]
+fn frames_report_a_key_wait_and_what_ran() {
+    let mut chip8 = machine("v0 := 1 v1 := 2 v2 := key");
+    let output = chip8.step_frame(2).unwrap();
+    assert_eq!(output.instructions, 2);
+    assert!(output.waiting_for_key);
+    assert!(!output.sound);
+
+    // Still waiting, whatever the budget
+    assert!(chip8.step_frame(15).unwrap().waiting_for_key);
+}
+
+#[test]
+fn a_limit_leaves_the_frame_unfinished() {
+    let mut chip8 = machine(&ending("v0 := 10 delay := v0 v1 := 1"));
+    let output = chip8.step_frame_upto(15, 2).unwrap();
+    assert_eq!(output.instructions, 2);
+    assert_eq!(chip8.pc(), 0x204);
+    // The timer was set, but the frame didn't get to tick it
+    assert_eq!(chip8.delay_timer(), 10);
+}
+
+#[test]
+fn only_new_watchpoint_hits_end_a_frame() {
+    let mut chip8 = Chip8::builder().watch(Watch::Register(1)).build().unwrap();
+    load(&mut chip8, ": top v1 += 1 v2 := 0 v3 := 0 jump top");
+    assert_eq!(chip8.step_frame(15).unwrap().instructions, 1);
+    // The hit is left untaken: the next frame stops at its own
+    assert_eq!(chip8.step_frame(15).unwrap().instructions, 4);
+    assert_eq!(chip8.take_watchpoint_hits().len(), 2);
+}
@@ -0,0 +1,145 @@
+// SUPER-CHIP's high resolution: 00FF and 00FE switch between
+// 128x64 and 64x32, DXY0 draws 16x16 sprites there, and VF counts
+// the rows that collided instead of just being set.
+
+mod common;
+
+use chip8_emu::{Chip8, Chip8Error, Quirks, Snapshot, Variant};
+use common::{ending, lit, load, run_to_end};
+
+// A `variant` machine that has run `source` to its end, with
+// `solid`, a 16x16 sprite of all lit pixels, after it
+fn finished(variant: Variant, quirks: Quirks, source: &str) -> Chip8 {
+    let mut chip8 = Chip8::builder()
+        .variant(variant)
+        .quirks(quirks)
+        .build()
+        .unwrap();
+    let solid = format!(": solid {}", "0xFF ".repeat(32));
+    load(&mut chip8, &format!("{} {}", ending(source), solid));
+    run_to_end(&mut chip8);
+    chip8
+}
+
+fn schip(source: &str) -> Chip8 {
+    finished(Variant::SuperChip, Quirks::default(), source)
+}
+
+fn size(chip8: &Chip8) -> (usize, usize) {
+    (chip8.display_width(), chip8.display_height())
+}
+
+#[test]
+fn hires_and_lores_switch_the_resolution() {
+    let chip8 = schip("hires");
+    assert!(chip8.hires());
+    assert_eq!(size(&chip8), (128, 64));
+    assert_eq!(chip8.display.len(), 128 * 64);
+
+    // Both ways clear the display
+    let chip8 = schip("i := solid sprite v0 v0 8 hires");
+    assert!(lit(&chip8, 1).is_empty());
+    let chip8 = schip("hires i := solid sprite v0 v0 8 lores");
+    assert!(!chip8.hires());
+    assert_eq!(size(&chip8), (64, 32));
+    assert!(lit(&chip8, 1).is_empty());
+}
+
+#[test]
+fn high_resolution_draws_past_column_64() {
+    let chip8 = schip("hires v0 := 100 v1 := 50 i := solid sprite v0 v1 1");
+    assert_eq!(
+        lit(&chip8, 1),
+        (100..108).map(|x| (x, 50)).collect::<Vec<_>>()
+    );
+    // And wraps at 128
+    let chip8 = schip("hires v0 := 124 i := solid sprite v0 v0 1");
+    let xs: Vec<usize> = lit(&chip8, 1).iter().map(|&(x, _)| x).collect();
+    assert_eq!(xs, [0, 1, 2, 3, 124, 125, 126, 127]);
+}
+
+#[test]
+fn dxy0_is_16x16_in_high_resolution_only() {
+    let chip8 = schip("hires v0 := 3 i := solid sprite v0 v0 0");
+    let pixels = lit(&chip8, 1);
+    assert_eq!(pixels.len(), 256);
+    assert_eq!(pixels[0], (3, 3));
+    assert_eq!(pixels[255], (18, 18));
+
+    // Zero rows in low resolution, as in CHIP-8
+    let chip8 = schip("i := solid sprite v0 v0 0");
+    assert!(lit(&chip8, 1).is_empty());
+    assert_eq!(chip8.registers()[0xF], 0);
+}
+
+#[test]
+fn vf_counts_the_rows_that_collided() {
+    let chip8 = schip("hires i := solid sprite v0 v0 0 sprite v0 v0 0");
+    assert_eq!(chip8.registers()[0xF], 16);
+    // Three rows overlap
+    let chip8 = schip("hires i := solid sprite v0 v0 0 v1 := 13 sprite v0 v1 0");
+    assert_eq!(chip8.registers()[0xF], 3);
+    // Rows clipped off the bottom count too
+    let clip = Quirks {
+        wrap_y: false,
+        ..Quirks::default()
+    };
+    let chip8 = finished(
+        Variant::SuperChip,
+        clip,
+        "hires v1 := 60 i := solid sprite v0 v1 0",
+    );
+    assert_eq!(chip8.registers()[0xF], 12);
+    assert_eq!(lit(&chip8, 1).len(), 4 * 16);
+
+    // Low resolution and XO-CHIP only set it to 1
+    let chip8 = schip("i := solid sprite v0 v0 8 v1 := 5 sprite v0 v1 8");
+    assert_eq!(chip8.registers()[0xF], 1);
+    let chip8 = finished(
+        Variant::XoChip,
+        Quirks::default(),
+        "hires i := solid sprite v0 v0 0 v1 := 13 sprite v0 v1 0",
+    );
+    assert_eq!(chip8.registers()[0xF], 1);
+}
+
+#[test]
+fn scrolls_move_high_resolution_pixels() {
+    // The half scroll quirk only halves low resolution scrolls
+    let half = Quirks {
+        half_scroll: true,
+        ..Quirks::default()
+    };
+    let source = "i := solid sprite v0 v0 1 scroll-right";
+    let chip8 = finished(Variant::SuperChip, half, &format!("hires {}", source));
+    assert_eq!(lit(&chip8, 1)[0], (4, 0));
+    let chip8 = finished(Variant::SuperChip, half, source);
+    assert_eq!(lit(&chip8, 1)[0], (2, 0));
+}
+
+#[test]
+fn chip8_stops_on_the_resolution_switches() {
+    for (source, addr) in [("hires", 0xFF), ("lores", 0xFE)] {
+        let mut chip8 = Chip8::builder().build().unwrap();
+        load(&mut chip8, source);
+        let err = chip8.emulate_cycle().err();
+        assert_eq!(err, Some(Chip8Error::MachineCodeCall { pc: 0x200, addr }));
+        assert_eq!(size(&chip8), (64, 32));
+    }
+}
+
+#[test]
+fn snapshots_keep_the_resolution() {
+    let chip8 = schip("hires v0 := 100 i := solid sprite v0 v0 1");
+    let bytes = chip8.snapshot().to_bytes();
+
+    let mut other = schip("");
+    other.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    assert!(other.hires());
+    assert_eq!(size(&other), (128, 64));
+    assert_eq!(other.display, chip8.display);
+
+    // And back
+    other.restore(&schip("").snapshot());
+    assert_eq!(size(&other), (64, 32));
+}
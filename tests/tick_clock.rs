@@ -0,0 +1,45 @@
+// TickClock: elapsed time split into whole ticks, with the rest
+// carried over, so stalls are caught up on exactly.
+
+use chip8_emu::TickClock;
+use std::time::Duration;
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+#[test]
+fn a_50ms_stall_is_3_ticks() {
+    let mut clock = TickClock::new(60);
+    assert_eq!(clock.take(ms(50)), 3);
+    // 3 ticks of 16.666666ms leave a few nanoseconds over
+    assert!(clock.carried() < Duration::from_micros(1));
+}
+
+#[test]
+fn short_frames_add_up() {
+    let mut clock = TickClock::new(60);
+    let ticks: Vec<u32> = (0..6).map(|_| clock.take(ms(10))).collect();
+    assert_eq!(ticks, [0, 1, 0, 1, 1, 0]);
+    // A second of uneven frames is 60 ticks
+    let mut clock = TickClock::new(60);
+    let total: u32 = [7, 30, 13, 50, 100, 300, 500]
+        .map(|t| clock.take(ms(t)))
+        .iter()
+        .sum();
+    assert_eq!(total, 60);
+}
+
+#[test]
+fn changing_the_rate_keeps_the_time_carried() {
+    let mut clock = TickClock::new(60);
+    assert_eq!(clock.take(ms(10)), 0);
+    clock.set_hz(200);
+    // 10ms carried plus 5 is three 5ms ticks
+    assert_eq!(clock.take(ms(5)), 3);
+    assert_eq!(clock.carried(), Duration::ZERO);
+
+    clock.take(ms(3));
+    clock.reset();
+    assert_eq!(clock.take(ms(4)), 0);
+}
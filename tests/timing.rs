@@ -0,0 +1,72 @@
+// Frame budgets under each timing model: how many instructions of
+// a given kind fit in one step_frame().
+
+mod common;
+
+use chip8_emu::{Chip8, Opcode, Timing, schip_cycles_for};
+use common::load;
+
+fn timed(timing: Timing, source: &str) -> Chip8 {
+    let mut chip8 = Chip8::builder().timing(timing).build().unwrap();
+    load(&mut chip8, source);
+    chip8
+}
+
+// Instructions run in the first frame of `budget`
+fn first_frame(timing: Timing, source: &str, budget: u32) -> u32 {
+    let mut chip8 = timed(timing, source);
+    chip8.step_frame(budget).unwrap().instructions
+}
+
+const ARITHMETIC: &str = "loop v0 += 1 v1 += v0 again";
+const DRAWING: &str = "i := hex v0 loop sprite v0 v1 5 v0 += 1 again";
+
+#[test]
+fn simple_timing_counts_instructions() {
+    assert_eq!(first_frame(Timing::Simple, ARITHMETIC, 15), 15);
+    assert_eq!(first_frame(Timing::Simple, DRAWING, 15), 15);
+}
+
+#[test]
+fn schip_timing_charges_draws_more() {
+    assert_eq!(first_frame(Timing::Schip, ARITHMETIC, 15), 15);
+    // i := (1), then rounds of sprite (3), v0 += 1 (1) and the
+    // jump (1): two rounds and a sprite and add of the third
+    assert_eq!(first_frame(Timing::Schip, DRAWING, 15), 1 + 2 * 3 + 2);
+}
+
+#[test]
+fn schip_costs() {
+    assert_eq!(schip_cycles_for(Opcode::AddVxByte(0, 1)), 1);
+    assert_eq!(schip_cycles_for(Opcode::Drw(0, 0, 5)), 3);
+    assert_eq!(schip_cycles_for(Opcode::Drw(0, 0, 0)), 9);
+    assert_eq!(schip_cycles_for(Opcode::ScrollLeft), 6);
+    assert_eq!(Timing::Schip.frame_budget(30), 30);
+    assert_eq!(Timing::Simple.cycle_cost(Opcode::Drw(0, 0, 0)), 1);
+    assert_eq!(
+        Timing::Schip.cycle_cost(Opcode::Cls),
+        schip_cycles_for(Opcode::Cls)
+    );
+}
+
+#[test]
+fn overspending_is_paid_from_the_next_frame() {
+    let mut chip8 = timed(Timing::Schip, "i := hex v0 loop sprite v0 v1 5 again");
+    // i := (1) and a draw (3) overspend a budget of 3 by 1
+    assert_eq!(chip8.step_frame(3).unwrap().instructions, 2);
+    // Then 1 is already spent: the jump (1) and a draw (3)
+    // overspend by 2
+    assert_eq!(chip8.step_frame(3).unwrap().instructions, 2);
+    // Leaving room for just the jump
+    assert_eq!(chip8.step_frame(3).unwrap().instructions, 1);
+}
+
+#[test]
+fn emulate_cycle_reports_the_cost() {
+    let mut chip8 = timed(Timing::Schip, "i := hex v0 sprite v0 v1 0 clear");
+    assert_eq!(chip8.emulate_cycle(), Ok(1));
+    assert_eq!(chip8.emulate_cycle(), Ok(9));
+    assert_eq!(chip8.emulate_cycle(), Ok(4));
+    let mut chip8 = timed(Timing::Vip, "v0 := 1");
+    assert_eq!(chip8.emulate_cycle(), Ok(46));
+}
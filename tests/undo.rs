@@ -0,0 +1,116 @@
+// Single-instruction undo: each instruction run with the log on
+// can be taken back, newest first, putting back exactly what it
+// changed.
+
+mod common;
+
+use chip8_emu::Chip8;
+use common::machine;
+
+// A machine with `source` loaded and the undo log on
+fn undoable(source: &str) -> Chip8 {
+    let mut chip8 = machine(source);
+    chip8.set_undo(true);
+    chip8
+}
+
+// Everything an instruction can change
+fn state(chip8: &Chip8) -> (u16, u16, [u8; 16], Vec<u16>, Vec<u8>, Vec<u8>) {
+    (
+        chip8.pc(),
+        chip8.i(),
+        *chip8.registers(),
+        chip8.stack().to_vec(),
+        chip8.memory().to_vec(),
+        chip8.display.clone(),
+    )
+}
+
+// Runs `n` instructions, then undoes them one at a time, checking
+// each lands back on the state from before it
+fn run_and_undo(source: &str, n: usize) {
+    let mut chip8 = undoable(source);
+    let mut states = vec![];
+    for _ in 0..n {
+        states.push(state(&chip8));
+        chip8.emulate_cycle().unwrap();
+    }
+    while let Some(before) = states.pop() {
+        assert!(chip8.undo());
+        assert_eq!(state(&chip8), before, "{} left", states.len());
+    }
+    assert!(!chip8.undo());
+}
+
+#[test]
+fn registers_and_arithmetic() {
+    run_and_undo(
+        "v0 := 200 v1 := 100 v0 += v1 v2 := 7 v2 <<= v2 i := 0x300 i += v2",
+        7,
+    );
+}
+
+#[test]
+fn calls_and_returns() {
+    run_and_undo(": main sub sub jump main : sub v0 += 1 return", 7);
+}
+
+#[test]
+fn memory_writes() {
+    run_and_undo(
+        "v0 := 1 v1 := 2 v2 := 3 i := 0x300 save v2 i := 0x301 save v1 v5 := 123 bcd v5 load v2",
+        10,
+    );
+}
+
+#[test]
+fn drawing_and_clearing() {
+    run_and_undo(
+        "v0 := 60 v1 := 30 v2 := 8 i := hex v2 sprite v0 v1 5 sprite v0 v1 5 sprite v1 v1 5 clear",
+        8,
+    );
+}
+
+#[test]
+fn random_numbers_come_out_the_same_again() {
+    let mut chip8 = undoable("v0 := random 0xFF v1 := random 0xFF");
+    chip8.emulate_cycle().unwrap();
+    chip8.emulate_cycle().unwrap();
+    let rolled = *chip8.registers();
+    assert!(chip8.undo());
+    assert!(chip8.undo());
+    chip8.emulate_cycle().unwrap();
+    chip8.emulate_cycle().unwrap();
+    assert_eq!(*chip8.registers(), rolled);
+}
+
+#[test]
+fn the_log_is_bounded() {
+    let mut chip8 = undoable(": top v0 += 1 jump top");
+    for _ in 0..5000 {
+        chip8.emulate_cycle().unwrap();
+    }
+    let mut undone = 0;
+    while chip8.undo() {
+        undone += 1;
+    }
+    assert_eq!(undone, 1024);
+    // 5000 - 1024 instructions in, half of them adds
+    assert_eq!(chip8.registers()[0], ((5000 - 1024) / 2) as u8);
+}
+
+#[test]
+fn nothing_to_undo_when_off_or_after_a_restore() {
+    let mut chip8 = machine("v0 := 1");
+    chip8.emulate_cycle().unwrap();
+    assert!(!chip8.undo());
+
+    let mut chip8 = undoable("v0 := 1 v0 := 2");
+    let snapshot = chip8.snapshot();
+    chip8.emulate_cycle().unwrap();
+    chip8.restore(&snapshot);
+    assert!(!chip8.undo());
+    chip8.emulate_cycle().unwrap();
+    assert!(chip8.undo());
+    assert_eq!(chip8.registers()[0], 0);
+}
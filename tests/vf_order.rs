@@ -0,0 +1,91 @@
+// VF as an operand of the arithmetic and shift instructions: the
+// result is computed from the registers as they were, written to
+// V[X], and then the flag is written to VF, so with X = F the flag
+// wins and with Y = F the old VF is what's added, subtracted or
+// shifted.
+
+use chip8_emu::{Chip8, Quirks};
+
+// V1 and VF after `opcode`, starting from `v1` and `vf`
+fn flagged(opcode: u16, v1: u8, vf: u8, shift_uses_vy: bool) -> (u8, u8) {
+    let quirks = Quirks {
+        shift_uses_vy,
+        ..Quirks::default()
+    };
+    let mut chip8 = Chip8::builder().quirks(quirks).build().unwrap();
+    chip8.load_rom_bytes(&opcode.to_be_bytes()).unwrap();
+    chip8.set_register(1, v1).unwrap();
+    chip8.set_register(0xF, vf).unwrap();
+    chip8.emulate_cycle().unwrap();
+    (chip8.registers()[1], chip8.registers()[0xF])
+}
+
+#[test]
+fn x_is_f() {
+    // (opcode, V1, VF before, VF after), the flag set then clear
+    let cases = [
+        (0x8F14, 1, 0xFF, 1),
+        (0x8F14, 1, 1, 0),
+        (0x8F15, 3, 5, 1),
+        (0x8F15, 5, 3, 0),
+        (0x8F17, 5, 3, 1),
+        (0x8F17, 3, 5, 0),
+        (0x8F16, 0, 0b11, 1),
+        (0x8F16, 0, 0b10, 0),
+        (0x8F1E, 0, 0x80, 1),
+        (0x8F1E, 0, 0x40, 0),
+    ];
+    for (opcode, v1, vf, flag) in cases {
+        assert_eq!(
+            flagged(opcode, v1, vf, false),
+            (v1, flag),
+            "{:04X} V1={:02X} VF={:02X}",
+            opcode,
+            v1,
+            vf
+        );
+    }
+}
+
+#[test]
+fn y_is_f() {
+    // (opcode, V1 and VF before, V1 and VF after), the flag set
+    // then clear
+    let cases = [
+        (0x81F4, (200, 56), (0, 1)),
+        (0x81F4, (200, 55), (255, 0)),
+        (0x81F5, (6, 5), (1, 1)),
+        (0x81F5, (5, 6), (0xFF, 0)),
+        (0x81F7, (5, 6), (1, 1)),
+        (0x81F7, (6, 5), (0xFF, 0)),
+        (0x81F6, (0b11, 0), (1, 1)),
+        (0x81F6, (0b10, 0), (1, 0)),
+        (0x81FE, (0x81, 0), (2, 1)),
+        (0x81FE, (0x41, 0), (0x82, 0)),
+    ];
+    for (opcode, (v1, vf), after) in cases {
+        assert_eq!(
+            flagged(opcode, v1, vf, false),
+            after,
+            "{:04X} V1={:02X} VF={:02X}",
+            opcode,
+            v1,
+            vf
+        );
+    }
+}
+
+#[test]
+fn shifts_with_shift_uses_vy() {
+    // Y = F: the old VF is shifted into V1, and then the bit
+    // shifted out replaces it
+    assert_eq!(flagged(0x81F6, 0, 0b11, true), (1, 1));
+    assert_eq!(flagged(0x81F6, 0, 0b10, true), (1, 0));
+    assert_eq!(flagged(0x81FE, 0, 0x81, true), (2, 1));
+    assert_eq!(flagged(0x81FE, 0, 0x41, true), (0x82, 0));
+    // X = F: V1 is shifted, and only the flag is left in VF
+    assert_eq!(flagged(0x8F16, 0b11, 0, true), (0b11, 1));
+    assert_eq!(flagged(0x8F16, 0b10, 0, true), (0b10, 0));
+    assert_eq!(flagged(0x8F1E, 0x80, 0, true), (0x80, 1));
+    assert_eq!(flagged(0x8F1E, 0x40, 0, true), (0x40, 0));
+}